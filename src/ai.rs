@@ -1,49 +1,274 @@
 //! AI-powered utilities using Google Gemini API.
 
-use crate::config::PrTemplate;
-use crate::errors::StResult;
+use crate::config::{PrTemplate, StConfig};
+use crate::errors::{StError, StResult};
+use crate::format::delta_label;
+use crate::git::{BranchDiff, BranchDiffOptions, CommitLogOptions, FileDelta};
+use crate::output::is_quiet;
+use reqwest::{header::CONTENT_TYPE, StatusCode};
+use std::io::{self, Write};
+use tracing::debug;
+
+/// The commit messages included in PR description prompts. Long-lived branches are capped to
+/// their most recent commits so the history does not crowd out the diff.
+pub(crate) const PROMPT_COMMIT_LOG: CommitLogOptions = CommitLogOptions {
+    max_commits: Some(50),
+    subjects_only: false,
+};
+
+/// The Gemini API endpoint.
+const GEMINI_API_URL: &str = "https://generativelanguage.googleapis.com/v1beta";
+
+/// The Gemini model that generates PR descriptions.
+const GEMINI_MODEL: &str = "gemini-flash-lite-latest";
+
+/// Paths of generated files whose changes are left out of PR description prompts.
+const PROMPT_DIFF_EXCLUDE: [&str; 8] = [
+    "*.lock",
+    "package-lock.json",
+    "pnpm-lock.yaml",
+    "go.sum",
+    "*.min.js",
+    "*.min.css",
+    "*.map",
+    "*.snap",
+];
+
+/// The size in bytes above which the changes of a file are left out of PR description prompts.
+const PROMPT_MAX_FILE_DIFF_SIZE: usize = 32 * 1024;
+
+/// The maximum number of words in AI-generated PR descriptions, unless configured with
+/// `ai_max_words`. The built-in prompts ask for at most 300 or 400 words, which models don't
+/// always respect.
+pub const DEFAULT_AI_MAX_WORDS: usize = 400;
+
+/// Returns the options of the diff included in PR description prompts. Moved files are shown
+/// as renames, and the changes of binary files, generated files, and very large files are
+/// replaced with a placeholder, as they crowd out the rest of the diff.
+///
+/// ## Takes
+/// - `config` - The [StConfig], with any additional paths to leave out.
+///
+/// ## Returns
+/// - `BranchDiffOptions` - The options of the prompt diff.
+pub(crate) fn prompt_diff_options(config: &StConfig) -> BranchDiffOptions {
+    BranchDiffOptions {
+        find_renames: true,
+        omit_binary: true,
+        omitted_paths: PROMPT_DIFF_EXCLUDE
+            .iter()
+            .map(ToString::to_string)
+            .chain(config.ai_diff_exclude.iter().cloned())
+            .collect(),
+        max_file_size: Some(PROMPT_MAX_FILE_DIFF_SIZE),
+        ..Default::default()
+    }
+}
+
+/// Builds the list of commit messages for prompts, one `- subject` line per commit.
+fn build_commit_list(commits: &[String]) -> String {
+    commits
+        .iter()
+        .map(|c| format!("- {}", c))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
 
 /// Builds the commit section string for prompts.
 fn build_commits_section(commits: &[String]) -> String {
     if !commits.is_empty() {
-        let commit_list = commits
-            .iter()
-            .map(|c| format!("- {}", c))
-            .collect::<Vec<_>>()
-            .join("\n");
-        format!("\n\nCommit messages:\n{}\n", commit_list)
+        format!("\n\nCommit messages:\n{}\n", build_commit_list(commits))
     } else {
         String::new()
     }
 }
 
-/// Generates a PR description using Google Gemini API.
+/// Builds the changed files section string for prompts, noting renames and mode changes that
+/// are easy to miss in the diff.
+fn build_files_section(files: &[FileDelta]) -> String {
+    if files.is_empty() {
+        return String::new();
+    }
+
+    let file_list = files
+        .iter()
+        .map(|file| {
+            let lines = if file.binary {
+                "binary".to_string()
+            } else {
+                format!("+{} -{}", file.insertions, file.deletions)
+            };
+            format!("- {} {} ({})", file.kind, delta_label(file), lines)
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    format!("\n\nFiles changed:\n{}\n", file_list)
+}
+
+/// Trims a generated description to at most `max_words` words, at the end of the last paragraph
+/// that fits, and appends a note saying so. Blank lines within code blocks don't end paragraphs,
+/// so that code blocks are never cut open. If not even the first paragraph fits, it is cut at its
+/// last line that fits instead.
 ///
 /// ## Takes
-/// - `api_key` - The Gemini API key
-/// - `title` - The PR title
-/// - `branch_name` - The name of the branch
-/// - `parent_name` - The name of the parent branch
-/// - `commits` - The commit messages in the branch
-/// - `diff` - The git diff between the branches
+/// - `text` - The generated description.
+/// - `max_words` - The word budget, or `0` to keep the description whole.
 ///
 /// ## Returns
-/// - `Result<String>` - The generated PR description
-pub async fn generate_pr_description_with_gemini(
-    api_key: &str,
+/// - `String` - The description, trimmed if it was over budget.
+pub(crate) fn trim_to_word_budget(text: &str, max_words: usize) -> String {
+    let count = |text: &str| text.split_whitespace().count();
+    if max_words == 0 || count(text) <= max_words {
+        return text.to_string();
+    }
+
+    // Split the description into paragraphs, keeping code blocks whole.
+    let mut paragraphs = vec![Vec::new()];
+    let mut in_code_block = false;
+    for line in text.lines() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+        }
+        if line.trim().is_empty() && !in_code_block {
+            paragraphs.push(Vec::new());
+        } else if let Some(paragraph) = paragraphs.last_mut() {
+            paragraph.push(line);
+        }
+    }
+    let paragraphs = paragraphs
+        .into_iter()
+        .filter(|p| !p.is_empty())
+        .map(|p| p.join("\n"))
+        .collect::<Vec<_>>();
+
+    let mut kept = Vec::new();
+    let mut words = 0;
+    for paragraph in &paragraphs {
+        words += count(paragraph);
+        if words > max_words {
+            break;
+        }
+        kept.push(paragraph.clone());
+    }
+
+    // Cut an oversized first paragraph at its last line that fits, closing any open code block.
+    if kept.is_empty() {
+        let first = paragraphs.first().map(String::as_str).unwrap_or_default();
+        let mut lines = Vec::new();
+        let mut words = 0;
+        for line in first.lines() {
+            words += count(line);
+            if words > max_words {
+                break;
+            }
+            lines.push(line);
+        }
+        let fences = lines
+            .iter()
+            .filter(|l| l.trim_start().starts_with("```"))
+            .count();
+        if fences % 2 == 1 {
+            lines.push("```");
+        }
+        kept.push(lines.join("\n"));
+    }
+
+    format!(
+        "{}\n\n_The generated description was trimmed to {} words._",
+        kept.join("\n\n").trim_end(),
+        max_words
+    )
+}
+
+/// Interpolates a prompt configured with `ai_prompt_template` or
+/// `ai_prompt_template_with_template`.
+///
+/// ## Takes
+/// - `prompt` - The configured prompt, with placeholders.
+/// - `template` - The selected PR template, substituted for `{template}`.
+/// - `title` - The PR title, substituted for `{title}`.
+/// - `branch_name` - The name of the branch, substituted for `{branch}`.
+/// - `parent_name` - The name of the parent branch, substituted for `{parent}`.
+/// - `commits` - The commit messages, substituted for `{commits}` one `- subject` line each.
+/// - `diff` - The changes between the branches, whose patch is substituted for `{diff}`.
+///
+/// ## Returns
+/// - `String` - The prompt, with every placeholder replaced.
+fn interpolate_prompt(
+    prompt: &str,
+    template: Option<&PrTemplate>,
     title: &str,
     branch_name: &str,
     parent_name: &str,
     commits: &[String],
-    diff: &str,
-) -> StResult<String> {
+    diff: &BranchDiff,
+) -> String {
+    let commit_list = build_commit_list(commits);
+    let mut interpolated = String::with_capacity(prompt.len());
+    let mut rest = prompt;
+    // Substitute in a single pass, so that placeholders in the substituted text (e.g. `{diff}`
+    // in a commit message) are copied verbatim.
+    while let Some(start) = rest.find('{') {
+        interpolated.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find('}').and_then(|end| {
+            let value = match &rest[1..end] {
+                "title" => title,
+                "branch" => branch_name,
+                "parent" => parent_name,
+                "commits" => commit_list.as_str(),
+                "template" => template.map_or("", |t| t.content.as_str()),
+                "diff" => diff.patch.as_str(),
+                _ => return None,
+            };
+            Some((value, end))
+        });
+        match value {
+            Some((value, end)) => {
+                interpolated.push_str(value);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                interpolated.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+    interpolated.push_str(rest);
+    interpolated
+}
+
+/// Builds the prompt for a freeform PR description. The built-in prompt is used unless
+/// `custom_prompt` is set, from `ai_prompt_template`.
+///
+/// Placeholders in `custom_prompt`: `{title}`, `{branch}`, `{parent}`, `{commits}`, `{diff}`.
+pub(crate) fn build_pr_prompt(
+    custom_prompt: &str,
+    title: &str,
+    branch_name: &str,
+    parent_name: &str,
+    commits: &[String],
+    diff: &BranchDiff,
+) -> String {
+    if !custom_prompt.is_empty() {
+        return interpolate_prompt(
+            custom_prompt,
+            None,
+            title,
+            branch_name,
+            parent_name,
+            commits,
+            diff,
+        );
+    }
     let commits_section = build_commits_section(commits);
+    let files_section = build_files_section(&diff.files);
 
-    let prompt = format!(
+    format!(
         r#"You are a technical writer creating a pull request description.
 
 PR Title: {}
-Branch: {} -> {}{}
+Branch: {} -> {}{}{}
 
 Git diff:
 ```
@@ -60,79 +285,43 @@ Write a concise pull request description in markdown format. Requirements:
 - Do NOT repeat the PR title in the description
 
 Generate the description now:"#,
-        title, branch_name, parent_name, commits_section, diff
-    );
-
-    // Build the request body for Gemini API
-    let request_body = serde_json::json!({
-        "contents": [{
-            "role": "user",
-            "parts": [{
-                "text": prompt
-            }]
-        }],
-        "generationConfig": {
-            "thinkingConfig": {
-                "thinkingBudget": 0
-            }
-        }
-    });
-
-    // Call Gemini API
-    let client = reqwest::Client::new();
-    let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/gemini-flash-lite-latest:generateContent?key={}",
-        api_key
-    );
-
-    let response = client
-        .post(&url)
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()
-        .await?;
-
-    let response_json: serde_json::Value = response.json().await?;
-
-    // Extract the text from the response
-    let text = response_json["candidates"][0]["content"]["parts"][0]["text"]
-        .as_str()
-        .ok_or_else(|| {
-            std::io::Error::new(std::io::ErrorKind::InvalidData, "No text in Gemini response")
-        })?;
-
-    Ok(text.trim().to_string())
+        title, branch_name, parent_name, commits_section, files_section, diff.patch
+    )
 }
 
-/// Generates a PR description using a template with Google Gemini API.
-///
-/// ## Takes
-/// - `api_key` - The Gemini API key
-/// - `template` - The PR template to use
-/// - `title` - The PR title
-/// - `branch_name` - The name of the branch
-/// - `parent_name` - The name of the parent branch
-/// - `commits` - The commit messages in the branch
-/// - `diff` - The git diff between the branches
+/// Builds the prompt for a PR description that follows the given [PrTemplate]. The built-in
+/// prompt is used unless `custom_prompt` is set, from `ai_prompt_template_with_template`.
 ///
-/// ## Returns
-/// - `Result<String>` - The generated PR description
-pub async fn generate_pr_description_with_template_gemini(
-    api_key: &str,
+/// Placeholders in `custom_prompt`: `{title}`, `{branch}`, `{parent}`, `{commits}`, `{diff}`,
+/// `{template}`.
+pub(crate) fn build_pr_prompt_with_template(
+    custom_prompt: &str,
     template: &PrTemplate,
     title: &str,
     branch_name: &str,
     parent_name: &str,
     commits: &[String],
-    diff: &str,
-) -> StResult<String> {
+    diff: &BranchDiff,
+) -> String {
+    if !custom_prompt.is_empty() {
+        return interpolate_prompt(
+            custom_prompt,
+            Some(template),
+            title,
+            branch_name,
+            parent_name,
+            commits,
+            diff,
+        );
+    }
     let commits_section = build_commits_section(commits);
+    let files_section = build_files_section(&diff.files);
 
-    let prompt = format!(
+    format!(
         r#"You are a technical writer creating a pull request description using a specific template.
 
 PR Title: {}
-Branch: {} -> {}{}
+Branch: {} -> {}{}{}
 
 Git diff:
 ```
@@ -156,9 +345,21 @@ Write a pull request description following the template structure above. Require
 - Do NOT include the template name in the output
 
 Generate the description now:"#,
-        title, branch_name, parent_name, commits_section, diff, template.name, template.content
-    );
+        title,
+        branch_name,
+        parent_name,
+        commits_section,
+        files_section,
+        diff.patch,
+        template.name,
+        template.content
+    )
+}
 
+/// Sends a prompt to the Google Gemini API and returns the generated text. The response is
+/// streamed, and printed as it arrives to show progress on large diffs, unless output is quiet.
+/// Reads a single response instead if the API does not stream.
+async fn generate_with_gemini(api_key: &str, prompt: String) -> StResult<String> {
     // Build the request body for Gemini API
     let request_body = serde_json::json!({
         "contents": [{
@@ -174,28 +375,445 @@ Generate the description now:"#,
         }
     });
 
-    // Call Gemini API
+    // Call Gemini API, requesting the response as a stream of server-sent events.
     let client = reqwest::Client::new();
     let url = format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/gemini-flash-lite-latest:generateContent?key={}",
-        api_key
+        "{}/models/{}:streamGenerateContent?alt=sse&key={}",
+        GEMINI_API_URL, GEMINI_MODEL, api_key
     );
 
-    let response = client
+    debug!(
+        "HTTP POST {}/models/{}:streamGenerateContent",
+        GEMINI_API_URL, GEMINI_MODEL
+    );
+    let mut response = client
         .post(&url)
         .header("Content-Type", "application/json")
         .json(&request_body)
         .send()
         .await?;
+    debug!(status = %response.status(), "Gemini API responded");
 
-    let response_json: serde_json::Value = response.json().await?;
+    let is_stream = response.status().is_success()
+        && response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/event-stream"));
+    if is_stream {
+        let mut stream = GeminiStream::default();
+        while let Some(chunk) = response.chunk().await? {
+            stream.feed(&chunk, &mut print_text)?;
+        }
+        let text = stream.finish(&mut print_text);
+        if !is_quiet() {
+            println!();
+        }
+        return text;
+    }
 
-    // Extract the text from the response
-    let text = response_json["candidates"][0]["content"]["parts"][0]["text"]
-        .as_str()
-        .ok_or_else(|| {
-            std::io::Error::new(std::io::ErrorKind::InvalidData, "No text in Gemini response")
-        })?;
+    // The API answered with a single response, or an error.
+    let status = response.status();
+    let body = response.text().await?;
+    single_response_text(status, &body)
+}
+
+/// Extracts the generated text from a Gemini response that was not streamed.
+///
+/// ## Takes
+/// - `status` - The HTTP status of the response
+/// - `body` - The response body
+///
+/// ## Returns
+/// - `Result<String>` - The text, or [StError::AiGenerationFailed] with the status and the
+///   message of the error if the request failed.
+fn single_response_text(status: StatusCode, body: &str) -> StResult<String> {
+    let response = serde_json::from_str::<serde_json::Value>(body).unwrap_or_default();
+    if !status.is_success() {
+        let message = response["error"]["message"]
+            .as_str()
+            .unwrap_or_else(|| body.trim());
+        return Err(StError::AiGenerationFailed(format!(
+            "Gemini API responded with {status}: {message}"
+        )));
+    }
+    let text = response_text(&response)?
+        .ok_or_else(|| StError::AiGenerationFailed("No text in Gemini response".to_string()))?;
 
     Ok(text.trim().to_string())
-}
\ No newline at end of file
+}
+
+/// Prints a piece of a streamed response as soon as it arrives, unless output is quiet.
+fn print_text(text: &str) {
+    if is_quiet() {
+        return;
+    }
+    print!("{}", text);
+    let _ = io::stdout().flush();
+}
+
+/// Extracts the generated text from a Gemini response, or from one event of a streamed response.
+///
+/// ## Takes
+/// - `response` - The JSON response body, or the JSON data of an event
+///
+/// ## Returns
+/// - `Result<Option<&str>>` - The text, if any, or [StError::AiGenerationFailed] if the response
+///   is an error.
+fn response_text(response: &serde_json::Value) -> StResult<Option<&str>> {
+    if let Some(message) = response["error"]["message"].as_str() {
+        return Err(StError::AiGenerationFailed(message.to_string()));
+    }
+    Ok(response["candidates"][0]["content"]["parts"][0]["text"].as_str())
+}
+
+/// Assembles the text of a streamed Gemini response from its server-sent events.
+#[derive(Debug, Default)]
+struct GeminiStream {
+    /// The bytes received after the last complete line.
+    pending: Vec<u8>,
+    /// The text received so far.
+    text: String,
+}
+
+impl GeminiStream {
+    /// Feeds a chunk of the response body, passing the text of each complete event to `on_text`.
+    fn feed(&mut self, chunk: &[u8], on_text: &mut impl FnMut(&str)) -> StResult<()> {
+        self.pending.extend_from_slice(chunk);
+        while let Some(end) = self.pending.iter().position(|&b| b == b'\n') {
+            let line = self.pending.drain(..=end).collect::<Vec<_>>();
+            self.read_line(&String::from_utf8_lossy(&line), on_text)?;
+        }
+        Ok(())
+    }
+
+    /// Reads one line of the response body. Lines other than `data:` lines are ignored.
+    fn read_line(&mut self, line: &str, on_text: &mut impl FnMut(&str)) -> StResult<()> {
+        let Some(data) = line.trim_end().strip_prefix("data:") else {
+            return Ok(());
+        };
+        let event: serde_json::Value = serde_json::from_str(data.trim())?;
+        if let Some(text) = response_text(&event)? {
+            on_text(text);
+            self.text.push_str(text);
+        }
+        Ok(())
+    }
+
+    /// Finishes the stream, reading any unterminated last line.
+    ///
+    /// ## Returns
+    /// - `Result<String>` - The assembled text, trimmed like a single response.
+    fn finish(mut self, on_text: &mut impl FnMut(&str)) -> StResult<String> {
+        let rest = std::mem::take(&mut self.pending);
+        self.read_line(&String::from_utf8_lossy(&rest), on_text)?;
+        let text = self.text.trim();
+        if text.is_empty() {
+            return Err(StError::AiGenerationFailed(
+                "No text in Gemini response".to_string(),
+            ));
+        }
+        Ok(text.to_string())
+    }
+}
+
+/// Lists the Gemini models available to an API key. As the request is authenticated, this also
+/// checks that the key is accepted, without spending any tokens.
+///
+/// ## Takes
+/// - `api_key` - The Gemini API key
+///
+/// ## Returns
+/// - `Result<Vec<String>>` - The names of the models, or [StError::AiRequestFailed] if Gemini
+///   rejected the key.
+pub async fn list_gemini_models(api_key: &str) -> StResult<Vec<String>> {
+    let url = format!("{}/models?key={}", GEMINI_API_URL, api_key);
+
+    debug!("HTTP GET {}/models", GEMINI_API_URL);
+    let response = reqwest::Client::new().get(&url).send().await?;
+    debug!(status = %response.status(), "Gemini API responded");
+
+    parse_gemini_models(&response.json().await?)
+}
+
+/// Parses the response of the Gemini models endpoint.
+///
+/// ## Takes
+/// - `response` - The JSON response body
+///
+/// ## Returns
+/// - `Result<Vec<String>>` - The names of the models, without their `models/` prefix, or
+///   [StError::AiRequestFailed] if the response is an error.
+fn parse_gemini_models(response: &serde_json::Value) -> StResult<Vec<String>> {
+    if let Some(message) = response["error"]["message"].as_str() {
+        return Err(StError::AiRequestFailed(message.to_string()));
+    }
+    let models = response["models"]
+        .as_array()
+        .ok_or_else(|| StError::AiRequestFailed("No models in Gemini response".to_string()))?;
+    Ok(models
+        .iter()
+        .filter_map(|model| model["name"].as_str())
+        .map(|name| name.trim_start_matches("models/").to_string())
+        .collect())
+}
+
+/// Returns whether an error from [generate_with_gemini] is a failure to reach the Gemini API,
+/// rather than a failure to generate a description.
+pub(crate) fn is_connection_error(err: &StError) -> bool {
+    matches!(err, StError::ReqwestError(e) if e.is_connect() || e.is_timeout())
+}
+
+/// Generates a PR description using Google Gemini API.
+///
+/// ## Takes
+/// - `config` - The configuration, with the Gemini API key and any custom prompt
+/// - `title` - The PR title
+/// - `branch_name` - The name of the branch
+/// - `parent_name` - The name of the parent branch
+/// - `commits` - The commit messages in the branch
+/// - `diff` - The changes between the branches
+///
+/// ## Returns
+/// - `Result<String>` - The generated PR description
+pub async fn generate_pr_description_with_gemini(
+    config: &StConfig,
+    title: &str,
+    branch_name: &str,
+    parent_name: &str,
+    commits: &[String],
+    diff: &BranchDiff,
+) -> StResult<String> {
+    let prompt = build_pr_prompt(
+        &config.ai_prompt_template,
+        title,
+        branch_name,
+        parent_name,
+        commits,
+        diff,
+    );
+    let description = generate_with_gemini(&config.gemini_api_key, prompt).await?;
+    Ok(trim_to_word_budget(
+        &description,
+        config.ai_max_words.unwrap_or(DEFAULT_AI_MAX_WORDS),
+    ))
+}
+
+/// Generates a PR description using a template with Google Gemini API.
+///
+/// ## Takes
+/// - `config` - The configuration, with the Gemini API key and any custom prompt
+/// - `template` - The PR template to use
+/// - `title` - The PR title
+/// - `branch_name` - The name of the branch
+/// - `parent_name` - The name of the parent branch
+/// - `commits` - The commit messages in the branch
+/// - `diff` - The changes between the branches
+///
+/// ## Returns
+/// - `Result<String>` - The generated PR description
+pub async fn generate_pr_description_with_template_gemini(
+    config: &StConfig,
+    template: &PrTemplate,
+    title: &str,
+    branch_name: &str,
+    parent_name: &str,
+    commits: &[String],
+    diff: &BranchDiff,
+) -> StResult<String> {
+    let prompt = build_pr_prompt_with_template(
+        &config.ai_prompt_template_with_template,
+        template,
+        title,
+        branch_name,
+        parent_name,
+        commits,
+        diff,
+    );
+    let description = generate_with_gemini(&config.gemini_api_key, prompt).await?;
+    Ok(trim_to_word_budget(
+        &description,
+        config.ai_max_words.unwrap_or(DEFAULT_AI_MAX_WORDS),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        build_pr_prompt, build_pr_prompt_with_template, parse_gemini_models, response_text,
+        single_response_text, trim_to_word_budget, GeminiStream,
+    };
+    use crate::{config::PrTemplate, errors::StError, git::BranchDiff};
+    use reqwest::StatusCode;
+    use serde_json::json;
+
+    #[test]
+    fn streamed_response_matches_single_response() {
+        let single = json!({
+            "candidates": [{ "content": { "parts": [{ "text": "## Summary\nAdds ✨ streaming.\n" }] } }]
+        });
+        let single = response_text(&single).unwrap().unwrap().trim();
+
+        let events = [
+            json!({ "candidates": [{ "content": { "parts": [{ "text": "## Summary\n" }] } }] }),
+            json!({ "candidates": [{ "content": { "parts": [{ "text": "Adds ✨ " }] } }] }),
+            json!({ "candidates": [{ "content": { "parts": [{ "text": "streaming.\n" }] } }] }),
+            json!({ "candidates": [{ "finishReason": "STOP" }] }),
+        ];
+        let body = events
+            .iter()
+            .map(|event| format!("data: {}\r\n\r\n", event))
+            .collect::<String>();
+
+        // Chunks split lines, and even characters, at arbitrary points.
+        let mut stream = GeminiStream::default();
+        let mut pieces = Vec::new();
+        let mut on_text = |text: &str| pieces.push(text.to_string());
+        for chunk in body.as_bytes().chunks(7) {
+            stream.feed(chunk, &mut on_text).unwrap();
+        }
+        let streamed = stream.finish(&mut on_text).unwrap();
+
+        assert_eq!(streamed, single);
+        assert_eq!(pieces, vec!["## Summary\n", "Adds ✨ ", "streaming.\n"]);
+    }
+
+    #[test]
+    fn single_response_errors_report_status_and_message() {
+        let body = json!({
+            "candidates": [{ "content": { "parts": [{ "text": "## Summary\n" }] } }]
+        })
+        .to_string();
+        assert_eq!(
+            single_response_text(StatusCode::OK, &body).unwrap(),
+            "## Summary"
+        );
+
+        let body = json!({
+            "error": { "code": 429, "message": "Resource exhausted.", "status": "RESOURCE_EXHAUSTED" }
+        })
+        .to_string();
+        assert!(matches!(
+            single_response_text(StatusCode::TOO_MANY_REQUESTS, &body),
+            Err(StError::AiGenerationFailed(message))
+                if message == "Gemini API responded with 429 Too Many Requests: Resource exhausted."
+        ));
+
+        // Errors without a JSON body report the body itself.
+        assert!(matches!(
+            single_response_text(StatusCode::BAD_GATEWAY, "upstream unavailable\n"),
+            Err(StError::AiGenerationFailed(message))
+                if message == "Gemini API responded with 502 Bad Gateway: upstream unavailable"
+        ));
+    }
+
+    #[test]
+    fn models_are_parsed_from_response() {
+        let response = json!({
+            "models": [
+                { "name": "models/gemini-2.5-flash", "displayName": "Gemini 2.5 Flash" },
+                { "name": "models/gemini-flash-lite-latest" },
+                { "displayName": "Unnamed" }
+            ],
+            "nextPageToken": "abc"
+        });
+        assert_eq!(
+            parse_gemini_models(&response).unwrap(),
+            vec!["gemini-2.5-flash", "gemini-flash-lite-latest"]
+        );
+    }
+
+    #[test]
+    fn rejected_key_is_reported() {
+        let response = json!({
+            "error": {
+                "code": 400,
+                "message": "API key not valid. Please pass a valid API key.",
+                "status": "INVALID_ARGUMENT"
+            }
+        });
+        assert!(matches!(
+            parse_gemini_models(&response),
+            Err(StError::AiRequestFailed(message)) if message.starts_with("API key not valid")
+        ));
+    }
+
+    #[test]
+    fn custom_prompt_template_is_interpolated() {
+        let commits = vec!["Add parser".to_string(), "Fix lexer".to_string()];
+        let diff = BranchDiff {
+            patch: "+fn parse() {}".to_string(),
+            ..Default::default()
+        };
+        let prompt = build_pr_prompt(
+            "Describe `{title}` ({branch} -> {parent}) in French.\n{commits}\n{diff}",
+            "Parser",
+            "feature-parser",
+            "main",
+            &commits,
+            &diff,
+        );
+        assert_eq!(
+            prompt,
+            "Describe `Parser` (feature-parser -> main) in French.\n- Add parser\n- Fix lexer\n+fn parse() {}"
+        );
+
+        let template = PrTemplate {
+            name: "feature".to_string(),
+            content: "## Summary".to_string(),
+        };
+        let prompt = build_pr_prompt_with_template(
+            "Fill in:\n{template}\nfor {title}",
+            &template,
+            "Parser",
+            "feature-parser",
+            "main",
+            &commits,
+            &diff,
+        );
+        assert_eq!(prompt, "Fill in:\n## Summary\nfor Parser");
+
+        // Placeholders in the substituted text, and unknown placeholders, are kept verbatim.
+        let placeholder_commits = vec!["Document {diff} and {title}".to_string()];
+        let prompt = build_pr_prompt(
+            "{title} {unknown} {commits} {",
+            "Use {branch}",
+            "feature-parser",
+            "main",
+            &placeholder_commits,
+            &diff,
+        );
+        assert_eq!(
+            prompt,
+            "Use {branch} {unknown} - Document {diff} and {title} {"
+        );
+
+        // Without a custom prompt, the built-in prompt is used.
+        let prompt = build_pr_prompt("", "Parser", "feature-parser", "main", &commits, &diff);
+        assert!(prompt.starts_with("You are a technical writer"));
+        assert!(prompt.contains("Branch: feature-parser -> main"));
+    }
+
+    #[test]
+    fn over_budget_description_is_trimmed_on_paragraph_boundary() {
+        let description = "## Summary\nAdds a parser for the config.\n\n```rust\nfn parse() {}\n\nfn lex() {}\n```\n\n## Details\nThe lexer now tracks positions, which the parser reports in its errors.";
+
+        // Under budget, or without a budget, the description is kept whole.
+        assert_eq!(trim_to_word_budget(description, 100), description);
+        assert_eq!(trim_to_word_budget(description, 0), description);
+
+        // The code block is kept whole, and the last paragraph is dropped.
+        let trimmed = trim_to_word_budget(description, 20);
+        let (kept, note) = trimmed.rsplit_once("\n\n").unwrap();
+        assert_eq!(
+            kept,
+            "## Summary\nAdds a parser for the config.\n\n```rust\nfn parse() {}\n\nfn lex() {}\n```"
+        );
+        assert!(kept.split_whitespace().count() <= 20);
+        assert_eq!(note, "_The generated description was trimmed to 20 words._");
+
+        // An oversized first paragraph is cut at its last line that fits.
+        let trimmed = trim_to_word_budget("one two\nthree four\nfive six", 5);
+        assert!(trimmed.starts_with("one two\nthree four\n\n_The generated"));
+    }
+}
@@ -1,10 +1,12 @@
 //! The CLI for `st`.
 
 use crate::{
-    config::{prompt_for_configuration, StConfig},
-    ctx::StContext,
+    config::{self, prompt_for_configuration, StConfig, StConfigError},
+    constants::ST_CONFIG_ENV,
+    ctx::{ctx_path, StContext, StoreLock},
     errors::{StError, StResult},
     subcommands::Subcommands,
+    theme::{self, Color::Blue, ColorChoice},
 };
 use clap::{
     builder::styling::{AnsiColor, Color, Style},
@@ -12,7 +14,7 @@ use clap::{
 };
 use git2::{BranchType, Repository};
 use inquire::Select;
-use nu_ansi_term::Color::Blue;
+use std::{env, path::PathBuf};
 
 const ABOUT: &str = "st is a CLI application for working with stacked PRs locally and on GitHub.";
 
@@ -20,9 +22,33 @@ const ABOUT: &str = "st is a CLI application for working with stacked PRs locall
 #[derive(Parser, Debug, Clone, Eq, PartialEq)]
 #[command(about = ABOUT, version, styles = cli_styles(), arg_required_else_help(true))]
 pub struct Cli {
-    /// Verbosity level (0-4)
-    #[arg(short, action = ArgAction::Count)]
-    pub v: u8,
+    /// Log diagnostics to stderr. Repeat for more detail (`-v`, `-vv`, `-vvv`)
+    #[arg(short, long, action = ArgAction::Count, global = true)]
+    pub verbose: u8,
+    /// Suppress informational output, such as progress lines. Errors, warnings, prompts, and
+    /// results, including `--json` output, are still printed
+    #[arg(short, long, global = true)]
+    pub quiet: bool,
+    /// Emit machine-readable JSON from read-only commands (`status`, `log`, `trunk list`)
+    #[arg(long, global = true)]
+    pub json: bool,
+    /// Wait for other `st` processes to finish with the store, rather than failing immediately
+    #[arg(long, global = true)]
+    pub wait_for_lock: bool,
+    /// Operate on another trunk for this command only, without switching the active trunk.
+    /// Supported by `log`, `export`, `open`, `status`, `submit`, and `trunk info`
+    #[arg(long, global = true, value_name = "NAME")]
+    pub trunk: Option<String>,
+    /// Run as if `st` was started in this directory, like `git -C`
+    #[arg(short = 'C', long = "repo", global = true, value_name = "PATH")]
+    pub repo: Option<PathBuf>,
+    /// Use this configuration file instead of `~/.st.toml`. Defaults to the `ST_CONFIG`
+    /// environment variable, if set
+    #[arg(long, global = true, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+    /// When to color output. `auto` colors output if stdout is a terminal and `NO_COLOR` is unset
+    #[arg(long, value_enum, global = true, default_value_t = ColorChoice::Auto)]
+    pub color: ColorChoice,
     /// The subcommand to run
     #[clap(subcommand)]
     pub subcommand: Subcommands,
@@ -31,11 +57,79 @@ pub struct Cli {
 impl Cli {
     /// Run the CLI application with the given arguments.
     pub async fn run(self) -> StResult<()> {
+        crate::logging::init(self.verbose);
+        theme::init(self.color);
+        crate::output::init(self.quiet);
+
+        // Resolve the repository from the given directory rather than the working directory.
+        if let Some(dir) = &self.repo {
+            crate::git::override_repository_dir(dir.clone())?;
+        }
+
+        if self.trunk.is_some() && !self.subcommand.supports_trunk_override() {
+            return Err(StError::TrunkOverrideUnsupported);
+        }
+
+        // Completions are generated without loading the configuration or repository context.
+        if let Subcommands::Completions(args) = self.subcommand {
+            return args.run();
+        }
+
+        // Override the config file for the whole run, from the flag or the environment.
+        let config_path = self.config.clone().or_else(|| {
+            env::var_os(ST_CONFIG_ENV)
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from)
+        });
+        if let Some(path) = config_path {
+            config::override_config_path(path)?;
+        } else if let Some(path) = Self::discover_config_path() {
+            config::use_discovered_config_path(path);
+        }
+
+        // Diagnostics must not prompt to set up a missing configuration or repository.
+        if let Subcommands::Doctor(args) = self.subcommand {
+            return args.run().await;
+        }
+
+        // Likewise, validating the configuration must report problems rather than prompt.
+        if let Subcommands::Config(args) = &self.subcommand
+            && let Some(result) = args.run_standalone()
+        {
+            return result;
+        }
+
         // Load the active repository.
         let repo = crate::git::active_repository().ok_or(StError::NotAGitRepository)?;
         let config = Self::load_cfg_or_initialize()?;
-        let context = Self::load_ctx_or_initialize(config, &repo)?;
-        self.subcommand.run(context).await
+
+        // Hold the store lock while mutating commands load, modify, and persist the store.
+        // Read-only commands run without it, and so never persist the store, unless it is yet
+        // to be set up.
+        let read_only = self.subcommand.is_read_only()
+            && ctx_path(&repo).is_some_and(|store_path| store_path.exists());
+        let _lock = if read_only {
+            None
+        } else {
+            Some(StoreLock::acquire(&repo, self.wait_for_lock)?)
+        };
+
+        let mut context = Self::load_ctx_or_initialize(config, &repo)?;
+        if read_only {
+            context.set_read_only();
+        }
+        if let Some(trunk) = &self.trunk {
+            context.override_trunk(trunk)?;
+        }
+        self.subcommand.run(context, self.json).await
+    }
+
+    /// Finds the nearest `.st.toml` between the directory the repository is discovered from and
+    /// the root of the repository, if any.
+    fn discover_config_path() -> Option<PathBuf> {
+        let repo = crate::git::active_repository()?;
+        let root = repo.workdir()?;
+        config::discover_config_path(&crate::git::repository_dir()?, root)
     }
 
     /// Loads the [StConfig]. If the config does not exist or is the default config, prompts
@@ -45,16 +139,28 @@ impl Cli {
     /// - `Result<StConfig>` - The global `st` config.
     pub(crate) fn load_cfg_or_initialize() -> StResult<StConfig> {
         // Load the global configuration for `st`, or initialize it if it doesn't exist.
+        // Only a missing token prompts for setup; other problems are left for the user to fix.
+        // The setup prompt is never written to a config file discovered in the repository, so the
+        // token must be set in `~/.st.toml` instead.
         let config = match StConfig::try_load()? {
-            Some(config) if config.validate().is_ok() => config,
-            _ => prompt_for_configuration(None)?,
+            Some(config) => match config.validate() {
+                Ok(()) => config,
+                Err(StConfigError::MissingField(_)) if !config.discovered => {
+                    prompt_for_configuration(None)?
+                }
+                Err(e) => return Err(e.into()),
+            },
+            None => prompt_for_configuration(None)?,
         };
         
-        // Set the EDITOR environment variable from the config
+        // Set the EDITOR environment variable from the config, keeping the environment's editor
+        // if none is configured.
         // SAFETY: This is called during single-threaded initialization before any
         // other threads are spawned, so there's no risk of data races.
-        unsafe {
-            std::env::set_var("EDITOR", &config.editor);
+        if !config.editor.is_empty() {
+            unsafe {
+                std::env::set_var("EDITOR", &config.editor);
+            }
         }
         
         Ok(config)
@@ -1,9 +1,22 @@
 //! Contains the global configuration for `st`.
 
-use crate::{constants::ST_CFG_FILE_NAME, errors::StResult};
-use nu_ansi_term::Color;
-use serde::{Deserialize, Serialize};
-use std::{fs, io, path::PathBuf, process::Command};
+use crate::{
+    constants::ST_CFG_FILE_NAME,
+    errors::StResult,
+    theme::{Color, Theme},
+};
+use serde::{
+    de::{self, Deserializer, Visitor},
+    Deserialize, Serialize,
+};
+use std::{
+    fmt, fs,
+    io::{self, Write},
+    mem::ManuallyDrop,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::OnceLock,
+};
 use thiserror::Error;
 
 pub(crate) const DEFAULT_CONFIG_PRETTY: &str = r##"# GitHub personal access token. Used for pushing branches to GitHub remotes as well as querying
@@ -14,6 +27,9 @@ pub(crate) const DEFAULT_CONFIG_PRETTY: &str = r##"# GitHub personal access toke
 # - repo:public_repo
 #
 # If you're planning to use st with private repositories, you'll need to add the full `repo` scope.
+#
+# Leave empty to use the token of the `gh` CLI (`gh auth token`), or the github.com credential in
+# the platform keychain.
 github_token = ""
 
 # Editor to use for commit messages and PR descriptions.
@@ -25,6 +41,105 @@ editor = "nano"
 # Get your API key from: https://aistudio.google.com/app/apikey
 gemini_api_key = ""
 
+# Paths whose changes are left out of the diff sent to Gemini, as git pathspecs. Binary files,
+# lockfiles, and very large files are always left out.
+# ai_diff_exclude = ["*.snap", "generated/*"]
+
+# Prompts sent to Gemini for PR descriptions, replacing the built-in ones. `ai_prompt_template` is
+# used when no PR template is selected, and `ai_prompt_template_with_template` when one is.
+# Placeholders: {title}, {branch}, {parent}, {commits} (one `- subject` line per commit), {diff},
+# {template} (the content of the selected PR template).
+#
+# Example:
+# ai_prompt_template = """
+# Describe this pull request in French, in at most 100 words.
+# Title: {title} ({branch} -> {parent})
+# Commits:
+# {commits}
+# Diff:
+# {diff}
+# """
+
+# Maximum number of words in AI-generated PR descriptions (default: 400). Longer descriptions are
+# trimmed at the last paragraph that fits, with a note. Set to 0 to keep them whole.
+# ai_max_words = 250
+
+# Git remotes for fork-based workflows. Branches are pushed to `push_remote` (default: "origin"),
+# while pull requests are opened against the GitHub repository of `pr_remote` (default: the push
+# remote).
+#
+# Example:
+# push_remote = "origin"
+# pr_remote = "upstream"
+
+# Host of the GitHub web interface that pull request links point to (default: "github.com").
+# github_host = "github.example.com"
+
+# Maximum number of branches stacked above a trunk branch before the store is reported as corrupted
+# (default: 1000).
+# max_stack_depth = 1000
+
+# Milestone assigned to each submitted PR, by title or number. Overridden by `st submit --milestone`.
+# default_milestone = "v1.0"
+
+# GitHub users assigned to each submitted PR. Overridden by `st submit --assignee`.
+# default_assignees = ["octocat"]
+
+# Whether the PR title suggested from commit subjects is cleaned up: conventional-commit prefixes
+# (`feat(parser):`) and squash-merge suffixes (`(#123)`) are stripped, and the title is capitalized.
+# Set to false to suggest the commit subject verbatim.
+# clean_commit_titles = true
+
+# Whether to prepend a table of files changed, insertions, and deletions to the description of new
+# PRs, like `git diff --stat`. Also enabled per submission with `st submit --stat`.
+# diff_stat = true
+
+# Whether to append a `Closes #N` footer to the description of new PRs, for each issue referenced
+# by the branch name (`fix/123-thing`) or by a closing keyword (`Fixes #123`) in its commits.
+# link_issues = true
+
+# Whether to re-insert the checklist items (`- [ ] ...`) of the selected PR template that an
+# AI-generated description dropped or rewrote, with their original wording and position.
+# preserve_template_checklists = true
+
+# Whether the draft prompt for new PRs defaults to yes. Overridden per trunk by
+# `st trunk set <trunk> --draft`, and skipped by `st submit --draft` or `--no-draft`.
+# default_draft = false
+
+# Template for the commit message of squash merges made by `st merge`, and of the commit that
+# `st fixup --squash` squashes into. The first line becomes the commit title. Placeholders:
+# {branch}, {pr_number}, {subjects} (one commit subject per line, oldest first). When unset,
+# GitHub's default squash message, or the squashed commit's message, is kept.
+#
+# Example:
+# commit_message_template = """
+# {branch} (#{pr_number})
+#
+# {subjects}
+# """
+
+# Whether to add a `Signed-off-by: Name <email>` trailer, from git's `user.name` and `user.email`,
+# to the commits created by `st create` and `st fixup`. Also enabled per command with `--signoff`.
+# signoff = true
+
+# Template for the stack navigation comment posted on each PR.
+# Placeholders: {stack_list}, {current_marker} (e.g. "#42"), {trunk}
+# Note: `st sync --discover` recognizes st PRs by the attribution line of the default template.
+#
+# Example:
+# stack_comment_template = """
+# ### Stack into `{trunk}`
+# {stack_list}
+# """
+
+# Skip posting and updating the stack navigation comment on each PR, e.g. when a bot manages it.
+# Also skipped per submission with `st submit --no-comment`.
+# disable_stack_comment = true
+
+# Keep tracking branches whose git branch was deleted outside of `st`, rather than untracking them
+# as the repository is loaded. `st doctor` reports them, and `st doctor --fix` untracks them.
+# keep_orphaned_branches = true
+
 # PR Templates for AI-generated descriptions.
 # When templates are configured, you will be prompted to select one when generating PR descriptions.
 # The AI will then use the selected template to structure the PR description.
@@ -41,7 +156,24 @@ gemini_api_key = ""
 #
 # ## Impact
 # What areas of the codebase are affected?
-# """"##;
+# """
+
+# Directory of markdown PR templates, relative to the repository root. Each `*.md` file is offered
+# as a template named after the file, e.g. `bugfix.md` as "bugfix". Templates configured in
+# `pr_templates` take precedence over files of the same name.
+# Default: ".github/PULL_REQUEST_TEMPLATE"
+# pr_templates_dir = ".github/pr-templates"
+
+# Colors and symbols of the stack tree. Colors are one of black, red, green, yellow, blue, purple,
+# cyan, white, or dark-gray, and the palette repeats for deeper levels of the tree. Output is never
+# colored when the `NO_COLOR` environment variable is set or with `st --color never`.
+#
+# Example:
+# [theme]
+# palette = ["blue", "cyan", "green", "purple", "yellow", "red"]
+# checked_out_symbol = "●"
+# branch_symbol = "○"
+"##;
 
 /// A PR template for AI-generated descriptions.
 #[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
@@ -52,7 +184,7 @@ pub struct PrTemplate {
     pub content: String,
 }
 
-#[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct StConfig {
     /// GitHub personal access token.
     pub github_token: String,
@@ -62,20 +194,311 @@ pub struct StConfig {
     /// Google Gemini API key for AI-generated PR descriptions.
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub gemini_api_key: String,
+    /// Paths, as git pathspecs, whose changes are left out of the diff in AI prompts, in addition
+    /// to binary files, lockfiles, and very large files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ai_diff_exclude: Vec<String>,
+    /// Prompt for AI-generated PR descriptions without a PR template, replacing the built-in
+    /// prompt. See [crate::ai::build_pr_prompt] for its placeholders.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub ai_prompt_template: String,
+    /// Prompt for AI-generated PR descriptions that follow a PR template, replacing the built-in
+    /// prompt. See [crate::ai::build_pr_prompt_with_template] for its placeholders.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub ai_prompt_template_with_template: String,
+    /// The maximum number of words in AI-generated PR descriptions. Defaults to
+    /// [crate::ai::DEFAULT_AI_MAX_WORDS], and `0` disables the limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ai_max_words: Option<usize>,
+    /// The git remote that branches are pushed to. Defaults to `origin`.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub push_remote: String,
+    /// The git remote whose GitHub repository pull requests are opened against. Defaults to the
+    /// push remote. Set to e.g. `upstream` when pushing to a fork.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub pr_remote: String,
+    /// The host of the GitHub web interface that pull request links point to. Defaults to
+    /// `github.com`. Set to the host of a GitHub Enterprise Server instance.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub github_host: String,
+    /// The maximum number of branches stacked above a trunk branch that `st` traverses before
+    /// reporting the store as corrupted. Defaults to 1000.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_stack_depth: Option<usize>,
+    /// The milestone, by title or number, assigned to each submitted PR.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub default_milestone: String,
+    /// The GitHub users assigned to each submitted PR.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_assignees: Vec<String>,
+    /// Whether PR titles suggested from commit subjects are cleaned up, stripping conventional
+    /// commit prefixes and squash-merge suffixes. Defaults to `true`.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub clean_commit_titles: bool,
+    /// Whether to prepend a table of per-file insertions and deletions to the description of new
+    /// PRs.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub diff_stat: bool,
+    /// Whether to append a `Closes #N` footer to the description of new PRs, for each issue
+    /// referenced by the branch name or by a closing keyword in its commit messages.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub link_issues: bool,
+    /// Whether to re-insert the checklist items of the selected PR template that an AI-generated
+    /// description dropped.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub preserve_template_checklists: bool,
+    /// Whether new PRs default to drafts when prompted, unless the active trunk overrides it.
+    /// Defaults to `true`.
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub default_draft: bool,
+    /// Template for the commit message of squash merges made by `st merge`, and of commits
+    /// squashed into by `st fixup --squash`. Unset to keep GitHub's default message, or the
+    /// squashed commit's message.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub commit_message_template: String,
+    /// Whether to add a `Signed-off-by` trailer to the commits created by `st`.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub signoff: bool,
+    /// Directory of markdown PR templates, relative to the repository root. Falls back to
+    /// [DEFAULT_PR_TEMPLATES_DIR] when empty.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub pr_templates_dir: String,
     /// PR templates for AI-generated descriptions.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub pr_templates: Vec<PrTemplate>,
+    /// PR templates loaded from the templates directory. Never written to the config file.
+    #[serde(skip)]
+    pub file_templates: Vec<PrTemplate>,
+    /// Whether the configuration was loaded from a `.st.toml` discovered in the repository, which
+    /// is never written back when the configuration is dropped.
+    #[serde(skip)]
+    pub discovered: bool,
+    /// Template for the stack navigation comment posted on each PR. Falls back to
+    /// [DEFAULT_STACK_COMMENT_TEMPLATE] when empty.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub stack_comment_template: String,
+    /// Whether to skip posting and updating the stack navigation comment on each PR.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub disable_stack_comment: bool,
+    /// Whether to keep tracking branches whose git branch no longer exists, rather than untracking
+    /// them when the repository is loaded.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub keep_orphaned_branches: bool,
+    /// The palette and symbols used to render the stack tree.
+    #[serde(default, skip_serializing_if = "Theme::is_default")]
+    pub theme: Theme,
 }
 
+impl Default for StConfig {
+    /// Returns the configuration loaded from a file that sets nothing, using the same defaults
+    /// as deserialization.
+    fn default() -> Self {
+        Self {
+            github_token: String::new(),
+            editor: default_editor(),
+            gemini_api_key: String::new(),
+            ai_diff_exclude: Vec::new(),
+            ai_prompt_template: String::new(),
+            ai_prompt_template_with_template: String::new(),
+            ai_max_words: None,
+            push_remote: String::new(),
+            pr_remote: String::new(),
+            github_host: String::new(),
+            max_stack_depth: None,
+            default_milestone: String::new(),
+            default_assignees: Vec::new(),
+            clean_commit_titles: default_true(),
+            diff_stat: false,
+            link_issues: false,
+            preserve_template_checklists: false,
+            default_draft: default_true(),
+            commit_message_template: String::new(),
+            signoff: false,
+            pr_templates_dir: String::new(),
+            pr_templates: Vec::new(),
+            file_templates: Vec::new(),
+            discovered: false,
+            stack_comment_template: String::new(),
+            disable_stack_comment: false,
+            keep_orphaned_branches: false,
+            theme: Theme::default(),
+        }
+    }
+}
+
+/// A problem with the configuration, reported by [StConfig::problems].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ConfigProblem {
+    /// The offending field, e.g. `pr_templates[1].name`.
+    pub field: String,
+    /// What is wrong with the field.
+    pub message: String,
+}
+
+impl ConfigProblem {
+    /// Creates a new [ConfigProblem].
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}`: {}", Color::Blue.paint(&self.field), self.message)
+    }
+}
+
+/// The default template for the stack navigation comment.
+///
+/// Supported placeholders:
+/// - `{stack_list}` - The list of PRs in the stack, followed by the trunk branch.
+/// - `{current_marker}` - A reference to the PR the comment is posted on (e.g. `#42`).
+/// - `{trunk}` - The name of the trunk branch.
+pub const DEFAULT_STACK_COMMENT_TEMPLATE: &str = "## 📚 $\\text{Stack Overview}$
+
+Pulls submitted in this stack:
+{stack_list}
+_This comment was automatically generated by [`st`](https://github.com/mahbd/st)._";
+
 fn default_editor() -> String {
     "nano".to_string()
 }
 
+fn default_true() -> bool {
+    true
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// A source of a GitHub token, consulted when `github_token` is not configured.
+pub(crate) trait TokenSource {
+    /// Returns the token held by the source, if any.
+    fn token(&self) -> Option<String>;
+}
+
+/// Reads the token of the official `gh` CLI, via `gh auth token`.
+pub(crate) struct GhCliTokenSource;
+
+impl TokenSource for GhCliTokenSource {
+    fn token(&self) -> Option<String> {
+        let output = Command::new("gh").args(["auth", "token"]).output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        let token = String::from_utf8(output.stdout).ok()?.trim().to_string();
+        (!token.is_empty()).then_some(token)
+    }
+}
+
+/// Reads the `github.com` credential from the platform keychain (Keychain, Secret Service, or
+/// Credential Manager), via the credential helper configured for git.
+pub(crate) struct KeychainTokenSource;
+
+impl TokenSource for KeychainTokenSource {
+    fn token(&self) -> Option<String> {
+        let mut child = Command::new("git")
+            .args(["credential", "fill"])
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        child
+            .stdin
+            .take()?
+            .write_all(b"protocol=https\nhost=github.com\n\n")
+            .ok()?;
+        let output = child.wait_with_output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout)
+            .ok()?
+            .lines()
+            .find_map(|line| line.strip_prefix("password="))
+            .map(|token| token.trim().to_string())
+            .filter(|token| !token.is_empty())
+    }
+}
+
+/// The sources consulted, in order, when `github_token` is not configured.
+const TOKEN_SOURCES: [&dyn TokenSource; 2] = [&GhCliTokenSource, &KeychainTokenSource];
+
+/// The token resolved from [TOKEN_SOURCES]. Only ever held in memory, never written to disk.
+static RESOLVED_GITHUB_TOKEN: OnceLock<Option<String>> = OnceLock::new();
+
+/// Resolves a token from [TOKEN_SOURCES], consulting them at most once per process.
+struct CachedTokenSource;
+
+impl TokenSource for CachedTokenSource {
+    fn token(&self) -> Option<String> {
+        RESOLVED_GITHUB_TOKEN
+            .get_or_init(|| TOKEN_SOURCES.iter().find_map(|source| source.token()))
+            .clone()
+    }
+}
+
 impl StConfig {
-    /// Loads the configuration from disk.
+    /// Loads the configuration from the config file of this run. See [config_path].
     pub fn try_load() -> Result<Option<Self>, StConfigError> {
-        // Load the default config file from disk
-        let config_path = PathBuf::from(env!("HOME")).join(ST_CFG_FILE_NAME);
+        match discovered_config_path() {
+            Some(path) => Self::try_load_discovered(path, &global_config_path()),
+            None => Self::try_load_from(&config_path()),
+        }
+    }
+
+    /// Loads the configuration from a `.st.toml` discovered in the repository. The file is never
+    /// written back when the configuration is dropped, and the secrets it leaves empty are read
+    /// from the global config file instead.
+    ///
+    /// ## Takes
+    /// - `path` - The path of the discovered config file.
+    /// - `global_path` - The path of the global config file, usually `~/.st.toml`.
+    ///
+    /// ## Returns
+    /// - `Result<Option<StConfig>>` - The configuration, or [None] if the file doesn't exist and
+    ///   no GitHub token can be resolved without it.
+    pub fn try_load_discovered(
+        path: &Path,
+        global_path: &Path,
+    ) -> Result<Option<Self>, StConfigError> {
+        let Some(mut config) = Self::try_load_from(path)? else {
+            return Ok(None);
+        };
+        config.discovered = true;
+
+        // The global config is only read, never written back.
+        if let Ok(Some(global)) = Self::try_load_from(global_path) {
+            let global = ManuallyDrop::new(global);
+            if config.github_token.is_empty() {
+                config.github_token = global.github_token.clone();
+            }
+            if config.gemini_api_key.is_empty() {
+                config.gemini_api_key = global.gemini_api_key.clone();
+            }
+        }
+        Ok(Some(config))
+    }
+
+    /// Loads the configuration from the given config file.
+    ///
+    /// ## Takes
+    /// - `config_path` - The path of the config file.
+    ///
+    /// ## Returns
+    /// - `Result<Option<StConfig>>` - The configuration, or [None] if the file doesn't exist and
+    ///   no GitHub token can be resolved without it.
+    pub fn try_load_from(config_path: &Path) -> Result<Option<Self>, StConfigError> {
         let file_config = match std::fs::read_to_string(config_path) {
             Ok(contents) => match toml::from_str(&contents) {
                 Ok(config) => Ok(Some(config)),
@@ -87,69 +510,549 @@ impl StConfig {
             Err(_) => Ok(None),
         };
 
-        // If file config failed or doesn't exist, attempt to get the token from gh CLI
+        // If the config file doesn't exist, fall back to an empty config as long as a token can
+        // be resolved from the gh CLI or keychain. The token itself is never written to disk.
         if let Ok(None) = file_config {
-            // Try to get token from gh CLI
-            match Command::new("gh").args(["auth", "token"]).output() {
-                Ok(output) => {
-                    if output.status.success() {
-                        if let Ok(token) = String::from_utf8(output.stdout) {
-                            let token = token.trim().to_string();
-                            if !token.is_empty() {
-                                // Create new config with the token
-                                return Ok(Some(Self {
-                                    github_token: token,
-                                    editor: default_editor(),
-                                    gemini_api_key: String::new(),
-                                    pr_templates: Vec::new(),
-                                }));
-                            }
-                        }
-                    }
-                    Ok(None)
-                }
-                Err(_) => Ok(None),
+            if CachedTokenSource.token().is_none() {
+                return Ok(None);
             }
+            Ok(Some(Self::default()))
         } else {
             file_config
         }
     }
 
-    /// Validates the configuration.
+    /// Validates the configuration. An empty `github_token` is valid as long as a token can be
+    /// resolved from the gh CLI or keychain.
     pub fn validate(&self) -> Result<(), StConfigError> {
-        if self.github_token.is_empty() {
-            return Err(StConfigError::MissingField("github_token".to_string()));
+        self.validate_with(&[&CachedTokenSource])
+    }
+
+    /// Validates the configuration, consulting the given [TokenSource]s in order when
+    /// `github_token` is empty.
+    pub(crate) fn validate_with(&self, sources: &[&dyn TokenSource]) -> Result<(), StConfigError> {
+        self.github_token_from(sources)?;
+
+        // Later templates with the same name could never be selected.
+        for (i, template) in self.pr_templates.iter().enumerate() {
+            if self.pr_templates[..i]
+                .iter()
+                .any(|t| t.name == template.name)
+            {
+                return Err(StConfigError::DuplicateTemplateName(template.name.clone()));
+            }
         }
         Ok(())
     }
 
-    /// Gets the list of template names.
+    /// Collects every problem with the configuration, each with the offending field.
+    pub fn problems(&self) -> Vec<ConfigProblem> {
+        self.problems_with(&[&CachedTokenSource])
+    }
+
+    /// Collects every problem with the configuration, consulting the given [TokenSource]s in
+    /// order when `github_token` is empty.
+    ///
+    /// ## Takes
+    /// - `sources` - The [TokenSource]s to consult.
+    ///
+    /// ## Returns
+    /// - `Vec<ConfigProblem>` - The problems found, in the order of the fields.
+    pub(crate) fn problems_with(&self, sources: &[&dyn TokenSource]) -> Vec<ConfigProblem> {
+        let mut problems = Vec::new();
+        if let Err(e) = self.github_token_from(sources) {
+            problems.push(ConfigProblem::new("github_token", e.to_string()));
+        }
+
+        // An API key never contains whitespace, so it was most likely pasted incompletely.
+        if self.gemini_api_key.chars().any(char::is_whitespace) {
+            problems.push(ConfigProblem::new(
+                "gemini_api_key",
+                "The API key contains whitespace.",
+            ));
+        }
+        if self.gemini_api_key.is_empty() && !self.ai_diff_exclude.is_empty() {
+            problems.push(ConfigProblem::new(
+                "ai_diff_exclude",
+                "Set without `gemini_api_key`, so descriptions are never generated.",
+            ));
+        }
+
+        for (i, template) in self.pr_templates.iter().enumerate() {
+            let field = format!("pr_templates[{}].name", i);
+            if template.name.trim().is_empty() {
+                problems.push(ConfigProblem::new(field, "The template name is empty."));
+            } else if self.pr_templates[..i]
+                .iter()
+                .any(|other| other.name == template.name)
+            {
+                problems.push(ConfigProblem::new(
+                    field,
+                    format!("Duplicate template name `{}`.", template.name),
+                ));
+            }
+        }
+        problems
+    }
+
+    /// Gets the GitHub token. Falls back to `gh auth token` and then the platform keychain when
+    /// `github_token` is empty, caching the resolved token in memory for the rest of the process.
+    pub fn github_token(&self) -> Result<String, StConfigError> {
+        self.github_token_from(&[&CachedTokenSource])
+    }
+
+    /// Gets the GitHub token, consulting the given [TokenSource]s in order when `github_token` is
+    /// empty. Nothing is cached.
+    ///
+    /// ## Takes
+    /// - `sources` - The [TokenSource]s to consult.
+    ///
+    /// ## Returns
+    /// - `Result<String>` - The configured or first resolved token.
+    pub(crate) fn github_token_from(
+        &self,
+        sources: &[&dyn TokenSource],
+    ) -> Result<String, StConfigError> {
+        if !self.github_token.is_empty() {
+            return Ok(self.github_token.clone());
+        }
+        sources
+            .iter()
+            .find_map(|source| source.token())
+            .ok_or_else(|| StConfigError::MissingField("github_token".to_string()))
+    }
+
+    /// Gets the directory of markdown PR templates for a repository.
+    ///
+    /// ## Takes
+    /// - `workdir` - The working directory of the repository.
+    ///
+    /// ## Returns
+    /// - `PathBuf` - The configured directory, joined to `workdir` when relative.
+    pub fn templates_dir(&self, workdir: &Path) -> PathBuf {
+        if self.pr_templates_dir.is_empty() {
+            workdir.join(DEFAULT_PR_TEMPLATES_DIR)
+        } else {
+            workdir.join(&self.pr_templates_dir)
+        }
+    }
+
+    /// Loads the `*.md` files in a directory as PR templates, named by their file stem. Templates
+    /// configured in `pr_templates` take precedence over files of the same name.
+    ///
+    /// ## Takes
+    /// - `dir` - The directory to load templates from. A missing directory has no templates.
+    ///
+    /// ## Returns
+    /// - `io::Result<()>` - Ok if the templates were loaded.
+    pub fn load_file_templates(&mut self, dir: &Path) -> io::Result<()> {
+        if !dir.is_dir() {
+            self.file_templates.clear();
+            return Ok(());
+        }
+
+        let mut templates = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let is_markdown = path
+                .extension()
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("md"));
+            if !is_markdown || !path.is_file() {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+            if self.pr_templates.iter().any(|t| t.name == name) {
+                continue;
+            }
+            templates.push(PrTemplate {
+                name: name.to_string(),
+                content: fs::read_to_string(&path)?,
+            });
+        }
+        templates.sort_by(|a, b| a.name.cmp(&b.name));
+        self.file_templates = templates;
+        Ok(())
+    }
+
+    /// Gets the sorted list of template names, without duplicates.
     pub fn template_names(&self) -> Vec<&str> {
-        self.pr_templates.iter().map(|t| t.name.as_str()).collect()
+        let mut names = self
+            .pr_templates
+            .iter()
+            .chain(&self.file_templates)
+            .map(|t| t.name.as_str())
+            .collect::<Vec<_>>();
+        names.sort_unstable();
+        names.dedup();
+        names
     }
 
-    /// Gets a template by name.
+    /// Gets the stack navigation comment template, falling back to the default.
+    pub fn stack_comment_template(&self) -> &str {
+        if self.stack_comment_template.is_empty() {
+            DEFAULT_STACK_COMMENT_TEMPLATE
+        } else {
+            &self.stack_comment_template
+        }
+    }
+
+    /// Gets the name of the git remote that branches are pushed to.
+    pub fn push_remote(&self) -> &str {
+        if self.push_remote.is_empty() {
+            DEFAULT_REMOTE
+        } else {
+            &self.push_remote
+        }
+    }
+
+    /// Gets the name of the git remote that pull requests are opened against, falling back to
+    /// the push remote.
+    pub fn pr_remote(&self) -> &str {
+        if self.pr_remote.is_empty() {
+            self.push_remote()
+        } else {
+            &self.pr_remote
+        }
+    }
+
+    /// Gets the host of the GitHub web interface that pull request links point to.
+    pub fn github_host(&self) -> &str {
+        if self.github_host.is_empty() {
+            DEFAULT_GITHUB_HOST
+        } else {
+            &self.github_host
+        }
+    }
+
+    /// Gets a template by name, preferring configured templates over template files.
     pub fn get_template(&self, name: &str) -> Option<&PrTemplate> {
-        self.pr_templates.iter().find(|t| t.name == name)
+        self.pr_templates
+            .iter()
+            .chain(&self.file_templates)
+            .find(|t| t.name == name)
+    }
+
+    /// Gets the value of a configuration field by key, as it is written to the config file.
+    ///
+    /// ## Takes
+    /// - `key` - The configuration key. See [config_keys].
+    ///
+    /// ## Returns
+    /// - `Result<Option<toml::Value>>` - The value, or [None] if the field is left at its default
+    ///   and so omitted from the config file.
+    pub fn get_field(&self, key: &str) -> Result<Option<toml::Value>, StConfigError> {
+        if !config_keys().contains(&key) {
+            return Err(StConfigError::UnknownKey(key.to_string()));
+        }
+        Ok(self.to_table().remove(key))
+    }
+
+    /// Sets the value of a configuration field by key. The value is parsed as TOML, e.g. `true`,
+    /// `42`, or `["a", "b"]`, falling back to a plain string, and must have the type of the field.
+    /// The configuration is validated with the new value, and left unchanged if it is invalid.
+    ///
+    /// ## Takes
+    /// - `key` - The configuration key. See [config_keys].
+    /// - `value` - The new value, as passed to `st config set`.
+    ///
+    /// ## Returns
+    /// - `Result<()>` - Ok if the field was set.
+    pub fn set_field(&mut self, key: &str, value: &str) -> Result<(), StConfigError> {
+        if !config_keys().contains(&key) {
+            return Err(StConfigError::UnknownKey(key.to_string()));
+        }
+
+        // Values such as `code --wait` aren't valid TOML, and strings needn't be quoted.
+        let string = toml::Value::String(value.to_string());
+        let parsed = toml::from_str::<toml::Table>(&format!("value = {}", value))
+            .ok()
+            .and_then(|mut table| table.remove("value"));
+        let table = self.to_table();
+        let with_value = |value: toml::Value| {
+            let mut table = table.clone();
+            table.insert(key.to_string(), value);
+            // Never dropped unless it replaces this configuration, since dropping writes it.
+            table.try_into::<StConfig>().map(ManuallyDrop::new)
+        };
+        let mut updated = match parsed {
+            Some(parsed) if parsed != string => {
+                with_value(parsed).or_else(|e| with_value(string).map_err(|_| e))
+            }
+            _ => with_value(string),
+        }
+        .map_err(|e| StConfigError::InvalidValue(key.to_string(), e.message().to_string()))?;
+        updated.validate()?;
+
+        // Carry over the fields that are never written to the config file.
+        updated.file_templates = std::mem::take(&mut self.file_templates);
+        updated.discovered = self.discovered;
+        let _previous =
+            ManuallyDrop::new(std::mem::replace(self, ManuallyDrop::into_inner(updated)));
+        Ok(())
+    }
+
+    /// Returns the configuration as the table written to the config file.
+    fn to_table(&self) -> toml::Table {
+        toml::Table::try_from(self).expect("The configuration always serializes to a table")
+    }
+}
+
+/// Returns the keys of the config file, one for each field of [StConfig], in order.
+pub fn config_keys() -> &'static [&'static str] {
+    static KEYS: OnceLock<&'static [&'static str]> = OnceLock::new();
+    KEYS.get_or_init(|| {
+        let mut keys: &'static [&'static str] = &[];
+        let _ = StConfig::deserialize(FieldNames(&mut keys));
+        keys
+    })
+}
+
+/// A [Deserializer] that records the names of the fields of the struct deserialized from it,
+/// and then fails without deserializing anything.
+struct FieldNames<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> Deserializer<'de> for FieldNames<'_> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only the fields of a struct can be read"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        fields: &'static [&'static str],
+        _: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.0 = fields;
+        Err(de::Error::custom("only the fields of a struct are read"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+    }
+}
+
+/// The git remote used when no remote is configured.
+pub const DEFAULT_REMOTE: &str = "origin";
+
+/// The host of the GitHub web interface used when no host is configured.
+pub const DEFAULT_GITHUB_HOST: &str = "github.com";
+
+/// The directory of markdown PR templates used when no directory is configured.
+pub const DEFAULT_PR_TEMPLATES_DIR: &str = ".github/PULL_REQUEST_TEMPLATE";
+
+/// The configuration keys that hold secrets, and must be masked when displayed.
+const SECRET_KEYS: [&str; 2] = ["github_token", "gemini_api_key"];
+
+/// Returns whether or not the configuration field by the name of `key` holds a secret.
+pub fn is_secret(key: &str) -> bool {
+    SECRET_KEYS.contains(&key)
+}
+
+/// Masks a secret value for display, revealing only its last four characters.
+pub fn mask_secret(value: &str) -> String {
+    let chars = value.chars().collect::<Vec<_>>();
+    if chars.len() <= 4 {
+        return "*".repeat(chars.len());
     }
+    let visible = chars[chars.len() - 4..].iter().collect::<String>();
+    format!("{}{}", "*".repeat(chars.len() - 4), visible)
+}
+
+/// Writes a field set with `st config set` to a `.st.toml` discovered in the repository, which is
+/// otherwise never written. Other fields are kept as they are in the file, though its comments and
+/// formatting are not. Secrets are refused, since the file may be committed with the repository.
+///
+/// ## Takes
+/// - `path` - The path of the discovered config file.
+/// - `key` - The configuration key.
+/// - `value` - The new value, as returned by [StConfig::get_field]. [None] removes the field.
+///
+/// ## Returns
+/// - `Result<()>` - Ok if the field was written.
+pub fn set_discovered_field(
+    path: &Path,
+    key: &str,
+    value: Option<toml::Value>,
+) -> Result<(), StConfigError> {
+    if is_secret(key) {
+        return Err(StConfigError::SecretInRepositoryConfig(
+            key.to_string(),
+            path.display().to_string(),
+        ));
+    }
+
+    let contents = fs::read_to_string(path)
+        .map_err(|e| StConfigError::Unreadable(path.display().to_string(), e))?;
+    let mut table = toml::from_str::<toml::Table>(&contents)
+        .map_err(|e| StConfigError::FailedToLoad(io::Error::new(io::ErrorKind::InvalidData, e)))?;
+    match value {
+        Some(value) => table.insert(key.to_string(), value),
+        None => table.remove(key),
+    };
+    toml::to_string(&table)
+        .map_err(io::Error::other)
+        .and_then(|contents| fs::write(path, contents))
+        .map_err(|e| StConfigError::Unwritable(path.display().to_string(), e))
+}
+
+/// Refuses a configuration that stores secrets, before it is written to a `.st.toml` discovered
+/// in the repository. See [set_discovered_field].
+///
+/// ## Takes
+/// - `config` - The configuration to write.
+/// - `path` - The path of the discovered config file.
+///
+/// ## Returns
+/// - `Result<()>` - Ok if the configuration holds no secrets.
+pub fn check_discovered_secrets(config: &StConfig, path: &Path) -> Result<(), StConfigError> {
+    for key in SECRET_KEYS {
+        let value = config.get_field(key)?;
+        if value
+            .as_ref()
+            .and_then(toml::Value::as_str)
+            .is_some_and(|v| !v.is_empty())
+        {
+            return Err(StConfigError::SecretInRepositoryConfig(
+                key.to_string(),
+                path.display().to_string(),
+            ));
+        }
+    }
+    Ok(())
 }
 
 impl Drop for StConfig {
     fn drop(&mut self) {
-        let config_path = PathBuf::from(env!("HOME")).join(ST_CFG_FILE_NAME);
-        fs::write(&config_path, toml::to_string(self).unwrap()).unwrap();
+        // A discovered config file is likely under version control, so only explicit edits with
+        // `st config` are written to it.
+        if self.discovered {
+            return;
+        }
+        fs::write(config_path(), toml::to_string(self).unwrap()).unwrap();
     }
 }
 
+/// The config file of this run, when overridden with `--config` or `ST_CONFIG`.
+static CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// The `.st.toml` discovered in the repository, used unless the config file is overridden.
+static DISCOVERED_CONFIG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// Overrides the config file for the rest of the run. Unlike the default `~/.st.toml`, which is
+/// created on first use, an overriding file must already exist.
+///
+/// ## Takes
+/// - `path` - The path of the config file, from `--config` or `ST_CONFIG`.
+///
+/// ## Returns
+/// - `Result<()>` - Ok if the file is readable, or [StConfigError::Unreadable] otherwise.
+pub fn override_config_path(path: PathBuf) -> Result<(), StConfigError> {
+    let readable = fs::metadata(&path).and_then(|metadata| {
+        if metadata.is_file() {
+            fs::File::open(&path).map(drop)
+        } else {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, "not a file"))
+        }
+    });
+    if let Err(e) = readable {
+        return Err(StConfigError::Unreadable(path.display().to_string(), e));
+    }
+    let _ = CONFIG_PATH.set(path);
+    Ok(())
+}
+
+/// Finds the nearest `.st.toml` by walking up from `start`, like git discovers `.git`, stopping at
+/// the repository root or, if `start` is outside of it, the filesystem root.
+///
+/// ## Takes
+/// - `start` - The directory to start the search from, usually the working directory.
+/// - `root` - The root of the repository's working tree.
+///
+/// ## Returns
+/// - `Option<PathBuf>` - The path of the nearest config file, or [None] if there is none.
+pub fn discover_config_path(start: &Path, root: &Path) -> Option<PathBuf> {
+    // Compare resolved paths, so that symlinked directories still stop at the repository root.
+    let start = start.canonicalize().ok()?;
+    let root = root.canonicalize().ok();
+    for dir in start.ancestors() {
+        let path = dir.join(ST_CFG_FILE_NAME);
+        if path.is_file() {
+            return Some(path);
+        }
+        if Some(dir) == root.as_deref() {
+            break;
+        }
+    }
+    None
+}
+
+/// Uses the discovered config file for the rest of the run, unless it was overridden with
+/// `--config` or `ST_CONFIG`. See [discover_config_path].
+pub fn use_discovered_config_path(path: PathBuf) {
+    let _ = DISCOVERED_CONFIG_PATH.set(path);
+}
+
+/// Returns the `.st.toml` discovered in the repository, if it is the config file of this run.
+pub fn discovered_config_path() -> Option<&'static Path> {
+    if CONFIG_PATH.get().is_some() {
+        return None;
+    }
+    DISCOVERED_CONFIG_PATH.get().map(PathBuf::as_path)
+}
+
+/// Returns the global config file, `~/.st.toml`.
+pub fn global_config_path() -> PathBuf {
+    PathBuf::from(env!("HOME")).join(ST_CFG_FILE_NAME)
+}
+
+/// Returns the config file of this run: the overriding file, if any, the nearest `.st.toml` in the
+/// repository, or `~/.st.toml`.
+pub fn config_path() -> PathBuf {
+    CONFIG_PATH
+        .get()
+        .or(DISCOVERED_CONFIG_PATH.get())
+        .cloned()
+        .unwrap_or_else(global_config_path)
+}
+
 /// Error type for global [StConfig] operations.
 #[derive(Error, Debug)]
 pub enum StConfigError {
     /// Failed to load the configuration file.
     #[error("Failed to load the configuration file: {}", .0)]
     FailedToLoad(io::Error),
+    /// The configuration file passed with `--config` or `ST_CONFIG` can't be read.
+    #[error("Cannot read the configuration file `{}`: {}", Color::Blue.paint(.0), .1)]
+    Unreadable(String, io::Error),
+    /// The configuration file can't be written.
+    #[error("Cannot write the configuration file `{}`: {}", Color::Blue.paint(.0), .1)]
+    Unwritable(String, io::Error),
+    /// A secret was to be written to a `.st.toml` discovered in the repository.
+    #[error(
+        "Refusing to store `{}` in `{}`, which may be committed with the repository. Set it in \
+         `~/.st.toml` instead, e.g. with `st --config ~/.st.toml config set {}`.",
+        .0,
+        Color::Blue.paint(.1),
+        .0
+    )]
+    SecretInRepositoryConfig(String, String),
     /// Missing a reqired field.
     #[error("Missing required field: {}", .0)]
     MissingField(String),
+    /// Multiple PR templates share a name.
+    #[error("Duplicate PR template name: `{}`. Template names must be unique.", .0)]
+    DuplicateTemplateName(String),
+    /// An unknown configuration key was passed.
+    #[error("Unknown configuration key: {}. Valid keys: {}", .0, config_keys().join(", "))]
+    UnknownKey(String),
+    /// A configuration value doesn't have the type of its field.
+    #[error("Invalid value for `{}`: {}", .0, .1)]
+    InvalidValue(String, String),
 }
 
 /// Prompts the user to set up the global configuration for `st`.
@@ -184,11 +1087,16 @@ pub fn prompt_for_configuration(existing_config: Option<&str>) -> StResult<StCon
 
 #[cfg(test)]
 mod test {
-    use super::{StConfig, DEFAULT_CONFIG_PRETTY};
+    use super::{
+        discover_config_path, set_discovered_field, PrTemplate, StConfig, StConfigError,
+        DEFAULT_CONFIG_PRETTY,
+    };
+    use crate::git::test::TempRepository;
+    use std::{fs, mem::ManuallyDrop};
 
     #[test]
     fn pretty_default_config_is_valid() {
-        let de = toml::from_str::<StConfig>(DEFAULT_CONFIG_PRETTY);
+        let de = toml::from_str::<StConfig>(DEFAULT_CONFIG_PRETTY).map(ManuallyDrop::new);
         assert!(de.is_ok());
     }
 
@@ -206,12 +1114,50 @@ content = "## Summary\nBrief description."
 name = "bugfix"
 content = "## Problem\nDescription of the bug."
 "###;
-        let config: StConfig = toml::from_str(config_with_templates).unwrap();
+        let config = ManuallyDrop::new(toml::from_str::<StConfig>(config_with_templates).unwrap());
         assert_eq!(config.pr_templates.len(), 2);
         assert_eq!(config.pr_templates[0].name, "feature");
         assert_eq!(config.pr_templates[1].name, "bugfix");
     }
 
+    #[test]
+    fn missing_token_is_invalid_without_token_sources() {
+        let mut config = ManuallyDrop::new(
+            toml::from_str::<StConfig>("github_token = \"\"\ngemini_api_key = \"key\"\n").unwrap(),
+        );
+        let err = config.validate_with(&[]).unwrap_err();
+        assert!(matches!(err, StConfigError::MissingField(field) if field == "github_token"));
+
+        config.github_token = "ghp_test".to_string();
+        assert!(config.validate_with(&[]).is_ok());
+    }
+
+    #[test]
+    fn problems_are_reported_with_their_fields() {
+        let config = r###"
+github_token = ""
+
+[[pr_templates]]
+name = "feature"
+content = "## Summary"
+
+[[pr_templates]]
+name = "feature"
+content = "## Problem"
+"###;
+        let config = ManuallyDrop::new(toml::from_str::<StConfig>(config).unwrap());
+
+        let problems = config.problems_with(&[]);
+        let fields = problems
+            .iter()
+            .map(|p| p.field.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(fields, vec!["github_token", "pr_templates[1].name"]);
+        assert!(problems[1]
+            .message
+            .contains("Duplicate template name `feature`"));
+    }
+
     #[test]
     fn template_helpers_work() {
         let config_with_templates = r###"
@@ -226,9 +1172,107 @@ content = "## Summary\nBrief description."
 name = "bugfix"
 content = "## Problem\nDescription of the bug."
 "###;
-        let config: StConfig = toml::from_str(config_with_templates).unwrap();
-        assert_eq!(config.template_names(), vec!["feature", "bugfix"]);
+        let config = ManuallyDrop::new(toml::from_str::<StConfig>(config_with_templates).unwrap());
+        assert_eq!(config.template_names(), vec!["bugfix", "feature"]);
         assert!(config.get_template("feature").is_some());
         assert!(config.get_template("nonexistent").is_none());
     }
+
+    #[test]
+    fn markdown_files_in_templates_dir_are_templates() {
+        let repo = TempRepository::new();
+        let workdir = repo.repository.workdir().unwrap();
+        let mut config = ManuallyDrop::new(StConfig::default());
+        let dir = config.templates_dir(workdir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("feature.md"), "## Summary").unwrap();
+        fs::write(dir.join("bugfix.md"), "## Problem").unwrap();
+        fs::write(dir.join("notes.txt"), "Not a template").unwrap();
+
+        config.load_file_templates(&dir).unwrap();
+        assert_eq!(config.template_names(), vec!["bugfix", "feature"]);
+        assert_eq!(config.get_template("bugfix").unwrap().content, "## Problem");
+
+        // Configured templates take precedence over files of the same name.
+        config.pr_templates.push(PrTemplate {
+            name: "feature".to_string(),
+            content: "## Motivation".to_string(),
+        });
+        config.load_file_templates(&dir).unwrap();
+        assert_eq!(config.template_names(), vec!["bugfix", "feature"]);
+        assert_eq!(
+            config.get_template("feature").unwrap().content,
+            "## Motivation"
+        );
+    }
+
+    #[test]
+    fn config_is_discovered_up_to_repository_root() {
+        let repo = TempRepository::new();
+        let workdir = repo.repository.workdir().unwrap();
+        let nested = workdir.join("crates/core/src");
+        fs::create_dir_all(&nested).unwrap();
+
+        // Without a config file in the repository, the global config is used.
+        assert_eq!(discover_config_path(&nested, workdir), None);
+
+        let root_config = workdir.join(".st.toml");
+        fs::write(&root_config, "github_token = \"root\"\n").unwrap();
+        let found = discover_config_path(&nested, workdir).unwrap();
+        assert_eq!(found, root_config.canonicalize().unwrap());
+        let config = ManuallyDrop::new(StConfig::try_load_from(&found).unwrap().unwrap());
+        assert_eq!(config.github_token, "root");
+
+        // The nearest config file wins.
+        let crate_config = workdir.join("crates/.st.toml");
+        fs::write(&crate_config, "github_token = \"crate\"\n").unwrap();
+        assert_eq!(
+            discover_config_path(&nested, workdir).unwrap(),
+            crate_config.canonicalize().unwrap()
+        );
+
+        // The search stops at the repository root, ignoring config files above it.
+        let root = workdir.join("crates/core");
+        assert_eq!(discover_config_path(&nested, &root), None);
+    }
+
+    #[test]
+    fn discovered_config_is_never_written_back() {
+        let repo = TempRepository::new();
+        let workdir = repo.repository.workdir().unwrap();
+        let path = workdir.join(".st.toml");
+        let contents =
+            "# Shared by the team.\npush_remote = \"fork\"\n\n[theme]\nbranch_symbol = \"-\"\n";
+        fs::write(&path, contents).unwrap();
+        let global = workdir.join("global.toml");
+        fs::write(&global, "github_token = \"global\"\n").unwrap();
+
+        // Loading and dropping the configuration, as every run does, leaves both files untouched.
+        let config = StConfig::try_load_discovered(&path, &global)
+            .unwrap()
+            .unwrap();
+        assert!(config.discovered);
+        assert_eq!(config.github_token, "global");
+        assert_eq!(config.push_remote(), "fork");
+        drop(config);
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+        assert_eq!(
+            fs::read_to_string(&global).unwrap(),
+            "github_token = \"global\"\n"
+        );
+
+        // Explicit edits are written, but secrets are refused.
+        set_discovered_field(&path, "editor", Some("vim".into())).unwrap();
+        let config = ManuallyDrop::new(StConfig::try_load_from(&path).unwrap().unwrap());
+        assert_eq!(config.editor, "vim");
+        assert_eq!(config.push_remote(), "fork");
+        assert_eq!(config.theme.branch_symbol, "-");
+
+        let err =
+            set_discovered_field(&path, "github_token", Some("ghp_secret".into())).unwrap_err();
+        assert!(
+            matches!(err, StConfigError::SecretInRepositoryConfig(key, _) if key == "github_token")
+        );
+        assert!(!fs::read_to_string(&path).unwrap().contains("ghp_secret"));
+    }
 }
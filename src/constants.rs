@@ -1,6 +1,7 @@
 //! Constants for the `st` application.
 
-use nu_ansi_term::Color;
+use crate::theme::Color;
+use std::time::Duration;
 
 /// Name of the `.git` directory.
 pub const GIT_DIR: &str = ".git";
@@ -8,9 +9,54 @@ pub const GIT_DIR: &str = ".git";
 /// Name of the global config file.
 pub const ST_CFG_FILE_NAME: &str = ".st.toml";
 
+/// Environment variable overriding the path of the global config file, like `--config`.
+pub const ST_CONFIG_ENV: &str = "ST_CONFIG";
+
 /// Name of the store file, within `.git`.
 pub const ST_CTX_FILE_NAME: &str = ".st_store.toml";
 
+/// Extension appended to the store file name for the copy of the previous successful write.
+pub const ST_CTX_BACKUP_EXTENSION: &str = "bak";
+
+/// Extension appended to the store file name for the in-progress write.
+pub const ST_CTX_TEMP_EXTENSION: &str = "tmp";
+
+/// Extension appended to the store file name for the lockfile held by mutating commands.
+pub const ST_CTX_LOCK_EXTENSION: &str = "lock";
+
+/// Name of the file, within `.git`, recording the progress of an interrupted `submit` run.
+pub const ST_SUBMIT_PROGRESS_FILE_NAME: &str = ".st_submit_progress.json";
+
+/// Directory, relative to the repository root, of descriptions prepared for new pull requests,
+/// one `<branch>.md` file per branch.
+pub const ST_PR_BODY_DIR: &str = ".st/pr";
+
+/// How long the URL and state of a pull request, cached by `submit` and `status`, are trusted
+/// before they are fetched from GitHub again.
+pub const PR_CACHE_TTL: Duration = Duration::from_secs(5 * 60);
+
+/// Exit code for an unexpected error.
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Exit code when the working tree has uncommitted changes.
+pub const EXIT_DIRTY_TREE: i32 = 2;
+
+/// Exit code when a branch must be restacked before continuing.
+pub const EXIT_NEEDS_RESTACK: i32 = 3;
+
+/// Exit code when GitHub authentication is missing or rejected.
+pub const EXIT_AUTH_FAILURE: i32 = 4;
+
+/// Exit code when restacking a branch stopped on conflicts.
+pub const EXIT_CONFLICT: i32 = 5;
+
+/// Exit code when there is nothing to do, e.g. no changes to commit.
+pub const EXIT_NOTHING_TO_DO: i32 = 6;
+
+/// Exit code for invalid command line usage, as `EX_USAGE` in `sysexits.h`. Replaces clap's
+/// default of 2, which is taken by [EXIT_DIRTY_TREE].
+pub const EXIT_USAGE: i32 = 64;
+
 /// Array of colors used for displaying stacks in the terminal.
 pub const COLORS: [Color; 6] = [
     Color::Blue,
@@ -21,6 +67,20 @@ pub const COLORS: [Color; 6] = [
     Color::Red,
 ];
 
+/// Maximum number of GitHub API requests in flight at once when fetching the state of each pull
+/// request in a stack. Kept low to stay clear of GitHub's secondary rate limits.
+pub const MAX_CONCURRENT_REQUESTS: usize = 8;
+
+/// Width of the terminal assumed when it can't be detected.
+pub const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
+/// Minimum number of characters of a branch name shown in the stack tree, however narrow the
+/// terminal.
+pub const MIN_BRANCH_NAME_WIDTH: usize = 12;
+
+/// Character appended to branch names truncated to fit the terminal.
+pub const ELLIPSIS: char = '…';
+
 pub const QUOTE_CHAR: char = '▌';
 pub const FILLED_CIRCLE: char = '●';
 pub const EMPTY_CIRCLE: char = '○';
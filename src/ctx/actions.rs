@@ -1,22 +1,43 @@
 //! Actions that can be dispatched by the user.
 
-use super::StContext;
+use super::{json::PrState, StContext};
 use crate::{
     errors::{StError, StResult},
-    git::RepositoryExt,
+    git::{RepositoryExt, RestackStrategy},
+    github::{pr_states, PrStateBackend},
+    output::progress,
+    theme::Color,
 };
-use git2::BranchType;
-use nu_ansi_term::Color;
-use octocrab::{models::IssueState, pulls::PullRequestHandler};
+use git2::{BranchType, Oid};
+use octocrab::pulls::PullRequestHandler;
+use tracing::debug;
 
 impl<'a> StContext<'a> {
-    /// Restacks the branch onto the parent branch passed.
-    ///
-    /// Returns `true` if the branch was restacked, `false` otherwise.
-    pub fn restack_branch(&mut self, branch_name: &str, parent_name: &str) -> StResult<()> {
+    /// Restacks the branch onto the parent branch passed, using the given [RestackStrategy]. If
+    /// the parent was rewritten since the branch was last rebased, only the commits after the
+    /// cached parent oid are rebased.
+    pub fn restack_branch(
+        &mut self,
+        branch_name: &str,
+        parent_name: &str,
+        strategy: RestackStrategy,
+    ) -> StResult<()> {
+        let parent_oid = self
+            .repository
+            .find_branch(parent_name, BranchType::Local)?
+            .get()
+            .target()
+            .ok_or(StError::MissingParentOidCache)?;
+        let parent_oid_cache = self
+            .tree
+            .get(branch_name)
+            .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?
+            .parent_oid_cache
+            .clone();
+
         // Skip branches that do not need to be restacked.
         if !self.needs_restack(branch_name)? {
-            println!(
+            progress!(
                 "Branch `{}` does not need to be restacked onto `{}`.",
                 Color::Green.paint(branch_name),
                 Color::Yellow.paint(parent_name)
@@ -24,29 +45,47 @@ impl<'a> StContext<'a> {
             return Ok(());
         }
 
-        // Rebase the branch onto its parent.
-        if let Err(e) = self.repository.rebase_branch_onto(branch_name, parent_name) {
-            eprintln!(
-                "Failed to rebase branch `{}` onto `{}`",
-                Color::Green.paint(branch_name),
-                Color::Yellow.paint(parent_name),
-            );
-            return Err(e.into());
+        // If the parent was rewritten rather than fast-forwarded, its old commits must not be
+        // replayed onto its new head, so only rebase the commits after the cached parent oid.
+        let cached_parent_oid = parent_oid_cache
+            .and_then(|oid| Oid::from_str(&oid).ok())
+            .filter(|oid| self.repository.find_commit(*oid).is_ok());
+        let diverged_from = match cached_parent_oid {
+            Some(cached) if !self.repository.is_ancestor(cached, parent_oid)? => Some(cached),
+            _ => None,
+        };
+        let rebased = match (strategy, diverged_from) {
+            (RestackStrategy::Merge, _) => {
+                self.repository.merge_branch_into(branch_name, parent_name)
+            }
+            (RestackStrategy::Rebase, Some(cached)) => {
+                debug!(
+                    branch = branch_name,
+                    parent = parent_name,
+                    "parent diverged from cache"
+                );
+                self.repository
+                    .rebase_commits_onto(branch_name, &cached.to_string(), parent_name)
+            }
+            (RestackStrategy::Rebase, None) => {
+                self.repository.rebase_branch_onto(branch_name, parent_name)
+            }
+        };
+        if let Err(source) = rebased {
+            return Err(StError::RestackConflict {
+                branch: branch_name.to_string(),
+                parent: parent_name.to_string(),
+                source,
+            });
         }
 
         // Update the parent oid cache.
-        let parent_oid = self
-            .repository
-            .find_branch(parent_name, BranchType::Local)?
-            .get()
-            .target()
-            .ok_or(StError::MissingParentOidCache)?;
         self.tree
             .get_mut(branch_name)
             .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?
             .parent_oid_cache = Some(parent_oid.to_string());
 
-        println!(
+        progress!(
             "Restacked branch `{}` onto `{}`.",
             Color::Green.paint(branch_name),
             Color::Yellow.paint(parent_name)
@@ -72,41 +111,69 @@ impl<'a> StContext<'a> {
         Ok(())
     }
 
+    /// Returns the branches passed whose pull requests have been merged or closed, along with
+    /// the [PrState] of each. Branches that have not been submitted are skipped.
+    pub(crate) async fn closed_branches(
+        &self,
+        branches: &[String],
+        backend: &impl PrStateBackend,
+    ) -> StResult<Vec<(String, PrState)>> {
+        let mut submitted = Vec::new();
+        for branch in branches.iter() {
+            let tracked_branch = self
+                .tree
+                .get(branch)
+                .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?;
+
+            if let Some(remote_meta) = tracked_branch.remote.as_ref() {
+                submitted.push((branch, remote_meta.pr_number));
+            }
+        }
+
+        // Fetch the states concurrently, then pick out the closed branches in stack order.
+        let pr_numbers = submitted.iter().map(|(_, n)| *n).collect::<Vec<_>>();
+        let states = pr_states(backend, &pr_numbers).await?;
+        Ok(submitted
+            .into_iter()
+            .zip(states)
+            .filter(|(_, state)| matches!(state, PrState::Merged | PrState::Closed))
+            .map(|((branch, _), state)| (branch.clone(), state))
+            .collect())
+    }
+
     /// Checks if any branches passed have corresponding closed pull requests, and deletes them
     /// if the user confirms.
     pub async fn delete_closed_branches(
         &mut self,
         branches: &[String],
         pulls: &mut PullRequestHandler<'_>,
+    ) -> StResult<usize> {
+        let closed = self.closed_branches(branches, &*pulls).await?;
+        self.offer_to_delete_closed(&closed)
+    }
+
+    /// Offers to delete each of the passed branches, whose pull requests are closed as reported by
+    /// [Self::closed_branches]. Returns the number of branches deleted.
+    pub(crate) fn offer_to_delete_closed(
+        &mut self,
+        closed: &[(String, PrState)],
     ) -> StResult<usize> {
         let mut num_closed = 0;
-        for branch in branches.iter() {
-            let tracked_branch = self
-                .tree
-                .get(branch)
-                .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?;
+        for (branch, state) in closed {
+            let confirm = inquire::Confirm::new(
+                format!(
+                    "Pull request for branch `{}` is {}. Would you like to delete the local branch?",
+                    Color::Green.paint(branch),
+                    Color::Purple.bold().paint(state.to_string())
+                )
+                .as_str(),
+            )
+            .with_default(false)
+            .prompt()?;
 
-            if let Some(remote_meta) = tracked_branch.remote.as_ref() {
-                let remote_pr = pulls.get(remote_meta.pr_number).await?;
-                let pr_state = remote_pr.state.ok_or(StError::PullRequestNotFound)?;
-
-                if matches!(pr_state, IssueState::Closed) || remote_pr.merged_at.is_some() {
-                    let confirm = inquire::Confirm::new(
-                        format!(
-                            "Pull request for branch `{}` is {}. Would you like to delete the local branch?",
-                            Color::Green.paint(branch),
-                            Color::Purple.bold().paint("closed")
-                        )
-                        .as_str(),
-                    )
-                    .with_default(false)
-                    .prompt()?;
-
-                    if confirm {
-                        self.delete_branch(branch, true)?;
-                        num_closed += 1;
-                    }
-                }
+            if confirm {
+                self.delete_branch(branch, true)?;
+                num_closed += 1;
             }
         }
         Ok(num_closed)
@@ -141,11 +208,21 @@ impl<'a> StContext<'a> {
         // Exit early if the user doesn't confirm.
         if !confirm {
             if must_delete_from_tree {
+                self.pin_fork_points(branch_name)?;
                 self.tree.delete(branch_name)?;
             }
             return Ok(());
         }
 
+        self.remove_branch(branch_name)
+    }
+
+    /// Deletes a tracked branch locally and untracks it without confirmation. Its children are
+    /// re-linked onto its parent.
+    pub fn remove_branch(&mut self, branch_name: &str) -> StResult<()> {
+        // Remember where the children forked off before the branch is gone.
+        self.pin_fork_points(branch_name)?;
+
         // Check out the trunk branch prior to deletion.
         self.repository
             .checkout_branch(self.tree.trunk_name())?;
@@ -160,4 +237,254 @@ impl<'a> StContext<'a> {
 
         Ok(())
     }
+
+    /// Returns the commit of `parent_name` that `branch_name` was built on: the parent oid cache,
+    /// if it is still in the branch's history, or else the merge base of the two branches.
+    pub(crate) fn fork_point(&self, branch_name: &str, parent_name: &str) -> StResult<Oid> {
+        let branch_oid = self.repository.revparse_single(branch_name)?.id();
+        let cached = self
+            .tree
+            .get(branch_name)
+            .and_then(|b| b.parent_oid_cache.as_deref())
+            .and_then(|oid| Oid::from_str(oid).ok())
+            .filter(|oid| self.repository.find_commit(*oid).is_ok());
+        match cached {
+            Some(cached) if self.repository.is_ancestor(cached, branch_oid)? => Ok(cached),
+            _ => RepositoryExt::merge_base(self.repository, branch_name, parent_name),
+        }
+    }
+
+    /// Pins the parent oid cache of each child of a branch that is about to be untracked to the
+    /// commit the child forked off at. Once the children are re-linked onto the trunk, e.g. after
+    /// the branch was squash-merged, restacking then rebases only their own commits, analogous to
+    /// `git rebase --onto <trunk> <old parent tip> <child>`.
+    fn pin_fork_points(&mut self, branch_name: &str) -> StResult<()> {
+        let children = self
+            .tree
+            .get(branch_name)
+            .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?
+            .children
+            .clone();
+        for child in children {
+            let fork_point = self.fork_point(&child, branch_name)?;
+            self.tree
+                .get_mut(&child)
+                .ok_or_else(|| StError::BranchNotTracked(child.clone()))?
+                .parent_oid_cache = Some(fork_point.to_string());
+        }
+        Ok(())
+    }
+
+    /// Restacks the branches passed onto their parents, parents before children. A branch whose
+    /// rebase stops on conflicts has it aborted and is reported, rather than failing the rest.
+    /// Any other failure, such as a dirty working tree, is returned as is.
+    ///
+    /// ## Takes
+    /// - `branches` - The names of the tracked branches to restack.
+    ///
+    /// ## Returns
+    /// - `Result<usize>` - The number of branches that failed to restack.
+    pub fn try_restack_branches(&mut self, branches: &[String]) -> StResult<usize> {
+        let mut num_conflicts = 0;
+        for branch in branches {
+            if !self.needs_restack(branch)? {
+                continue;
+            }
+            let parent_name = self
+                .tree
+                .get(branch)
+                .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?
+                .parent
+                .clone()
+                .expect("Parent must exist");
+
+            if let Err(e) = self.restack_branch(branch, &parent_name, RestackStrategy::Rebase) {
+                // Without a rebase in progress, there is nothing to abort.
+                if !self.repository.is_rebasing() {
+                    return Err(e);
+                }
+                self.repository.abort_rebase()?;
+                println!(
+                    "Failed to restack branch `{}` onto `{}`.",
+                    Color::Green.paint(branch),
+                    Color::Yellow.paint(parent_name)
+                );
+                num_conflicts += 1;
+            }
+        }
+        Ok(num_conflicts)
+    }
+
+    /// Restacks the descendants of a branch after the branch was rewritten, then checks the
+    /// branch out again. Branches that fail to restack are reported, with how to resolve them.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the rewritten branch.
+    ///
+    /// ## Returns
+    /// - `Result<usize>` - The number of descendants that failed to restack.
+    pub fn restack_descendants(&mut self, branch_name: &str) -> StResult<usize> {
+        let descendants = self.tree.descendants(branch_name)?;
+        if descendants.is_empty() {
+            return Ok(0);
+        }
+
+        let num_conflicts = self.try_restack_branches(&descendants)?;
+        self.repository.checkout_branch(branch_name)?;
+        if num_conflicts > 0 {
+            println!(
+                "Failed to restack {} branches. You can resolve conflicts by checking out the stack and running `{}`.",
+                Color::Red.paint(num_conflicts.to_string()),
+                Color::Blue.paint("st restack")
+            );
+        }
+        Ok(num_conflicts)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        constants::MAX_CONCURRENT_REQUESTS,
+        ctx::{json::PrState, test::test_context},
+        errors::StResult,
+        git::test::TempRepository,
+        github::PrStateBackend,
+        tree::{RemoteMetadata, StackTree},
+    };
+    use git2::build::CheckoutBuilder;
+    use std::{cell::Cell, collections::HashMap};
+
+    /// A mock [PrStateBackend] that serves canned pull request states.
+    struct MockStates(HashMap<u64, PrState>);
+
+    impl PrStateBackend for MockStates {
+        async fn pr_state(&self, pr_number: u64) -> StResult<PrState> {
+            Ok(self.0[&pr_number])
+        }
+    }
+
+    #[tokio::test]
+    async fn merged_and_closed_branches_are_pruned() {
+        let repo = TempRepository::new();
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "merged").unwrap();
+        tree.insert("merged", "b", "open").unwrap();
+        tree.insert("open", "c", "closed").unwrap();
+        tree.insert("closed", "d", "draft").unwrap();
+        tree.insert("draft", "e", "unsubmitted").unwrap();
+        for (branch, pr_number) in [("merged", 1), ("open", 2), ("closed", 3), ("draft", 4)] {
+            tree.get_mut(branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+        }
+        let ctx = test_context(&repo.repository, tree);
+        let backend = MockStates(HashMap::from([
+            (1, PrState::Merged),
+            (2, PrState::Open),
+            (3, PrState::Closed),
+            (4, PrState::Draft),
+        ]));
+
+        let branches = ctx.tree.branches().unwrap();
+        let closed = ctx.closed_branches(&branches[1..], &backend).await.unwrap();
+        assert_eq!(
+            closed,
+            vec![
+                ("merged".to_string(), PrState::Merged),
+                ("closed".to_string(), PrState::Closed)
+            ]
+        );
+    }
+
+    /// A mock [PrStateBackend] that records the most requests it had in flight at once.
+    #[derive(Default)]
+    struct CountingStates {
+        in_flight: Cell<usize>,
+        max_in_flight: Cell<usize>,
+    }
+
+    impl PrStateBackend for CountingStates {
+        async fn pr_state(&self, pr_number: u64) -> StResult<PrState> {
+            self.in_flight.set(self.in_flight.get() + 1);
+            self.max_in_flight
+                .set(self.max_in_flight.get().max(self.in_flight.get()));
+            tokio::task::yield_now().await;
+            self.in_flight.set(self.in_flight.get() - 1);
+            Ok(if pr_number.is_multiple_of(3) {
+                PrState::Merged
+            } else {
+                PrState::Open
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn pr_states_are_fetched_concurrently_within_bound() {
+        let repo = TempRepository::new();
+        let mut tree = StackTree::new("main".to_string());
+        let mut parent = "main".to_string();
+        for pr_number in 1..=20 {
+            let branch = format!("feature-{}", pr_number);
+            tree.insert(&parent, "a", &branch).unwrap();
+            tree.get_mut(&branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+            parent = branch;
+        }
+        let ctx = test_context(&repo.repository, tree);
+        let backend = CountingStates::default();
+
+        let branches = ctx.tree.branches().unwrap();
+        let closed = ctx.closed_branches(&branches[1..], &backend).await.unwrap();
+        let expected = [3, 6, 9, 12, 15, 18]
+            .map(|n| (format!("feature-{}", n), PrState::Merged))
+            .to_vec();
+        assert_eq!(closed, expected);
+        assert!(backend.max_in_flight.get() > 1);
+        assert!(backend.max_in_flight.get() <= MAX_CONCURRENT_REQUESTS);
+    }
+
+    #[test]
+    fn squash_merged_parent_leaves_child_with_own_commits() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit_file("Feature 1", "feature-1.txt", "feature 1");
+        repo.branch("feature-2");
+        repo.commit_file("Feature 2a", "feature-2.txt", "feature 2a");
+        repo.commit_file("Feature 2b", "feature-2.txt", "feature 2b");
+
+        // `feature-2` was tracked without a parent oid cache, e.g. by `st sync --discover`.
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2")
+            .unwrap();
+        tree.get_mut("feature-2").unwrap().parent_oid_cache = None;
+        let mut ctx = test_context(&repo.repository, tree);
+
+        // Squash merge `feature-1` into `main`, then prune it.
+        repo.checkout("main");
+        repo.commit_file("Feature 1 (#1)", "feature-1.txt", "feature 1");
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        ctx.remove_branch("feature-1").unwrap();
+
+        // `feature-2` is re-linked onto `main`, and rebasing it from the old tip of `feature-1`
+        // replays only its own commits.
+        let feature_2 = ctx.tree.get("feature-2").unwrap();
+        assert_eq!(feature_2.parent.as_deref(), Some("main"));
+        let upstream = feature_2.parent_oid_cache.clone().unwrap();
+        assert_eq!(upstream, feature_1_head.to_string());
+        assert!(ctx.needs_restack("feature-2").unwrap());
+
+        let mut walk = repo.repository.revwalk().unwrap();
+        walk.push_range(&format!("{}..feature-2", upstream))
+            .unwrap();
+        let replayed = walk
+            .map(|oid| {
+                let commit = repo.repository.find_commit(oid.unwrap()).unwrap();
+                commit.summary().unwrap().to_string()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(replayed, vec!["Feature 2b", "Feature 2a"]);
+    }
 }
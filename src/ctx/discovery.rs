@@ -6,11 +6,12 @@
 use crate::{
     config::StConfig,
     errors::{StError, StResult},
+    github::github_client,
+    theme::Color,
     tree::{RemoteMetadata, StackTree},
 };
 use git2::{BranchType, Repository};
 use inquire::Confirm;
-use nu_ansi_term::Color;
 use octocrab::Octocrab;
 use std::collections::{HashMap, HashSet};
 
@@ -47,10 +48,10 @@ async fn check_for_st_signature(
         .await
     {
         for comment in comments.items {
-            if let Some(body) = comment.body {
-                if body.contains(ST_SIGNATURE) {
-                    return true;
-                }
+            if let Some(body) = comment.body
+                && body.contains(ST_SIGNATURE)
+            {
+                return true;
             }
         }
     }
@@ -59,9 +60,12 @@ async fn check_for_st_signature(
 }
 
 /// Fetches a branch from origin and creates a local tracking branch.
-fn fetch_and_create_local_branch(branch_name: &str) -> StResult<()> {
+fn fetch_and_create_local_branch(repo: &Repository, branch_name: &str) -> StResult<()> {
+    let workdir = repo.workdir().unwrap_or_else(|| repo.path());
+
     // Fetch the branch from origin
     let output = std::process::Command::new("git")
+        .current_dir(workdir)
         .args(["fetch", "origin", branch_name])
         .output()?;
 
@@ -74,6 +78,7 @@ fn fetch_and_create_local_branch(branch_name: &str) -> StResult<()> {
 
     // Create local branch from remote
     let output = std::process::Command::new("git")
+        .current_dir(workdir)
         .args([
             "branch",
             "--track",
@@ -85,6 +90,7 @@ fn fetch_and_create_local_branch(branch_name: &str) -> StResult<()> {
     if !output.status.success() {
         // Branch might already exist, try to set upstream
         let output = std::process::Command::new("git")
+            .current_dir(workdir)
             .args([
                 "branch",
                 "--set-upstream-to",
@@ -225,9 +231,7 @@ pub async fn sync_discovered_branches(
     let (owner, repo_name) = parse_owner_and_repo(repo)?;
     let trunk_name = existing_tree.trunk_name().to_string();
 
-    let gh_client = Octocrab::builder()
-        .personal_token(config.github_token.clone())
-        .build()?;
+    let gh_client = github_client(config)?;
 
     println!("🔍 Scanning for st PRs...\n");
 
@@ -485,16 +489,16 @@ pub async fn sync_discovered_branches(
             // Parent is ready, track this branch
             // Check if branch exists locally, if not try to fetch
             let branch_exists_locally = repo.find_branch(branch_name, BranchType::Local).is_ok();
-            if !branch_exists_locally {
-                if let Err(e) = fetch_and_create_local_branch(branch_name) {
-                    eprintln!(
-                        "  {} Failed to fetch '{}': {}",
-                        Color::Red.paint("✗"),
-                        branch_name,
-                        e
-                    );
-                    return false; // Remove from remaining
-                }
+            if !branch_exists_locally
+                && let Err(e) = fetch_and_create_local_branch(repo, branch_name)
+            {
+                eprintln!(
+                    "  {} Failed to fetch '{}': {}",
+                    Color::Red.paint("✗"),
+                    branch_name,
+                    e
+                );
+                return false; // Remove from remaining
             }
 
             // Get parent OID
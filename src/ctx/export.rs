@@ -0,0 +1,185 @@
+//! Portable snapshots of a stack, written by `st export` and read by `st import`.
+//!
+//! Unlike the store, a snapshot only records the shape of the stack and its pull requests, so it
+//! can be shared with collaborators or restored into a fresh clone.
+
+use super::StContext;
+use crate::{
+    errors::{StError, StResult},
+    tree::{RemoteMetadata, StackTree},
+};
+use git2::BranchType;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// The version of the snapshot format written by [StContext::export_stack].
+pub const EXPORT_VERSION: u32 = 1;
+
+/// A portable snapshot of the stacks on a single trunk branch.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StackExport {
+    /// The version of the snapshot format.
+    pub version: u32,
+    /// The name of the trunk branch.
+    pub trunk: String,
+    /// The branches stacked on the trunk, with parents listed before their children.
+    pub branches: Vec<ExportedBranch>,
+}
+
+/// A single branch within a [StackExport].
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ExportedBranch {
+    /// The name of the branch.
+    pub name: String,
+    /// The name of the parent branch.
+    pub parent: String,
+    /// The number of the pull request for the branch, if submitted.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr_number: Option<u64>,
+}
+
+impl StContext<'_> {
+    /// Snapshots the stacks on the active trunk as a [StackExport].
+    pub fn export_stack(&self) -> StResult<StackExport> {
+        let branches = self
+            .tree
+            .branches()?
+            .into_iter()
+            .skip(1)
+            .map(|branch| {
+                let tracked_branch = self
+                    .tree
+                    .get(&branch)
+                    .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?;
+                Ok(ExportedBranch {
+                    parent: tracked_branch
+                        .parent
+                        .clone()
+                        .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?,
+                    pr_number: tracked_branch.remote.as_ref().map(|r| r.pr_number),
+                    name: branch,
+                })
+            })
+            .collect::<StResult<Vec<_>>>()?;
+
+        Ok(StackExport {
+            version: EXPORT_VERSION,
+            trunk: self.tree.trunk_name().to_string(),
+            branches,
+        })
+    }
+
+    /// Reconstructs the stacks in a [StackExport], replacing any branches currently tracked on
+    /// its trunk, and makes its trunk the active trunk. Each branch must exist locally. Parent
+    /// oid caches are set to the merge-base of each branch and its parent, so that branches
+    /// based on an older parent commit are flagged for restacking.
+    ///
+    /// ## Takes
+    /// - `export` - The snapshot to import.
+    ///
+    /// ## Returns
+    /// - `Result<usize>` - The number of imported branches, excluding the trunk.
+    pub fn import_stack(&mut self, export: &StackExport) -> StResult<usize> {
+        if export.version != EXPORT_VERSION {
+            return Err(StError::DecodingError(format!(
+                "unsupported stack export version {}",
+                export.version
+            )));
+        }
+
+        // Validate that every referenced branch exists before modifying the tree.
+        let heads = std::iter::once(&export.trunk)
+            .chain(export.branches.iter().map(|b| &b.name))
+            .map(|name| {
+                let oid = self
+                    .repository
+                    .find_branch(name, BranchType::Local)
+                    .map_err(|_| StError::BranchNotFound(name.clone()))?
+                    .get()
+                    .peel_to_commit()?
+                    .id();
+                Ok((name.as_str(), oid))
+            })
+            .collect::<StResult<HashMap<_, _>>>()?;
+
+        // Build the imported trunk in a scratch tree, so a malformed snapshot leaves the store
+        // untouched.
+        let mut imported = StackTree::new(export.trunk.clone());
+        for branch in &export.branches {
+            let parent_head = heads
+                .get(branch.parent.as_str())
+                .ok_or_else(|| StError::BranchNotTracked(branch.parent.clone()))?;
+            let merge_base = self
+                .repository
+                .merge_base(heads[branch.name.as_str()], *parent_head)?;
+            imported.insert(&branch.parent, &merge_base.to_string(), &branch.name)?;
+            imported.get_mut(&branch.name).expect("Must exist").remote =
+                branch.pr_number.map(RemoteMetadata::new);
+        }
+
+        let trunk = imported
+            .trunks
+            .remove(&export.trunk)
+            .expect("Imported trunk must exist");
+        self.tree.trunks.insert(export.trunk.clone(), trunk);
+        self.tree.active_trunk = export.trunk.clone();
+        Ok(export.branches.len())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ctx::test::test_context,
+        errors::StError,
+        git::test::TempRepository,
+        tree::{RemoteMetadata, StackTree},
+    };
+
+    #[test]
+    fn export_then_import_reproduces_tree() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit("Feature 1");
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+        repo.checkout("feature-1");
+        repo.branch("feature-3");
+        repo.commit("Feature 3");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-3")
+            .unwrap();
+        tree.get_mut("feature-1").unwrap().remote = Some(RemoteMetadata::new(7));
+        let ctx = test_context(&repo.repository, tree.clone());
+
+        let export = ctx.export_stack().unwrap();
+        let json = serde_json::to_string_pretty(&export).unwrap();
+
+        let mut imported = test_context(&repo.repository, StackTree::new("main".to_string()));
+        let count = imported
+            .import_stack(&serde_json::from_str(&json).unwrap())
+            .unwrap();
+
+        assert_eq!(count, 3);
+        assert_eq!(imported.tree, tree);
+    }
+
+    #[test]
+    fn import_rejects_missing_branches() {
+        let repo = TempRepository::new();
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "unused", "feature-1").unwrap();
+        let export = test_context(&repo.repository, tree).export_stack().unwrap();
+
+        let mut ctx = test_context(&repo.repository, StackTree::new("main".to_string()));
+        let err = ctx.import_stack(&export).unwrap_err();
+        assert!(matches!(err, StError::BranchNotFound(name) if name == "feature-1"));
+        assert!(ctx.tree.get("feature-1").is_none());
+    }
+}
@@ -3,14 +3,19 @@
 use super::StContext;
 use crate::{
     constants::{
-        BOTTOM_LEFT_BOX, COLORS, EMPTY_CIRCLE, FILLED_CIRCLE, HORIZONTAL_BOX, LEFT_FORK_BOX,
-        VERTICAL_BOX,
+        BOTTOM_LEFT_BOX, DEFAULT_TERMINAL_WIDTH, ELLIPSIS, HORIZONTAL_BOX, LEFT_FORK_BOX,
+        MIN_BRANCH_NAME_WIDTH, VERTICAL_BOX,
     },
     errors::{StError, StResult},
-    git::RepositoryExt,
+    github::pull_request_url,
+    theme::Color,
+};
+use std::{
+    borrow::Cow,
+    env,
+    fmt::{Display, Write},
+    io::{self, IsTerminal},
 };
-use nu_ansi_term::Color;
-use std::fmt::{Display, Write};
 
 impl StContext<'_> {
     /// Gathers an in-order list of [DisplayBranch]es, containing the log-line and branch name.
@@ -46,37 +51,62 @@ impl StContext<'_> {
         Ok(())
     }
 
-    /// Writes the tree of branches contained within the [StContext] to the given [Write]r.
+    /// Writes the tree of branches contained within the [StContext] to the given [Write]r,
+    /// truncating branch names that don't fit within the width of the terminal.
     pub fn write_tree<W: Write>(&self, w: &mut W) -> StResult<()> {
+        self.write_tree_with_width(w, terminal_width())
+    }
+
+    /// Writes the tree of branches contained within the [StContext] to the given [Write]r,
+    /// truncating branch names that don't fit within `width` columns.
+    ///
+    /// ## Takes
+    /// - `w` - The writer to write the tree to.
+    /// - `width` - The width of the terminal, in columns.
+    pub fn write_tree_with_width<W: Write>(&self, w: &mut W, width: usize) -> StResult<()> {
         let trunk_name = self.tree.trunk_name();
-        self.write_tree_recursive(w, trunk_name, 0, "", "", true)
+        let mut tree_writer = TreeWriter { w, width };
+        self.write_tree_recursive(&mut tree_writer, trunk_name, 0, "", "", true)
     }
 
     /// Writes the tree of branches to the given writer recursively.
     fn write_tree_recursive<W: Write>(
         &self,
-        w: &mut W,
+        w: &mut TreeWriter<'_, W>,
         branch: &str,
         depth: usize,
         prefix: &str,
         connection: &str,
         is_parent_last_child: bool,
     ) -> StResult<()> {
-        // Grab the checked out branch.
-        let checked_out = self.repository.current_branch_name()?;
+        // Grab the checked out branch. With a detached HEAD, no branch is marked as checked out.
+        let checked_out = match self.current_branch() {
+            Ok(checked_out) => Some(checked_out),
+            Err(StError::DetachedHead) => None,
+            Err(e) => return Err(e),
+        };
         let current = self
             .tree
             .get(branch)
             .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?;
 
         // Form the log-line for the current branch.
-        let checked_out_icon = if branch == checked_out {
-            FILLED_CIRCLE
+        let theme = &self.cfg.theme;
+        let checked_out_icon = if checked_out.as_deref() == Some(branch) {
+            &theme.checked_out_symbol
         } else {
-            EMPTY_CIRCLE
+            &theme.branch_symbol
         };
-        let rendered_branch = COLORS[depth % COLORS.len()]
-            .paint(format!("{}{} {}", connection, checked_out_icon, branch));
+        // Every level of the tree is indented by two columns, followed by the icon and a space.
+        let indent = depth * 2 + checked_out_icon.chars().count() + 1;
+        let branch_name = truncate_branch_name(
+            branch,
+            w.width.saturating_sub(indent).max(MIN_BRANCH_NAME_WIDTH),
+        );
+        let rendered_branch = theme.color(depth).paint(format!(
+            "{}{} {}",
+            connection, checked_out_icon, branch_name
+        ));
         let branch_metadata = {
             let needs_restack = if self.needs_restack(branch)? {
                 " (needs restack)"
@@ -85,11 +115,14 @@ impl StContext<'_> {
             };
             let pull_request = current
                 .remote
+                .as_ref()
                 .map(|r| {
                     let (owner, repo) = self.owner_and_repository()?;
-                    Ok::<_, StError>(Color::Purple.italic().paint(format!(
-                        "https://github.com/{}/{}/pull/{}",
-                        owner, repo, r.pr_number
+                    Ok::<_, StError>(Color::Purple.italic().paint(pull_request_url(
+                        self.cfg.github_host(),
+                        &owner,
+                        &repo,
+                        r.pr_number,
                     )))
                 })
                 .transpose()?;
@@ -101,7 +134,7 @@ impl StContext<'_> {
         };
 
         // Write the current branch to the writer.
-        writeln!(w, "{}{}{}", prefix, rendered_branch, branch_metadata)?;
+        writeln!(w.w, "{}{}{}", prefix, rendered_branch, branch_metadata)?;
 
         // Write the children of the branch recursively.
         let mut children = current.children.iter().peekable();
@@ -120,7 +153,7 @@ impl StContext<'_> {
 
             // Form the prefix for the current log-line
             let prefix = if depth > 0 {
-                let color = COLORS[depth % COLORS.len()];
+                let color = self.cfg.theme.color(depth);
                 is_parent_last_child
                     .then(|| format!("{}  ", prefix))
                     .unwrap_or(format!(
@@ -147,6 +180,53 @@ impl StContext<'_> {
     }
 }
 
+/// A [Write]r for the stack tree, along with the width of the terminal it is rendered for.
+struct TreeWriter<'a, W: Write> {
+    /// The writer to write the tree to.
+    w: &'a mut W,
+    /// The width of the terminal, in columns.
+    width: usize,
+}
+
+/// Returns the width of the terminal, from the `COLUMNS` environment variable or the size of the
+/// terminal attached to stdout. Falls back to [DEFAULT_TERMINAL_WIDTH] when neither is available.
+pub(crate) fn terminal_width() -> usize {
+    env::var("COLUMNS")
+        .ok()
+        .and_then(|columns| columns.parse::<usize>().ok())
+        .filter(|&columns| columns > 0)
+        .or_else(|| {
+            io::stdout()
+                .is_terminal()
+                .then(crossterm::terminal::size)
+                .and_then(Result::ok)
+                .map(|(columns, _)| columns as usize)
+                .filter(|&columns| columns > 0)
+        })
+        .unwrap_or(DEFAULT_TERMINAL_WIDTH)
+}
+
+/// Truncates the branch name to at most `max_width` characters, ending it with [ELLIPSIS] if
+/// it was shortened.
+///
+/// ## Takes
+/// - `branch` - The name of the branch.
+/// - `max_width` - The maximum number of characters to keep.
+///
+/// ## Returns
+/// - `Cow<str>` - The branch name, truncated if necessary.
+fn truncate_branch_name(branch: &str, max_width: usize) -> Cow<'_, str> {
+    if branch.chars().count() <= max_width {
+        return Cow::Borrowed(branch);
+    }
+    let mut truncated = branch
+        .chars()
+        .take(max_width.saturating_sub(1))
+        .collect::<String>();
+    truncated.push(ELLIPSIS);
+    Cow::Owned(truncated)
+}
+
 /// A pair of a log-line and a branch name, which implements [Display].
 #[derive(Debug)]
 pub struct DisplayBranch {
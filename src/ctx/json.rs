@@ -0,0 +1,251 @@
+//! Machine-readable representations of the stack state, emitted by read-only commands when
+//! `--json` is passed.
+//!
+//! The structures in this module form a stable output schema. Fields may be added, but existing
+//! fields are not renamed or removed.
+
+use super::StContext;
+use crate::errors::{StError, StResult};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub use crate::tree::PrState;
+
+/// The state of the active trunk's stacks.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct StackState {
+    /// The name of the active trunk branch.
+    pub trunk: String,
+    /// The tracked branches, with parents listed before their children.
+    pub branches: Vec<BranchState>,
+}
+
+/// The state of a single tracked branch.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct BranchState {
+    /// The name of the branch.
+    pub name: String,
+    /// The name of the parent branch, or [None] for the trunk branch.
+    pub parent: Option<String>,
+    /// The names of the child branches, sorted.
+    pub children: Vec<String>,
+    /// The number of the pull request for the branch, if submitted.
+    pub pr_number: Option<u64>,
+    /// The state of the pull request on GitHub. Only reported by commands that query GitHub.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr_state: Option<PrState>,
+    /// Whether the branch needs to be restacked onto its parent.
+    pub needs_restack: bool,
+}
+
+/// The trunk branches tracked in the repository.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TrunkList {
+    /// The name of the active trunk branch.
+    pub active: String,
+    /// The names of all trunk branches, sorted.
+    pub trunks: Vec<String>,
+}
+
+/// Statistics about the branches tracked on a trunk.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct TrunkInfo {
+    /// The name of the trunk branch.
+    pub trunk: String,
+    /// The number of tracked branches, excluding the trunk branch.
+    pub branches: usize,
+    /// The number of tracked branches submitted as pull requests.
+    pub pull_requests: usize,
+    /// The number of tracked branches that need to be restacked onto their parents.
+    pub needs_restack: usize,
+    /// The number of branches in the longest chain stacked on the trunk branch.
+    pub max_depth: usize,
+}
+
+impl StContext<'_> {
+    /// Assembles the [StackState] of the active trunk. PR states are left unset.
+    pub fn stack_state(&self) -> StResult<StackState> {
+        let branches = self
+            .tree
+            .branches()?
+            .into_iter()
+            .map(|branch| {
+                let tracked_branch = self
+                    .tree
+                    .get(&branch)
+                    .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?;
+                let mut children = tracked_branch.children.iter().cloned().collect::<Vec<_>>();
+                children.sort();
+
+                Ok(BranchState {
+                    needs_restack: self.needs_restack(&branch)?,
+                    parent: tracked_branch.parent.clone(),
+                    children,
+                    pr_number: tracked_branch.remote.as_ref().map(|r| r.pr_number),
+                    pr_state: None,
+                    name: branch,
+                })
+            })
+            .collect::<StResult<Vec<_>>>()?;
+
+        Ok(StackState {
+            trunk: self.tree.trunk_name().to_string(),
+            branches,
+        })
+    }
+
+    /// Assembles the [TrunkInfo] of the active trunk.
+    pub fn trunk_info(&self) -> StResult<TrunkInfo> {
+        let mut info = TrunkInfo {
+            trunk: self.tree.trunk_name().to_string(),
+            branches: 0,
+            pull_requests: 0,
+            needs_restack: 0,
+            max_depth: 0,
+        };
+
+        // Branches are listed after their parents, so each parent's depth is known first.
+        let mut depths = HashMap::new();
+        for name in self.tree.branches()?.into_iter().skip(1) {
+            let branch = self
+                .tree
+                .get(&name)
+                .ok_or_else(|| StError::BranchNotTracked(name.clone()))?;
+            let depth = branch
+                .parent
+                .as_ref()
+                .and_then(|parent| depths.get(parent))
+                .unwrap_or(&0)
+                + 1;
+            info.branches += 1;
+            info.max_depth = info.max_depth.max(depth);
+            if branch.remote.is_some() {
+                info.pull_requests += 1;
+            }
+            if self.needs_restack(&name)? {
+                info.needs_restack += 1;
+            }
+            depths.insert(name, depth);
+        }
+        Ok(info)
+    }
+
+    /// Assembles the [TrunkList] of the repository.
+    pub fn trunk_list(&self) -> TrunkList {
+        let mut trunks = self.tree.list_trunks();
+        trunks.sort();
+        TrunkList {
+            active: self.tree.trunk_name().to_string(),
+            trunks,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{BranchState, StackState, TrunkInfo};
+    use crate::{
+        ctx::test::test_context,
+        git::test::TempRepository,
+        tree::{RemoteMetadata, StackTree},
+    };
+    use git2::BranchType;
+
+    #[test]
+    fn stack_state_roundtrips_through_json() {
+        let repo = TempRepository::new();
+        let main_head = repo
+            .repository
+            .find_branch("main", BranchType::Local)
+            .unwrap()
+            .get()
+            .target()
+            .unwrap();
+        repo.branch("feature-1");
+        repo.commit("Feature 1");
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", "stale", "feature-2").unwrap();
+        tree.get_mut("feature-1").unwrap().remote = Some(RemoteMetadata::new(7));
+
+        let ctx = test_context(&repo.repository, tree);
+        let json = serde_json::to_string(&ctx.stack_state().unwrap()).unwrap();
+        let state: StackState = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(state.trunk, "main");
+        assert_eq!(
+            state.branches,
+            vec![
+                BranchState {
+                    name: "main".to_string(),
+                    parent: None,
+                    children: vec!["feature-1".to_string()],
+                    pr_number: None,
+                    pr_state: None,
+                    needs_restack: false,
+                },
+                BranchState {
+                    name: "feature-1".to_string(),
+                    parent: Some("main".to_string()),
+                    children: vec!["feature-2".to_string()],
+                    pr_number: Some(7),
+                    pr_state: None,
+                    needs_restack: false,
+                },
+                BranchState {
+                    name: "feature-2".to_string(),
+                    parent: Some("feature-1".to_string()),
+                    children: vec![],
+                    pr_number: None,
+                    pr_state: None,
+                    needs_restack: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn trunk_info_counts_branches() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main").to_string();
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit("Feature 1").to_string();
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+        repo.checkout("main");
+        repo.branch("other");
+        repo.commit("Other");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head, "feature-1").unwrap();
+        tree.insert("feature-1", &feature_1_head, "feature-2")
+            .unwrap();
+        tree.insert("feature-2", "stale", "feature-3").unwrap();
+        tree.insert("main", "stale", "other").unwrap();
+        tree.get_mut("feature-1").unwrap().remote = Some(RemoteMetadata::new(1));
+        tree.get_mut("feature-2").unwrap().remote = Some(RemoteMetadata::new(2));
+        repo.repository
+            .branch(
+                "feature-3",
+                &repo.repository.head().unwrap().peel_to_commit().unwrap(),
+                false,
+            )
+            .unwrap();
+
+        let ctx = test_context(&repo.repository, tree);
+        assert_eq!(
+            ctx.trunk_info().unwrap(),
+            TrunkInfo {
+                trunk: "main".to_string(),
+                branches: 4,
+                pull_requests: 2,
+                needs_restack: 2,
+                max_depth: 3,
+            }
+        );
+    }
+}
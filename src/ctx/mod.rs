@@ -2,16 +2,33 @@
 
 use crate::{
     config::StConfig,
-    constants::{GIT_DIR, ST_CTX_FILE_NAME},
+    constants::{
+        GIT_DIR, ST_CTX_BACKUP_EXTENSION, ST_CTX_FILE_NAME, ST_CTX_LOCK_EXTENSION,
+        ST_CTX_TEMP_EXTENSION,
+    },
     errors::{StError, StResult},
+    git::RepositoryExt,
+    output::progress,
+    theme::Color,
     tree::StackTree,
 };
 use git2::{BranchType, Repository};
-use std::path::PathBuf;
+use std::{
+    borrow::Cow,
+    cell::RefCell,
+    collections::HashMap,
+    fs::{self, File, TryLockError},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+use tracing::{debug, info};
 
 mod actions;
 pub mod discovery;
+pub mod export;
 mod fmt;
+pub mod json;
 mod stack_management;
 
 /// Returns the path to the persistent application context for the given [Repository].
@@ -28,6 +45,257 @@ pub fn ctx_path(repository: &Repository) -> Option<PathBuf> {
         .map(|p| p.join(GIT_DIR).join(ST_CTX_FILE_NAME))
 }
 
+/// Returns the path of `store_path` with `extension` appended, e.g. `.st_store.toml.bak`.
+fn sibling_path(store_path: &Path, extension: &str) -> PathBuf {
+    let mut path = store_path.as_os_str().to_owned();
+    path.push(".");
+    path.push(extension);
+    PathBuf::from(path)
+}
+
+/// Loads the persisted [StackTree] for the given [Repository], migrating legacy stores. If the
+/// store is corrupt, the backup of the previous successful write is loaded instead.
+///
+/// ## Returns
+/// - `Ok(Some(StackTree))` - The persisted tree.
+/// - `Ok(None)` - If the repository has not been set up with `st`.
+pub fn load_tree(repository: &Repository) -> StResult<Option<StackTree>> {
+    let store_path = ctx_path(repository).ok_or(StError::GitRepositoryRootNotFound)?;
+
+    // If the store doesn't exist, return None.
+    if !store_path.exists() {
+        return Ok(None);
+    }
+
+    let mut stack: StackTree = match toml::from_str(&fs::read_to_string(&store_path)?) {
+        Ok(stack) => stack,
+        Err(e) => {
+            // Fall back to the backup, reporting the original error if it is unusable too.
+            let backup_path = sibling_path(&store_path, ST_CTX_BACKUP_EXTENSION);
+            let backup = fs::read_to_string(&backup_path)
+                .ok()
+                .and_then(|backup| toml::from_str(&backup).ok())
+                .ok_or(e)?;
+            eprintln!(
+                "⚠️  The store at `{}` is corrupt. Recovered the stack from `{}`.",
+                Color::Yellow.paint(store_path.display().to_string()),
+                Color::Yellow.paint(backup_path.display().to_string())
+            );
+            backup
+        }
+    };
+    // Migrate old format if needed
+    stack.migrate_if_needed();
+    Ok(Some(stack))
+}
+
+/// Persists the [StackTree] for the given [Repository].
+///
+/// The tree is written to a temporary file in the same directory, synced to disk, and renamed
+/// over the store, so an interrupted write never leaves a truncated store behind. The previous
+/// store is kept as a backup for [load_tree] to recover from.
+pub fn persist_tree(repository: &Repository, tree: &StackTree) -> StResult<()> {
+    let store_path = ctx_path(repository).ok_or(StError::GitRepositoryRootNotFound)?;
+    let temp_path = sibling_path(&store_path, ST_CTX_TEMP_EXTENSION);
+
+    let store = toml::to_string_pretty(tree)?;
+    let previous = fs::read_to_string(&store_path).ok();
+
+    // Skip the write if nothing changed, so read-only commands never touch the store.
+    if previous.as_deref() == Some(store.as_str()) {
+        debug!("Store unchanged, skipping write");
+        return Ok(());
+    }
+
+    let mut temp = File::create(&temp_path)?;
+    temp.write_all(store.as_bytes())?;
+    temp.sync_all()?;
+
+    // Only back up a store that parses, so a corrupt store never replaces a good backup.
+    if previous.is_some_and(|previous| toml::from_str::<StackTree>(&previous).is_ok()) {
+        fs::copy(&store_path, sibling_path(&store_path, ST_CTX_BACKUP_EXTENSION))?;
+    }
+
+    fs::rename(&temp_path, &store_path)?;
+    info!(path = %store_path.display(), "Persisted store");
+    Ok(())
+}
+
+/// Returns the branches tracked on the active trunk whose git branch no longer exists, e.g. after
+/// being deleted with `git branch -D`.
+///
+/// ## Takes
+/// - `repository` - The repository the branches are looked up in.
+/// - `tree` - The [StackTree] tracking the branches.
+///
+/// ## Returns
+/// - `Result<Vec<String>>` - The names of the orphaned branches, parents before children.
+pub fn orphaned_branches(repository: &Repository, tree: &StackTree) -> StResult<Vec<String>> {
+    Ok(tree
+        .branches()?
+        .into_iter()
+        .filter(|b| b != tree.trunk_name())
+        .filter(|b| repository.find_branch(b, BranchType::Local).is_err())
+        .collect())
+}
+
+/// Untracks the orphaned branches of the active trunk, re-linking their children to their
+/// parents. See [orphaned_branches].
+///
+/// ## Returns
+/// - `Result<Vec<String>>` - The names of the untracked branches.
+pub fn untrack_orphans(repository: &Repository, tree: &mut StackTree) -> StResult<Vec<String>> {
+    let orphans = orphaned_branches(repository, tree)?;
+    orphans
+        .iter()
+        .try_for_each(|b| tree.delete(b).map(|_| ()))?;
+    Ok(orphans)
+}
+
+/// Returns the local branches that aren't tracked but fork off a tracked branch of the active
+/// trunk, e.g. after being created with `git checkout -b`. Each branch is attributed to the tracked
+/// branch with the closest merge-base, as its likely parent. Branches without commits of their own
+/// on top of that parent, and branches tracked on other trunks, are left out.
+///
+/// ## Takes
+/// - `repository` - The repository the branches are listed from.
+/// - `tree` - The [StackTree] tracking the branches.
+///
+/// ## Returns
+/// - `Result<Vec<(String, String)>>` - Each untracked branch and its likely parent, sorted by name.
+pub fn untracked_branches(
+    repository: &Repository,
+    tree: &StackTree,
+) -> StResult<Vec<(String, String)>> {
+    let tracked = tree.branches()?;
+    let is_tracked = |name: &str| {
+        tree.list_trunks().iter().any(|t| t == name)
+            || tree.trunks.values().any(|t| t.branches.contains_key(name))
+    };
+
+    let mut untracked = Vec::new();
+    for branch in repository.branches(Some(BranchType::Local))? {
+        let (branch, _) = branch?;
+        let Some(name) = branch.name()?.map(ToOwned::to_owned) else {
+            continue;
+        };
+        if is_tracked(&name) {
+            continue;
+        }
+        let Some(parent) = repository.closest_branch(&name, &tracked)? else {
+            continue;
+        };
+        let head = branch.get().peel_to_commit()?.id();
+        let parent_head = repository.revparse_single(&parent)?.peel_to_commit()?.id();
+        if !repository.is_ancestor(head, parent_head)? {
+            untracked.push((name, parent));
+        }
+    }
+    untracked.sort();
+    Ok(untracked)
+}
+
+/// An advisory lock on the store, held by mutating commands so that concurrent `st` processes
+/// cannot interleave their writes. The lock is released when dropped.
+pub struct StoreLock {
+    /// The open lockfile, which holds the lock.
+    _file: File,
+}
+
+impl StoreLock {
+    /// The interval between attempts to take the lock while waiting for it.
+    const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+    /// Takes the store lock for the given [Repository].
+    ///
+    /// ## Takes
+    /// - `repository` - The repository whose store to lock.
+    /// - `wait` - Whether to wait for another process to release the lock, rather than failing
+    ///   with [StError::StoreLocked].
+    pub fn acquire(repository: &Repository, wait: bool) -> StResult<Self> {
+        let store_path = ctx_path(repository).ok_or(StError::GitRepositoryRootNotFound)?;
+        let file = File::create(sibling_path(&store_path, ST_CTX_LOCK_EXTENSION))?;
+
+        let spinner = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+        let mut attempts = 0;
+        loop {
+            match file.try_lock() {
+                Ok(()) => break,
+                Err(TryLockError::WouldBlock) if wait => {
+                    eprint!(
+                        "\r{} Waiting for another `{}` process to release the store...",
+                        spinner[attempts % spinner.len()],
+                        Color::Blue.paint("st")
+                    );
+                    attempts += 1;
+                    std::thread::sleep(Self::POLL_INTERVAL);
+                }
+                Err(TryLockError::WouldBlock) => return Err(StError::StoreLocked),
+                Err(TryLockError::Error(e)) => return Err(e.into()),
+            }
+        }
+        if attempts > 0 {
+            eprintln!();
+        }
+        Ok(Self { _file: file })
+    }
+}
+
+/// Parses the GitHub owner and repository from a remote URL, in either the SSH
+/// (`git@github.com:org/repo.git`) or HTTPS (`https://github.com/org/repo.git`) format.
+///
+/// ## Takes
+/// - `url` - The URL of the remote.
+///
+/// ## Returns
+/// - `Result<(String, String)>` - The owner and repository.
+pub(crate) fn parse_remote_url(url: &str) -> StResult<(String, String)> {
+    let (org, repo) = if url.starts_with("git@") {
+        // Handle SSH URL: git@github.com:org/repo.git
+        let parts = url.split(':').collect::<Vec<_>>();
+        let repo_parts = parts
+            .get(1)
+            .ok_or(StError::DecodingError(
+                "Invalid SSH URL format.".to_string(),
+            ))?
+            .split('/')
+            .collect::<Vec<_>>();
+        let org = repo_parts.first().ok_or(StError::DecodingError(
+            "Organization not found.".to_string(),
+        ))?;
+        let repo = repo_parts.get(1).ok_or(StError::DecodingError(
+            "Repository not found while decoding remote URL.".to_string(),
+        ))?;
+        (org.to_string(), repo.trim_end_matches(".git").to_string())
+    } else if url.starts_with("https://") {
+        // Handle HTTPS URL: https://github.com/org/repo.git
+        let parts = url.split('/').collect::<Vec<_>>();
+        let org = parts.get(parts.len() - 2).ok_or(StError::DecodingError(
+            "Organization not found.".to_string(),
+        ))?;
+        let repo = parts.last().ok_or(StError::DecodingError(
+            "Repository not found while decoding remote URL.".to_string(),
+        ))?;
+        (org.to_string(), repo.trim_end_matches(".git").to_string())
+    } else {
+        return Err(StError::DecodingError(
+            "Unsupported remote URL format.".to_string(),
+        ));
+    };
+
+    Ok((org, repo))
+}
+
+/// The owner and repository parsed from the URL of a remote.
+struct ParsedRemote {
+    /// The URL the owner and repository were parsed from.
+    url: String,
+    /// The GitHub owner.
+    owner: String,
+    /// The GitHub repository.
+    repository: String,
+}
+
 /// The in-memory context of the `st` application.
 pub struct StContext<'a> {
     /// The global configuration for `st`.
@@ -36,101 +304,532 @@ pub struct StContext<'a> {
     pub repository: &'a Repository,
     /// The tree of branches tracked by `st`.
     pub tree: StackTree,
+    /// The active trunk to persist, if another trunk was activated with `--trunk` for the
+    /// duration of the command.
+    persisted_trunk: Option<String>,
+    /// Whether the store is left as it is when the context is dropped, as the context was loaded
+    /// without holding the [StoreLock].
+    read_only: bool,
+    /// The owner and repository of each remote resolved so far, by remote name.
+    remotes: RefCell<HashMap<String, ParsedRemote>>,
 }
 
 impl<'a> StContext<'a> {
     /// Creates a fresh [StContext] with the given [Repository] and trunk branch name.
     pub fn fresh(cfg: StConfig, repository: &'a Repository, trunk: String) -> Self {
+        let mut tree = StackTree::new(trunk);
+        tree.max_depth = cfg.max_stack_depth;
         Self {
             cfg,
             repository,
-            tree: StackTree::new(trunk),
+            tree,
+            persisted_trunk: None,
+            read_only: false,
+            remotes: RefCell::default(),
         }
     }
 
     /// Loads the [StackTree] for the given [Repository], and assembles a [StContext].
     pub fn try_load(cfg: StConfig, repository: &'a Repository) -> StResult<Option<Self>> {
-        let store_path = ctx_path(repository).ok_or(StError::GitRepositoryRootNotFound)?;
-
-        // If the store doesn't exist, return None.
-        if !store_path.exists() {
+        let Some(mut stack) = load_tree(repository)? else {
             return Ok(None);
-        }
+        };
+        stack.max_depth = cfg.max_stack_depth;
 
-        let mut stack: StackTree = toml::from_str(&std::fs::read_to_string(store_path)?)?;
-        // Migrate old format if needed
-        stack.migrate_if_needed();
-        
         let mut store_with_repo = Self {
             cfg,
             repository,
             tree: stack,
+            persisted_trunk: None,
+            read_only: false,
+            remotes: RefCell::default(),
         };
         store_with_repo.prune()?;
 
         Ok(Some(store_with_repo))
     }
 
-    /// Parses the GitHub owner and repository from the current repository's remote URL.
+    /// Activates another trunk for the lifetime of the context, without persisting the switch.
+    ///
+    /// ## Takes
+    /// - `trunk_name` - The name of the trunk to operate on.
+    ///
+    /// ## Returns
+    /// - `Result<()>` - [StError::TrunkNotFound] if the trunk is not tracked.
+    pub fn override_trunk(&mut self, trunk_name: &str) -> StResult<()> {
+        if !self.tree.trunks.contains_key(trunk_name) {
+            return Err(StError::TrunkNotFound(trunk_name.to_string()));
+        }
+        self.persisted_trunk
+            .get_or_insert_with(|| self.tree.active_trunk.clone());
+        self.tree.active_trunk = trunk_name.to_string();
+        Ok(())
+    }
+
+    /// Marks the context as read-only, so that the store is never persisted. A context loaded
+    /// without holding the [StoreLock] must be read-only, as persisting it, e.g. after pruning
+    /// orphaned branches, could clobber the write of a concurrent `st` process.
+    pub fn set_read_only(&mut self) {
+        self.read_only = true;
+    }
+
+    /// Persists the store, unless the context is read-only.
+    fn persist(&self) -> StResult<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        persist_tree(self.repository, &self.persisted_tree())
+    }
+
+    /// Returns the [StackTree] to persist, with the active trunk restored if it was overridden
+    /// with [StContext::override_trunk].
+    fn persisted_tree(&self) -> Cow<'_, StackTree> {
+        match &self.persisted_trunk {
+            Some(trunk) => {
+                let mut tree = self.tree.clone();
+                tree.active_trunk = trunk.clone();
+                Cow::Owned(tree)
+            }
+            None => Cow::Borrowed(&self.tree),
+        }
+    }
+
+    /// Parses the GitHub owner and repository that pull requests are opened against, from the
+    /// URL of the configured PR remote.
     pub fn owner_and_repository(&self) -> StResult<(String, String)> {
-        let remote = self.repository.find_remote("origin")?;
+        self.remote_owner_and_repository(self.cfg.pr_remote())
+    }
+
+    /// Parses the GitHub owner and repository from the URL of the remote by the name of
+    /// `remote_name`. The result is cached for the lifetime of the context, until the URL of the
+    /// remote changes.
+    pub fn remote_owner_and_repository(&self, remote_name: &str) -> StResult<(String, String)> {
+        self.remote_owner_and_repository_with(remote_name, parse_remote_url)
+    }
+
+    /// Resolves the GitHub owner and repository of the remote by the name of `remote_name`,
+    /// parsing its URL with `parse` unless the result for the same URL is cached.
+    ///
+    /// ## Takes
+    /// - `remote_name` - The name of the remote.
+    /// - `parse` - Parses the owner and repository from a remote URL.
+    ///
+    /// ## Returns
+    /// - `Result<(String, String)>` - The owner and repository.
+    fn remote_owner_and_repository_with(
+        &self,
+        remote_name: &str,
+        parse: impl FnOnce(&str) -> StResult<(String, String)>,
+    ) -> StResult<(String, String)> {
+        let remote = self.repository.find_remote(remote_name)?;
         let url = remote
             .url()
-            .ok_or(StError::RemoteNotFound("origin".to_string()))?;
-
-        let (org, repo) = if url.starts_with("git@") {
-            // Handle SSH URL: git@github.com:org/repo.git
-            let parts = url.split(':').collect::<Vec<_>>();
-            let repo_parts = parts
-                .get(1)
-                .ok_or(StError::DecodingError(
-                    "Invalid SSH URL format.".to_string(),
-                ))?
-                .split('/')
-                .collect::<Vec<_>>();
-            let org = repo_parts.first().ok_or(StError::DecodingError(
-                "Organization not found.".to_string(),
-            ))?;
-            let repo = repo_parts.get(1).ok_or(StError::DecodingError(
-                "Repository not found while decoding remote URL.".to_string(),
-            ))?;
-            (org.to_string(), repo.trim_end_matches(".git").to_string())
-        } else if url.starts_with("https://") {
-            // Handle HTTPS URL: https://github.com/org/repo.git
-            let parts = url.split('/').collect::<Vec<_>>();
-            let org = parts.get(parts.len() - 2).ok_or(StError::DecodingError(
-                "Organization not found.".to_string(),
-            ))?;
-            let repo = parts.last().ok_or(StError::DecodingError(
-                "Repository not found while decoding remote URL.".to_string(),
-            ))?;
-            (org.to_string(), repo.trim_end_matches(".git").to_string())
+            .ok_or(StError::RemoteNotFound(remote_name.to_string()))?;
+
+        if let Some(cached) = self.remotes.borrow().get(remote_name)
+            && cached.url == url
+        {
+            return Ok((cached.owner.clone(), cached.repository.clone()));
+        }
+
+        let (owner, repository) = parse(url)?;
+        self.remotes.borrow_mut().insert(
+            remote_name.to_string(),
+            ParsedRemote {
+                url: url.to_string(),
+                owner: owner.clone(),
+                repository: repository.clone(),
+            },
+        );
+        Ok((owner, repository))
+    }
+
+    /// Returns the head reference to open a pull request for `branch_name` with. When branches
+    /// are pushed to a fork of the PR repository, the head is qualified with the fork's owner.
+    pub fn pr_head(&self, branch_name: &str) -> StResult<String> {
+        let push_remote = self.cfg.push_remote();
+        if push_remote == self.cfg.pr_remote() {
+            return Ok(branch_name.to_string());
+        }
+
+        let (push_owner, _) = self.remote_owner_and_repository(push_remote)?;
+        let (pr_owner, _) = self.owner_and_repository()?;
+        if push_owner == pr_owner {
+            Ok(branch_name.to_string())
         } else {
-            return Err(StError::DecodingError(
-                "Unsupported remote URL format.".to_string(),
-            ));
-        };
+            Ok(format!("{}:{}", push_owner, branch_name))
+        }
+    }
 
-        Ok((org, repo))
+    /// Returns the name of the checked out branch. See [RepositoryExt::current_branch_name].
+    pub fn current_branch(&self) -> StResult<String> {
+        self.repository.current_branch_name()
     }
 
-    /// Prunes branches in the context that no longer exist in the git repository.
+    /// Untracks the branches whose git branch no longer exists, unless the configuration keeps
+    /// them for `st doctor` to report.
     fn prune(&mut self) -> StResult<()> {
-        let branches = self.tree.branches()?;
-        branches.iter().try_for_each(|b| {
-            if self.repository.find_branch(b, BranchType::Local).is_err() {
-                self.tree.delete(b)?;
-            }
-            Ok::<_, StError>(())
-        })
+        if self.cfg.keep_orphaned_branches {
+            return Ok(());
+        }
+        for branch in untrack_orphans(self.repository, &mut self.tree)? {
+            progress!(
+                "Untracked `{}`, whose git branch no longer exists.",
+                Color::Yellow.paint(&branch)
+            );
+        }
+        Ok(())
     }
 }
 
 impl Drop for StContext<'_> {
     fn drop(&mut self) {
         // Persist the store on drop.
-        let store_path = ctx_path(self.repository).expect("Failed to get context path.");
-        let store = toml::to_string_pretty(&self.tree).expect("Failed to serialize context.");
-        std::fs::write(store_path, store).expect("Failed to persist context to disk.");
+        self.persist().expect("Failed to persist context to disk.");
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{
+        ctx_path, load_tree, parse_remote_url, persist_tree, untracked_branches, StContext,
+        StoreLock,
+    };
+    use crate::errors::StError;
+    use crate::{
+        config::StConfig,
+        git::test::TempRepository,
+        theme::{test::ColorOverride, Theme},
+        tree::StackTree,
+    };
+    use git2::{BranchType, Repository};
+    use std::{
+        cell::{Cell, RefCell},
+        mem::ManuallyDrop,
+    };
+
+    /// Assembles a [StContext] over `repository` and `tree` for tests. The context is never
+    /// dropped, so neither the global configuration nor the store are persisted to disk.
+    pub(crate) fn test_context(
+        repository: &Repository,
+        tree: StackTree,
+    ) -> ManuallyDrop<StContext<'_>> {
+        ManuallyDrop::new(StContext {
+            cfg: StConfig::default(),
+            repository,
+            tree,
+            persisted_trunk: None,
+            read_only: false,
+            remotes: RefCell::default(),
+        })
+    }
+
+    #[test]
+    fn trunk_override_renders_other_trunk_without_persisting_switch() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        repo.commit("Feature 1");
+        repo.checkout("main");
+        repo.branch("dev");
+        let dev_head = repo.commit("Dev");
+        repo.branch("dev-feature");
+        repo.commit("Dev feature");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.add_trunk("dev".to_string());
+        tree.switch_trunk("dev").unwrap();
+        tree.insert("dev", &dev_head.to_string(), "dev-feature")
+            .unwrap();
+        tree.switch_trunk("main").unwrap();
+        persist_tree(&repo.repository, &tree).unwrap();
+
+        let mut ctx = test_context(&repo.repository, tree);
+        assert!(matches!(
+            ctx.override_trunk("missing"),
+            Err(StError::TrunkNotFound(name)) if name == "missing"
+        ));
+        ctx.override_trunk("dev").unwrap();
+
+        let mut log = String::new();
+        ctx.write_tree(&mut log).unwrap();
+        assert!(log.contains("dev-feature"));
+        assert!(!log.contains("feature-1"));
+
+        persist_tree(&repo.repository, &ctx.persisted_tree()).unwrap();
+        let persisted = load_tree(&repo.repository).unwrap().unwrap();
+        assert_eq!(persisted.trunk_name(), "main");
+    }
+
+    #[test]
+    fn remote_owner_and_repository_is_parsed_once_per_url() {
+        let repo = TempRepository::new();
+        repo.repository
+            .remote("origin", "git@github.com:org/repo.git")
+            .unwrap();
+        let ctx = test_context(&repo.repository, StackTree::new("main".to_string()));
+
+        let parses = Cell::new(0);
+        let counting_parse = |url: &str| {
+            parses.set(parses.get() + 1);
+            parse_remote_url(url)
+        };
+        let expected = ("org".to_string(), "repo".to_string());
+        for _ in 0..3 {
+            let resolved = ctx.remote_owner_and_repository_with("origin", counting_parse);
+            assert_eq!(resolved.unwrap(), expected);
+        }
+        assert_eq!(ctx.owner_and_repository().unwrap(), expected);
+        assert_eq!(parses.get(), 1);
+
+        // Changing the URL of the remote invalidates the cached result.
+        repo.repository
+            .remote_set_url("origin", "https://github.com/fork/repo.git")
+            .unwrap();
+        let resolved = ctx.remote_owner_and_repository_with("origin", counting_parse);
+        assert_eq!(resolved.unwrap(), ("fork".to_string(), "repo".to_string()));
+        assert_eq!(parses.get(), 2);
+    }
+
+    #[test]
+    fn detached_head_has_no_current_branch() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        repo.commit("Feature 1");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        let ctx = test_context(&repo.repository, tree);
+        assert_eq!(ctx.current_branch().unwrap(), "feature-1");
+
+        repo.repository.set_head_detached(main_head).unwrap();
+        assert!(matches!(ctx.current_branch(), Err(StError::DetachedHead)));
+        assert!(matches!(ctx.discover_stack(), Err(StError::DetachedHead)));
+
+        // The tree still renders, without a checked out branch.
+        let mut rendered = String::new();
+        ctx.write_tree(&mut rendered).unwrap();
+        assert!(rendered.contains("feature-1"));
+        assert!(!rendered.contains(&ctx.cfg.theme.checked_out_symbol));
+    }
+
+    #[test]
+    fn disabled_colors_render_tree_without_escape_sequences() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        repo.commit("Feature 1");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+        ctx.cfg.theme = Theme {
+            checked_out_symbol: "*".to_string(),
+            branch_symbol: "-".to_string(),
+            ..Theme::default()
+        };
+
+        let mut colored = String::new();
+        ctx.write_tree(&mut colored).unwrap();
+        assert!(colored.contains('\x1b'));
+
+        let colors = ColorOverride::start(false);
+        let mut plain = String::new();
+        ctx.write_tree(&mut plain).unwrap();
+        drop(colors);
+
+        assert!(!plain.contains('\x1b'));
+        assert_eq!(plain, "- main\n└─* feature-1\n");
+    }
+
+    /// Removes ANSI escape sequences from rendered output.
+    fn strip_ansi(rendered: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = rendered.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|&c| c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn narrow_width_truncates_long_branch_names() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_head = repo.commit("Feature 1");
+        let long_name = "feature/an-extremely-long-branch-name-for-narrow-terminals";
+        repo.branch(long_name);
+        repo.commit("Long");
+        repo.checkout("main");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_head.to_string(), long_name)
+            .unwrap();
+        let ctx = test_context(&repo.repository, tree);
+
+        let mut log = String::new();
+        ctx.write_tree_with_width(&mut log, 40).unwrap();
+        let lines = strip_ansi(&log)
+            .lines()
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+
+        assert_eq!(lines.len(), 3);
+        assert!(lines.iter().all(|line| line.chars().count() <= 40));
+        assert!(!lines[2].contains(long_name));
+        assert!(lines[2].ends_with('…'));
+        assert_eq!(lines[1], "└─○ feature-1");
+        assert!(lines[2].starts_with("  └─○ feature/an-extremely"));
+
+        let mut wide = String::new();
+        ctx.write_tree_with_width(&mut wide, 120).unwrap();
+        assert!(strip_ansi(&wide).contains(long_name));
+    }
+
+    #[test]
+    fn truncated_store_recovers_from_backup() {
+        let repo = TempRepository::new();
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        persist_tree(&repo.repository, &tree).unwrap();
+
+        let mut updated = tree.clone();
+        updated.insert("feature-1", "b", "feature-2").unwrap();
+        persist_tree(&repo.repository, &updated).unwrap();
+        assert_eq!(load_tree(&repo.repository).unwrap(), Some(updated));
+
+        // Simulate a write interrupted halfway through the store.
+        let store_path = ctx_path(&repo.repository).unwrap();
+        let store = std::fs::read_to_string(&store_path).unwrap();
+        std::fs::write(&store_path, &store[..store.len() / 2]).unwrap();
+
+        assert_eq!(load_tree(&repo.repository).unwrap(), Some(tree));
+    }
+
+    #[test]
+    fn read_only_context_never_persists_the_store() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        repo.commit("Feature 1");
+        repo.checkout("main");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        persist_tree(&repo.repository, &tree).unwrap();
+        let store_path = ctx_path(&repo.repository).unwrap();
+        let store = std::fs::read_to_string(&store_path).unwrap();
+
+        // The orphaned branch is pruned as the store is loaded, but only in memory.
+        repo.repository
+            .find_branch("feature-1", BranchType::Local)
+            .unwrap()
+            .delete()
+            .unwrap();
+        let mut ctx = ManuallyDrop::new(
+            StContext::try_load(StConfig::default(), &repo.repository)
+                .unwrap()
+                .unwrap(),
+        );
+        assert!(ctx.tree.get("feature-1").is_none());
+        ctx.set_read_only();
+        ctx.persist().unwrap();
+        assert_eq!(std::fs::read_to_string(&store_path).unwrap(), store);
+    }
+
+    #[test]
+    fn second_store_lock_fails_fast_or_waits() {
+        let repo = TempRepository::new();
+        let held = StoreLock::acquire(&repo.repository, false).unwrap();
+
+        // Without waiting, the second attempt fails immediately.
+        assert!(matches!(
+            StoreLock::acquire(&repo.repository, false),
+            Err(StError::StoreLocked)
+        ));
+
+        // While waiting, the second attempt succeeds once the first lock is released.
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(250));
+            drop(held);
+        });
+        assert!(StoreLock::acquire(&repo.repository, true).is_ok());
+        release.join().unwrap();
+    }
+
+    #[test]
+    fn fork_workflow_pushes_to_fork_and_targets_upstream() {
+        let repo = TempRepository::new();
+        repo.repository
+            .remote("origin", "git@github.com:contributor/st.git")
+            .unwrap();
+        repo.repository
+            .remote("upstream", "https://github.com/mahbd/st.git")
+            .unwrap();
+
+        let mut ctx = test_context(&repo.repository, StackTree::new("main".to_string()));
+        ctx.cfg.pr_remote = "upstream".to_string();
+
+        assert_eq!(ctx.cfg.push_remote(), "origin");
+        assert_eq!(
+            ctx.owner_and_repository().unwrap(),
+            ("mahbd".to_string(), "st".to_string())
+        );
+        assert_eq!(ctx.pr_head("feature").unwrap(), "contributor:feature");
+    }
+
+    #[test]
+    fn single_remote_targets_push_remote() {
+        let repo = TempRepository::new();
+        repo.repository
+            .remote("origin", "git@github.com:mahbd/st.git")
+            .unwrap();
+
+        let ctx = test_context(&repo.repository, StackTree::new("main".to_string()));
+        assert_eq!(ctx.cfg.pr_remote(), "origin");
+        assert_eq!(
+            ctx.owner_and_repository().unwrap(),
+            ("mahbd".to_string(), "st".to_string())
+        );
+        assert_eq!(ctx.pr_head("feature").unwrap(), "feature");
+    }
+
+    #[test]
+    fn manually_created_child_is_reported_on_its_parent() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main").to_string();
+        repo.branch("feature-1");
+        let head_1 = repo.commit("Feature 1").to_string();
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+
+        // Created with git alone: a child of `feature-1`, and a branch with no commits of its own.
+        repo.checkout("feature-1");
+        repo.branch("manual");
+        repo.commit("Manual");
+        repo.checkout("main");
+        repo.branch("empty");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head, "feature-1").unwrap();
+        tree.insert("feature-1", &head_1, "feature-2").unwrap();
+
+        assert_eq!(
+            untracked_branches(&repo.repository, &tree).unwrap(),
+            vec![("manual".to_string(), "feature-1".to_string())]
+        );
     }
 }
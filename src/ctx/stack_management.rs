@@ -3,9 +3,11 @@
 use super::StContext;
 use crate::{
     errors::{StError, StResult},
-    git::RepositoryExt,
+    git::{RepositoryExt, RestackStrategy},
+    output::progress,
+    theme::Color,
 };
-use git2::BranchType;
+use git2::{BranchType, Oid};
 use std::collections::VecDeque;
 
 impl StContext<'_> {
@@ -16,7 +18,7 @@ impl StContext<'_> {
         let mut stack = VecDeque::new();
 
         // Get the current branch name.
-        let current_branch = self.repository.current_branch_name()?;
+        let current_branch = self.current_branch()?;
         let current_tracked_branch = self
             .tree
             .get(&current_branch)
@@ -85,13 +87,235 @@ impl StContext<'_> {
         Ok(&parent_oid.to_string() != parent_oid_cache || self.needs_restack(parent_name)?)
     }
 
+    /// Returns the tracked branches whose cached parent [git2::Oid] no longer matches the head of
+    /// their parent branch, or whose parent needs to be restacked itself.
+    ///
+    /// ## Returns
+    /// - `Result<Vec<String>>` - The branches needing a restack, with parents before children.
+    pub fn branches_needing_restack(&self) -> StResult<Vec<String>> {
+        let mut stale = Vec::new();
+        for branch in self.tree.branches()? {
+            if self.needs_restack(&branch)? {
+                stale.push(branch);
+            }
+        }
+        Ok(stale)
+    }
+
     /// Performs a restack of the active stack.
     pub fn restack(&mut self) -> StResult<()> {
-        // Get all branches in the tree (ordered: parents before children)
-        let all_branches = self.tree.branches()?;
+        self.restack_with(RestackStrategy::Rebase, None)
+    }
+
+    /// Returns the branches restacked by [StContext::restack_with], ordered parents before
+    /// children.
+    ///
+    /// ## Takes
+    /// - `only` - The root of the subtree to restack, or [None] for every branch.
+    ///
+    /// ## Returns
+    /// - `Result<Vec<String>>` - The branches to restack, never including the trunk.
+    pub fn restack_targets(&self, only: Option<&str>) -> StResult<Vec<String>> {
+        let root = only.unwrap_or(self.tree.trunk_name());
+        let mut targets = self.tree.subtree(root)?;
+        targets.retain(|branch| branch != self.tree.trunk_name());
+        Ok(targets)
+    }
+
+    /// Returns the branches among `targets` that a restack would rebase, paired with the parent
+    /// each would be rebased onto. A branch is included when its cached parent [git2::Oid] no
+    /// longer matches its parent's head, or when an ancestor of it needs restacking. Nothing in
+    /// the repository is touched.
+    ///
+    /// ## Takes
+    /// - `targets` - The branches to consider, ordered parents before children.
+    ///
+    /// ## Returns
+    /// - `Result<Vec<(String, String)>>` - The `(branch, parent)` pairs, in the order passed.
+    pub fn restack_plan(&self, targets: &[String]) -> StResult<Vec<(String, String)>> {
+        let mut plan = Vec::new();
+        for branch in targets {
+            let Some(parent) = self.tree.get(branch).and_then(|b| b.parent.clone()) else {
+                continue;
+            };
+            if self.needs_restack(branch)? {
+                plan.push((branch.clone(), parent));
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Returns the branches passed whose changes are already contained in the trunk, e.g. after
+    /// a squash merge. Branches that introduce no changes relative to their parent are skipped.
+    ///
+    /// ## Takes
+    /// - `branches` - The names of the tracked branches to check.
+    ///
+    /// ## Returns
+    /// - `Result<Vec<String>>` - The merged branches, in the order passed.
+    pub fn merged_branches(&self, branches: &[String]) -> StResult<Vec<String>> {
+        let trunk_name = self.tree.trunk_name();
+        let mut merged = Vec::new();
+        for branch in branches {
+            let Some(parent) = self.tree.get(branch).and_then(|b| b.parent.as_ref()) else {
+                continue;
+            };
+
+            // A branch without changes of its own is trivially contained in the trunk; skip it.
+            let fork_point = RepositoryExt::merge_base(self.repository, branch, parent)?;
+            let branch_tree = self
+                .repository
+                .revparse_single(branch)?
+                .peel_to_tree()?
+                .id();
+            if self.repository.find_commit(fork_point)?.tree_id() == branch_tree {
+                continue;
+            }
+
+            if self.repository.is_merged_into(branch, trunk_name)? {
+                merged.push(branch.clone());
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Performs a restack of the active stack, or of a single subtree of it. Before restacking,
+    /// offers to delete branches whose changes were already merged into the trunk, as rebasing
+    /// them would produce empty or conflicting commits.
+    ///
+    /// ## Takes
+    /// - `strategy` - The [RestackStrategy] used to bring each branch up to date.
+    /// - `only` - The root of the subtree to restack, or [None] for every branch.
+    pub fn restack_with(&mut self, strategy: RestackStrategy, only: Option<&str>) -> StResult<()> {
+        let targets = self.restack_targets(only)?;
+        self.restack_branches_with(strategy, targets)
+    }
+
+    /// Restacks the stacks on every trunk, one trunk at a time, reporting the result for each.
+    /// The active trunk is always restored. The branch that was checked out is checked out again,
+    /// unless a restack stopped on conflicts that are left to be resolved.
+    ///
+    /// ## Takes
+    /// - `strategy` - The [RestackStrategy] used to bring each branch up to date.
+    /// - `keep_going` - Whether to abort the restack of a trunk that stops on conflicts and carry
+    ///   on with the remaining trunks, rather than stopping at the conflicts.
+    ///
+    /// ## Returns
+    /// - `Result<()>` - [StError::TrunksNotRestacked] listing the trunks that failed to restack
+    ///   with `keep_going`, or the first error otherwise.
+    pub fn restack_all_trunks(
+        &mut self,
+        strategy: RestackStrategy,
+        keep_going: bool,
+    ) -> StResult<()> {
+        let current_branch = self.current_branch()?;
+        let active_trunk = self.tree.active_trunk.clone();
+        let restacked = self.restack_each_trunk(strategy, keep_going);
+        self.tree.active_trunk = active_trunk;
+
+        let failed = match restacked {
+            Err(e @ StError::RestackConflict { .. }) => return Err(e),
+            restacked => {
+                let checked_out = self.repository.checkout_branch(&current_branch);
+                let failed = restacked?;
+                checked_out?;
+                failed
+            }
+        };
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(StError::TrunksNotRestacked(failed))
+        }
+    }
+
+    /// Restacks the stack on each trunk in turn, leaving the last one restacked as the active
+    /// trunk. See [StContext::restack_all_trunks].
+    ///
+    /// ## Returns
+    /// - `Result<Vec<String>>` - The trunks whose restack was aborted with `keep_going`.
+    fn restack_each_trunk(
+        &mut self,
+        strategy: RestackStrategy,
+        keep_going: bool,
+    ) -> StResult<Vec<String>> {
+        let mut trunks = self.tree.list_trunks();
+        trunks.sort();
+
+        let mut failed = Vec::new();
+        for trunk in trunks {
+            progress!("\n🧙 Restacking trunk `{}`...", Color::Yellow.paint(&trunk));
+            self.tree.active_trunk = trunk.clone();
+            match self.restack_with(strategy, None) {
+                Ok(()) => progress!("✅ Restacked trunk `{}`.", Color::Yellow.paint(&trunk)),
+                Err(StError::RestackConflict { branch, parent, .. }) if keep_going => {
+                    match strategy {
+                        RestackStrategy::Rebase => self.repository.abort_rebase()?,
+                        RestackStrategy::Merge => self.repository.abort_merge()?,
+                    }
+                    println!(
+                        "❌ Failed to restack trunk `{}`: `{}` conflicts with `{}`.",
+                        Color::Yellow.paint(&trunk),
+                        Color::Green.paint(branch),
+                        Color::Yellow.paint(parent)
+                    );
+                    failed.push(trunk);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(failed)
+    }
+
+    /// Restacks the descendants of the checked out branch, leaving the branch itself and its
+    /// ancestors untouched, then checks the branch out again. Only the parent oid caches of the
+    /// restacked descendants are updated.
+    ///
+    /// ## Takes
+    /// - `strategy` - The [RestackStrategy] used to bring each branch up to date.
+    pub fn restack_from_here(&mut self, strategy: RestackStrategy) -> StResult<()> {
+        let current_branch = self.current_branch()?;
+        let targets = self.tree.descendants(&current_branch)?;
+        self.restack_branches_with(strategy, targets)?;
+        self.repository.checkout_branch(&current_branch)?;
+        Ok(())
+    }
+
+    /// Restacks the branches passed onto their parents, after offering to delete the ones whose
+    /// changes were already merged into the trunk. See [StContext::restack_with].
+    fn restack_branches_with(
+        &mut self,
+        strategy: RestackStrategy,
+        mut targets: Vec<String>,
+    ) -> StResult<()> {
+        // Offer to prune merged branches instead of restacking them. Their children are re-linked
+        // onto their parents, and rebased past the pruned commits.
+        let mut stale = Vec::new();
+        for branch in &targets {
+            if self.needs_restack(branch)? {
+                stale.push(branch.clone());
+            }
+        }
+        for branch in self.merged_branches(&stale)? {
+            let confirm = inquire::Confirm::new(
+                format!(
+                    "Branch `{}` is already merged into `{}`. Delete it instead of restacking?",
+                    Color::Green.paint(&branch),
+                    Color::Yellow.paint(self.tree.trunk_name())
+                )
+                .as_str(),
+            )
+            .with_default(true)
+            .prompt()?;
+            if confirm {
+                self.remove_branch(&branch)?;
+                targets.retain(|target| target != &branch);
+                progress!("Pruned merged branch `{}`.", Color::Green.paint(&branch));
+            }
+        }
 
-        // Restack each branch onto its parent
-        for branch in all_branches.iter().skip(1) {
+        // Restack each branch onto its parent, parents before children.
+        for branch in targets.iter() {
             let parent_name = {
                 let tracked_branch = self
                     .tree
@@ -102,10 +326,840 @@ impl StContext<'_> {
             };
             
             if let Some(parent) = parent_name {
-                self.restack_branch(branch, &parent)?;
+                self.restack_branch(branch, &parent, strategy)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Moves the current stack onto another tracked branch. The bottom branch of the stack is
+    /// rebased onto `onto` and stacked on it, and the rest of the stack is then restacked on top.
+    /// If a rebase stops on conflicts, the branches restacked so far keep their new parents, so
+    /// `st restack` picks up where it left off once the conflicts are resolved.
+    ///
+    /// ## Takes
+    /// - `onto` - The name of the tracked branch to move the stack onto.
+    pub fn restack_onto(&mut self, onto: &str) -> StResult<()> {
+        if !self.repository.is_working_tree_clean()? {
+            return Err(StError::WorkingTreeDirty);
+        }
+
+        let stack = self.discover_stack()?;
+        let bottom = stack
+            .get(1)
+            .cloned()
+            .ok_or(StError::CannotMoveTrunkBranch)?;
+        if self.tree.get(onto).is_none() {
+            return Err(StError::BranchNotTracked(onto.to_string()));
+        }
+        if self.tree.subtree(&bottom)?.iter().any(|b| b == onto) {
+            return Err(StError::InvalidOnto(bottom, onto.to_string()));
+        }
+
+        // Only the commits of the bottom branch itself are moved, so find where they start.
+        let old_parent = stack[0].clone();
+        let upstream = self
+            .tree
+            .get(&bottom)
+            .and_then(|b| b.parent_oid_cache.as_deref())
+            .and_then(|oid| Oid::from_str(oid).ok())
+            .filter(|oid| self.repository.find_commit(*oid).is_ok());
+        let upstream = match upstream {
+            Some(oid) => oid,
+            None => RepositoryExt::merge_base(self.repository, &bottom, &old_parent)?,
+        };
+        if let Err(e) = self
+            .repository
+            .rebase_commits_onto(&bottom, &upstream.to_string(), onto)
+        {
+            eprintln!(
+                "Failed to move branch `{}` onto `{}`",
+                Color::Green.paint(&bottom),
+                Color::Yellow.paint(onto),
+            );
+            return Err(e.into());
+        }
+
+        // Stack the bottom branch on its new parent.
+        let onto_oid = self
+            .repository
+            .find_branch(onto, BranchType::Local)?
+            .get()
+            .target()
+            .ok_or(StError::BranchUnavailable)?;
+        self.tree.reparent(&bottom, onto)?;
+        self.tree
+            .get_mut(&bottom)
+            .expect("Must exist")
+            .parent_oid_cache = Some(onto_oid.to_string());
+        progress!(
+            "Moved branch `{}` onto `{}`.",
+            Color::Green.paint(&bottom),
+            Color::Yellow.paint(onto)
+        );
+
+        // Cascade the move up the rest of the stack.
+        self.restack_with(RestackStrategy::Rebase, Some(&bottom))
+    }
+
+    /// Infers the chain of untracked branches below `leaf`, down to the closest tracked branch.
+    /// Each branch's parent is the branch it most recently diverged from, as determined by
+    /// [RepositoryExt::closest_branch]. Branches that contain the current branch are never
+    /// considered as its parent.
+    ///
+    /// ## Takes
+    /// - `leaf` - The name of the untracked branch at the top of the chain.
+    ///
+    /// ## Returns
+    /// - `Result<Vec<(String, String)>>` - Each branch in the chain and its parent, ordered from
+    ///   the branch on top of the tracked branch to `leaf`.
+    pub fn adoption_chain(&self, leaf: &str) -> StResult<Vec<(String, String)>> {
+        if self.tree.get(leaf).is_some() {
+            return Err(StError::BranchAlreadyTracked(leaf.to_string()));
+        }
+
+        let local_branches = self
+            .repository
+            .branches(Some(BranchType::Local))?
+            .map(|b| {
+                let (b, _) = b?;
+                b.name()?
+                    .map(ToOwned::to_owned)
+                    .ok_or(StError::BranchUnavailable)
+            })
+            .collect::<StResult<Vec<_>>>()?;
+        let head = |name: &str| -> StResult<Oid> {
+            Ok(self
+                .repository
+                .revparse_single(name)?
+                .peel_to_commit()?
+                .id())
+        };
+
+        let mut chain = Vec::new();
+        let mut current = leaf.to_string();
+        loop {
+            let current_head = head(&current)?;
+            let mut candidates = Vec::new();
+            for candidate in &local_branches {
+                let in_chain = candidate == leaf || chain.iter().any(|(b, _)| b == candidate);
+                if !in_chain
+                    && !self
+                        .repository
+                        .is_ancestor(current_head, head(candidate)?)?
+                {
+                    candidates.push(candidate.clone());
+                }
             }
+
+            let parent = self
+                .repository
+                .closest_branch(&current, &candidates)?
+                .ok_or_else(|| StError::NoTrackedAncestor(leaf.to_string()))?;
+            let is_tracked = self.tree.get(&parent).is_some();
+            chain.push((current, parent.clone()));
+            if is_tracked {
+                break;
+            }
+            current = parent;
         }
 
+        chain.reverse();
+        Ok(chain)
+    }
+
+    /// Tracks each branch of a chain returned by [StContext::adoption_chain] on top of its
+    /// parent. Parent oid caches are set to the merge-base of each branch and its parent.
+    ///
+    /// ## Takes
+    /// - `chain` - Each branch and its parent, with parents tracked before their children.
+    pub fn adopt_chain(&mut self, chain: &[(String, String)]) -> StResult<()> {
+        for (branch, parent) in chain {
+            let merge_base = RepositoryExt::merge_base(self.repository, branch, parent)?;
+            self.tree.insert(parent, &merge_base.to_string(), branch)?;
+        }
         Ok(())
     }
+
+    /// Swaps a branch with its parent in the stack. The branch's own commits are rebased onto
+    /// its former grandparent, the former parent's commits onto the branch, and the commits of
+    /// the branch's child, if any, onto the former parent.
+    ///
+    /// The involved branches must be restacked and the working tree clean, as each branch's own
+    /// commits are delimited by the head of its parent.
+    pub fn reorder(&mut self, branch_name: &str) -> StResult<()> {
+        // Validate the new order on a copy of the tree before touching any branches.
+        let mut reordered = self.tree.clone();
+        reordered.swap_with_parent(branch_name)?;
+        let parent_name = reordered
+            .get(branch_name)
+            .and_then(|b| b.children.iter().next().cloned())
+            .expect("Former parent must be a child after the swap");
+        let grandparent_name = reordered
+            .get(branch_name)
+            .and_then(|b| b.parent.clone())
+            .expect("Former grandparent must be the parent after the swap");
+        let child_name = reordered
+            .get(&parent_name)
+            .and_then(|b| b.children.iter().next().cloned());
+
+        let mut involved = vec![parent_name.clone(), branch_name.to_string()];
+        involved.extend(child_name.clone());
+        self.check_cleanliness(&involved)?;
+
+        let repository = self.repository;
+        let head = |name: &str| -> StResult<String> {
+            Ok(repository
+                .find_branch(name, BranchType::Local)?
+                .get()
+                .target()
+                .ok_or(StError::BranchUnavailable)?
+                .to_string())
+        };
+        let grandparent_head = head(&grandparent_name)?;
+        let parent_head = head(&parent_name)?;
+        let branch_head = head(branch_name)?;
+
+        // Replay each branch's own commits in the new order.
+        repository.rebase_commits_onto(branch_name, &parent_head, &grandparent_name)?;
+        repository.rebase_commits_onto(&parent_name, &grandparent_head, branch_name)?;
+        if let Some(child_name) = &child_name {
+            repository.rebase_commits_onto(child_name, &branch_head, &parent_name)?;
+        }
+
+        // Commit the new order, caching the new heads of the rebased parents.
+        self.tree = reordered;
+        let mut rebased = vec![
+            (branch_name.to_string(), grandparent_name),
+            (parent_name.clone(), branch_name.to_string()),
+        ];
+        rebased.extend(child_name.map(|child| (child, parent_name)));
+        for (name, parent) in rebased {
+            self.tree
+                .get_mut(&name)
+                .ok_or_else(|| StError::BranchNotTracked(name.clone()))?
+                .parent_oid_cache = Some(head(&parent)?);
+        }
+
+        repository.checkout_branch(branch_name)?;
+        Ok(())
+    }
+
+    /// Rearranges the branches of a stack, as planned with `st edit`. Each branch is stacked on
+    /// its new parent, parents before children, and its own commits are rebased whenever its
+    /// parent changed or was rewritten. Dropped branches are deleted, which removes their commits
+    /// from the branches above them. If a rebase stops on conflicts, the branches rebased so far
+    /// keep their new parents.
+    ///
+    /// The involved branches must be restacked and the working tree clean, as each branch's own
+    /// commits are delimited by where it forked off its former parent.
+    ///
+    /// ## Takes
+    /// - `parents` - The new parent of each kept branch, with parents listed before children.
+    /// - `dropped` - The names of the branches to delete.
+    ///
+    /// ## Returns
+    /// - `Result<usize>` - The number of rebased branches.
+    pub fn edit_stack(
+        &mut self,
+        parents: &[(String, String)],
+        dropped: &[String],
+    ) -> StResult<usize> {
+        // Validate the new shape on a copy of the tree before touching any branches.
+        let mut edited = self.tree.clone();
+        for (branch, parent) in parents {
+            if edited.get(branch).and_then(|b| b.parent.as_deref()) != Some(parent.as_str()) {
+                edited.reparent(branch, parent)?;
+            }
+        }
+        for branch in dropped {
+            edited.delete(branch)?;
+        }
+        edited.validate()?;
+
+        let involved = parents
+            .iter()
+            .map(|(branch, _)| branch.clone())
+            .chain(dropped.iter().cloned())
+            .collect::<Vec<_>>();
+        self.check_cleanliness(&involved)?;
+
+        // Delimit the own commits of each branch before any branch is rewritten.
+        let mut moves = Vec::new();
+        for (branch, parent) in parents {
+            let old_parent = self
+                .tree
+                .get(branch)
+                .and_then(|b| b.parent.clone())
+                .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?;
+            let fork_point = self.fork_point(branch, &old_parent)?;
+            moves.push((branch, parent, old_parent, fork_point));
+        }
+
+        // Replay the own commits of each moved branch, and of each branch above a rewritten one.
+        // Each branch is stacked on its new parent as soon as it is rebased, caching the parent's
+        // head, so that the tree still matches git if a later rebase stops on conflicts.
+        let current_branch = self.current_branch()?;
+        let mut rebased = Vec::new();
+        for (branch, parent, old_parent, fork_point) in moves {
+            if *parent == old_parent && !rebased.contains(parent) {
+                continue;
+            }
+            self.repository
+                .rebase_commits_onto(branch, &fork_point.to_string(), parent)
+                .map_err(|source| StError::RestackConflict {
+                    branch: branch.clone(),
+                    parent: parent.clone(),
+                    source,
+                })?;
+
+            let parent_head = self
+                .repository
+                .find_branch(parent, BranchType::Local)?
+                .get()
+                .target()
+                .ok_or(StError::BranchUnavailable)?;
+            if *parent != old_parent {
+                self.tree.reparent(branch, parent)?;
+            }
+            self.tree
+                .get_mut(branch)
+                .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?
+                .parent_oid_cache = Some(parent_head.to_string());
+            rebased.push(branch.clone());
+        }
+
+        // Delete the dropped branches, returning to the branch that was checked out.
+        for branch in dropped {
+            self.remove_branch(branch)?;
+        }
+        if !dropped.contains(&current_branch) {
+            self.repository.checkout_branch(&current_branch)?;
+        }
+        Ok(rebased.len())
+    }
+
+    /// Returns the commits on a branch that are not on its parent branch, newest first.
+    pub fn own_commits(&self, branch_name: &str) -> StResult<Vec<Oid>> {
+        let parent_name = self
+            .tree
+            .get(branch_name)
+            .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?
+            .parent
+            .clone()
+            .ok_or(StError::CannotSplitTrunkBranch)?;
+
+        let mut revwalk = self.repository.revwalk()?;
+        revwalk.push_ref(&format!("refs/heads/{}", branch_name))?;
+        revwalk.hide_ref(&format!("refs/heads/{}", parent_name))?;
+        Ok(revwalk.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Splits a branch in two at one of its own commits. A new branch is created at the commit
+    /// and inserted between the branch and its parent, so that the commits up to and including
+    /// it move to the new branch, and the remainder stay on the branch. No commits are rewritten.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch to split.
+    /// - `at` - The last commit to move to the new branch. Must not be the head of the branch.
+    /// - `new_branch_name` - The name of the new, lower branch.
+    pub fn split_branch(
+        &mut self,
+        branch_name: &str,
+        at: Oid,
+        new_branch_name: &str,
+    ) -> StResult<()> {
+        if self.tree.get(new_branch_name).is_some() {
+            return Err(StError::BranchAlreadyTracked(new_branch_name.to_string()));
+        }
+        if self
+            .repository
+            .find_branch(new_branch_name, BranchType::Local)
+            .is_ok()
+        {
+            return Err(StError::BranchAlreadyExists(new_branch_name.to_string()));
+        }
+        self.check_cleanliness(&[branch_name.to_string()])?;
+
+        // The head must stay on the branch, so that neither half is empty.
+        if !self
+            .own_commits(branch_name)?
+            .iter()
+            .skip(1)
+            .any(|oid| *oid == at)
+        {
+            return Err(StError::InvalidSplitPoint(at.to_string()));
+        }
+
+        let commit = self.repository.find_commit(at)?;
+        self.repository.branch(new_branch_name, &commit, false)?;
+        self.tree
+            .insert_below(branch_name, new_branch_name, &at.to_string())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ctx::{test::test_context, StContext},
+        errors::StError,
+        git::{test::TempRepository, RepositoryExt, RestackStrategy},
+        tree::StackTree,
+    };
+    use git2::{build::CheckoutBuilder, BranchType, RepositoryState};
+
+    #[test]
+    fn advancing_parent_marks_child_for_restack() {
+        let repo = TempRepository::new();
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit("Feature 1");
+        repo.branch("feature-2");
+        let feature_2_head = repo.commit("Feature 2");
+        repo.branch("feature-3");
+        repo.commit("Feature 3");
+
+        let main_head = repo
+            .repository
+            .find_branch("main", git2::BranchType::Local)
+            .unwrap()
+            .get()
+            .target()
+            .unwrap();
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1").unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2").unwrap();
+        tree.insert("feature-2", &feature_2_head.to_string(), "feature-3").unwrap();
+
+        let ctx = test_context(&repo.repository, tree);
+        assert!(ctx.branches_needing_restack().unwrap().is_empty());
+
+        // Advance `feature-1`, invalidating the cache of `feature-2` and, transitively, `feature-3`.
+        repo.checkout("feature-1");
+        repo.commit("Feature 1 follow-up");
+        assert_eq!(
+            ctx.branches_needing_restack().unwrap(),
+            vec!["feature-2", "feature-3"]
+        );
+    }
+
+    #[test]
+    fn splitting_branch_moves_lower_commits_to_new_parent() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature");
+        let commits = (1..=4)
+            .map(|i| repo.commit(&format!("Feature {}", i)))
+            .collect::<Vec<_>>();
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        assert!(matches!(
+            ctx.split_branch("feature", commits[3], "feature-base"),
+            Err(StError::InvalidSplitPoint(_))
+        ));
+        ctx.split_branch("feature", commits[1], "feature-base")
+            .unwrap();
+
+        assert_eq!(
+            ctx.tree.branches().unwrap(),
+            vec!["main", "feature-base", "feature"]
+        );
+        assert_eq!(
+            ctx.tree.get("feature").unwrap().parent.as_deref(),
+            Some("feature-base")
+        );
+        assert_eq!(
+            ctx.tree.get("feature-base").unwrap().parent.as_deref(),
+            Some("main")
+        );
+        assert_eq!(
+            ctx.own_commits("feature-base").unwrap(),
+            vec![commits[1], commits[0]]
+        );
+        assert_eq!(
+            ctx.own_commits("feature").unwrap(),
+            vec![commits[3], commits[2]]
+        );
+        assert!(ctx.branches_needing_restack().unwrap().is_empty());
+    }
+
+    #[test]
+    fn restack_only_targets_named_subtree() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit("Feature 1");
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+        repo.checkout("main");
+        repo.branch("sibling");
+        repo.commit("Sibling");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2")
+            .unwrap();
+        tree.insert("main", &main_head.to_string(), "sibling")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        let mut all_targets = ctx.restack_targets(None).unwrap();
+        all_targets.sort();
+        assert_eq!(all_targets, vec!["feature-1", "feature-2", "sibling"]);
+        assert_eq!(
+            ctx.restack_targets(Some("feature-1")).unwrap(),
+            vec!["feature-1", "feature-2"]
+        );
+
+        // Advance `main`, so that every branch needs restacking, but mark the `feature-1`
+        // subtree as already restacked. Restacking it must leave `sibling` untouched.
+        repo.checkout("main");
+        let main_head = repo.commit("Main follow-up");
+        ctx.tree.get_mut("feature-1").unwrap().parent_oid_cache = Some(main_head.to_string());
+        assert_eq!(ctx.branches_needing_restack().unwrap(), vec!["sibling"]);
+
+        ctx.restack_with(RestackStrategy::Rebase, Some("feature-1"))
+            .unwrap();
+        assert_eq!(ctx.branches_needing_restack().unwrap(), vec!["sibling"]);
+        assert!(ctx.restack_targets(Some("untracked")).is_err());
+    }
+
+    #[test]
+    fn restack_from_here_only_rebases_descendants() {
+        let repo = TempRepository::new();
+        let mut heads = vec![repo.commit_file("Main", "main.txt", "main")];
+        for name in ["feature-1", "feature-2", "feature-3"] {
+            repo.branch(name);
+            heads.push(repo.commit_file(name, &format!("{}.txt", name), name));
+        }
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &heads[0].to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &heads[1].to_string(), "feature-2")
+            .unwrap();
+        tree.insert("feature-2", &heads[2].to_string(), "feature-3")
+            .unwrap();
+
+        // Advance `main` below the current branch, and `feature-2` itself.
+        repo.checkout("main");
+        repo.commit_file("Main follow-up", "main.txt", "main 2");
+        repo.checkout("feature-2");
+        let feature_2_head = repo.commit_file("Feature 2 follow-up", "feature-2.txt", "2");
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+        assert_eq!(
+            ctx.tree.descendants("feature-1").unwrap(),
+            vec!["feature-2", "feature-3"]
+        );
+
+        ctx.restack_from_here(RestackStrategy::Rebase).unwrap();
+
+        // The ancestors keep their heads and caches, so `feature-1` still needs restacking.
+        let cache = |branch: &str| ctx.tree.get(branch).unwrap().parent_oid_cache.clone();
+        let head = |branch: &str| {
+            repo.repository
+                .find_branch(branch, BranchType::Local)
+                .unwrap()
+                .get()
+                .target()
+                .unwrap()
+        };
+        assert_eq!(cache("feature-1"), Some(heads[0].to_string()));
+        assert_eq!(cache("feature-2"), Some(heads[1].to_string()));
+        assert_eq!(head("feature-1"), heads[1]);
+        assert_eq!(head("feature-2"), feature_2_head);
+        assert!(ctx.needs_restack("feature-1").unwrap());
+
+        // The descendant is rebased onto the current branch, and its cache updated.
+        assert_eq!(cache("feature-3"), Some(feature_2_head.to_string()));
+        assert!(repo
+            .repository
+            .is_ancestor(feature_2_head, head("feature-3"))
+            .unwrap());
+        assert_eq!(repo.repository.current_branch_name().unwrap(), "feature-2");
+    }
+
+    #[test]
+    fn restack_plan_lists_affected_descendants_without_rebasing() {
+        let repo = TempRepository::new();
+        let mut heads = vec![repo.commit_file("Main", "main.txt", "main")];
+        for name in ["feature-1", "feature-2", "feature-3"] {
+            repo.branch(name);
+            heads.push(repo.commit_file(name, &format!("{}.txt", name), name));
+        }
+        repo.checkout("main");
+        repo.branch("sibling");
+        let sibling_head = repo.commit_file("Sibling", "sibling.txt", "sibling");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &heads[0].to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &heads[1].to_string(), "feature-2")
+            .unwrap();
+        tree.insert("feature-2", &heads[2].to_string(), "feature-3")
+            .unwrap();
+        tree.insert("main", &heads[0].to_string(), "sibling")
+            .unwrap();
+
+        // Advance `feature-1`, so that only the branches stacked above it are affected.
+        repo.checkout("feature-1");
+        let feature_1_head = repo.commit_file("Feature 1 follow-up", "feature-1.txt", "1");
+        let ctx = test_context(&repo.repository, tree);
+
+        let targets = ctx.restack_targets(None).unwrap();
+        let plan = ctx.restack_plan(&targets).unwrap();
+        assert_eq!(
+            plan,
+            vec![
+                ("feature-2".to_string(), "feature-1".to_string()),
+                ("feature-3".to_string(), "feature-2".to_string()),
+            ]
+        );
+
+        // No branch was rebased, and no cache was updated.
+        let head = |branch: &str| {
+            repo.repository
+                .find_branch(branch, BranchType::Local)
+                .unwrap()
+                .get()
+                .target()
+                .unwrap()
+        };
+        assert_eq!(head("feature-1"), feature_1_head);
+        assert_eq!(head("feature-2"), heads[2]);
+        assert_eq!(head("feature-3"), heads[3]);
+        assert_eq!(head("sibling"), sibling_head);
+        assert_eq!(
+            ctx.tree.get("feature-2").unwrap().parent_oid_cache,
+            Some(heads[1].to_string())
+        );
+        assert_eq!(repo.repository.current_branch_name().unwrap(), "feature-1");
+    }
+
+    #[test]
+    fn restack_all_trunks_restacks_every_stack() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit_file("Main", "main.txt", "main");
+        repo.branch("develop");
+        let develop_head = repo.commit_file("Develop", "develop.txt", "develop");
+        repo.branch("dev-feature");
+        repo.commit_file("Dev feature", "dev-feature.txt", "dev");
+        repo.checkout("main");
+        repo.branch("feature");
+        repo.commit_file("Feature", "feature.txt", "feature");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature")
+            .unwrap();
+        tree.add_trunk("develop".to_string());
+        tree.switch_trunk("develop").unwrap();
+        tree.insert("develop", &develop_head.to_string(), "dev-feature")
+            .unwrap();
+        tree.switch_trunk("main").unwrap();
+
+        // Advance both trunks, so that the stack on each needs restacking.
+        for trunk in ["main", "develop"] {
+            repo.checkout(trunk);
+            repo.commit_file("Follow-up", &format!("{}.txt", trunk), "follow-up");
+        }
+        repo.checkout("feature");
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+        let stale = |ctx: &mut StContext<'_>| {
+            ["main", "develop"]
+                .into_iter()
+                .filter(|trunk| {
+                    ctx.tree.active_trunk = trunk.to_string();
+                    !ctx.branches_needing_restack().unwrap().is_empty()
+                })
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(stale(&mut ctx), vec!["main", "develop"]);
+        ctx.tree.active_trunk = "main".to_string();
+
+        ctx.restack_all_trunks(RestackStrategy::Rebase, false)
+            .unwrap();
+        assert_eq!(ctx.tree.active_trunk, "main");
+        assert_eq!(repo.repository.current_branch_name().unwrap(), "feature");
+        assert!(stale(&mut ctx).is_empty());
+    }
+
+    #[test]
+    fn restack_all_trunks_keeps_going_past_merge_conflicts() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit_file("Main", "main.txt", "main");
+        repo.branch("develop");
+        let develop_head = repo.commit_file("Develop", "develop.txt", "develop");
+        repo.branch("dev-feature");
+        repo.commit_file("Dev feature", "develop.txt", "dev feature");
+        repo.checkout("main");
+        repo.branch("feature");
+        repo.commit_file("Feature", "feature.txt", "feature");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature")
+            .unwrap();
+        tree.add_trunk("develop".to_string());
+        tree.switch_trunk("develop").unwrap();
+        tree.insert("develop", &develop_head.to_string(), "dev-feature")
+            .unwrap();
+        tree.switch_trunk("main").unwrap();
+
+        // Advance both trunks. The follow-up on `develop` conflicts with `dev-feature`.
+        for (trunk, file) in [("main", "main.txt"), ("develop", "develop.txt")] {
+            repo.checkout(trunk);
+            repo.commit_file("Follow-up", file, "follow-up");
+        }
+        repo.checkout("feature");
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        // The conflicting merge is aborted, and the other trunk is still restacked.
+        assert!(matches!(
+            ctx.restack_all_trunks(RestackStrategy::Merge, true),
+            Err(StError::TrunksNotRestacked(failed)) if failed == vec!["develop"]
+        ));
+        assert_eq!(repo.repository.state(), RepositoryState::Clean);
+        assert!(repo.repository.is_working_tree_clean().unwrap());
+        assert_eq!(ctx.tree.active_trunk, "main");
+        assert_eq!(repo.repository.current_branch_name().unwrap(), "feature");
+        assert!(ctx.branches_needing_restack().unwrap().is_empty());
+    }
+
+    #[test]
+    fn restack_onto_refuses_invalid_targets() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit("Feature 1");
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+        repo.checkout("main");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        assert!(matches!(
+            ctx.restack_onto("feature-2"),
+            Err(StError::InvalidOnto(bottom, onto)) if bottom == "feature-1" && onto == "feature-2"
+        ));
+        assert!(matches!(
+            ctx.restack_onto("release"),
+            Err(StError::BranchNotTracked(name)) if name == "release"
+        ));
+
+        // With multiple stacks on the trunk, the trunk has no single stack to move.
+        repo.branch("release");
+        ctx.tree
+            .insert("main", &main_head.to_string(), "release")
+            .unwrap();
+        repo.checkout("main");
+        assert!(matches!(
+            ctx.restack_onto("release"),
+            Err(StError::CannotMoveTrunkBranch)
+        ));
+        assert_eq!(
+            ctx.tree.get("feature-1").unwrap().parent.as_deref(),
+            Some("main")
+        );
+    }
+
+    #[test]
+    fn squash_merged_branch_is_flagged_as_merged() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature");
+        repo.commit_file("Feature", "feature.txt", "feature");
+        repo.checkout("main");
+        repo.branch("sibling");
+        repo.commit_file("Sibling", "sibling.txt", "sibling");
+        repo.checkout("main");
+        repo.branch("empty");
+        repo.commit("Empty");
+
+        let mut tree = StackTree::new("main".to_string());
+        for branch in ["feature", "sibling", "empty"] {
+            tree.insert("main", &main_head.to_string(), branch).unwrap();
+        }
+        let ctx = test_context(&repo.repository, tree);
+        let branches = ["feature", "sibling", "empty"].map(String::from);
+        assert!(ctx.merged_branches(&branches).unwrap().is_empty());
+
+        // Squash merge `feature` into `main`, which then moves past every branch.
+        repo.checkout("main");
+        repo.commit_file("Feature (#1)", "feature.txt", "feature");
+        repo.commit_file("Unrelated", "unrelated.txt", "unrelated");
+
+        let mut stale = ctx.branches_needing_restack().unwrap();
+        stale.sort();
+        assert_eq!(stale, vec!["empty", "feature", "sibling"]);
+        assert_eq!(ctx.merged_branches(&branches).unwrap(), vec!["feature"]);
+    }
+
+    #[test]
+    fn adopting_leaf_tracks_whole_chain() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit("Feature 1");
+        repo.branch("feature-2");
+        let feature_2_head = repo.commit("Feature 2");
+        repo.branch("feature-3");
+        repo.commit("Feature 3");
+        repo.branch("feature-4");
+        repo.commit("Feature 4");
+        repo.checkout("feature-1");
+        repo.branch("sibling");
+        repo.commit("Sibling");
+        repo.checkout("main");
+        repo.commit("Main follow-up");
+
+        let mut ctx = test_context(&repo.repository, StackTree::new("main".to_string()));
+        let chain = ctx.adoption_chain("feature-3").unwrap();
+        assert_eq!(
+            chain,
+            [
+                ("feature-1", "main"),
+                ("feature-2", "feature-1"),
+                ("feature-3", "feature-2"),
+            ]
+            .map(|(b, p)| (b.to_string(), p.to_string()))
+        );
+        ctx.adopt_chain(&chain).unwrap();
+
+        for (branch, parent, parent_oid) in [
+            ("feature-1", "main", main_head),
+            ("feature-2", "feature-1", feature_1_head),
+            ("feature-3", "feature-2", feature_2_head),
+        ] {
+            let tracked = ctx.tree.get(branch).unwrap();
+            assert_eq!(tracked.parent.as_deref(), Some(parent));
+            assert_eq!(
+                tracked.parent_oid_cache.as_deref(),
+                Some(parent_oid.to_string().as_str())
+            );
+        }
+        assert!(ctx.tree.get("sibling").is_none());
+        assert!(ctx.tree.get("feature-4").is_none());
+        assert!(matches!(
+            ctx.adoption_chain("feature-3"),
+            Err(StError::BranchAlreadyTracked(_))
+        ));
+    }
 }
@@ -1,7 +1,15 @@
 //! Errors for the `st` application.
 
-use crate::{config::StConfigError, git::GitCommandError};
-use nu_ansi_term::Color;
+use crate::{
+    config::StConfigError,
+    constants::{
+        EXIT_AUTH_FAILURE, EXIT_CONFLICT, EXIT_DIRTY_TREE, EXIT_FAILURE, EXIT_NEEDS_RESTACK,
+        EXIT_NOTHING_TO_DO,
+    },
+    git::GitCommandError,
+    theme::Color,
+};
+use std::fmt;
 use thiserror::Error;
 
 /// Errors for the `st` application.
@@ -19,9 +27,15 @@ pub enum StError {
     /// The branch is already tracked with `st`.
     #[error("Branch `{}` is already tracked with `{}`.", Color::Blue.paint(.0), Color::Blue.paint("st"))]
     BranchAlreadyTracked(String),
+    /// A branch with the given name already exists.
+    #[error("Branch `{}` already exists.", Color::Blue.paint(.0))]
+    BranchAlreadyExists(String),
     /// Cannot delete the trunk branch.
     #[error("Cannot delete the trunk branch.")]
     CannotDeleteTrunkBranch,
+    /// Cannot untrack the trunk branch.
+    #[error("Cannot untrack the trunk branch.")]
+    CannotUntrackTrunkBranch,
     /// A branch needs to be restacked.
     #[error(
         "Branch `{}` needs to be restacked before continuing. Restack with `{}` before continuing.",
@@ -29,12 +43,139 @@ pub enum StError {
         Color::Blue.paint("st restack")
     )]
     NeedsRestack(String),
+    /// The branch has no tracked parent to be reordered with.
+    #[error("Branch `{}` is already at the bottom of its stack.", Color::Blue.paint(.0))]
+    ReorderAtBottom(String),
+    /// The branch or its parent has multiple children, so the reordered stack is ambiguous.
+    #[error(
+        "Cannot reorder `{}` with its parent, as one of them has multiple children.",
+        Color::Blue.paint(.0)
+    )]
+    ReorderAmbiguous(String),
+    /// The plan saved from `st edit` can't be applied.
+    #[error("Invalid stack plan: {}.", .0)]
+    InvalidStackPlan(String),
+    /// No tracked branch shares history with the branch.
+    #[error(
+        "Branch `{}` does not share history with any tracked branch.",
+        Color::Blue.paint(.0)
+    )]
+    NoTrackedAncestor(String),
+    /// A trunk with the given name is not tracked.
+    #[error("Trunk `{}` does not exist.", Color::Blue.paint(.0))]
+    TrunkNotFound(String),
+    /// A trunk with the given name is already tracked.
+    #[error("Trunk `{}` already exists.", Color::Blue.paint(.0))]
+    TrunkAlreadyExists(String),
+    /// `--trunk` was passed to a command that does not operate on a single trunk.
+    #[error(
+        "`--trunk` is only supported by `log`, `export`, `open`, `status`, `submit`, and `trunk info`."
+    )]
+    TrunkOverrideUnsupported,
+    /// Cannot split the trunk branch.
+    #[error("Cannot split the trunk branch.")]
+    CannotSplitTrunkBranch,
+    /// The commit to split a branch at is not one of the branch's own commits below its head.
+    #[error(
+        "Cannot split at commit `{}`, as it is not one of the branch's own commits below its head.",
+        Color::Blue.paint(.0)
+    )]
+    InvalidSplitPoint(String),
+    /// Cannot commit a fixup onto the trunk branch.
+    #[error("Cannot commit a fixup onto the trunk branch.")]
+    CannotFixupTrunkBranch,
+    /// There are no working changes to commit as a fixup.
+    #[error(
+        "No changes to commit onto branch `{}`. Pass `{}` to include untracked files.",
+        Color::Blue.paint(.0),
+        Color::Blue.paint("--all")
+    )]
+    NothingToFixup(String),
+    /// The branch has no commits of its own to squash changes into.
+    #[error(
+        "Branch `{}` has no commits of its own to squash the changes into.",
+        Color::Blue.paint(.0)
+    )]
+    NoCommitsToSquash(String),
+    /// Cannot amend a commit of the trunk branch.
+    #[error("Cannot amend the trunk branch.")]
+    CannotAmendTrunkBranch,
+    /// The trunk branch is not part of a stack, and cannot be described.
+    #[error("Cannot describe the trunk branch. Check out a branch of the stack to describe.")]
+    CannotDescribeTrunkBranch,
+    /// There are neither staged changes nor a new message to amend the last commit with.
+    #[error(
+        "Nothing to amend on branch `{}`. Stage changes, or pass `{}` or `{}`.",
+        Color::Blue.paint(.0),
+        Color::Blue.paint("--all"),
+        Color::Blue.paint("--message")
+    )]
+    NothingToAmend(String),
+    /// Cannot move the trunk branch onto another branch.
+    #[error("Cannot move the trunk branch.")]
+    CannotMoveTrunkBranch,
+    /// The branch to move a stack onto is part of the stack itself.
+    #[error(
+        "Cannot move `{}` onto `{}`, which is part of the stack being moved.",
+        Color::Blue.paint(.0),
+        Color::Blue.paint(.1)
+    )]
+    InvalidOnto(String, String),
     /// A commit message is required with --all or --update
     #[error("Commit message is required with --all or --update")]
     CommitMessageRequired,
+    /// A pull request title is required.
+    #[error("Pull request title is required and cannot be empty.")]
+    PrTitleRequired,
     /// The working tree is dirty.
     #[error("Working tree is dirty. Please commit or stash changes before continuing.")]
     WorkingTreeDirty,
+    /// Restacking a branch onto its parent stopped on conflicts.
+    #[error(
+        "Failed to restack branch `{}` onto `{}`. Resolve the conflicts, or abort with `{}`.\n{}",
+        Color::Green.paint(.branch),
+        Color::Yellow.paint(.parent),
+        Color::Blue.paint("git rebase --abort"),
+        .source
+    )]
+    RestackConflict {
+        /// The name of the branch.
+        branch: String,
+        /// The name of the parent branch.
+        parent: String,
+        /// The failed `git` command.
+        source: GitCommandError,
+    },
+    /// Restacking stopped on conflicts on some trunks, with `restack --all-trunks --keep-going`.
+    #[error(
+        "Failed to restack the stacks on {}. Switch to each trunk to resolve the conflicts with `{}`.",
+        .0.iter().map(|trunk| format!("`{}`", Color::Yellow.paint(trunk))).collect::<Vec<_>>().join(", "),
+        Color::Blue.paint("st restack")
+    )]
+    TrunksNotRestacked(Vec<String>),
+    /// HEAD is detached, so there is no current branch.
+    #[error(
+        "HEAD is detached. Finish any rebase in progress, or check out a branch with `{}`.",
+        Color::Blue.paint("git checkout <branch>")
+    )]
+    DetachedHead,
+    /// HEAD points at a branch without any commits, e.g. in a freshly initialized repository.
+    #[error(
+        "Branch `{}` has no commits yet. Create the first commit with `{}`.",
+        Color::Green.paint(.0),
+        Color::Blue.paint("git commit")
+    )]
+    UnbornBranch(String),
+    /// The links between tracked branches are inconsistent.
+    #[error("The stack tree is inconsistent: {}.", .0)]
+    InvalidStackTree(String),
+    /// The store could not be traversed, e.g. because a branch is listed as its own descendant.
+    #[error(
+        "The store is corrupted: {}. Run `{}` to check it.",
+        .0,
+        Color::Blue.paint("st doctor")
+    )]
+    CorruptedStore(String),
     /// The parent's [git2::Oid] cache is missing.
     #[error("Parent's [git2::Oid] cache is missing.")]
     MissingParentOidCache,
@@ -46,6 +187,24 @@ pub enum StError {
     /// A remote pull request could not be found.
     #[error("Remote pull request not found.")]
     PullRequestNotFound,
+    /// A tracked branch has not been submitted as a pull request.
+    #[error(
+        "Branch `{}` has no pull request. Submit it first with `{}`.",
+        Color::Green.paint(.0),
+        Color::Blue.paint("st submit")
+    )]
+    BranchNotSubmitted(String),
+    /// The trunk branch cannot be submitted as a pull request on its own.
+    #[error("Cannot submit the trunk branch on its own.")]
+    CannotSubmitTrunkBranch,
+    /// The parent of a branch submitted on its own is neither submitted nor on the remote.
+    #[error(
+        "Parent branch `{}` of `{}` is not on the remote. Submit it first, or submit the whole stack with `{}`.",
+        Color::Yellow.paint(.1),
+        Color::Green.paint(.0),
+        Color::Blue.paint("st submit")
+    )]
+    ParentNotSubmitted(String, String),
     /// A base branch does not exist on remote.
     #[error(
         "Base branch `{}` does not exist on remote. Please push it first with: {}",
@@ -53,6 +212,60 @@ pub enum StError {
         Color::Blue.paint(format!("git push origin {}", .0))
     )]
     BaseBranchNotOnRemote(String),
+    /// CI checks are failing on a branch.
+    #[error(
+        "CI checks are failing for branch `{}`. Fix them, or submit without `{}`.",
+        Color::Green.paint(.0),
+        Color::Blue.paint("--require-green")
+    )]
+    ChecksFailing(String),
+    /// The local trunk branch differs from its remote counterpart.
+    #[error(
+        "Trunk branch `{}` is out of sync with the remote. Run `{}` first, or submit without `{}`.",
+        Color::Green.paint(.0),
+        Color::Blue.paint("st sync"),
+        Color::Blue.paint("--require-synced-trunk")
+    )]
+    TrunkOutOfSync(String),
+    /// The remote branch has commits that the local branch does not, e.g. after a collaborator
+    /// force-pushed it.
+    #[error(
+        "Branch `{}` has diverged from the remote. Pull the remote changes, or submit with `{}` to overwrite them.",
+        Color::Green.paint(.0),
+        Color::Blue.paint("--force")
+    )]
+    RemoteBranchDiverged(String),
+    /// Auto-merge is not enabled for the repository.
+    #[error(
+        "Auto-merge is not allowed for this repository. Enable it under `{}` in the repository settings.",
+        Color::Blue.paint("Settings > General > Allow auto-merge")
+    )]
+    AutoMergeNotAllowed,
+    /// Gemini responded without a PR description.
+    #[error("Gemini failed to generate a description: {}", .0)]
+    AiGenerationFailed(String),
+    /// The Gemini API rejected a request, e.g. because of an invalid API key.
+    #[error("Gemini rejected the request: {}", .0)]
+    AiRequestFailed(String),
+    /// The submission was aborted by the user.
+    #[error("Submission aborted.")]
+    SubmitAborted,
+    /// A GitHub GraphQL request returned errors.
+    #[error("🐙 GitHub GraphQL error: {}", .0)]
+    GraphQlError(String),
+    /// The GitHub token lacks the scopes or permissions needed to write pull requests.
+    #[error(
+        "🔑 The GitHub token can't write pull requests to `{}`. Missing: {}.\nUpdate the token at https://github.com/settings/tokens, or set another with `{}`.",
+        Color::Blue.paint(.repository),
+        .missing.join(", "),
+        Color::Blue.paint("st config set github_token <token>")
+    )]
+    MissingTokenScopes {
+        /// The repository, as `owner/repo`.
+        repository: String,
+        /// The missing scopes or permissions.
+        missing: Vec<String>,
+    },
 
     // ---- [ Git Errors ] ----
     /// `st` mused be run within a git repository.
@@ -61,12 +274,85 @@ pub enum StError {
     /// The git repository root could not be found.
     #[error("Git repository root could not be found.")]
     GitRepositoryRootNotFound,
+    /// The name is not a valid git branch name.
+    #[error("`{}` is not a valid branch name: {}.", Color::Blue.paint(.name), .reason)]
+    InvalidBranchName {
+        /// The rejected name.
+        name: String,
+        /// The rule of `git check-ref-format` that the name breaks.
+        reason: &'static str,
+    },
     /// Remote not found.
     #[error("Remote `{}` not found.", Color::Blue.paint(.0))]
     RemoteNotFound(String),
+    /// Two revisions share no history.
+    #[error(
+        "`{}` and `{}` have no common ancestor.",
+        Color::Blue.paint(.0),
+        Color::Blue.paint(.1)
+    )]
+    NoMergeBase(String, String),
+    /// A branch with the given name does not exist in the local repository.
+    #[error("Branch `{}` does not exist in the local repository.", Color::Blue.paint(.0))]
+    BranchNotFound(String),
+    /// Checks run by `st doctor` failed.
+    #[error(
+        "{} check{} failed. Follow the hints above, then rerun `{}`.",
+        .0,
+        if *.0 != 1 { "s" } else { "" },
+        Color::Blue.paint("st doctor")
+    )]
+    DoctorChecksFailed(usize),
+    /// `st config validate` found problems with the configuration.
+    #[error(
+        "Found {} problem{} with `{}`.",
+        .problems,
+        if *.problems != 1 { "s" } else { "" },
+        Color::Blue.paint(.path)
+    )]
+    InvalidConfig {
+        /// The path of the validated config file.
+        path: String,
+        /// The number of problems found.
+        problems: usize,
+    },
+    /// The PR template file passed to `st submit --template-file` could not be read.
+    #[error("Failed to read PR template file `{}`: {}", Color::Blue.paint(.path), .source)]
+    TemplateFileUnreadable {
+        /// The path of the template file.
+        path: String,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// The file passed to `st describe --file` could not be read.
+    #[error("Failed to read stack description file `{}`: {}", Color::Blue.paint(.path), .source)]
+    StackDescriptionUnreadable {
+        /// The path of the description file.
+        path: String,
+        /// The underlying error.
+        source: std::io::Error,
+    },
+    /// Another `st` process holds the store lock.
+    #[error(
+        "Another `{}` process holds the lock on the store. Retry once it finishes, or pass `{}`.",
+        Color::Blue.paint("st"),
+        Color::Blue.paint("--wait-for-lock")
+    )]
+    StoreLocked,
     /// The branch was not found in the local git tree.
     #[error("Branch was not found in local git tree.")]
     BranchUnavailable,
+    /// An operation on a branch failed. Wraps the underlying error with the branch and the
+    /// [BranchPhase] it failed in.
+    #[error("Failed to {} for branch `{}`: {}", .phase, Color::Blue.paint(.branch), .source)]
+    BranchOperationFailed {
+        /// The name of the branch.
+        branch: String,
+        /// The phase of the operation that failed.
+        phase: BranchPhase,
+        /// The underlying error.
+        source: Box<StError>,
+    },
 
     // ---- [ Child Errors ] ----
     /// An [StConfigError] occurred.
@@ -100,7 +386,92 @@ pub enum StError {
     /// A [toml::de::Error] occurred.
     #[error("🍅 toml decoding error: {}", .0)]
     TomlDecodingError(#[from] toml::de::Error),
+    /// A [serde_json::Error] occurred.
+    #[error("📦 json error: {}", .0)]
+    JsonError(#[from] serde_json::Error),
+}
+
+impl StError {
+    /// Returns the process exit code for the error, so scripts can tell failures apart:
+    ///
+    /// | Code | Meaning |
+    /// |------|---------|
+    /// | `1` | Unexpected error |
+    /// | `2` | The working tree is dirty |
+    /// | `3` | A branch needs to be restacked |
+    /// | `4` | GitHub authentication is missing or rejected |
+    /// | `5` | Restacking stopped on conflicts |
+    /// | `6` | Nothing to do |
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::WorkingTreeDirty => EXIT_DIRTY_TREE,
+            Self::NeedsRestack(_) => EXIT_NEEDS_RESTACK,
+            Self::StConfigError(StConfigError::MissingField(field)) if field == "github_token" => {
+                EXIT_AUTH_FAILURE
+            }
+            Self::OctocrabError(octocrab::Error::GitHub { source, .. })
+                if matches!(source.status_code.as_u16(), 401 | 403) =>
+            {
+                EXIT_AUTH_FAILURE
+            }
+            Self::MissingTokenScopes { .. } => EXIT_AUTH_FAILURE,
+            Self::RestackConflict { .. } | Self::TrunksNotRestacked(_) => EXIT_CONFLICT,
+            Self::NothingToFixup(_) | Self::NoCommitsToSquash(_) | Self::NothingToAmend(_) => {
+                EXIT_NOTHING_TO_DO
+            }
+            Self::BranchOperationFailed { source, .. } => source.exit_code(),
+            _ => EXIT_FAILURE,
+        }
+    }
 }
 
 /// A short-hand [Result] type alias for the [StError].
 pub type StResult<T> = Result<T, StError>;
+
+/// The phase of an operation on a branch, reported by [StError::BranchOperationFailed].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum BranchPhase {
+    /// Pushing the branch to the remote.
+    Push,
+    /// Updating the base branch of the branch's pull request.
+    UpdateBase,
+    /// Opening a pull request for the branch.
+    CreatePullRequest,
+    /// Updating the title and description of the branch's pull request.
+    UpdatePullRequest,
+    /// Posting or updating the stack comment on the branch's pull request.
+    WriteComment,
+}
+
+impl fmt::Display for BranchPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let phase = match self {
+            Self::Push => "push",
+            Self::UpdateBase => "update base",
+            Self::CreatePullRequest => "create pull request",
+            Self::UpdatePullRequest => "update pull request",
+            Self::WriteComment => "write stack comment",
+        };
+        f.write_str(phase)
+    }
+}
+
+/// Extension trait attaching the branch and [BranchPhase] of a failed operation to its error.
+pub trait BranchContext<T> {
+    /// Wraps the error, if any, in [StError::BranchOperationFailed].
+    ///
+    /// ## Takes
+    /// - `branch` - The name of the branch the operation was performed on.
+    /// - `phase` - The [BranchPhase] of the operation.
+    fn with_branch(self, branch: &str, phase: BranchPhase) -> StResult<T>;
+}
+
+impl<T, E: Into<StError>> BranchContext<T> for Result<T, E> {
+    fn with_branch(self, branch: &str, phase: BranchPhase) -> StResult<T> {
+        self.map_err(|e| StError::BranchOperationFailed {
+            branch: branch.to_string(),
+            phase,
+            source: Box::new(e.into()),
+        })
+    }
+}
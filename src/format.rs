@@ -0,0 +1,612 @@
+//! Formatting helpers for text derived from git history.
+
+use crate::git::FileDelta;
+
+/// Conventional commit types stripped from the start of commit subjects.
+const CONVENTIONAL_TYPES: [&str; 11] = [
+    "feat", "fix", "chore", "docs", "refactor", "perf", "test", "build", "ci", "style", "revert",
+];
+
+/// Autosquash markers stripped from the start of commit subjects.
+const AUTOSQUASH_PREFIXES: [&str; 3] = ["fixup! ", "squash! ", "amend! "];
+
+/// Marks the start of the stack context block in a pull request description.
+const STACK_CONTEXT_START: &str = "<!-- st:stack-context -->";
+
+/// Marks the end of the stack context block in a pull request description.
+const STACK_CONTEXT_END: &str = "<!-- /st:stack-context -->";
+
+/// Derives a pull request title from commit subjects, as listed by `git log` (newest first). The
+/// oldest subject is used, as the first commit on a branch usually names the change.
+///
+/// With `clean` set, the subject is tidied up: autosquash markers, conventional commit prefixes
+/// such as `feat(parser)!: `, and squash-merge suffixes such as ` (#123)` are stripped, the
+/// first letter is capitalized, and a trailing period is dropped.
+///
+/// ## Takes
+/// - `subjects` - The commit subjects, newest first. Only the first line of each is considered.
+/// - `clean` - Whether to clean up the subject.
+///
+/// ## Returns
+/// - `Option<String>` - The title, or [None] if there are no non-empty subjects.
+pub fn title_from_commits(subjects: &[String], clean: bool) -> Option<String> {
+    let subject = subjects
+        .iter()
+        .rev()
+        .map(|subject| subject.lines().next().unwrap_or_default().trim())
+        .find(|subject| !subject.is_empty())?;
+    if !clean {
+        return Some(subject.to_string());
+    }
+
+    let mut title = subject;
+    while let Some(rest) = AUTOSQUASH_PREFIXES
+        .iter()
+        .find_map(|prefix| title.strip_prefix(prefix))
+    {
+        title = rest.trim_start();
+    }
+    title = strip_conventional_prefix(title);
+    title = strip_pr_suffix(title);
+    let title = title.trim().trim_end_matches('.').trim_end();
+
+    let mut chars = title.chars();
+    let title = match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+        None => return Some(subject.to_string()),
+    };
+    Some(title)
+}
+
+/// Strips a conventional commit prefix, such as `fix: ` or `feat(parser)!: `, from a subject.
+fn strip_conventional_prefix(subject: &str) -> &str {
+    let Some((prefix, rest)) = subject.split_once(':') else {
+        return subject;
+    };
+    let prefix = prefix.strip_suffix('!').unwrap_or(prefix);
+    let kind = match prefix.split_once('(') {
+        Some((kind, scope)) if scope.ends_with(')') && !scope.contains(' ') => kind,
+        Some(_) => return subject,
+        None => prefix,
+    };
+
+    if CONVENTIONAL_TYPES
+        .iter()
+        .any(|t| t.eq_ignore_ascii_case(kind))
+        && rest.starts_with(' ')
+    {
+        rest.trim_start()
+    } else {
+        subject
+    }
+}
+
+/// Strips a squash-merge pull request suffix, such as ` (#123)`, from a subject.
+fn strip_pr_suffix(subject: &str) -> &str {
+    let Some(rest) = subject.strip_suffix(')') else {
+        return subject;
+    };
+    match rest.rsplit_once(" (#") {
+        Some((title, number))
+            if !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            title
+        }
+        _ => subject,
+    }
+}
+
+/// Renders a commit message for the squashed commits of a branch from a template.
+///
+/// Supported placeholders:
+/// - `{branch}` - The name of the branch.
+/// - `{pr_number}` - The number of the branch's pull request, or nothing if it has none.
+/// - `{subjects}` - The commit subjects, oldest first, one per line.
+///
+/// ## Takes
+/// - `template` - The template. If empty, the combined subjects are returned.
+/// - `branch_name` - The name of the branch.
+/// - `pr_number` - The number of the branch's pull request, if it has been submitted.
+/// - `subjects` - The commit subjects, newest first, as listed by `git log`.
+///
+/// ## Returns
+/// - `String` - The rendered commit message, without surrounding whitespace.
+pub fn commit_message(
+    template: &str,
+    branch_name: &str,
+    pr_number: Option<u64>,
+    subjects: &[String],
+) -> String {
+    let subjects = subjects
+        .iter()
+        .rev()
+        .map(|subject| subject.lines().next().unwrap_or_default().trim())
+        .filter(|subject| !subject.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    if template.trim().is_empty() {
+        return subjects;
+    }
+
+    template
+        .replace("{branch}", branch_name)
+        .replace(
+            "{pr_number}",
+            &pr_number.map(|n| n.to_string()).unwrap_or_default(),
+        )
+        .replace("{subjects}", &subjects)
+        .trim()
+        .to_string()
+}
+
+/// Appends a `Signed-off-by` trailer to a commit message, as `git commit --signoff` does. The
+/// message is left as-is if it already carries the same trailer.
+///
+/// ## Takes
+/// - `message` - The commit message.
+/// - `name` - The name of the committer.
+/// - `email` - The email address of the committer.
+///
+/// ## Returns
+/// - `String` - The commit message, ending with the sign-off trailer.
+pub fn sign_off(message: &str, name: &str, email: &str) -> String {
+    let trailer = format!("Signed-off-by: {} <{}>", name, email);
+    let message = message.trim_end();
+    if message.lines().any(|line| line.trim() == trailer) {
+        return message.to_string();
+    }
+
+    // Join an existing block of trailers, or start a new paragraph.
+    let last_line = message.lines().last().unwrap_or_default();
+    let in_trailers = message.lines().count() > 1
+        && last_line.split_once(": ").is_some_and(|(key, _)| {
+            !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        });
+    if message.is_empty() {
+        trailer
+    } else if in_trailers {
+        format!("{}\n{}", message, trailer)
+    } else {
+        format!("{}\n\n{}", message, trailer)
+    }
+}
+
+/// Keywords that close an issue when they precede a reference to it, as recognized by GitHub.
+const CLOSING_KEYWORDS: [&str; 9] = [
+    "close", "closes", "closed", "fix", "fixes", "fixed", "resolve", "resolves", "resolved",
+];
+
+/// Collects the issues referenced by a branch name and its commit messages. A branch references
+/// an issue when a segment of its name starts with the issue number, as in `fix/123-thing`, and a
+/// commit references one with a closing keyword, as in a `Fixes #123` trailer.
+///
+/// ## Takes
+/// - `branch_name` - The name of the branch.
+/// - `commits` - The commit messages of the branch.
+///
+/// ## Returns
+/// - `Vec<u64>` - The issue numbers, without duplicates, in the order they were found.
+pub fn issue_references(branch_name: &str, commits: &[String]) -> Vec<u64> {
+    let from_branch = branch_name.split('/').filter_map(|segment| {
+        let digits = segment.len()
+            - segment
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        match segment[digits..].chars().next() {
+            None | Some('-' | '_') => segment[..digits].parse().ok(),
+            Some(_) => None,
+        }
+    });
+    let from_commits = commits.iter().flat_map(|message| closed_issues(message));
+
+    let mut issues = Vec::new();
+    for issue in from_branch.chain(from_commits) {
+        if !issues.contains(&issue) {
+            issues.push(issue);
+        }
+    }
+    issues
+}
+
+/// Appends a `Closes #N` footer to a pull request description for each issue that the
+/// description does not already close.
+///
+/// ## Takes
+/// - `body` - The pull request description.
+/// - `issues` - The issue numbers to close.
+///
+/// ## Returns
+/// - `String` - The description, with the footer appended.
+pub fn link_issues(body: &str, issues: &[u64]) -> String {
+    let closed = closed_issues(body);
+    let footer = issues
+        .iter()
+        .filter(|issue| !closed.contains(issue))
+        .map(|issue| format!("Closes #{}", issue))
+        .collect::<Vec<_>>();
+    if footer.is_empty() {
+        return body.to_string();
+    }
+
+    let body = body.trim_end();
+    if body.is_empty() {
+        footer.join("\n")
+    } else {
+        format!("{}\n\n{}", body, footer.join("\n"))
+    }
+}
+
+/// Returns the issues closed by the text, as a closing keyword followed by an issue reference,
+/// such as `Fixes #123` or `closes: #4, #5`.
+fn closed_issues(text: &str) -> Vec<u64> {
+    let mut issues = Vec::new();
+    let mut after_keyword = false;
+    for word in text.split_whitespace() {
+        let reference = word
+            .strip_prefix('#')
+            .map(|rest| rest.trim_end_matches([',', '.', ')']))
+            .and_then(|number| number.parse::<u64>().ok());
+        match reference {
+            Some(issue) if after_keyword => {
+                issues.push(issue);
+                // Keep collecting a comma-separated list of references.
+                after_keyword = word.ends_with(',');
+            }
+            _ => {
+                let word = word.trim_end_matches(':').to_ascii_lowercase();
+                after_keyword = CLOSING_KEYWORDS.contains(&word.as_str());
+            }
+        }
+    }
+    issues
+}
+
+/// Places the shared description of a stack at the top of a pull request description, replacing
+/// the block written by a previous submission.
+///
+/// ## Takes
+/// - `body` - The pull request description.
+/// - `context` - The shared description of the stack. The block is removed if [None] or blank.
+///
+/// ## Returns
+/// - `String` - The description, starting with the stack context block.
+pub fn with_stack_context(body: &str, context: Option<&str>) -> String {
+    let body = match (body.find(STACK_CONTEXT_START), body.find(STACK_CONTEXT_END)) {
+        (Some(start), Some(end)) if start < end => format!(
+            "{}{}",
+            &body[..start],
+            body[end + STACK_CONTEXT_END.len()..].trim_start()
+        ),
+        _ => body.to_string(),
+    };
+
+    let Some(context) = context.map(str::trim).filter(|c| !c.is_empty()) else {
+        return body;
+    };
+    let block =
+        format!("{STACK_CONTEXT_START}\n## Stack context\n\n{context}\n{STACK_CONTEXT_END}");
+    if body.trim().is_empty() {
+        block
+    } else {
+        format!("{block}\n\n{body}")
+    }
+}
+
+/// Re-inserts the checklist items (`- [ ]` or `- [x]`) of a PR template that a generated
+/// description dropped. Each missing item is inserted with its original wording after the line
+/// that precedes it in the template, or appended if that line is missing too.
+///
+/// ## Takes
+/// - `template` - The content of the PR template.
+/// - `body` - The generated description.
+///
+/// ## Returns
+/// - `String` - The description, with every checklist item of the template.
+pub fn restore_checklist(template: &str, body: &str) -> String {
+    let template_lines = template.lines().collect::<Vec<_>>();
+    let mut lines = body.lines().map(ToOwned::to_owned).collect::<Vec<_>>();
+    let mut restored = false;
+
+    for (i, line) in template_lines.iter().enumerate() {
+        let Some(item) = checklist_item(line) else {
+            continue;
+        };
+        if lines.iter().any(|l| checklist_item(l) == Some(item)) {
+            continue;
+        }
+
+        // Insert after the closest preceding template line that appears in the description.
+        let position = template_lines[..i]
+            .iter()
+            .rev()
+            .filter(|prev| !prev.trim().is_empty())
+            .find_map(|prev| lines.iter().position(|l| same_line(l, prev)))
+            .map_or(lines.len(), |p| p + 1);
+        lines.insert(position, line.to_string());
+        restored = true;
+    }
+
+    if restored {
+        lines.join("\n")
+    } else {
+        body.to_string()
+    }
+}
+
+/// Returns the text of a markdown checklist item, without its checkbox, or [None] if the line is
+/// not a checklist item.
+fn checklist_item(line: &str) -> Option<&str> {
+    let rest = line.trim_start().strip_prefix(['-', '*'])?.trim_start();
+    let rest = ["[ ]", "[x]", "[X]"]
+        .iter()
+        .find_map(|checkbox| rest.strip_prefix(checkbox))?;
+    Some(rest.trim())
+}
+
+/// Returns whether two lines are the same, ignoring surrounding whitespace and whether
+/// checklist items are checked.
+fn same_line(a: &str, b: &str) -> bool {
+    match (checklist_item(a), checklist_item(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => a.trim() == b.trim(),
+    }
+}
+
+/// Summarizes the files changed by a diff as a markdown table of their insertions and deletions,
+/// similar to `git diff --stat`. Renamed files and mode changes are noted next to each path.
+///
+/// ## Takes
+/// - `deltas` - The changed files, from [crate::git::RepositoryExt::diff_summary].
+///
+/// ## Returns
+/// - `Option<String>` - The markdown table, or [None] if no files changed.
+pub fn diff_stat(deltas: &[FileDelta]) -> Option<String> {
+    if deltas.is_empty() {
+        return None;
+    }
+
+    let mut table =
+        String::from("| File | Insertions | Deletions |\n|------|-----------:|----------:|\n");
+    for delta in deltas {
+        let file = delta_label(delta);
+        if delta.binary {
+            table.push_str(&format!("| {} | binary | binary |\n", file));
+        } else {
+            table.push_str(&format!(
+                "| {} | {} | {} |\n",
+                file, delta.insertions, delta.deletions
+            ));
+        }
+    }
+    table.push_str(&format!(
+        "| **{} file{} changed** | **{}** | **{}** |",
+        deltas.len(),
+        if deltas.len() == 1 { "" } else { "s" },
+        deltas.iter().map(|d| d.insertions).sum::<usize>(),
+        deltas.iter().map(|d| d.deletions).sum::<usize>()
+    ));
+    Some(table)
+}
+
+/// Describes a changed file by its path, noting renames and mode changes, e.g.
+/// `` `old.rs` → `new.rs` (mode 100644 → 100755) ``.
+pub fn delta_label(delta: &FileDelta) -> String {
+    let mut label = match &delta.old_path {
+        Some(old_path) => format!("`{}` → `{}`", old_path, delta.path),
+        None => format!("`{}`", delta.path),
+    };
+    if let (Some(old), Some(new)) = (delta.old_mode, delta.new_mode)
+        && delta.mode_changed()
+    {
+        label.push_str(&format!(" (mode {:o} → {:o})", old, new));
+    }
+    label
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        commit_message, diff_stat, issue_references, link_issues, restore_checklist, sign_off,
+        title_from_commits, with_stack_context,
+    };
+    use crate::git::{DeltaKind, FileDelta};
+
+    fn title(subjects: &[&str]) -> Option<String> {
+        let subjects = subjects.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        title_from_commits(&subjects, true)
+    }
+
+    #[test]
+    fn conventional_prefixes_are_stripped() {
+        assert_eq!(
+            title(&["feat: add split command"]).unwrap(),
+            "Add split command"
+        );
+        assert_eq!(
+            title(&["fix(tree)!: relink children on delete."]).unwrap(),
+            "Relink children on delete"
+        );
+        assert_eq!(title(&["Docs: tweak README"]).unwrap(), "Tweak README");
+    }
+
+    #[test]
+    fn squash_merge_suffixes_and_markers_are_stripped() {
+        assert_eq!(
+            title(&["refactor(ctx): split actions (#123)"]).unwrap(),
+            "Split actions"
+        );
+        assert_eq!(title(&["fixup! feat: add flag"]).unwrap(), "Add flag");
+        assert_eq!(
+            title(&["Support (#ifdef) blocks (#12a)"]).unwrap(),
+            "Support (#ifdef) blocks (#12a)"
+        );
+    }
+
+    #[test]
+    fn unrecognized_prefixes_are_kept() {
+        assert_eq!(title(&["Note: this is odd"]).unwrap(), "Note: this is odd");
+        assert_eq!(
+            title(&["feat(two words): keep"]).unwrap(),
+            "Feat(two words): keep"
+        );
+        assert_eq!(title(&["fix:no space"]).unwrap(), "Fix:no space");
+    }
+
+    #[test]
+    fn oldest_subject_is_used() {
+        assert_eq!(
+            title(&["chore: address review", "", "feat: add reorder\n\nBody."]).unwrap(),
+            "Add reorder"
+        );
+        assert_eq!(title(&[]), None);
+        assert_eq!(title(&["   "]), None);
+    }
+
+    #[test]
+    fn cleanup_can_be_disabled() {
+        let subjects = vec!["feat: add split (#4)".to_string()];
+        assert_eq!(
+            title_from_commits(&subjects, false).unwrap(),
+            "feat: add split (#4)"
+        );
+    }
+
+    #[test]
+    fn diff_stat_counts_each_file() {
+        let delta = |kind, path: &str, insertions, deletions| FileDelta {
+            kind,
+            old_path: None,
+            path: path.to_string(),
+            old_mode: (kind != DeltaKind::Added).then_some(0o100644),
+            new_mode: Some(0o100644),
+            insertions,
+            deletions,
+            binary: false,
+        };
+        let deltas = [
+            delta(DeltaKind::Modified, "src/lib.rs", 2, 1),
+            delta(DeltaKind::Added, "README.md", 2, 0),
+            FileDelta {
+                binary: true,
+                ..delta(DeltaKind::Modified, "logo.png", 0, 0)
+            },
+            FileDelta {
+                old_path: Some("src/old.rs".to_string()),
+                ..delta(DeltaKind::Renamed, "src/new.rs", 1, 1)
+            },
+            FileDelta {
+                new_mode: Some(0o100755),
+                ..delta(DeltaKind::Modified, "run.sh", 0, 0)
+            },
+        ];
+        assert_eq!(
+            diff_stat(&deltas).unwrap(),
+            "\
+| File | Insertions | Deletions |
+|------|-----------:|----------:|
+| `src/lib.rs` | 2 | 1 |
+| `README.md` | 2 | 0 |
+| `logo.png` | binary | binary |
+| `src/old.rs` → `src/new.rs` | 1 | 1 |
+| `run.sh` (mode 100644 → 100755) | 0 | 0 |
+| **5 files changed** | **5** | **2** |"
+        );
+        assert_eq!(diff_stat(&[]), None);
+    }
+
+    #[test]
+    fn branch_name_issue_is_linked_once() {
+        let commits = vec!["Fix the bug\n\nFixes #42".to_string()];
+        let issues = issue_references("fix/42-bug", &commits);
+        assert_eq!(issues, vec![42]);
+
+        let body = link_issues("Fixes the bug.", &issues);
+        assert_eq!(body, "Fixes the bug.\n\nCloses #42");
+        assert_eq!(body.matches("Closes #42").count(), 1);
+        assert_eq!(link_issues(&body, &issues), body);
+    }
+
+    #[test]
+    fn issue_references_come_from_segments_and_closing_keywords() {
+        let commits = vec![
+            "Refactor parser\n\nSee #7.\nResolves: #8, #9".to_string(),
+            "closes #10".to_string(),
+        ];
+        assert_eq!(
+            issue_references("mahbd/123_parser/v2", &commits),
+            vec![123, 8, 9, 10]
+        );
+        assert!(issue_references("feature/v2-123", &[]).is_empty());
+        assert_eq!(link_issues("", &[1, 2]), "Closes #1\nCloses #2");
+    }
+
+    #[test]
+    fn commit_message_template_is_rendered() {
+        let subjects = ["Fix review comments", "", "Add parser\n\nBody."].map(String::from);
+        let template = "\n{branch} (#{pr_number})\n\n{subjects}\n";
+        assert_eq!(
+            commit_message(template, "feat/parser", Some(42), &subjects),
+            "feat/parser (#42)\n\nAdd parser\nFix review comments"
+        );
+        assert_eq!(
+            commit_message("", "feat/parser", Some(42), &subjects),
+            "Add parser\nFix review comments"
+        );
+        assert_eq!(
+            commit_message("{branch}{pr_number}", "feat/parser", None, &[]),
+            "feat/parser"
+        );
+    }
+
+    #[test]
+    fn sign_off_joins_existing_trailers() {
+        let signed = sign_off("Add parser", "Jane Doe", "jane@example.com");
+        assert_eq!(
+            signed,
+            "Add parser\n\nSigned-off-by: Jane Doe <jane@example.com>"
+        );
+        assert_eq!(sign_off(&signed, "Jane Doe", "jane@example.com"), signed);
+        assert_eq!(
+            sign_off("Add parser\n\nCo-authored-by: A <a@b.c>\n", "B", "b@c.d"),
+            "Add parser\n\nCo-authored-by: A <a@b.c>\nSigned-off-by: B <b@c.d>"
+        );
+    }
+
+    #[test]
+    fn stack_context_block_is_replaced_not_duplicated() {
+        let body = with_stack_context("## Summary\nAdds the parser.", Some("Rewrite the lexer."));
+        assert!(body.starts_with("<!-- st:stack-context -->\n## Stack context\n\n"));
+        assert!(body.ends_with("<!-- /st:stack-context -->\n\n## Summary\nAdds the parser."));
+
+        let updated = with_stack_context(&body, Some("Rewrite the lexer and parser."));
+        assert_eq!(updated.matches("## Stack context").count(), 1);
+        assert!(updated.contains("Rewrite the lexer and parser."));
+        assert!(!updated.contains("Rewrite the lexer.\n"));
+
+        assert_eq!(
+            with_stack_context(&updated, None),
+            "## Summary\nAdds the parser."
+        );
+    }
+
+    #[test]
+    fn dropped_checklist_items_are_restored() {
+        let template = "## Summary\n\n## Checklist\n- [ ] Tests added\n- [ ] Docs updated\n";
+        let body = "## Summary\nAdds a parser.\n\n## Checklist\n- [x] Tests added\n\nThanks!";
+        assert_eq!(
+            restore_checklist(template, body),
+            "## Summary\nAdds a parser.\n\n## Checklist\n- [x] Tests added\n- [ ] Docs updated\n\nThanks!"
+        );
+
+        // Without the heading or any items, the checklist is appended in order.
+        let restored = restore_checklist(template, "Adds a parser.");
+        assert_eq!(
+            restored,
+            "Adds a parser.\n- [ ] Tests added\n- [ ] Docs updated"
+        );
+
+        // A description with every item is left untouched.
+        let complete = "Adds a parser.\n* [x] Tests added\n- [X] Docs updated\n";
+        assert_eq!(restore_checklist(template, complete), complete);
+    }
+}
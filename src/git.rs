@@ -1,34 +1,133 @@
 //! Utilities for interacting with `git` repositories for the `st` application.
 
-use crate::constants::QUOTE_CHAR;
+use crate::{
+    constants::QUOTE_CHAR,
+    errors::{StError, StResult},
+    theme::Color::Red,
+};
 use git2::{
-    build::CheckoutBuilder, Branch, BranchType, ErrorClass, ErrorCode, Repository, StatusOptions,
+    build::CheckoutBuilder, BranchType, Delta, DiffFile, DiffFindOptions, DiffFormat, DiffOptions,
+    ErrorClass, ErrorCode, FileMode, Oid, Patch, Pathspec, PathspecFlags, Repository,
+    RepositoryState, Status, StatusOptions,
+};
+use std::{
+    env, fmt,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::OnceLock,
 };
-use nu_ansi_term::Color::Red;
-use std::{env, process::Command};
 use thiserror::Error;
+use tracing::{debug, info, trace};
+
+/// The directory that the repository is discovered from, when overridden with `--repo`.
+static REPOSITORY_DIR: OnceLock<PathBuf> = OnceLock::new();
 
-/// Returns the repository for the current working directory, and [None] if
-/// the current working directory is not within a git repository or an error
-/// occurs.
+/// Returns the repository for the current working directory, or for the directory passed with
+/// `--repo`, and [None] if the directory is not within a git repository or an error occurs.
 pub fn active_repository() -> Option<Repository> {
-    Repository::discover(env::current_dir().ok()?).ok()
+    discover_repository(&repository_dir()?).ok()
+}
+
+/// Discovers the repository containing a directory, searching its parents like `git -C`.
+///
+/// ## Takes
+/// - `dir` - A directory within the repository.
+///
+/// ## Returns
+/// - `Result<Repository>` - The repository, or [StError::NotAGitRepository] if the directory is
+///   not within a git repository.
+pub fn discover_repository(dir: &Path) -> StResult<Repository> {
+    Repository::discover(dir).map_err(|_| StError::NotAGitRepository)
+}
+
+/// Returns the directory that the repository is discovered from: the directory passed with
+/// `--repo`, or the current working directory.
+pub fn repository_dir() -> Option<PathBuf> {
+    match REPOSITORY_DIR.get() {
+        Some(dir) => Some(dir.clone()),
+        None => env::current_dir().ok(),
+    }
+}
+
+/// Discovers the repository from the given directory for the rest of the run, instead of the
+/// current working directory, like `git -C`.
+///
+/// ## Takes
+/// - `dir` - A directory within the repository.
+///
+/// ## Returns
+/// - `Result<()>` - Ok if the directory is within a git repository, or
+///   [StError::NotAGitRepository] otherwise.
+pub fn override_repository_dir(dir: PathBuf) -> StResult<()> {
+    discover_repository(&dir)?;
+    let _ = REPOSITORY_DIR.set(dir);
+    Ok(())
+}
+
+/// Checks that a name is a valid git branch name, following the rules of
+/// `git check-ref-format --branch`, so that invalid names are reported before any git operation.
+///
+/// ## Takes
+/// - `name` - The branch name.
+///
+/// ## Returns
+/// - `Result<()>` - Ok if the name is valid, or [StError::InvalidBranchName] with the rule it
+///   breaks.
+pub fn validate_branch_name(name: &str) -> StResult<()> {
+    let components = name.split('/').collect::<Vec<_>>();
+    let reason = if name.is_empty() {
+        Some("it is empty")
+    } else if name.starts_with('-') {
+        Some("it starts with `-`")
+    } else if name == "@" || name == "HEAD" {
+        Some("it is reserved by git")
+    } else if name.chars().any(|c| c.is_control() || c.is_whitespace()) {
+        Some("it contains whitespace or control characters")
+    } else if let Some(c) = name.chars().find(|c| "~^:?*[\\".contains(*c)) {
+        Some(match c {
+            '~' => "it contains `~`",
+            '^' => "it contains `^`",
+            ':' => "it contains `:`",
+            '?' => "it contains `?`",
+            '*' => "it contains `*`",
+            '[' => "it contains `[`",
+            _ => "it contains `\\`",
+        })
+    } else if name.contains("..") {
+        Some("it contains `..`")
+    } else if name.contains("@{") {
+        Some("it contains `@{`")
+    } else if name.starts_with('/') || name.ends_with('/') || name.contains("//") {
+        Some("it has an empty path component")
+    } else if name.ends_with('.') {
+        Some("it ends with `.`")
+    } else if components.iter().any(|c| c.starts_with('.')) {
+        Some("a path component starts with `.`")
+    } else if components.iter().any(|c| c.ends_with(".lock")) {
+        Some("a path component ends with `.lock`")
+    } else {
+        None
+    };
+
+    match reason {
+        Some(reason) => Err(StError::InvalidBranchName {
+            name: name.to_string(),
+            reason,
+        }),
+        None => Ok(()),
+    }
 }
 
 /// Extension trait for the [Repository] type to expose helper functions related to
 /// repository management.
 pub trait RepositoryExt {
-    /// Returns the current [Branch].
+    /// Returns the short name of the current [Branch].
     ///
     /// ## Returns
-    /// - `Result<Branch>` - The current [Branch], or an error.
-    fn current_branch(&self) -> Result<Branch<'_>, git2::Error>;
-
-    /// Returns the name of the current [Branch].
-    ///
-    /// ## Returns
-    /// - `Result<String>` - The name of the current branch, or an error.
-    fn current_branch_name(&self) -> Result<String, git2::Error>;
+    /// - `Result<String>` - The name of the current branch, [StError::DetachedHead] if HEAD does
+    ///   not point at a branch, e.g. mid-rebase or after checking out a tag, or
+    ///   [StError::UnbornBranch] if the branch has no commits yet.
+    fn current_branch_name(&self) -> StResult<String>;
 
     /// Returns whether or not the working tree is clean.
     ///
@@ -36,6 +135,17 @@ pub trait RepositoryExt {
     /// - `Result<bool>` - True if the working tree is clean, false otherwise.
     fn is_working_tree_clean(&self) -> Result<bool, git2::Error>;
 
+    /// Returns the changes in the working tree that are not staged in the index, such as
+    /// [Status::WT_NEW] for untracked files.
+    ///
+    /// ## Returns
+    /// - `Result<Status>` - The working tree flags of every changed file, empty if the working
+    ///   tree matches the index.
+    fn unstaged_changes(&self) -> Result<Status, git2::Error>;
+
+    /// Returns whether or not a rebase is in progress, e.g. one that stopped on conflicts.
+    fn is_rebasing(&self) -> bool;
+
     /// Checks out a branch with the given `branch_name`.
     ///
     /// ## Takes
@@ -69,12 +179,46 @@ pub trait RepositoryExt {
     /// - `Result<()>` - The result of the operation.
     fn rebase_branch_onto(&self, branch_name: &str, onto: &str) -> Result<(), GitCommandError>;
 
+    /// Rebases the commits of a branch after `upstream` onto another commit, analogous to
+    /// `git rebase --onto <onto> <upstream> <branch>`.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch to rebase.
+    /// - `upstream` - The commit after which the branch's own commits start.
+    /// - `onto` - The branch or commit to rebase onto.
+    ///
+    /// ## Returns
+    /// - `Result<()>` - The result of the operation.
+    fn rebase_commits_onto(
+        &self,
+        branch_name: &str,
+        upstream: &str,
+        onto: &str,
+    ) -> Result<(), GitCommandError>;
+
+    /// Merges another branch into a branch, creating a merge commit, analogous to
+    /// `git merge --no-edit <from>` with the branch checked out.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch to merge into.
+    /// - `from` - The name of the branch to merge.
+    ///
+    /// ## Returns
+    /// - `Result<()>` - The result of the operation.
+    fn merge_branch_into(&self, branch_name: &str, from: &str) -> Result<(), GitCommandError>;
+
     /// Aborts a rebase in progress.
     ///
     /// ## Returns
     /// - `Result<()>` - The result of the operation.
     fn abort_rebase(&self) -> Result<(), GitCommandError>;
 
+    /// Aborts a merge in progress.
+    ///
+    /// ## Returns
+    /// - `Result<()>` - The result of the operation.
+    fn abort_merge(&self) -> Result<(), GitCommandError>;
+
     /// Pushes a branch to a registered remote.
     ///
     /// ## Takes
@@ -101,6 +245,15 @@ pub trait RepositoryExt {
     /// - `Result<()>` - The result of the operation.
     fn pull_branch(&self, branch_name: &str, remote_name: &str) -> Result<(), GitCommandError>;
 
+    /// Checks that a registered remote is reachable, analogous to `git ls-remote <remote> HEAD`.
+    ///
+    /// ## Takes
+    /// - `remote_name` - The name of the remote to reach.
+    ///
+    /// ## Returns
+    /// - `Result<()>` - The result of the operation.
+    fn ls_remote(&self, remote_name: &str) -> Result<(), GitCommandError>;
+
     /// Gets the diff between two branches.
     ///
     /// ## Takes
@@ -109,13 +262,42 @@ pub trait RepositoryExt {
     ///
     /// ## Returns
     /// - `Result<String>` - The diff as a string.
+    #[allow(dead_code)] // The binary only diffs with prompt options; kept for plain diffs.
     fn diff_branches(&self, branch_name: &str, base_name: &str) -> Result<String, GitCommandError>;
 
-    /// Gets commit messages between two branches.
+    /// Gets the diff between two branches, formatted as a patch with the given options.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the first branch.
+    /// - `base_name` - The name of the base branch.
+    /// - `options` - The [BranchDiffOptions] controlling context, rename detection, and omitted
+    ///   files.
+    ///
+    /// ## Returns
+    /// - `Result<String>` - The diff as a string.
+    fn diff_branches_opts(
+        &self,
+        branch_name: &str,
+        base_name: &str,
+        options: &BranchDiffOptions,
+    ) -> Result<String, GitCommandError>;
+
+    /// Summarizes the files changed between two branches, detecting renamed files.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch with changes.
+    /// - `base_name` - The name of the base branch.
+    ///
+    /// ## Returns
+    /// - `Result<Vec<FileDelta>>` - The changed files, in path order.
+    fn diff_summary(&self, branch_name: &str, base_name: &str) -> StResult<Vec<FileDelta>>;
+
+    /// Gets commit messages between two branches, newest first.
     ///
     /// ## Takes
     /// - `branch_name` - The name of the branch with commits.
     /// - `base_name` - The name of the base branch.
+    /// - `options` - The [CommitLogOptions] limiting the returned messages.
     ///
     /// ## Returns
     /// - `Result<Vec<String>>` - Vector of commit messages.
@@ -123,35 +305,78 @@ pub trait RepositoryExt {
         &self,
         branch_name: &str,
         base_name: &str,
+        options: CommitLogOptions,
     ) -> Result<Vec<String>, GitCommandError>;
+
+    /// Returns the candidate branch that `branch_name` most recently diverged from. This is the
+    /// candidate whose merge-base with `branch_name` is the fewest commits behind it, preferring
+    /// candidates with the fewest commits of their own past that merge-base.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch to find the closest candidate for.
+    /// - `candidates` - The names of the candidate branches.
+    ///
+    /// ## Returns
+    /// - `Result<Option<String>>` - The closest candidate, or [None] if no candidate shares
+    ///   history with the branch.
+    fn closest_branch(
+        &self,
+        branch_name: &str,
+        candidates: &[String],
+    ) -> Result<Option<String>, git2::Error>;
+
+    /// Returns the fork point of two revisions: their best common ancestor.
+    ///
+    /// As [Repository::merge_base] takes precedence in method calls, call this as
+    /// `RepositoryExt::merge_base(repository, a, b)`.
+    ///
+    /// ## Takes
+    /// - `a` - The first branch name or revision.
+    /// - `b` - The second branch name or revision.
+    ///
+    /// ## Returns
+    /// - `Result<Oid>` - The merge-base, or [StError::NoMergeBase] if the revisions share no
+    ///   history.
+    fn merge_base(&self, a: &str, b: &str) -> StResult<Oid>;
+
+    /// Returns whether a commit is reachable from another. A commit is its own ancestor.
+    ///
+    /// ## Takes
+    /// - `maybe_ancestor` - The candidate ancestor commit.
+    /// - `descendant` - The commit to walk back from.
+    ///
+    /// ## Returns
+    /// - `Result<bool>` - `true` if `maybe_ancestor` is `descendant` or one of its ancestors.
+    fn is_ancestor(&self, maybe_ancestor: Oid, descendant: Oid) -> StResult<bool>;
+
+    /// Returns whether the changes of a branch are already contained in another branch, either
+    /// because it was merged, or because its changes were applied upstream, e.g. by a squash
+    /// merge. The latter is detected by merging the branch into `target` in memory, and checking
+    /// that the merge leaves `target` unchanged.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch to check.
+    /// - `target` - The name of the branch that may contain its changes.
+    ///
+    /// ## Returns
+    /// - `Result<bool>` - `true` if merging the branch into `target` would be a no-op.
+    fn is_merged_into(&self, branch_name: &str, target: &str) -> StResult<bool>;
 }
 
 impl RepositoryExt for Repository {
-    fn current_branch(&self) -> Result<Branch<'_>, git2::Error> {
-        let head = self.head()?;
-        let branch = self.find_branch(
-            head.name()
-                .ok_or(git2::Error::new(
-                    ErrorCode::GenericError,
-                    ErrorClass::Object,
-                    "HEAD name not found",
-                ))?
-                .trim_start_matches("refs/heads/"),
-            BranchType::Local,
-        )?;
-        Ok(branch)
-    }
-
-    fn current_branch_name(&self) -> Result<String, git2::Error> {
-        let branch = self.current_branch()?;
-        branch
-            .name()?
-            .ok_or(git2::Error::new(
-                ErrorCode::GenericError,
-                ErrorClass::Object,
-                "Branch name not found",
-            ))
-            .map(|n| n.to_string())
+    fn current_branch_name(&self) -> StResult<String> {
+        // HEAD of an unborn branch can't be resolved, so read its symbolic target instead.
+        let head = self.find_reference("HEAD")?;
+        let name = head
+            .symbolic_target_bytes()
+            .and_then(|target| target.strip_prefix(b"refs/heads/"))
+            .map(|name| String::from_utf8_lossy(name).into_owned())
+            .ok_or(StError::DetachedHead)?;
+        match self.head() {
+            Err(e) if e.code() == ErrorCode::UnbornBranch => Err(StError::UnbornBranch(name)),
+            Err(e) => Err(e.into()),
+            Ok(_) => Ok(name),
+        }
     }
 
     fn is_working_tree_clean(&self) -> Result<bool, git2::Error> {
@@ -164,9 +389,37 @@ impl RepositoryExt for Repository {
             .exclude_submodules(false) // Include submodules
             .recurse_untracked_dirs(true); // Look in untracked directories
         let statuses = self.statuses(Some(&mut status_opts))?;
+        trace!(changes = statuses.len(), "git2: read working tree status");
         Ok(statuses.is_empty())
     }
 
+    fn unstaged_changes(&self) -> Result<Status, git2::Error> {
+        let mut status_opts = StatusOptions::new();
+        status_opts
+            .include_untracked(true)
+            .include_ignored(false)
+            .recurse_untracked_dirs(true);
+        let unstaged = Status::WT_NEW
+            | Status::WT_MODIFIED
+            | Status::WT_DELETED
+            | Status::WT_TYPECHANGE
+            | Status::WT_RENAMED
+            | Status::CONFLICTED;
+        let statuses = self.statuses(Some(&mut status_opts))?;
+        Ok(statuses.iter().fold(Status::empty(), |changes, entry| {
+            changes | (entry.status() & unstaged)
+        }))
+    }
+
+    fn is_rebasing(&self) -> bool {
+        matches!(
+            self.state(),
+            RepositoryState::Rebase
+                | RepositoryState::RebaseInteractive
+                | RepositoryState::RebaseMerge
+        )
+    }
+
     fn checkout_branch(&self, branch_name: &str) -> Result<(), git2::Error> {
         if !self.is_working_tree_clean()? {
             return Err(git2::Error::new(
@@ -176,6 +429,7 @@ impl RepositoryExt for Repository {
             ));
         }
 
+        debug!(branch = branch_name, "git2: checking out branch");
         self.set_head(format!("refs/heads/{}", branch_name).as_str())?;
         self.checkout_head(Some(CheckoutBuilder::new().force()))?;
         Ok(())
@@ -196,6 +450,11 @@ impl RepositoryExt for Repository {
             "Upstream ref target not found",
         ))?;
 
+        debug!(
+            branch = branch_name,
+            target = %upstream_ref_target,
+            "git2: resetting branch to upstream ref"
+        );
         branch
             .get_mut()
             .set_target(upstream_ref_target, "Set ref to upstream ref")?;
@@ -212,11 +471,33 @@ impl RepositoryExt for Repository {
 
         // Cheat and shell out to git to rebase the branch. This is mainly because git2 doesn't
         // create rebase-todo files, etc., and it's easier to just shell out to git.
-        execute_git_command(&["rebase", onto_name], false)
+        execute_git_command(self, &["rebase", onto_name], false)
+    }
+
+    fn rebase_commits_onto(
+        &self,
+        branch_name: &str,
+        upstream: &str,
+        onto: &str,
+    ) -> Result<(), GitCommandError> {
+        execute_git_command(
+            self,
+            &["rebase", "--onto", onto, upstream, branch_name],
+            false,
+        )
+    }
+
+    fn merge_branch_into(&self, branch_name: &str, from: &str) -> Result<(), GitCommandError> {
+        self.checkout_branch(branch_name)?;
+        execute_git_command(self, &["merge", "--no-edit", from], false)
     }
 
     fn abort_rebase(&self) -> Result<(), GitCommandError> {
-        execute_git_command(&["rebase", "--abort"], false)
+        execute_git_command(self, &["rebase", "--abort"], false)
+    }
+
+    fn abort_merge(&self) -> Result<(), GitCommandError> {
+        execute_git_command(self, &["merge", "--abort"], false)
     }
 
     fn push_branch(
@@ -230,38 +511,153 @@ impl RepositoryExt for Repository {
             args.push("--force");
         }
 
-        execute_git_command(args.as_slice(), false)
+        execute_git_command(self, args.as_slice(), false)
     }
 
     fn pull_branch(&self, branch_name: &str, remote_name: &str) -> Result<(), GitCommandError> {
         self.checkout_branch(branch_name)?;
-        execute_git_command(&["pull", remote_name, branch_name], false)
+        execute_git_command(self, &["pull", remote_name, branch_name], false)
+    }
+
+    fn ls_remote(&self, remote_name: &str) -> Result<(), GitCommandError> {
+        execute_git_command(self, &["ls-remote", "--quiet", remote_name, "HEAD"], false)
     }
 
     fn diff_branches(&self, branch_name: &str, base_name: &str) -> Result<String, GitCommandError> {
-        let output = Command::new("git")
-            .args(["diff", base_name, branch_name])
-            .output()?;
+        self.diff_branches_opts(branch_name, base_name, &BranchDiffOptions::default())
+    }
 
-        if !output.status.success() {
-            return Err(GitCommandError::Command(format!(
-                "Failed to get diff between {} and {}",
-                base_name, branch_name
-            )));
+    fn diff_branches_opts(
+        &self,
+        branch_name: &str,
+        base_name: &str,
+        options: &BranchDiffOptions,
+    ) -> Result<String, GitCommandError> {
+        let base_tree = self.revparse_single(base_name)?.peel_to_tree()?;
+        let branch_tree = self.revparse_single(branch_name)?.peel_to_tree()?;
+
+        let mut diff_options = DiffOptions::new();
+        diff_options.context_lines(options.context_lines);
+        for pathspec in &options.pathspecs {
+            diff_options.pathspec(pathspec);
         }
+        let mut diff = self.diff_tree_to_tree(
+            Some(&base_tree),
+            Some(&branch_tree),
+            Some(&mut diff_options),
+        )?;
+        if options.find_renames {
+            let mut find_options = DiffFindOptions::new();
+            find_options.renames(true);
+            diff.find_similar(Some(&mut find_options))?;
+        }
+
+        let omitted_paths = (!options.omitted_paths.is_empty())
+            .then(|| Pathspec::new(&options.omitted_paths))
+            .transpose()?;
 
-        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+        // Format the diff like `git diff`, prefixing content lines with their origin. Each file
+        // is collected separately, so that omitted files can be replaced with a placeholder.
+        let mut files: Vec<FileDiff> = Vec::new();
+        diff.print(DiffFormat::Patch, |delta, _, line| {
+            let content = String::from_utf8_lossy(line.content());
+            match line.origin() {
+                'F' => {
+                    let path = delta
+                        .new_file()
+                        .path()
+                        .or(delta.old_file().path())
+                        .unwrap_or(Path::new(""));
+                    files.push(FileDiff {
+                        path: path.display().to_string(),
+                        header: content.to_string(),
+                        body: String::new(),
+                        omitted: omitted_paths
+                            .as_ref()
+                            .is_some_and(|p| p.matches_path(path, PathspecFlags::DEFAULT)),
+                    });
+                }
+                origin => {
+                    let Some(file) = files.last_mut() else {
+                        return true;
+                    };
+                    file.omitted |= origin == 'B' && options.omit_binary;
+                    if matches!(origin, '+' | '-' | ' ') {
+                        file.body.push(origin);
+                    }
+                    file.body.push_str(&content);
+                }
+            }
+            true
+        })?;
+
+        Ok(files
+            .into_iter()
+            .map(|file| {
+                let too_large = options
+                    .max_file_size
+                    .is_some_and(|max| file.body.len() > max);
+                if file.omitted || too_large {
+                    format!("{}Binary/large file omitted: {}\n", file.header, file.path)
+                } else {
+                    file.header + &file.body
+                }
+            })
+            .collect())
+    }
+
+    fn diff_summary(&self, branch_name: &str, base_name: &str) -> StResult<Vec<FileDelta>> {
+        let base_tree = self.revparse_single(base_name)?.peel_to_tree()?;
+        let branch_tree = self.revparse_single(branch_name)?.peel_to_tree()?;
+        let mut diff = self.diff_tree_to_tree(Some(&base_tree), Some(&branch_tree), None)?;
+        diff.find_similar(Some(DiffFindOptions::new().renames(true)))?;
+
+        let mut deltas = Vec::with_capacity(diff.deltas().len());
+        for index in 0..diff.deltas().len() {
+            let Some(patch) = Patch::from_diff(&diff, index)? else {
+                continue;
+            };
+            let delta = patch.delta();
+            let kind = match delta.status() {
+                Delta::Added => DeltaKind::Added,
+                Delta::Deleted => DeltaKind::Deleted,
+                Delta::Renamed => DeltaKind::Renamed,
+                _ => DeltaKind::Modified,
+            };
+            let path = |file: DiffFile<'_>| file.path().map(|p| p.display().to_string());
+            let mode = |file: DiffFile<'_>| {
+                (file.mode() != FileMode::Unreadable).then(|| u32::from(file.mode()))
+            };
+            let (_, insertions, deletions) = patch.line_stats()?;
+            deltas.push(FileDelta {
+                kind,
+                old_path: (kind == DeltaKind::Renamed)
+                    .then(|| path(delta.old_file()))
+                    .flatten(),
+                path: path(delta.new_file())
+                    .or_else(|| path(delta.old_file()))
+                    .unwrap_or_default(),
+                old_mode: mode(delta.old_file()),
+                new_mode: mode(delta.new_file()),
+                insertions,
+                deletions,
+                binary: delta.flags().is_binary(),
+            });
+        }
+        Ok(deltas)
     }
 
     fn commit_messages_between(
         &self,
         branch_name: &str,
         base_name: &str,
+        options: CommitLogOptions,
     ) -> Result<Vec<String>, GitCommandError> {
         let output = Command::new("git")
+            .current_dir(self.workdir().unwrap_or_else(|| self.path()))
             .args([
                 "log",
-                "--pretty=format:%s%n%n%b",
+                "--pretty=format:%s%n%n%b%x1e",
                 &format!("{}..{}", base_name, branch_name),
             ])
             .output()?;
@@ -274,16 +670,221 @@ impl RepositoryExt for Repository {
         }
 
         let log = String::from_utf8_lossy(&output.stdout);
-        let messages: Vec<String> = log
-            .split("\n\n")
-            .filter(|s| !s.trim().is_empty())
-            .map(|s| s.trim().to_string())
-            .collect();
+        Ok(parse_commit_log(&log, options))
+    }
+
+    fn closest_branch(
+        &self,
+        branch_name: &str,
+        candidates: &[String],
+    ) -> Result<Option<String>, git2::Error> {
+        let branch_oid = self
+            .find_branch(branch_name, BranchType::Local)?
+            .get()
+            .peel_to_commit()?
+            .id();
+
+        let mut closest = None;
+        for candidate in candidates.iter().filter(|c| *c != branch_name) {
+            let Ok(candidate_branch) = self.find_branch(candidate, BranchType::Local) else {
+                continue;
+            };
+            let candidate_oid = candidate_branch.get().peel_to_commit()?.id();
 
-        Ok(messages)
+            // Skip candidates with unrelated histories.
+            let Ok(merge_base) = self.merge_base(branch_oid, candidate_oid) else {
+                continue;
+            };
+            let (branch_ahead, _) = self.graph_ahead_behind(branch_oid, merge_base)?;
+            let (candidate_ahead, _) = self.graph_ahead_behind(candidate_oid, merge_base)?;
+
+            let distance = (branch_ahead, candidate_ahead);
+            if closest.as_ref().is_none_or(|(d, _)| distance < *d) {
+                closest = Some((distance, candidate.clone()));
+            }
+        }
+        Ok(closest.map(|(_, c)| c))
+    }
+
+    fn merge_base(&self, a: &str, b: &str) -> StResult<Oid> {
+        let a_oid = self.revparse_single(a)?.peel_to_commit()?.id();
+        let b_oid = self.revparse_single(b)?.peel_to_commit()?.id();
+        match Repository::merge_base(self, a_oid, b_oid) {
+            Ok(merge_base) => Ok(merge_base),
+            Err(e) if e.code() == ErrorCode::NotFound => {
+                Err(StError::NoMergeBase(a.to_string(), b.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn is_ancestor(&self, maybe_ancestor: Oid, descendant: Oid) -> StResult<bool> {
+        Ok(maybe_ancestor == descendant || self.graph_descendant_of(descendant, maybe_ancestor)?)
+    }
+
+    fn is_merged_into(&self, branch_name: &str, target: &str) -> StResult<bool> {
+        let branch = self.revparse_single(branch_name)?.peel_to_commit()?;
+        let target = self.revparse_single(target)?.peel_to_commit()?;
+        if self.is_ancestor(branch.id(), target.id())? {
+            return Ok(true);
+        }
+
+        let mut index = self.merge_commits(&target, &branch, None)?;
+        if index.has_conflicts() {
+            return Ok(false);
+        }
+        Ok(index.write_tree_to(self)? == target.tree_id())
     }
 }
 
+/// How a branch is brought up to date with its parent when restacking.
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq, clap::ValueEnum)]
+pub enum RestackStrategy {
+    /// Rebase the branch's own commits onto its parent.
+    #[default]
+    Rebase,
+    /// Merge the parent into the branch, creating a merge commit.
+    Merge,
+}
+
+/// Options limiting the commit messages returned by [RepositoryExt::commit_messages_between].
+#[derive(Default, Debug, Clone, Copy, Eq, PartialEq)]
+pub struct CommitLogOptions {
+    /// The maximum number of commits to return, keeping the most recent. [None] for no limit.
+    pub max_commits: Option<usize>,
+    /// Whether to drop commit bodies, returning only the subject lines.
+    pub subjects_only: bool,
+}
+
+/// Options for the diff returned by [RepositoryExt::diff_branches_opts].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BranchDiffOptions {
+    /// The number of unchanged lines shown around each change.
+    pub context_lines: u32,
+    /// Whether to detect renamed files, showing them as a rename rather than a deletion and an
+    /// addition.
+    pub find_renames: bool,
+    /// The paths to limit the diff to, as git pathspecs. Empty for the whole tree.
+    pub pathspecs: Vec<String>,
+    /// Whether to replace the changes of binary files with a placeholder.
+    pub omit_binary: bool,
+    /// The paths whose changes are replaced with a placeholder, as git pathspecs. Used for
+    /// generated files, such as lockfiles.
+    pub omitted_paths: Vec<String>,
+    /// The size in bytes above which the changes of a file are replaced with a placeholder.
+    /// [None] for no limit.
+    pub max_file_size: Option<usize>,
+}
+
+impl Default for BranchDiffOptions {
+    fn default() -> Self {
+        Self {
+            context_lines: 3,
+            find_renames: false,
+            pathspecs: Vec::new(),
+            omit_binary: false,
+            omitted_paths: Vec::new(),
+            max_file_size: None,
+        }
+    }
+}
+
+/// How a file changed between two branches, as reported by [RepositoryExt::diff_summary].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum DeltaKind {
+    /// The file was added.
+    Added,
+    /// The file was deleted.
+    Deleted,
+    /// The contents or mode of the file changed.
+    Modified,
+    /// The file was moved, and its contents may have changed.
+    Renamed,
+}
+
+/// A file changed between two branches, as reported by [RepositoryExt::diff_summary].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FileDelta {
+    /// How the file changed.
+    pub kind: DeltaKind,
+    /// The path of the file before it was renamed. [None] unless the file was renamed.
+    pub old_path: Option<String>,
+    /// The path of the file after the change, or before it for deleted files.
+    pub path: String,
+    /// The mode of the file before the change, e.g. `0o100644`. [None] for added files.
+    pub old_mode: Option<u32>,
+    /// The mode of the file after the change. [None] for deleted files.
+    pub new_mode: Option<u32>,
+    /// The number of inserted lines.
+    pub insertions: usize,
+    /// The number of deleted lines.
+    pub deletions: usize,
+    /// Whether the file is binary, in which case no lines are counted.
+    pub binary: bool,
+}
+
+impl fmt::Display for DeltaKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Added => "added",
+            Self::Deleted => "deleted",
+            Self::Modified => "modified",
+            Self::Renamed => "renamed",
+        })
+    }
+}
+
+impl FileDelta {
+    /// Returns whether the mode of the file changed, e.g. when it was made executable.
+    pub fn mode_changed(&self) -> bool {
+        matches!((self.old_mode, self.new_mode), (Some(old), Some(new)) if old != new)
+    }
+}
+
+/// The changes of a branch relative to its parent, as included in PR description prompts.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct BranchDiff {
+    /// The diff, formatted as a patch by [RepositoryExt::diff_branches_opts].
+    pub patch: String,
+    /// The changed files, from [RepositoryExt::diff_summary].
+    pub files: Vec<FileDelta>,
+}
+
+/// The patch of a single file within the diff formatted by [RepositoryExt::diff_branches_opts].
+struct FileDiff {
+    /// The path of the file.
+    path: String,
+    /// The `diff --git` header of the file.
+    header: String,
+    /// The hunks of the file.
+    body: String,
+    /// Whether the changes of the file are replaced with a placeholder.
+    omitted: bool,
+}
+
+/// Parses the output of `git log` with `%x1e`-terminated records into commit messages.
+///
+/// ## Takes
+/// - `log` - The raw `git log` output.
+/// - `options` - The [CommitLogOptions] limiting the returned messages.
+///
+/// ## Returns
+/// - `Vec<String>` - The commit messages, in log order.
+fn parse_commit_log(log: &str, options: CommitLogOptions) -> Vec<String> {
+    log.split('\x1e')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .take(options.max_commits.unwrap_or(usize::MAX))
+        .map(|s| {
+            if options.subjects_only {
+                s.lines().next().unwrap_or_default().to_string()
+            } else {
+                s.to_string()
+            }
+        })
+        .collect()
+}
+
 /// Error type for git command execution.
 #[derive(Error, Debug)]
 pub enum GitCommandError {
@@ -298,13 +899,21 @@ pub enum GitCommandError {
     Git2(#[from] git2::Error),
 }
 
-/// Executes a `git` command with the given arguments in a blocking child task.
+/// Executes a `git` command with the given arguments in a blocking child task, from the working
+/// directory of the repository.
 ///
 /// ## Takes
+/// - `repository` - The repository to run the command in.
 /// - `args` - The arguments to pass to the `git` command.
 /// - `interactive` - Whether the command should be interactive.
-fn execute_git_command(args: &[&str], interactive: bool) -> Result<(), GitCommandError> {
+fn execute_git_command(
+    repository: &Repository,
+    args: &[&str],
+    interactive: bool,
+) -> Result<(), GitCommandError> {
+    info!("git {}", args.join(" "));
     let mut cmd = Command::new("git");
+    cmd.current_dir(repository.workdir().unwrap_or_else(|| repository.path()));
     if interactive {
         let status = cmd.args(args).status()?;
 
@@ -332,3 +941,490 @@ fn execute_git_command(args: &[&str], interactive: bool) -> Result<(), GitComman
 
     Ok(())
 }
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{
+        discover_repository, parse_commit_log, validate_branch_name, BranchDiffOptions,
+        CommitLogOptions, DeltaKind, FileDelta, RepositoryExt,
+    };
+    use crate::{
+        ctx::{load_tree, persist_tree},
+        errors::StError,
+        tree::StackTree,
+    };
+    use git2::{Oid, Repository, RepositoryInitOptions, Signature};
+    use std::{
+        env, fs,
+        path::PathBuf,
+        process,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    /// A throwaway repository in the system temporary directory, with an initial commit on
+    /// `main`. The repository is removed when dropped.
+    pub(crate) struct TempRepository {
+        /// The repository.
+        pub(crate) repository: Repository,
+        /// The path to the repository.
+        path: PathBuf,
+    }
+
+    impl TempRepository {
+        /// Creates a new [TempRepository].
+        pub(crate) fn new() -> Self {
+            let repo = Self::unborn();
+            repo.commit("Initial commit");
+            repo
+        }
+
+        /// Creates a new [TempRepository] without any commits, so that `main` is unborn.
+        pub(crate) fn unborn() -> Self {
+            static COUNTER: AtomicUsize = AtomicUsize::new(0);
+            let path = env::temp_dir().join(format!(
+                "st-test-{}-{}",
+                process::id(),
+                COUNTER.fetch_add(1, Ordering::SeqCst)
+            ));
+            let repository =
+                Repository::init_opts(&path, RepositoryInitOptions::new().initial_head("main"))
+                    .unwrap();
+            let mut config = repository.config().unwrap();
+            config.set_str("user.name", "st").unwrap();
+            config.set_str("user.email", "st@example.com").unwrap();
+            Self { repository, path }
+        }
+
+        /// Creates an empty commit on top of `HEAD`.
+        pub(crate) fn commit(&self, message: &str) -> Oid {
+            let sig = Signature::now("st", "st@example.com").unwrap();
+            let tree_id = self.repository.treebuilder(None).unwrap().write().unwrap();
+            let tree = self.repository.find_tree(tree_id).unwrap();
+            let parent = self.repository.head().ok().map(|h| h.peel_to_commit().unwrap());
+            self.repository
+                .commit(
+                    Some("HEAD"),
+                    &sig,
+                    &sig,
+                    message,
+                    &tree,
+                    parent.as_ref().into_iter().collect::<Vec<_>>().as_slice(),
+                )
+                .unwrap()
+        }
+
+        /// Creates a commit on top of `HEAD` that writes `contents` to the file at `path`.
+        pub(crate) fn commit_file(&self, message: &str, path: &str, contents: &str) -> Oid {
+            let sig = Signature::now("st", "st@example.com").unwrap();
+            let parent = self.repository.head().unwrap().peel_to_commit().unwrap();
+            let blob = self.repository.blob(contents.as_bytes()).unwrap();
+            let mut builder = self
+                .repository
+                .treebuilder(Some(&parent.tree().unwrap()))
+                .unwrap();
+            builder.insert(path, blob, 0o100644).unwrap();
+            let tree = self.repository.find_tree(builder.write().unwrap()).unwrap();
+            self.repository
+                .commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])
+                .unwrap()
+        }
+
+        /// Creates a commit on top of `HEAD` that moves the file at `from` to `to`.
+        pub(crate) fn move_file(&self, message: &str, from: &str, to: &str) -> Oid {
+            let sig = Signature::now("st", "st@example.com").unwrap();
+            let parent = self.repository.head().unwrap().peel_to_commit().unwrap();
+            let parent_tree = parent.tree().unwrap();
+            let entry = parent_tree.get_name(from).unwrap();
+            let mut builder = self.repository.treebuilder(Some(&parent_tree)).unwrap();
+            builder.insert(to, entry.id(), entry.filemode()).unwrap();
+            builder.remove(from).unwrap();
+            let tree = self.repository.find_tree(builder.write().unwrap()).unwrap();
+            self.repository
+                .commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])
+                .unwrap()
+        }
+
+        /// Creates a commit on top of `HEAD` that changes the mode of the file at `path`.
+        pub(crate) fn set_mode(&self, message: &str, path: &str, mode: i32) -> Oid {
+            let sig = Signature::now("st", "st@example.com").unwrap();
+            let parent = self.repository.head().unwrap().peel_to_commit().unwrap();
+            let parent_tree = parent.tree().unwrap();
+            let entry = parent_tree.get_name(path).unwrap();
+            let mut builder = self.repository.treebuilder(Some(&parent_tree)).unwrap();
+            builder.insert(path, entry.id(), mode).unwrap();
+            let tree = self.repository.find_tree(builder.write().unwrap()).unwrap();
+            self.repository
+                .commit(Some("HEAD"), &sig, &sig, message, &tree, &[&parent])
+                .unwrap()
+        }
+
+        /// Creates a branch at `HEAD` and checks it out.
+        pub(crate) fn branch(&self, branch_name: &str) {
+            let head = self.repository.head().unwrap().peel_to_commit().unwrap();
+            self.repository.branch(branch_name, &head, false).unwrap();
+            self.checkout(branch_name);
+        }
+
+        /// Checks out an existing branch.
+        pub(crate) fn checkout(&self, branch_name: &str) {
+            self.repository
+                .set_head(&format!("refs/heads/{}", branch_name))
+                .unwrap();
+        }
+    }
+
+    impl Drop for TempRepository {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.path);
+        }
+    }
+
+    /// Builds `git log` output for `n` commits, each with a subject and a body.
+    fn commit_log(n: usize) -> String {
+        (0..n)
+            .map(|i| {
+                format!(
+                    "Commit {}\n\nBody of commit {}.\n\nMore details.\n\x1e\n",
+                    i, i
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_commit_log_keeps_multi_paragraph_bodies() {
+        let messages = parse_commit_log(&commit_log(2), CommitLogOptions::default());
+        assert_eq!(
+            messages,
+            vec![
+                "Commit 0\n\nBody of commit 0.\n\nMore details.",
+                "Commit 1\n\nBody of commit 1.\n\nMore details."
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_commit_log_limits_to_subjects() {
+        let options = CommitLogOptions {
+            max_commits: Some(5),
+            subjects_only: true,
+        };
+        let messages = parse_commit_log(&commit_log(100), options);
+        assert_eq!(
+            messages,
+            vec!["Commit 0", "Commit 1", "Commit 2", "Commit 3", "Commit 4"]
+        );
+    }
+
+    #[test]
+    fn closest_branch_is_most_recent_ancestor() {
+        let repo = TempRepository::new();
+        repo.branch("feature-1");
+        repo.commit("Feature 1");
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+        repo.branch("untracked");
+        repo.commit("Untracked");
+
+        let candidates = ["main", "feature-1", "feature-2"].map(String::from);
+        let closest = repo
+            .repository
+            .closest_branch("untracked", &candidates)
+            .unwrap();
+        assert_eq!(closest.as_deref(), Some("feature-2"));
+    }
+
+    #[test]
+    fn diff_summary_reports_renames_and_mode_changes() {
+        let repo = TempRepository::new();
+        repo.commit_file("Add notes", "notes.txt", "one\ntwo\nthree\n");
+        repo.commit_file("Add script", "run.sh", "echo hi\n");
+        repo.branch("feature-1");
+        repo.move_file("Move notes", "notes.txt", "docs.txt");
+        repo.set_mode("Make script executable", "run.sh", 0o100755);
+        repo.commit_file("Add readme", "README.md", "# Title\n");
+
+        let deltas = repo.repository.diff_summary("feature-1", "main").unwrap();
+        assert_eq!(
+            deltas,
+            vec![
+                FileDelta {
+                    kind: DeltaKind::Added,
+                    old_path: None,
+                    path: "README.md".to_string(),
+                    old_mode: None,
+                    new_mode: Some(0o100644),
+                    insertions: 1,
+                    deletions: 0,
+                    binary: false,
+                },
+                FileDelta {
+                    kind: DeltaKind::Renamed,
+                    old_path: Some("notes.txt".to_string()),
+                    path: "docs.txt".to_string(),
+                    old_mode: Some(0o100644),
+                    new_mode: Some(0o100644),
+                    insertions: 0,
+                    deletions: 0,
+                    binary: false,
+                },
+                FileDelta {
+                    kind: DeltaKind::Modified,
+                    old_path: None,
+                    path: "run.sh".to_string(),
+                    old_mode: Some(0o100644),
+                    new_mode: Some(0o100755),
+                    insertions: 0,
+                    deletions: 0,
+                    binary: false,
+                },
+            ]
+        );
+        assert!(!deltas[1].mode_changed());
+        assert!(deltas[2].mode_changed());
+    }
+
+    #[test]
+    fn closest_branch_ignores_diverged_siblings() {
+        let repo = TempRepository::new();
+        repo.branch("feature-1");
+        repo.commit("Feature 1");
+        repo.checkout("main");
+        repo.branch("untracked");
+        repo.commit("Untracked");
+
+        let candidates = ["main", "feature-1"].map(String::from);
+        let closest = repo
+            .repository
+            .closest_branch("untracked", &candidates)
+            .unwrap();
+        assert_eq!(closest.as_deref(), Some("main"));
+    }
+
+    #[test]
+    fn merge_base_is_fork_point() {
+        let repo = TempRepository::new();
+        let fork_point = repo.commit("Main 1");
+        repo.branch("feature-1");
+        repo.commit("Feature 1");
+        repo.branch("feature-2");
+        let feature_2_head = repo.commit("Feature 2");
+        repo.checkout("main");
+        repo.commit("Main 2");
+
+        let merge_base = |a, b| RepositoryExt::merge_base(&repo.repository, a, b).unwrap();
+        assert_eq!(merge_base("feature-1", "main"), fork_point);
+        assert_eq!(merge_base("main", "feature-2"), fork_point);
+        assert_eq!(
+            merge_base("feature-1", "feature-2"),
+            repo.repository
+                .find_commit(feature_2_head)
+                .unwrap()
+                .parent_id(0)
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn rename_detection_collapses_moved_file() {
+        let repo = TempRepository::new();
+        let contents = (0..20).map(|i| format!("line {}\n", i)).collect::<String>();
+        repo.commit_file("Add file", "old.txt", &contents);
+        repo.branch("feature-1");
+        repo.move_file("Move file", "old.txt", "new.txt");
+
+        let diff = |options: &BranchDiffOptions| {
+            repo.repository
+                .diff_branches_opts("feature-1", "main", options)
+                .unwrap()
+        };
+
+        let plain = diff(&BranchDiffOptions::default());
+        assert_eq!(
+            repo.repository.diff_branches("feature-1", "main").unwrap(),
+            plain
+        );
+        assert!(plain.contains("deleted file mode"));
+        assert!(plain.contains("new file mode"));
+
+        let renamed = diff(&BranchDiffOptions {
+            find_renames: true,
+            ..Default::default()
+        });
+        assert_eq!(renamed.matches("diff --git").count(), 1);
+        assert!(renamed.contains("rename from old.txt\nrename to new.txt"));
+        assert!(!renamed.contains("line 0"));
+
+        let filtered = diff(&BranchDiffOptions {
+            pathspecs: vec!["new.txt".to_string()],
+            ..Default::default()
+        });
+        assert!(filtered.contains("+line 0"));
+        assert!(!filtered.contains("old.txt"));
+    }
+
+    #[test]
+    fn omitted_files_are_replaced_with_placeholder() {
+        let repo = TempRepository::new();
+        repo.branch("feature-1");
+        repo.commit_file("Add logo", "logo.png", "\u{0}\u{1}PNG\u{0}");
+        repo.commit_file("Add lockfile", "Cargo.lock", "[[package]]\n");
+        repo.commit_file("Add source", "main.rs", "fn main() {}\n");
+
+        let mut options = BranchDiffOptions {
+            omit_binary: true,
+            omitted_paths: vec!["*.lock".to_string()],
+            ..Default::default()
+        };
+        let diff = repo
+            .repository
+            .diff_branches_opts("feature-1", "main", &options)
+            .unwrap();
+        assert!(diff.contains("Binary/large file omitted: logo.png"));
+        assert!(diff.contains("Binary/large file omitted: Cargo.lock"));
+        assert!(!diff.contains("PNG"));
+        assert!(!diff.contains('\u{0}'));
+        assert!(!diff.contains("[[package]]"));
+        assert!(diff.contains("+fn main() {}"));
+
+        options.max_file_size = Some(8);
+        let diff = repo
+            .repository
+            .diff_branches_opts("feature-1", "main", &options)
+            .unwrap();
+        assert!(diff.contains("Binary/large file omitted: main.rs"));
+        assert!(!diff.contains("fn main"));
+    }
+
+    #[test]
+    fn is_ancestor_follows_history() {
+        let repo = TempRepository::new();
+        let fork_point = repo.commit("Main 1");
+        repo.branch("feature");
+        let feature_head = repo.commit("Feature");
+        repo.checkout("main");
+        let main_head = repo.commit("Main 2");
+
+        let is_ancestor = |a, b| repo.repository.is_ancestor(a, b).unwrap();
+        assert!(is_ancestor(fork_point, feature_head));
+        assert!(is_ancestor(fork_point, main_head));
+        assert!(!is_ancestor(feature_head, fork_point));
+        assert!(!is_ancestor(feature_head, main_head));
+        assert!(is_ancestor(main_head, main_head));
+    }
+
+    #[test]
+    fn merge_base_of_unrelated_histories_errors() {
+        let repo = TempRepository::new();
+        let sig = Signature::now("st", "st@example.com").unwrap();
+        let tree_id = repo.repository.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.repository.find_tree(tree_id).unwrap();
+        repo.repository
+            .commit(Some("refs/heads/orphan"), &sig, &sig, "Orphan", &tree, &[])
+            .unwrap();
+
+        let err = RepositoryExt::merge_base(&repo.repository, "main", "orphan").unwrap_err();
+        assert!(matches!(err, StError::NoMergeBase(a, b) if a == "main" && b == "orphan"));
+    }
+
+    #[test]
+    fn current_branch_name_handles_detached_and_unborn_head() {
+        let repo = TempRepository::new();
+        repo.branch("feature/nested-name");
+        let head = repo.commit("Feature");
+        assert_eq!(
+            repo.repository.current_branch_name().unwrap(),
+            "feature/nested-name"
+        );
+
+        repo.repository.set_head_detached(head).unwrap();
+        assert!(matches!(
+            repo.repository.current_branch_name(),
+            Err(StError::DetachedHead)
+        ));
+
+        let unborn = TempRepository::unborn();
+        assert!(matches!(
+            unborn.repository.current_branch_name(),
+            Err(StError::UnbornBranch(branch)) if branch == "main"
+        ));
+    }
+
+    #[test]
+    fn repository_is_discovered_from_overriding_dir() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        persist_tree(&repo.repository, &tree).unwrap();
+
+        // A directory outside of any repository is rejected.
+        let outside = env::temp_dir().join(format!("st-not-a-repo-{}", process::id()));
+        fs::create_dir_all(&outside).unwrap();
+        assert!(matches!(
+            discover_repository(&outside),
+            Err(StError::NotAGitRepository)
+        ));
+        fs::remove_dir_all(&outside).unwrap();
+
+        // The repository, and its stack, are found from a directory within it.
+        let nested = repo.repository.workdir().unwrap().join("nested");
+        fs::create_dir_all(&nested).unwrap();
+        let found = discover_repository(&nested).unwrap();
+        assert_eq!(found.path(), repo.repository.path());
+        let stack = load_tree(&found).unwrap().unwrap();
+        assert_eq!(stack.branches().unwrap(), vec!["main", "feature-1"]);
+    }
+
+    #[test]
+    fn invalid_branch_names_are_rejected() {
+        let reason = |name: &str| match validate_branch_name(name) {
+            Err(StError::InvalidBranchName { reason, .. }) => reason,
+            other => panic!("`{}` was not rejected: {:?}", name, other),
+        };
+        assert_eq!(reason("-feature"), "it starts with `-`");
+        assert_eq!(reason("feature..fix"), "it contains `..`");
+        assert_eq!(reason("feature.lock"), "a path component ends with `.lock`");
+        assert_eq!(
+            reason("feature.lock/fix"),
+            "a path component ends with `.lock`"
+        );
+        assert_eq!(
+            reason("my feature"),
+            "it contains whitespace or control characters"
+        );
+        assert_eq!(
+            reason("feature\u{7}"),
+            "it contains whitespace or control characters"
+        );
+        assert_eq!(reason("feat/.hidden"), "a path component starts with `.`");
+        assert_eq!(reason("feat//fix"), "it has an empty path component");
+        assert_eq!(reason("feature/"), "it has an empty path component");
+        assert_eq!(reason("feature."), "it ends with `.`");
+        assert_eq!(reason("fix@{1}"), "it contains `@{`");
+        assert_eq!(reason("fix:main"), "it contains `:`");
+        assert_eq!(reason("@"), "it is reserved by git");
+        assert_eq!(reason(""), "it is empty");
+    }
+
+    #[test]
+    fn valid_branch_names_are_accepted() {
+        for name in [
+            "feature",
+            "mahbd/123_parser/v2",
+            "fix-a.b",
+            "release@2",
+            "feat/lock.file",
+            "ünïcode",
+        ] {
+            assert!(
+                validate_branch_name(name).is_ok(),
+                "`{}` was rejected",
+                name
+            );
+            assert!(git2::Branch::name_is_valid(name).unwrap());
+        }
+    }
+}
@@ -0,0 +1,836 @@
+//! Utilities for interacting with GitHub's GraphQL API, for operations that are not exposed
+//! through the REST API.
+
+use crate::{
+    config::StConfig,
+    constants::MAX_CONCURRENT_REQUESTS,
+    ctx::json::PrState,
+    errors::{StError, StResult},
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use octocrab::{
+    models::{
+        pulls::{PullRequest, ReviewState},
+        IssueState, StatusState,
+    },
+    params::repos::Reference,
+    pulls::PullRequestHandler,
+    Octocrab,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+/// Builds a GitHub API client, authenticated with the token resolved by
+/// [StConfig::github_token].
+pub fn github_client(config: &StConfig) -> StResult<Octocrab> {
+    authenticated_client(config.github_token()?, None)
+}
+
+/// Builds a GitHub API client authenticated with `token`, against `base_uri` if given.
+fn authenticated_client(token: String, base_uri: Option<&str>) -> StResult<Octocrab> {
+    let mut builder = Octocrab::builder().personal_token(token);
+    if let Some(base_uri) = base_uri {
+        builder = builder.base_uri(base_uri)?;
+    }
+    Ok(builder.build()?)
+}
+
+/// Queries the node ID, draft state, and auto-merge state of a pull request.
+const PULL_REQUEST_QUERY: &str = r#"
+query($owner: String!, $repo: String!, $number: Int!) {
+  repository(owner: $owner, name: $repo) {
+    pullRequest(number: $number) {
+      id
+      isDraft
+      autoMergeRequest { enabledAt }
+    }
+  }
+}"#;
+
+/// Enables auto-merge on a pull request.
+const ENABLE_AUTO_MERGE_MUTATION: &str = r#"
+mutation($id: ID!, $method: PullRequestMergeMethod!) {
+  enablePullRequestAutoMerge(input: { pullRequestId: $id, mergeMethod: $method }) {
+    clientMutationId
+  }
+}"#;
+
+/// Marks a draft pull request as ready for review.
+const MARK_READY_FOR_REVIEW_MUTATION: &str = r#"
+mutation($id: ID!) {
+  markPullRequestReadyForReview(input: { pullRequestId: $id }) {
+    clientMutationId
+  }
+}"#;
+
+/// Converts a pull request that is ready for review back into a draft.
+const CONVERT_TO_DRAFT_MUTATION: &str = r#"
+mutation($id: ID!) {
+  convertPullRequestToDraft(input: { pullRequestId: $id }) {
+    clientMutationId
+  }
+}"#;
+
+/// A client capable of executing GitHub GraphQL requests.
+pub(crate) trait GraphQlClient {
+    /// Executes a GraphQL request, returning the `data` field of the response.
+    ///
+    /// ## Takes
+    /// - `query` - The GraphQL query or mutation.
+    /// - `variables` - The variables for the query.
+    ///
+    /// ## Returns
+    /// - `Result<Value>` - The `data` field of the response, or an error if the response
+    ///   contains any errors.
+    async fn graphql_request(&self, query: &str, variables: Value) -> StResult<Value>;
+}
+
+impl GraphQlClient for Octocrab {
+    async fn graphql_request(&self, query: &str, variables: Value) -> StResult<Value> {
+        let response: Value = self
+            .graphql(&json!({ "query": query, "variables": variables }))
+            .await?;
+
+        if let Some(errors) = response["errors"].as_array() {
+            let messages = errors
+                .iter()
+                .filter_map(|e| e["message"].as_str())
+                .collect::<Vec<_>>()
+                .join("; ");
+            return Err(StError::GraphQlError(messages));
+        }
+
+        Ok(response["data"].clone())
+    }
+}
+
+/// Returns the URL of a pull request on the GitHub web interface.
+///
+/// ## Takes
+/// - `host` - The host of the GitHub web interface, e.g. `github.com`.
+/// - `owner` - The owner of the repository.
+/// - `repo` - The name of the repository.
+/// - `pr_number` - The number of the pull request.
+///
+/// ## Returns
+/// - `String` - The URL of the pull request.
+pub(crate) fn pull_request_url(host: &str, owner: &str, repo: &str, pr_number: u64) -> String {
+    format!("https://{host}/{owner}/{repo}/pull/{pr_number}")
+}
+
+/// The method used to merge a pull request.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, clap::ValueEnum)]
+pub enum MergeMethod {
+    /// Squash all commits into a single commit.
+    Squash,
+    /// Create a merge commit.
+    Merge,
+    /// Rebase the commits onto the base branch.
+    Rebase,
+}
+
+impl MergeMethod {
+    /// Returns the GraphQL `PullRequestMergeMethod` enum value for the [MergeMethod].
+    pub fn as_graphql(&self) -> &'static str {
+        match self {
+            Self::Squash => "SQUASH",
+            Self::Merge => "MERGE",
+            Self::Rebase => "REBASE",
+        }
+    }
+}
+
+impl From<MergeMethod> for octocrab::params::pulls::MergeMethod {
+    fn from(method: MergeMethod) -> Self {
+        match method {
+            MergeMethod::Squash => Self::Squash,
+            MergeMethod::Merge => Self::Merge,
+            MergeMethod::Rebase => Self::Rebase,
+        }
+    }
+}
+
+impl From<&PullRequest> for PrState {
+    fn from(pr: &PullRequest) -> Self {
+        if pr.merged_at.is_some() {
+            Self::Merged
+        } else if pr
+            .state
+            .as_ref()
+            .is_none_or(|s| matches!(s, IssueState::Closed))
+        {
+            Self::Closed
+        } else if pr.draft.unwrap_or_default() {
+            Self::Draft
+        } else {
+            Self::Open
+        }
+    }
+}
+
+/// A source of the [PrState] of pull requests.
+pub(crate) trait PrStateBackend {
+    /// Returns the [PrState] of a pull request.
+    async fn pr_state(&self, pr_number: u64) -> StResult<PrState>;
+}
+
+impl PrStateBackend for PullRequestHandler<'_> {
+    async fn pr_state(&self, pr_number: u64) -> StResult<PrState> {
+        Ok(PrState::from(&self.get(pr_number).await?))
+    }
+}
+
+/// Fetches the [PrState] of each pull request concurrently, with at most
+/// [MAX_CONCURRENT_REQUESTS] requests in flight at once.
+///
+/// ## Takes
+/// - `backend` - The [PrStateBackend] to fetch states from.
+/// - `pr_numbers` - The numbers of the pull requests.
+///
+/// ## Returns
+/// - `Result<Vec<PrState>>` - The state of each pull request, in the order of `pr_numbers`.
+pub(crate) async fn pr_states(
+    backend: &impl PrStateBackend,
+    pr_numbers: &[u64],
+) -> StResult<Vec<PrState>> {
+    stream::iter(pr_numbers)
+        .map(|pr_number| backend.pr_state(*pr_number))
+        .buffered(MAX_CONCURRENT_REQUESTS)
+        .try_collect()
+        .await
+}
+
+/// The access of a token to a repository, as reported by GitHub.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct TokenAccess {
+    /// The OAuth scopes of a classic token, from the `x-oauth-scopes` header. [None] for
+    /// fine-grained tokens, which don't report scopes.
+    pub(crate) scopes: Option<Vec<String>>,
+    /// Whether the token can push to the repository, from the repository's `permissions`.
+    pub(crate) can_push: bool,
+    /// Whether the repository is private.
+    pub(crate) private: bool,
+}
+
+impl TokenAccess {
+    /// Returns the scopes or permissions the token lacks to push branches and write pull requests
+    /// to the repository.
+    pub(crate) fn missing(&self) -> Vec<String> {
+        let mut missing = Vec::new();
+        if let Some(scopes) = &self.scopes {
+            let has = |scope: &str| scopes.iter().any(|s| s == scope);
+            if !has("repo") && (self.private || !has("public_repo")) {
+                missing.push(format!(
+                    "the `{}` scope",
+                    if self.private { "repo" } else { "public_repo" }
+                ));
+            }
+        }
+        if !self.can_push {
+            missing.push(match self.scopes {
+                Some(_) => "push access to the repository".to_string(),
+                None => "the `Contents` and `Pull requests` read and write permissions".to_string(),
+            });
+        }
+        missing
+    }
+}
+
+/// A source of the [TokenAccess] of the configured token.
+pub(crate) trait TokenAccessBackend {
+    /// Returns the access of the token to a repository.
+    async fn token_access(&self, owner: &str, repo: &str) -> StResult<TokenAccess>;
+}
+
+impl TokenAccessBackend for Octocrab {
+    async fn token_access(&self, owner: &str, repo: &str) -> StResult<TokenAccess> {
+        let response = self._get(format!("/repos/{}/{}", owner, repo)).await?;
+        let response = octocrab::map_github_error(response).await?;
+        let scopes = response
+            .headers()
+            .get("x-oauth-scopes")
+            .and_then(|scopes| scopes.to_str().ok())
+            .map(|scopes| {
+                scopes
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|scope| !scope.is_empty())
+                    .map(ToString::to_string)
+                    .collect()
+            });
+        let repository: Value = serde_json::from_str(&self.body_to_string(response).await?)?;
+        Ok(TokenAccess {
+            scopes,
+            can_push: repository["permissions"]["push"]
+                .as_bool()
+                .unwrap_or_default(),
+            private: repository["private"].as_bool().unwrap_or_default(),
+        })
+    }
+}
+
+/// Verifies that the token can push branches and write pull requests to a repository, so that
+/// misconfigured tokens fail before anything is written rather than partway through.
+///
+/// ## Takes
+/// - `backend` - The [TokenAccessBackend] to check the token with.
+/// - `owner` - The owner of the repository.
+/// - `repo` - The name of the repository.
+///
+/// ## Returns
+/// - `Result<()>` - Ok, or [StError::MissingTokenScopes] listing what the token lacks.
+pub(crate) async fn verify_token_access(
+    backend: &impl TokenAccessBackend,
+    owner: &str,
+    repo: &str,
+) -> StResult<()> {
+    let missing = backend.token_access(owner, repo).await?.missing();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(StError::MissingTokenScopes {
+            repository: format!("{}/{}", owner, repo),
+            missing,
+        })
+    }
+}
+
+/// The aggregate review decision of a pull request.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReviewDecision {
+    /// At least one reviewer approved, and no reviewer requested changes.
+    Approved,
+    /// At least one reviewer requested changes.
+    ChangesRequested,
+    /// No reviewer has approved or requested changes yet.
+    Pending,
+}
+
+/// Computes the [ReviewDecision] from a pull request's reviews, in chronological order.
+///
+/// Only the latest approving, change-requesting, or dismissed review of each reviewer counts
+/// towards the decision; comments do not override a previous review.
+pub(crate) fn review_decision<'a>(
+    reviews: impl IntoIterator<Item = (&'a str, ReviewState)>,
+) -> ReviewDecision {
+    let mut latest = HashMap::new();
+    for (reviewer, state) in reviews {
+        if matches!(
+            state,
+            ReviewState::Approved | ReviewState::ChangesRequested | ReviewState::Dismissed
+        ) {
+            latest.insert(reviewer, state);
+        }
+    }
+
+    if latest
+        .values()
+        .any(|s| matches!(s, ReviewState::ChangesRequested))
+    {
+        ReviewDecision::ChangesRequested
+    } else if latest.values().any(|s| matches!(s, ReviewState::Approved)) {
+        ReviewDecision::Approved
+    } else {
+        ReviewDecision::Pending
+    }
+}
+
+/// Fetches the reviews of a pull request and computes its [ReviewDecision].
+pub(crate) async fn fetch_review_decision(
+    pulls: &PullRequestHandler<'_>,
+    pr_number: u64,
+) -> StResult<ReviewDecision> {
+    let reviews = pulls.list_reviews(pr_number).per_page(100).send().await?;
+    Ok(review_decision(reviews.items.iter().filter_map(|r| {
+        Some((r.user.as_ref()?.login.as_str(), r.state?))
+    })))
+}
+
+/// The aggregate CI state of a commit, across commit statuses and check runs.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CiStatus {
+    /// All statuses and check runs passed.
+    Passing,
+    /// At least one status or check run failed.
+    Failing,
+    /// No failures, but at least one status or check run has not completed.
+    Pending,
+    /// No statuses or check runs were reported.
+    NoChecks,
+}
+
+/// Computes the [CiStatus] from the states of a commit's statuses and the conclusions of its
+/// check runs. A check run without a conclusion has not completed yet.
+pub(crate) fn aggregate_ci_status<'a>(
+    statuses: impl IntoIterator<Item = StatusState>,
+    check_conclusions: impl IntoIterator<Item = Option<&'a str>>,
+) -> CiStatus {
+    let (mut any_checks, mut any_pending) = (false, false);
+
+    for state in statuses {
+        any_checks = true;
+        match state {
+            StatusState::Success => {}
+            StatusState::Pending => any_pending = true,
+            _ => return CiStatus::Failing,
+        }
+    }
+    for conclusion in check_conclusions {
+        any_checks = true;
+        match conclusion {
+            Some("success" | "neutral" | "skipped") => {}
+            None => any_pending = true,
+            Some(_) => return CiStatus::Failing,
+        }
+    }
+
+    match (any_checks, any_pending) {
+        (false, _) => CiStatus::NoChecks,
+        (true, true) => CiStatus::Pending,
+        (true, false) => CiStatus::Passing,
+    }
+}
+
+/// Fetches the commit statuses and check runs for the head of a remote branch, and computes its
+/// [CiStatus].
+pub(crate) async fn fetch_ci_status(
+    gh_client: &Octocrab,
+    owner: &str,
+    repo: &str,
+    branch_name: &str,
+) -> StResult<CiStatus> {
+    let reference = Reference::Branch(branch_name.to_string());
+    let combined = gh_client
+        .repos(owner, repo)
+        .combined_status_for_ref(&reference)
+        .await?;
+    let check_runs = gh_client
+        .checks(owner, repo)
+        .list_check_runs_for_git_ref(reference.into())
+        .per_page(100)
+        .send()
+        .await?;
+
+    Ok(aggregate_ci_status(
+        combined.statuses.iter().map(|s| s.state),
+        check_runs
+            .check_runs
+            .iter()
+            .map(|c| c.conclusion.as_deref()),
+    ))
+}
+
+/// Enables auto-merge on a pull request with the given [MergeMethod], unless auto-merge is
+/// already enabled.
+///
+/// ## Takes
+/// - `client` - The GraphQL client.
+/// - `owner` - The owner of the repository.
+/// - `repo` - The name of the repository.
+/// - `pr_number` - The number of the pull request.
+/// - `method` - The merge method to use once the pull request is mergeable.
+///
+/// ## Returns
+/// - `Result<bool>` - `true` if auto-merge was enabled, `false` if it was already enabled.
+pub(crate) async fn enable_auto_merge(
+    client: &impl GraphQlClient,
+    owner: &str,
+    repo: &str,
+    pr_number: u64,
+    method: MergeMethod,
+) -> StResult<bool> {
+    let pull_request = query_pull_request(client, owner, repo, pr_number).await?;
+
+    // Skip pull requests that already have auto-merge enabled.
+    if !pull_request["autoMergeRequest"].is_null() {
+        return Ok(false);
+    }
+
+    let id = pull_request["id"]
+        .as_str()
+        .ok_or(StError::PullRequestNotFound)?;
+    client
+        .graphql_request(
+            ENABLE_AUTO_MERGE_MUTATION,
+            json!({ "id": id, "method": method.as_graphql() }),
+        )
+        .await
+        .map_err(|e| match e {
+            StError::GraphQlError(msg)
+                if msg.to_lowercase().contains("auto merge is not allowed") =>
+            {
+                StError::AutoMergeNotAllowed
+            }
+            e => e,
+        })?;
+
+    Ok(true)
+}
+
+/// Marks a draft pull request as ready for review. Pull requests that are not drafts are left
+/// untouched.
+///
+/// ## Takes
+/// - `client` - The GraphQL client.
+/// - `owner` - The owner of the repository.
+/// - `repo` - The name of the repository.
+/// - `pr_number` - The number of the pull request.
+///
+/// ## Returns
+/// - `Result<bool>` - `true` if the pull request was marked as ready, `false` if it was not a
+///   draft.
+pub(crate) async fn mark_ready_for_review(
+    client: &impl GraphQlClient,
+    owner: &str,
+    repo: &str,
+    pr_number: u64,
+) -> StResult<bool> {
+    let pull_request = query_pull_request(client, owner, repo, pr_number).await?;
+
+    // Skip pull requests that are not drafts.
+    if !pull_request["isDraft"].as_bool().unwrap_or_default() {
+        return Ok(false);
+    }
+
+    let id = pull_request["id"]
+        .as_str()
+        .ok_or(StError::PullRequestNotFound)?;
+    client
+        .graphql_request(MARK_READY_FOR_REVIEW_MUTATION, json!({ "id": id }))
+        .await?;
+
+    Ok(true)
+}
+
+/// Converts a pull request that is ready for review back into a draft. Pull requests that are
+/// already drafts are left untouched.
+///
+/// ## Takes
+/// - `client` - The GraphQL client.
+/// - `owner` - The owner of the repository.
+/// - `repo` - The name of the repository.
+/// - `pr_number` - The number of the pull request.
+///
+/// ## Returns
+/// - `Result<bool>` - `true` if the pull request was converted to a draft, `false` if it was
+///   already a draft.
+pub(crate) async fn convert_to_draft(
+    client: &impl GraphQlClient,
+    owner: &str,
+    repo: &str,
+    pr_number: u64,
+) -> StResult<bool> {
+    let pull_request = query_pull_request(client, owner, repo, pr_number).await?;
+
+    // Skip pull requests that are already drafts.
+    if pull_request["isDraft"].as_bool().unwrap_or_default() {
+        return Ok(false);
+    }
+
+    let id = pull_request["id"]
+        .as_str()
+        .ok_or(StError::PullRequestNotFound)?;
+    client
+        .graphql_request(CONVERT_TO_DRAFT_MUTATION, json!({ "id": id }))
+        .await?;
+
+    Ok(true)
+}
+
+/// Queries the pull request node with the given number.
+async fn query_pull_request(
+    client: &impl GraphQlClient,
+    owner: &str,
+    repo: &str,
+    pr_number: u64,
+) -> StResult<Value> {
+    let mut data = client
+        .graphql_request(
+            PULL_REQUEST_QUERY,
+            json!({ "owner": owner, "repo": repo, "number": pr_number }),
+        )
+        .await?;
+    Ok(data["repository"]["pullRequest"].take())
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::{
+        aggregate_ci_status, authenticated_client, enable_auto_merge, mark_ready_for_review,
+        review_decision, verify_token_access, CiStatus, GraphQlClient, MergeMethod, ReviewDecision,
+        TokenAccess, ENABLE_AUTO_MERGE_MUTATION, MARK_READY_FOR_REVIEW_MUTATION,
+    };
+    use crate::{
+        config::{StConfig, TokenSource},
+        errors::{StError, StResult},
+    };
+    use octocrab::models::{pulls::ReviewState, StatusState};
+    use serde_json::{json, Value};
+    use std::{cell::RefCell, collections::HashMap, mem::ManuallyDrop};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    /// A mock `gh auth token` [TokenSource].
+    struct MockGhCli(&'static str);
+
+    impl TokenSource for MockGhCli {
+        fn token(&self) -> Option<String> {
+            Some(self.0.to_string())
+        }
+    }
+
+    /// A mock [GraphQlClient] that serves canned pull request state and records all requests.
+    #[derive(Default)]
+    pub(crate) struct MockGraphQl {
+        /// Pull request number -> pull request node returned by queries.
+        pub(crate) pull_requests: HashMap<u64, Value>,
+        /// Recorded `(query, variables)` pairs.
+        pub(crate) requests: RefCell<Vec<(String, Value)>>,
+    }
+
+    impl MockGraphQl {
+        /// Returns the number of requests issued with the given query.
+        pub(crate) fn count(&self, query: &str) -> usize {
+            self.requests
+                .borrow()
+                .iter()
+                .filter(|(q, _)| q == query)
+                .count()
+        }
+    }
+
+    impl GraphQlClient for MockGraphQl {
+        async fn graphql_request(&self, query: &str, variables: Value) -> StResult<Value> {
+            self.requests
+                .borrow_mut()
+                .push((query.to_string(), variables.clone()));
+            let pull_request = variables["number"]
+                .as_u64()
+                .and_then(|n| self.pull_requests.get(&n))
+                .cloned()
+                .unwrap_or(Value::Null);
+            Ok(json!({ "repository": { "pullRequest": pull_request } }))
+        }
+    }
+
+    #[tokio::test]
+    async fn auto_merge_mutation_issued_once_per_eligible_pr() {
+        let client = MockGraphQl {
+            pull_requests: HashMap::from([
+                (1, json!({ "id": "PR_1", "autoMergeRequest": null })),
+                (
+                    2,
+                    json!({ "id": "PR_2", "autoMergeRequest": { "enabledAt": "now" } }),
+                ),
+                (3, json!({ "id": "PR_3", "autoMergeRequest": null })),
+            ]),
+            ..Default::default()
+        };
+
+        let mut enabled = vec![];
+        for pr in [1, 2, 3] {
+            if enable_auto_merge(&client, "owner", "repo", pr, MergeMethod::Squash)
+                .await
+                .unwrap()
+            {
+                enabled.push(pr);
+            }
+        }
+
+        assert_eq!(enabled, vec![1, 3]);
+        assert_eq!(client.count(ENABLE_AUTO_MERGE_MUTATION), 2);
+        assert!(client.requests.borrow().iter().any(|(q, v)| {
+            q == ENABLE_AUTO_MERGE_MUTATION && v["id"] == "PR_3" && v["method"] == "SQUASH"
+        }));
+    }
+
+    #[tokio::test]
+    async fn ready_for_review_mutation_issued_only_for_drafts() {
+        let client = MockGraphQl {
+            pull_requests: HashMap::from([
+                (1, json!({ "id": "PR_1", "isDraft": true })),
+                (2, json!({ "id": "PR_2", "isDraft": false })),
+                (3, json!({ "id": "PR_3", "isDraft": true })),
+            ]),
+            ..Default::default()
+        };
+
+        let mut transitioned = vec![];
+        for pr in [1, 2, 3] {
+            if mark_ready_for_review(&client, "owner", "repo", pr)
+                .await
+                .unwrap()
+            {
+                transitioned.push(pr);
+            }
+        }
+
+        assert_eq!(transitioned, vec![1, 3]);
+        assert_eq!(client.count(MARK_READY_FOR_REVIEW_MUTATION), 2);
+        assert!(!client
+            .requests
+            .borrow()
+            .iter()
+            .any(|(q, v)| { q == MARK_READY_FOR_REVIEW_MUTATION && v["id"] == "PR_2" }));
+    }
+
+    #[test]
+    fn review_decision_uses_latest_review_per_reviewer() {
+        assert_eq!(review_decision([]), ReviewDecision::Pending);
+        assert_eq!(
+            review_decision([("alice", ReviewState::Commented)]),
+            ReviewDecision::Pending
+        );
+        assert_eq!(
+            review_decision([
+                ("alice", ReviewState::ChangesRequested),
+                ("alice", ReviewState::Approved),
+                ("bob", ReviewState::Commented),
+            ]),
+            ReviewDecision::Approved
+        );
+        assert_eq!(
+            review_decision([
+                ("alice", ReviewState::Approved),
+                ("bob", ReviewState::ChangesRequested),
+            ]),
+            ReviewDecision::ChangesRequested
+        );
+        assert_eq!(
+            review_decision([
+                ("alice", ReviewState::Approved),
+                ("alice", ReviewState::Dismissed),
+            ]),
+            ReviewDecision::Pending
+        );
+    }
+
+    #[test]
+    fn ci_status_aggregates_statuses_and_check_runs() {
+        assert_eq!(aggregate_ci_status([], []), CiStatus::NoChecks);
+        assert_eq!(
+            aggregate_ci_status([StatusState::Success], [Some("success"), Some("skipped")]),
+            CiStatus::Passing
+        );
+        assert_eq!(
+            aggregate_ci_status([StatusState::Success], [None]),
+            CiStatus::Pending
+        );
+        assert_eq!(
+            aggregate_ci_status([StatusState::Pending], [Some("failure")]),
+            CiStatus::Failing
+        );
+        assert_eq!(
+            aggregate_ci_status([StatusState::Error], []),
+            CiStatus::Failing
+        );
+    }
+
+    #[tokio::test]
+    async fn token_from_gh_cli_authenticates_client() {
+        // Serve a single request, capturing its headers.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_uri = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await.unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\nconnection: close\r\n\r\n{}")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request[..len]).to_lowercase()
+        });
+
+        let config = ManuallyDrop::new(StConfig::default());
+        let token = config
+            .github_token_from(&[&MockGhCli("gho_from_gh")])
+            .unwrap();
+        let client = authenticated_client(token, Some(&base_uri)).unwrap();
+        client._get(format!("{}/user", base_uri)).await.unwrap();
+
+        let request = server.await.unwrap();
+        assert!(request.contains("authorization: bearer gho_from_gh"));
+    }
+
+    #[test]
+    fn configured_token_takes_precedence_over_gh_cli() {
+        let mut config = ManuallyDrop::new(StConfig::default());
+        config.github_token = "ghp_configured".to_string();
+        assert_eq!(
+            config
+                .github_token_from(&[&MockGhCli("gho_from_gh")])
+                .unwrap(),
+            "ghp_configured"
+        );
+        assert!(ManuallyDrop::new(StConfig::default())
+            .github_token_from(&[])
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn token_missing_repo_scope_is_rejected_before_writing() {
+        // Serve the repository to a classic token with no `repo` scope.
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_uri = format!("http://{}", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = vec![0; 4096];
+            let len = stream.read(&mut request).await.unwrap();
+            let body = r#"{"private":true,"permissions":{"push":true}}"#;
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nx-oauth-scopes: read:org, gist\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&request[..len]).to_string()
+        });
+
+        let client = authenticated_client("ghp_no_repo".to_string(), Some(&base_uri)).unwrap();
+        let result = verify_token_access(&client, "org", "repo").await;
+        assert!(server.await.unwrap().starts_with("GET /repos/org/repo "));
+        match result {
+            Err(StError::MissingTokenScopes {
+                repository,
+                missing,
+            }) => {
+                assert_eq!(repository, "org/repo");
+                assert_eq!(missing, vec!["the `repo` scope"]);
+            }
+            other => panic!("expected missing scopes, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn token_access_reports_missing_scopes_and_permissions() {
+        let classic = |scopes: &[&str], private| TokenAccess {
+            scopes: Some(scopes.iter().map(ToString::to_string).collect()),
+            can_push: true,
+            private,
+        };
+        assert!(classic(&["repo"], true).missing().is_empty());
+        assert!(classic(&["public_repo"], false).missing().is_empty());
+        assert_eq!(
+            classic(&["public_repo"], true).missing(),
+            vec!["the `repo` scope"]
+        );
+
+        // Fine-grained tokens report no scopes, only whether they can push.
+        let fine_grained = TokenAccess {
+            scopes: None,
+            can_push: false,
+            private: true,
+        };
+        assert_eq!(fine_grained.missing().len(), 1);
+        assert!(fine_grained.missing()[0].contains("`Pull requests`"));
+        assert!(TokenAccess {
+            can_push: true,
+            ..fine_grained
+        }
+        .missing()
+        .is_empty());
+    }
+}
@@ -7,11 +7,16 @@
 pub mod config;
 pub mod constants;
 pub mod errors;
+pub mod theme;
 pub mod tree;
 
 // Internal modules (used by binary)
 mod ai;
 mod cli;
 mod ctx;
+mod format;
 mod git;
+mod github;
+mod logging;
+mod output;
 mod subcommands;
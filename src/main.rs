@@ -10,14 +10,27 @@ mod config;
 mod constants;
 mod ctx;
 mod errors;
+mod format;
 mod git;
+mod github;
+mod logging;
+mod output;
 mod subcommands;
+mod theme;
 mod tree;
 
 #[tokio::main]
 async fn main() {
-    if let Err(e) = cli::Cli::parse().run().await {
+    let cli = cli::Cli::try_parse().unwrap_or_else(|e| {
+        let _ = e.print();
+        std::process::exit(if e.use_stderr() {
+            constants::EXIT_USAGE
+        } else {
+            0
+        });
+    });
+    if let Err(e) = cli.run().await {
         eprintln!("{}", e);
-        std::process::exit(1);
+        std::process::exit(e.exit_code());
     }
 }
@@ -0,0 +1,96 @@
+//! Informational output for `st`, silenced with the global `--quiet` flag.
+//!
+//! Progress lines, such as the steps of `submit`, are printed with [progress], which does nothing
+//! while output is quiet. Errors, warnings, prompts, and results that scripts rely on, such as the
+//! URLs of new pull requests or `--json` output, are always printed.
+
+use std::{
+    fmt,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Whether or not informational output is suppressed.
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Suppresses informational output for the rest of the run if `quiet` is set.
+///
+/// ## Takes
+/// - `quiet` - Whether `--quiet` was passed on the command line.
+pub fn init(quiet: bool) {
+    QUIET.store(quiet, Ordering::Relaxed);
+}
+
+/// Returns whether or not informational output is suppressed.
+pub fn is_quiet() -> bool {
+    QUIET.load(Ordering::Relaxed)
+}
+
+/// Prints a line of informational output to stdout, unless output is quiet. See [progress].
+pub fn print_progress(args: fmt::Arguments<'_>) {
+    #[cfg(test)]
+    if test::capture_progress(args) {
+        return;
+    }
+    if !is_quiet() {
+        println!("{args}");
+    }
+}
+
+/// Prints a line of informational output, like [println], unless `--quiet` was passed.
+macro_rules! progress {
+    ($($arg:tt)*) => {
+        $crate::output::print_progress(format_args!($($arg)*))
+    };
+}
+pub(crate) use progress;
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::{cell::RefCell, fmt};
+
+    thread_local! {
+        /// Whether the captured output is quiet, and the lines printed so far.
+        static CAPTURED: RefCell<Option<(bool, String)>> = const { RefCell::new(None) };
+    }
+
+    /// Captures the informational output of the current thread, rather than printing it, until
+    /// dropped. Unlike the global `--quiet` flag, this doesn't affect tests running in parallel.
+    pub(crate) struct CapturedOutput;
+
+    impl CapturedOutput {
+        /// Starts capturing informational output, as if `--quiet` was `quiet`.
+        pub(crate) fn start(quiet: bool) -> Self {
+            CAPTURED.with_borrow_mut(|captured| *captured = Some((quiet, String::new())));
+            Self
+        }
+
+        /// Returns the informational output printed so far.
+        pub(crate) fn output(&self) -> String {
+            CAPTURED.with_borrow(|captured| {
+                captured
+                    .as_ref()
+                    .map(|(_, output)| output.clone())
+                    .unwrap_or_default()
+            })
+        }
+    }
+
+    impl Drop for CapturedOutput {
+        fn drop(&mut self) {
+            CAPTURED.with_borrow_mut(|captured| *captured = None);
+        }
+    }
+
+    /// Records the line if output is being captured. Returns whether or not it was captured.
+    pub(super) fn capture_progress(args: fmt::Arguments<'_>) -> bool {
+        CAPTURED.with_borrow_mut(|captured| match captured {
+            Some((quiet, output)) => {
+                if !*quiet {
+                    output.push_str(&format!("{args}\n"));
+                }
+                true
+            }
+            None => false,
+        })
+    }
+}
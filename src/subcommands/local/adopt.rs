@@ -0,0 +1,55 @@
+//! `adopt` subcommand.
+
+use crate::{ctx::StContext, errors::StResult, git::RepositoryExt, theme::Color};
+use clap::Args;
+
+/// CLI arguments for the `adopt` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct AdoptCmd {
+    /// Name of the branch at the top of the chain to adopt. Defaults to the current branch.
+    #[clap(index = 1)]
+    branch_name: Option<String>,
+    /// Adopt the inferred chain without asking for confirmation.
+    #[clap(long, short)]
+    yes: bool,
+}
+
+impl AdoptCmd {
+    /// Run the `adopt` subcommand.
+    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        let branch_name = match self.branch_name {
+            Some(name) => name,
+            None => ctx.repository.current_branch_name()?,
+        };
+
+        // Infer the chain of untracked branches down to the closest tracked branch.
+        let chain = ctx.adoption_chain(&branch_name)?;
+        println!("Inferred chain:");
+        for (branch, parent) in &chain {
+            println!(
+                "  `{}` on top of `{}`",
+                Color::Green.paint(branch),
+                Color::Yellow.paint(parent)
+            );
+        }
+
+        if !self.yes {
+            let confirm = inquire::Confirm::new("Track these branches?")
+                .with_default(true)
+                .prompt()?;
+            if !confirm {
+                println!("Cancelled.");
+                return Ok(());
+            }
+        }
+
+        ctx.adopt_chain(&chain)?;
+        println!(
+            "Adopted {} branch{} up to `{}`",
+            Color::Green.paint(chain.len().to_string()),
+            if chain.len() != 1 { "es" } else { "" },
+            Color::Green.paint(&branch_name)
+        );
+        Ok(())
+    }
+}
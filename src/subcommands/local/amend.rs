@@ -0,0 +1,232 @@
+//! `amend` subcommand.
+
+use crate::{
+    ctx::StContext,
+    errors::{StError, StResult},
+    git::RepositoryExt,
+    theme::Color,
+};
+use clap::Args;
+use git2::{Oid, Repository, Status};
+
+/// CLI arguments for the `amend` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct AmendCmd {
+    /// Stage changes to tracked files before amending, rather than only amending with the staged
+    /// changes.
+    #[clap(short, long)]
+    all: bool,
+    /// Replace the message of the last commit.
+    #[clap(short, long)]
+    message: Option<String>,
+}
+
+impl AmendCmd {
+    /// Run the `amend` subcommand.
+    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        self.amend(&mut ctx)
+    }
+
+    /// Amends the last commit of the current branch, and restacks its descendants.
+    fn amend(&self, ctx: &mut StContext<'_>) -> StResult<()> {
+        let branch_name = ctx.current_branch()?;
+        if branch_name == ctx.tree.trunk_name() {
+            return Err(StError::CannotAmendTrunkBranch);
+        }
+        if ctx.tree.get(&branch_name).is_none() {
+            return Err(StError::BranchNotTracked(branch_name));
+        }
+        if ctx.own_commits(&branch_name)?.is_empty() {
+            return Err(StError::NothingToAmend(branch_name));
+        }
+
+        // Restacking the descendants checks them out, which requires the working tree to be clean
+        // once the commit is amended. Untracked files, and without `--all` any unstaged changes,
+        // are left behind.
+        let unstaged = ctx.repository.unstaged_changes()?;
+        let left_behind = if self.all {
+            unstaged & Status::WT_NEW
+        } else {
+            unstaged
+        };
+        if !left_behind.is_empty() && !ctx.tree.descendants(&branch_name)?.is_empty() {
+            return Err(StError::WorkingTreeDirty);
+        }
+
+        commit_amend(
+            ctx.repository,
+            &branch_name,
+            self.all,
+            self.message.as_deref(),
+        )?;
+        println!(
+            "Amended the last commit of `{}`.",
+            Color::Green.paint(&branch_name)
+        );
+
+        // Restack the branches above, then return to the amended branch.
+        ctx.restack_descendants(&branch_name)?;
+        Ok(())
+    }
+}
+
+/// Amends the commit at `HEAD` with the staged changes, like `git commit --amend`.
+///
+/// ## Takes
+/// - `repository` - The repository to commit in.
+/// - `branch_name` - The name of the checked out branch.
+/// - `all` - Whether to stage changes to tracked files first.
+/// - `message` - The new commit message, or [None] to keep the message.
+///
+/// ## Returns
+/// - `Result<Oid>` - The amended commit, or [StError::NothingToAmend] if there are no staged
+///   changes and no new message.
+fn commit_amend(
+    repository: &Repository,
+    branch_name: &str,
+    all: bool,
+    message: Option<&str>,
+) -> StResult<Oid> {
+    let mut index = repository.index()?;
+    if all {
+        index.update_all(["*"], None)?;
+        index.write()?;
+    }
+
+    let tree = repository.find_tree(index.write_tree()?)?;
+    let head = repository.head()?.peel_to_commit()?;
+    if tree.id() == head.tree_id() && message.is_none() {
+        return Err(StError::NothingToAmend(branch_name.to_string()));
+    }
+
+    Ok(head.amend(Some("HEAD"), None, None, None, message, Some(&tree))?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::AmendCmd;
+    use crate::{
+        ctx::test::test_context, errors::StError, git::test::TempRepository, tree::StackTree,
+    };
+    use git2::{build::CheckoutBuilder, BranchType};
+    use std::{fs, path::Path};
+
+    #[test]
+    fn amend_restacks_descendants_onto_new_tip() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit_file("Feature 1", "feature-1.txt", "one");
+        repo.branch("feature-2");
+        let feature_2_head = repo.commit_file("Feature 2", "feature-2.txt", "two");
+        repo.branch("feature-3");
+        repo.commit_file("Feature 3", "feature-3.txt", "three");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2")
+            .unwrap();
+        tree.insert("feature-2", &feature_2_head.to_string(), "feature-3")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        // Stage a change to `feature-1`.
+        repo.checkout("feature-1");
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let workdir = repo.repository.workdir().unwrap();
+        fs::write(workdir.join("feature-1.txt"), "one, amended").unwrap();
+        let mut index = repo.repository.index().unwrap();
+        index.add_path(Path::new("feature-1.txt")).unwrap();
+        index.write().unwrap();
+
+        let cmd = AmendCmd {
+            all: false,
+            message: Some("Feature 1, amended".to_string()),
+        };
+        cmd.amend(&mut ctx).unwrap();
+
+        let head = |branch: &str| {
+            let branch = repo.repository.find_branch(branch, BranchType::Local);
+            branch.unwrap().get().peel_to_commit().unwrap()
+        };
+
+        // The commit is replaced rather than added to.
+        let amended = head("feature-1");
+        assert_ne!(amended.id(), feature_1_head);
+        assert_eq!(amended.summary(), Some("Feature 1, amended"));
+        assert_eq!(amended.parent_id(0).unwrap(), main_head);
+
+        // The descendants are rebased onto the new tip, and their caches follow.
+        assert_eq!(head("feature-2").parent_id(0).unwrap(), amended.id());
+        assert_eq!(
+            ctx.tree.get("feature-2").unwrap().parent_oid_cache,
+            Some(amended.id().to_string())
+        );
+        assert_eq!(
+            ctx.tree.get("feature-3").unwrap().parent_oid_cache,
+            Some(head("feature-2").id().to_string())
+        );
+        assert!(ctx.branches_needing_restack().unwrap().is_empty());
+        assert_eq!(ctx.current_branch().unwrap(), "feature-1");
+
+        // Without staged changes or a new message, there is nothing to amend.
+        let cmd = AmendCmd {
+            all: true,
+            message: None,
+        };
+        assert!(matches!(
+            cmd.amend(&mut ctx),
+            Err(StError::NothingToAmend(branch)) if branch == "feature-1"
+        ));
+    }
+    #[test]
+    fn unstaged_changes_left_behind_refuse_amend_before_committing() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit_file("Feature 1", "feature-1.txt", "one");
+        repo.branch("feature-2");
+        repo.commit_file("Feature 2", "feature-2.txt", "two");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        // Stage a change to `feature-1`, and change the file again without staging it.
+        repo.checkout("feature-1");
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let workdir = repo.repository.workdir().unwrap();
+        fs::write(workdir.join("feature-1.txt"), "one, amended").unwrap();
+        let mut index = repo.repository.index().unwrap();
+        index.add_path(Path::new("feature-1.txt")).unwrap();
+        index.write().unwrap();
+        fs::write(workdir.join("feature-1.txt"), "one, amended twice").unwrap();
+
+        // The unstaged change would keep `feature-2` from being checked out to restack it, so
+        // nothing is amended.
+        let cmd = AmendCmd {
+            all: false,
+            message: None,
+        };
+        assert!(matches!(
+            cmd.amend(&mut ctx),
+            Err(StError::WorkingTreeDirty)
+        ));
+        let head = repo.repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.id(), feature_1_head);
+
+        // With `--all`, the unstaged change is amended too.
+        let cmd = AmendCmd { all: true, ..cmd };
+        cmd.amend(&mut ctx).unwrap();
+        assert!(ctx.branches_needing_restack().unwrap().is_empty());
+        assert_eq!(ctx.current_branch().unwrap(), "feature-1");
+    }
+}
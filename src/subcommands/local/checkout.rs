@@ -18,6 +18,7 @@ pub struct CheckoutCmd {
 impl CheckoutCmd {
     /// Run the `checkout` subcommand.
     pub fn run(self, ctx: StContext<'_>) -> StResult<()> {
+        ctx.current_branch()?;
         let branches = ctx.display_branches()?;
 
         // Prompt the user for the name of the branch to checkout, or use the provided name.
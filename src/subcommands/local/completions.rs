@@ -0,0 +1,140 @@
+//! `completions` subcommand.
+
+use crate::{
+    cli::Cli,
+    ctx::load_tree,
+    errors::{StError, StResult},
+    git::active_repository,
+};
+use clap::{Args, CommandFactory};
+use clap_complete::Shell;
+
+/// Subcommands (and their aliases) that take a tracked branch name.
+const BRANCH_SUBCOMMANDS: &str = "checkout co delete d del untrack ut";
+
+/// `trunk` subcommands (and their aliases) that take a trunk name.
+const TRUNK_SUBCOMMANDS: &str = "switch sw remove rm rename mv set";
+
+/// CLI arguments for the `completions` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct CompletionsCmd {
+    /// The shell to generate the completion script for.
+    #[clap(value_enum, required_unless_present_any = ["branches", "trunks"])]
+    shell: Option<Shell>,
+    /// List the tracked branches, one per line. Used by the completion scripts.
+    #[clap(long, hide = true, conflicts_with_all = ["shell", "trunks"])]
+    branches: bool,
+    /// List the trunk branches, one per line. Used by the completion scripts.
+    #[clap(long, hide = true, conflicts_with = "shell")]
+    trunks: bool,
+}
+
+impl CompletionsCmd {
+    /// Run the `completions` subcommand.
+    pub fn run(self) -> StResult<()> {
+        match self.shell {
+            Some(shell) => print!("{}", completion_script(shell)),
+            None => {
+                // Completion helpers must never prompt, so read the store directly and list
+                // nothing if the repository has not been set up.
+                let repo = active_repository().ok_or(StError::NotAGitRepository)?;
+                let Some(tree) = load_tree(&repo)? else {
+                    return Ok(());
+                };
+                let names = if self.branches {
+                    tree.branches()?
+                } else {
+                    tree.list_trunks()
+                };
+                names.iter().for_each(|name| println!("{}", name));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Generates the completion script for the given [Shell]. For bash, zsh, and fish, the static
+/// script is extended with a helper that completes tracked branch and trunk names by calling
+/// back into `st`.
+///
+/// ## Takes
+/// - `shell` - The [Shell] to generate the completion script for.
+///
+/// ## Returns
+/// - `String` - The completion script.
+pub(crate) fn completion_script(shell: Shell) -> String {
+    let mut cmd = Cli::command();
+    let bin_name = cmd.get_name().to_string();
+    let mut buf = Vec::new();
+    clap_complete::generate(shell, &mut cmd, &bin_name, &mut buf);
+    let mut script = String::from_utf8_lossy(&buf).into_owned();
+
+    let dynamic = match shell {
+        Shell::Bash => format!(
+            r#"
+_st_dynamic() {{
+    local cur="${{COMP_WORDS[COMP_CWORD]}}"
+    if [[ $COMP_CWORD -eq 2 && " {branch_cmds} " == *" ${{COMP_WORDS[1]}} "* ]]; then
+        COMPREPLY=($(compgen -W "$(st completions --branches 2>/dev/null)" -- "$cur"))
+    elif [[ $COMP_CWORD -eq 3 && "${{COMP_WORDS[1]}}" =~ ^(trunk|t)$ && " {trunk_cmds} " == *" ${{COMP_WORDS[2]}} "* ]]; then
+        COMPREPLY=($(compgen -W "$(st completions --trunks 2>/dev/null)" -- "$cur"))
+    else
+        _st "$@"
+    fi
+}}
+complete -F _st_dynamic -o nosort -o bashdefault -o default st
+"#,
+            branch_cmds = BRANCH_SUBCOMMANDS,
+            trunk_cmds = TRUNK_SUBCOMMANDS
+        ),
+        Shell::Zsh => format!(
+            r#"
+_st_dynamic() {{
+    if (( CURRENT == 3 )) && [[ " {branch_cmds} " == *" ${{words[2]}} "* ]]; then
+        compadd -- ${{(f)"$(st completions --branches 2>/dev/null)"}}
+    elif (( CURRENT == 4 )) && [[ ${{words[2]}} == (trunk|t) && " {trunk_cmds} " == *" ${{words[3]}} "* ]]; then
+        compadd -- ${{(f)"$(st completions --trunks 2>/dev/null)"}}
+    else
+        _st "$@"
+    fi
+}}
+compdef _st_dynamic st
+"#,
+            branch_cmds = BRANCH_SUBCOMMANDS,
+            trunk_cmds = TRUNK_SUBCOMMANDS
+        ),
+        Shell::Fish => format!(
+            r#"
+complete -c st -n "__fish_seen_subcommand_from {branch_cmds}" -f -a "(st completions --branches 2>/dev/null)"
+complete -c st -n "__fish_seen_subcommand_from trunk t; and __fish_seen_subcommand_from {trunk_cmds}" -f -a "(st completions --trunks 2>/dev/null)"
+"#,
+            branch_cmds = BRANCH_SUBCOMMANDS,
+            trunk_cmds = TRUNK_SUBCOMMANDS
+        ),
+        _ => String::new(),
+    };
+    script.push_str(&dynamic);
+    script
+}
+
+#[cfg(test)]
+mod test {
+    use super::completion_script;
+    use clap_complete::Shell;
+
+    #[test]
+    fn completions_generate_for_supported_shells() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let script = completion_script(shell);
+            assert!(!script.trim().is_empty(), "empty script for {}", shell);
+            assert!(script.contains("submit"), "missing subcommands for {}", shell);
+        }
+    }
+
+    #[test]
+    fn completions_list_tracked_branches_dynamically() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish] {
+            assert!(completion_script(shell).contains("st completions --branches"));
+        }
+    }
+}
@@ -1,17 +1,145 @@
 //! `config` subcommand.
 
-use crate::{config::prompt_for_configuration, ctx::StContext, errors::StResult};
+use crate::{
+    config::{
+        check_discovered_secrets, config_keys, config_path, discovered_config_path, is_secret,
+        mask_secret, prompt_for_configuration, set_discovered_field, StConfig,
+    },
+    ctx::StContext,
+    errors::{StError, StResult},
+    theme::Color,
+};
+use clap::{Args, Subcommand};
+use std::{fs, mem::ManuallyDrop, path::Path};
 
-#[derive(Debug, Clone, Eq, PartialEq, clap::Args)]
-pub struct ConfigCmd;
+/// CLI arguments for the `config` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct ConfigCmd {
+    /// Operation to perform. Opens the configuration in the editor if omitted.
+    #[clap(subcommand)]
+    pub command: Option<ConfigSubcommand>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Subcommand)]
+pub enum ConfigSubcommand {
+    /// Print the value of a configuration field
+    Get {
+        /// The configuration key
+        key: String,
+    },
+    /// Set the value of a configuration field
+    Set {
+        /// The configuration key
+        key: String,
+        /// The new value
+        value: String,
+    },
+    /// List all configuration fields
+    #[clap(visible_alias = "ls")]
+    List,
+    /// Check the configuration for problems, without running any other operation
+    Validate,
+}
 
 impl ConfigCmd {
     /// Run the `config` subcommand to force or allow configuration editing.
     pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
-        let ser = toml::to_string_pretty(&ctx.cfg)?;
-        let cfg = prompt_for_configuration(Some(&ser))?;
-        ctx.cfg = cfg;
+        match self.command {
+            None => match discovered_config_path() {
+                Some(path) => edit_discovered(path)?,
+                None => {
+                    let ser = toml::to_string_pretty(&ctx.cfg)?;
+                    let cfg = prompt_for_configuration(Some(&ser))?;
+                    ctx.cfg = cfg;
+                }
+            },
+            Some(ConfigSubcommand::Get { key }) => {
+                let value = ctx.cfg.get_field(&key)?;
+                println!("{}", display_value(&key, value.as_ref()));
+            }
+            Some(ConfigSubcommand::Set { key, value }) => {
+                ctx.cfg.set_field(&key, &value)?;
+                // A discovered config file is never written back implicitly, so write the edit.
+                if let Some(path) = discovered_config_path() {
+                    set_discovered_field(path, &key, ctx.cfg.get_field(&key)?)?;
+                }
+                println!("Set `{}`.", Color::Blue.paint(&key));
+            }
+            Some(ConfigSubcommand::List) => {
+                for &key in config_keys() {
+                    let value = ctx.cfg.get_field(key)?;
+                    println!(
+                        "{} = {}",
+                        Color::Blue.paint(key),
+                        display_value(key, value.as_ref())
+                    );
+                }
+            }
+            Some(ConfigSubcommand::Validate) => validate()?,
+        }
 
         Ok(())
     }
+
+    /// Runs the subcommand if it must run before the configuration is loaded, which is the case
+    /// for `config validate`: an invalid configuration is reported rather than set up again.
+    ///
+    /// ## Returns
+    /// - `Option<Result<()>>` - The result of the subcommand, or [None] if it needs a context.
+    pub fn run_standalone(&self) -> Option<StResult<()>> {
+        matches!(self.command, Some(ConfigSubcommand::Validate)).then(validate)
+    }
+}
+
+/// Loads the configuration and reports each problem with it.
+fn validate() -> StResult<()> {
+    // Leave the file untouched, rather than writing it back when the config is dropped.
+    let path = config_path().display().to_string();
+    let Some(cfg) = StConfig::try_load()?.map(ManuallyDrop::new) else {
+        println!("No configuration found at `{}`.", Color::Blue.paint(&path));
+        return Err(StError::InvalidConfig { path, problems: 1 });
+    };
+
+    let problems = cfg.problems();
+    if problems.is_empty() {
+        println!("`{}` is valid.", Color::Blue.paint(&path));
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("❌ {}", problem);
+    }
+    Err(StError::InvalidConfig {
+        path,
+        problems: problems.len(),
+    })
+}
+
+/// Opens a `.st.toml` discovered in the repository in the editor, and writes it back as edited,
+/// keeping its comments. Secrets are refused, since the file may be committed with the repository.
+fn edit_discovered(path: &Path) -> StResult<()> {
+    let contents = fs::read_to_string(path)?;
+    let edited = inquire::Editor::new(&format!(
+        "Edit the repository configuration `{}`.",
+        Color::Blue.paint(path.display().to_string())
+    ))
+    .with_file_extension(".toml")
+    .with_predefined_text(&contents)
+    .prompt()?;
+
+    // Never dropped, so that only the edited text is written.
+    let cfg = ManuallyDrop::new(toml::from_str::<StConfig>(&edited)?);
+    check_discovered_secrets(&cfg, path)?;
+    fs::write(path, edited)?;
+    Ok(())
+}
+
+/// Returns the displayable form of a configuration value, masking secrets. Strings are displayed
+/// unquoted, and fields left at their default are displayed as empty.
+fn display_value(key: &str, value: Option<&toml::Value>) -> String {
+    match value {
+        None => String::new(),
+        Some(toml::Value::String(value)) if is_secret(key) => mask_secret(value),
+        Some(toml::Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+    }
 }
@@ -3,11 +3,13 @@
 use crate::{
     ctx::StContext,
     errors::{StError, StResult},
-    git::RepositoryExt,
+    format::sign_off,
+    git::{validate_branch_name, RepositoryExt},
+    theme::Color,
+    tree::StackTree,
 };
 use clap::Args;
-use git2::IndexAddOption;
-use nu_ansi_term::Color;
+use git2::{BranchType, IndexAddOption, Repository};
 
 /// CLI arguments for the `create` subcommand.
 #[derive(Debug, Clone, Eq, PartialEq, Args)]
@@ -24,14 +26,15 @@ pub struct CreateCmd {
     /// Specify a commit message
     #[clap(short, long, requires = "all", conflicts_with = "update")]
     message: Option<String>,
+    /// Add a `Signed-off-by` trailer to the commit. Defaults to the `signoff` configuration value.
+    #[clap(long)]
+    signoff: bool,
 }
 
 impl CreateCmd {
     /// Run the `create` subcommand.
     pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
         // Gather metadata about the current branch.
-        let current_branch = ctx.repository.current_branch()?;
-        let current_branch_head = current_branch.get().peel_to_commit()?;
         let current_branch_name = ctx.repository.current_branch_name()?;
 
         // Prompt the user for the name of their new branch, or use the provided name.
@@ -39,11 +42,38 @@ impl CreateCmd {
             Some(name) => name,
             None => inquire::Text::new("Name of new branch:").prompt()?,
         };
+        validate_branch_name(&new_branch_name)?;
 
-        // Stage changes if requested
-        if self.all || self.update {
-            let message = self.message.ok_or(StError::CommitMessageRequired)?;
+        // Refuse to clobber an existing branch.
+        if ctx
+            .repository
+            .find_branch(&new_branch_name, BranchType::Local)
+            .is_ok()
+        {
+            return Err(StError::BranchAlreadyExists(new_branch_name));
+        }
+
+        // Unless changes are being committed onto the new branch, the working tree must be clean.
+        let stage = self.all || self.update;
+        let message = if stage {
+            Some(self.message.ok_or(StError::CommitMessageRequired)?)
+        } else {
+            None
+        };
+        if !stage && !ctx.repository.is_working_tree_clean()? {
+            return Err(StError::WorkingTreeDirty);
+        }
+
+        // Create the new branch on top of the current branch, and track it.
+        create_tracked_branch(
+            ctx.repository,
+            &mut ctx.tree,
+            &current_branch_name,
+            &new_branch_name,
+        )?;
 
+        // Stage changes and commit them onto the new branch, if requested.
+        if let Some(message) = message {
             // Get the index.
             let mut index = ctx.repository.index()?;
 
@@ -57,6 +87,15 @@ impl CreateCmd {
 
             // Create the commit.
             let sig = ctx.repository.signature()?;
+            let message = if self.signoff || ctx.cfg.signoff {
+                sign_off(
+                    &message,
+                    sig.name().unwrap_or_default(),
+                    sig.email().unwrap_or_default(),
+                )
+            } else {
+                message
+            };
             // Get the tree.
             let tree_id = ctx.repository.index()?.write_tree()?;
             let tree = ctx.repository.find_tree(tree_id)?;
@@ -67,23 +106,6 @@ impl CreateCmd {
                 .commit(Some("HEAD"), &sig, &sig, &message, &tree, &[&parent_commit])?;
         }
 
-        // Check if the working tree is clean.
-        if !ctx.repository.is_working_tree_clean()? {
-            return Err(crate::errors::StError::WorkingTreeDirty);
-        }
-
-        // Attempt to create the new branch.
-        ctx.repository
-            .branch(&new_branch_name, &current_branch_head, false)?;
-        ctx.repository.checkout_branch(&new_branch_name)?;
-
-        // Insert the new branch into the stack tree.
-        ctx.tree.insert(
-            &current_branch_name,
-            &current_branch_head.id().to_string(),
-            &new_branch_name,
-        )?;
-
         println!(
             "Successfully created and tracked new branch `{}` on top of `{}`",
             Color::Blue.paint(&new_branch_name),
@@ -92,3 +114,65 @@ impl CreateCmd {
         Ok(())
     }
 }
+
+/// Creates a new git branch at the head of `parent_name`, switches `HEAD` to it, and tracks it on
+/// top of the parent. As the new branch points at the parent's head, the working tree and index
+/// are left untouched.
+///
+/// ## Takes
+/// - `repository` - The repository to create the branch in.
+/// - `tree` - The [StackTree] to track the branch in.
+/// - `parent_name` - The name of the tracked parent branch.
+/// - `branch_name` - The name of the new branch.
+fn create_tracked_branch(
+    repository: &Repository,
+    tree: &mut StackTree,
+    parent_name: &str,
+    branch_name: &str,
+) -> StResult<()> {
+    // Ensure the parent is tracked before touching the repository.
+    if tree.get(parent_name).is_none() {
+        return Err(StError::BranchNotTracked(parent_name.to_string()));
+    }
+
+    let parent_head = repository
+        .find_branch(parent_name, BranchType::Local)?
+        .get()
+        .peel_to_commit()?;
+
+    // Attempt to create the new branch.
+    repository.branch(branch_name, &parent_head, false)?;
+    repository.set_head(&format!("refs/heads/{}", branch_name))?;
+
+    // Insert the new branch into the stack tree.
+    tree.insert(parent_name, &parent_head.id().to_string(), branch_name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::create_tracked_branch;
+    use crate::{
+        git::{test::TempRepository, RepositoryExt},
+        tree::StackTree,
+    };
+
+    #[test]
+    fn created_branch_is_tracked_on_current_branch() {
+        let repo = TempRepository::new();
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit("Feature 1");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+
+        create_tracked_branch(&repo.repository, &mut tree, "feature-1", "feature-2").unwrap();
+
+        let tracked = tree.get("feature-2").unwrap();
+        assert_eq!(tracked.parent.as_deref(), Some("feature-1"));
+        assert_eq!(
+            tracked.parent_oid_cache.as_deref(),
+            Some(feature_1_head.to_string().as_str())
+        );
+        assert_eq!(repo.repository.current_branch_name().unwrap(), "feature-2");
+    }
+}
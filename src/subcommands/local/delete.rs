@@ -1,8 +1,9 @@
 //! `delete` subcommand.
 
-use crate::{ctx::StContext, errors::StResult};
+use crate::{
+    ctx::StContext, errors::StResult, subcommands::remote::remove_stack_comment, theme::Color,
+};
 use clap::Args;
-use nu_ansi_term::Color;
 
 /// CLI arguments for the `delete` subcommand.
 #[derive(Debug, Clone, Eq, PartialEq, Args)]
@@ -14,7 +15,7 @@ pub struct DeleteCmd {
 
 impl DeleteCmd {
     /// Run the `delete` subcommand.
-    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+    pub async fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
         // Gather the display branches.
         let display_branches = ctx.display_branches()?;
 
@@ -29,7 +30,14 @@ impl DeleteCmd {
             }
         };
 
+        let remote = ctx.tree.get(&branch_name).and_then(|b| b.remote.clone());
         ctx.delete_branch(&branch_name, false)?;
+        if ctx.tree.get(&branch_name).is_some() {
+            return Ok(());
+        }
+
+        // The PR is no longer part of the stack, so its stack comment is removed.
+        remove_stack_comment(&ctx, remote.as_ref()).await;
         println!(
             "Successfully deleted branch `{}`.",
             Color::Blue.paint(&branch_name)
@@ -0,0 +1,86 @@
+//! `describe` subcommand.
+
+use crate::{
+    ctx::StContext,
+    errors::{StError, StResult},
+    subcommands::remote::{editor_command, pr_description_editor},
+    theme::Color,
+};
+use clap::Args;
+use std::{fs, path::PathBuf};
+
+/// CLI arguments for the `describe` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct DescribeCmd {
+    /// The description of the stack. Opens an editor if neither this nor `--file` is passed.
+    #[clap(conflicts_with_all = ["file", "clear"])]
+    description: Option<String>,
+    /// Read the description of the stack from this file.
+    #[clap(long, value_name = "PATH", conflicts_with = "clear")]
+    file: Option<PathBuf>,
+    /// Remove the description of the stack.
+    #[clap(long)]
+    clear: bool,
+}
+
+impl DescribeCmd {
+    /// Run the `describe` subcommand.
+    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        let branch_name = ctx.current_branch()?;
+        let base = ctx
+            .tree
+            .stack_base(&branch_name)?
+            .ok_or(StError::CannotDescribeTrunkBranch)?;
+
+        let description = if self.clear {
+            None
+        } else {
+            Some(self.read_description(&ctx, &branch_name)?)
+                .map(|description| description.trim().to_string())
+                .filter(|description| !description.is_empty())
+        };
+
+        let described = description.is_some();
+        ctx.tree
+            .get_mut(&base)
+            .ok_or_else(|| StError::BranchNotTracked(base.clone()))?
+            .stack_description = description;
+
+        if described {
+            println!(
+                "Set the description of the stack on `{}`. Run `{}` to add it to submitted PRs.",
+                Color::Green.paint(&base),
+                Color::Blue.paint("st submit --update-metadata")
+            );
+        } else {
+            println!(
+                "Cleared the description of the stack on `{}`.",
+                Color::Green.paint(&base)
+            );
+        }
+        Ok(())
+    }
+
+    /// Reads the new description of the stack from the arguments, the passed file, or an editor
+    /// pre-filled with the current description.
+    fn read_description(&self, ctx: &StContext<'_>, branch_name: &str) -> StResult<String> {
+        if let Some(description) = &self.description {
+            return Ok(description.clone());
+        }
+        if let Some(path) = &self.file {
+            return fs::read_to_string(path).map_err(|source| {
+                StError::StackDescriptionUnreadable {
+                    path: path.display().to_string(),
+                    source,
+                }
+            });
+        }
+
+        let current = ctx.tree.stack_description(branch_name).unwrap_or_default();
+        let editor = editor_command(&ctx.cfg.editor);
+        let description = pr_description_editor("Describe the stack", &editor)
+            .with_predefined_text(current)
+            .prompt()?;
+        Ok(description)
+    }
+}
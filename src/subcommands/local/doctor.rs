@@ -0,0 +1,576 @@
+//! `doctor` subcommand.
+
+use crate::{
+    ai::list_gemini_models,
+    config::{config_path, StConfig, StConfigError},
+    ctx::{
+        load_tree, orphaned_branches, parse_remote_url, persist_tree, untrack_orphans,
+        untracked_branches, StoreLock,
+    },
+    errors::{StError, StResult},
+    git::{active_repository, RepositoryExt},
+    github::{github_client, verify_token_access},
+    theme::Color,
+    tree::StackTree,
+};
+use clap::Args;
+use git2::{BranchType, Repository};
+use inquire::Confirm;
+use std::mem::ManuallyDrop;
+
+/// CLI arguments for the `doctor` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct DoctorCmd {
+    /// Offer to untrack branches whose git branch no longer exists, re-linking their children to
+    /// their parents.
+    #[clap(long)]
+    fix: bool,
+}
+
+impl DoctorCmd {
+    /// Run the `doctor` subcommand.
+    pub async fn run(self) -> StResult<()> {
+        println!("🩺 Checking your `{}` setup...\n", Color::Blue.paint("st"));
+        let mut checks = Vec::new();
+
+        // The global configuration, and the GitHub token it resolves to. It is never dropped, so
+        // the file being diagnosed is not rewritten.
+        let path = config_path().display().to_string();
+        let cfg = match StConfig::try_load().map(|c| c.map(ManuallyDrop::new)) {
+            Ok(Some(cfg)) => Some(cfg),
+            Ok(None) => {
+                checks.push(Check::fail(
+                    "Configuration",
+                    format!("No configuration found at `{}`.", path),
+                    "Run any `st` command to set up the configuration.",
+                ));
+                None
+            }
+            Err(e) => {
+                checks.push(Check::fail(
+                    "Configuration",
+                    format!("Failed to load `{}`: {}", path, e),
+                    "Fix the file by hand, or delete it and run `st` to set it up again.",
+                ));
+                None
+            }
+        };
+        let mut authenticated = false;
+        if let Some(cfg) = &cfg {
+            checks.push(Check::pass("Configuration", format!("Loaded `{}`.", path)));
+            let token = check_token(cfg.validate());
+            let has_token = token.status == CheckStatus::Pass;
+            checks.push(token);
+            if has_token {
+                let authentication = check_authentication(cfg).await;
+                authenticated = authentication.status == CheckStatus::Pass;
+                checks.push(authentication);
+            }
+            if !cfg.gemini_api_key.is_empty() {
+                checks.push(check_gemini(&cfg.gemini_api_key).await);
+            }
+        }
+
+        // The repository, its store, and its remotes.
+        match active_repository() {
+            Some(repository) => {
+                checks.push(Check::pass("Git repository", "Found a git repository."));
+                let (store, tree) = check_store(&repository);
+                checks.push(store);
+                if let Some(mut tree) = tree {
+                    checks.push(check_trunk(&repository, &tree));
+                    let orphans = check_orphans(&repository, &tree);
+                    if self.fix && orphans.status != CheckStatus::Pass {
+                        let _lock = StoreLock::acquire(&repository, false)?;
+                        checks.push(repair_orphans(&repository, &mut tree, confirm_untrack)?);
+                    } else {
+                        checks.push(orphans);
+                    }
+                    checks.push(check_untracked(&repository, &tree));
+                }
+                if let Some(cfg) = &cfg {
+                    checks.push(check_remote(&repository, cfg.push_remote()));
+                    if cfg.pr_remote() != cfg.push_remote() {
+                        checks.push(check_remote(&repository, cfg.pr_remote()));
+                    }
+                    if authenticated {
+                        checks.push(check_token_access(&repository, cfg).await);
+                    }
+                }
+            }
+            None => checks.push(Check::fail(
+                "Git repository",
+                "The current directory is not within a git repository.",
+                "Run `st` from within a git repository.",
+            )),
+        }
+
+        for check in &checks {
+            println!("{}", check);
+        }
+        match failures(&checks) {
+            0 => {
+                println!("\nEverything looks good ✨");
+                Ok(())
+            }
+            n => Err(StError::DoctorChecksFailed(n)),
+        }
+    }
+}
+
+/// The outcome of a single `doctor` check.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum CheckStatus {
+    /// The check passed.
+    Pass,
+    /// The check found a problem that may not prevent `st` from working.
+    Warn,
+    /// The check found a problem that prevents `st` from working.
+    Fail,
+}
+
+/// A single `doctor` check, with a hint to remedy it if it did not pass.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) struct Check {
+    /// The name of the check.
+    pub(crate) name: &'static str,
+    /// The outcome of the check.
+    pub(crate) status: CheckStatus,
+    /// What the check found.
+    pub(crate) message: String,
+    /// How to remedy the problem, if the check did not pass.
+    pub(crate) hint: Option<String>,
+}
+
+impl Check {
+    /// Creates a passing [Check].
+    fn pass(name: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    /// Creates a [Check] that warns, with a hint to remedy it.
+    fn warn(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    /// Creates a failing [Check], with a hint to remedy it.
+    fn fail(name: &'static str, message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: CheckStatus::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+impl std::fmt::Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (symbol, color) = match self.status {
+            CheckStatus::Pass => ("✅", Color::Green),
+            CheckStatus::Warn => ("⚠️ ", Color::Yellow),
+            CheckStatus::Fail => ("❌", Color::Red),
+        };
+        write!(f, "{} {}: {}", symbol, color.paint(self.name), self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n   -> {}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the number of failed checks.
+pub(crate) fn failures(checks: &[Check]) -> usize {
+    checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count()
+}
+
+/// Checks that a GitHub token is configured, or can be resolved from the gh CLI or keychain.
+///
+/// ## Takes
+/// - `validation` - The result of [StConfig::validate].
+pub(crate) fn check_token(validation: Result<(), StConfigError>) -> Check {
+    match validation {
+        Ok(()) => Check::pass("GitHub token", "Found a GitHub token."),
+        Err(e) => Check::fail(
+            "GitHub token",
+            e.to_string(),
+            "Set one with `st config set github_token <token>`, or log in with `gh auth login`.",
+        ),
+    }
+}
+
+/// Checks that GitHub accepts the configured token.
+async fn check_authentication(cfg: &StConfig) -> Check {
+    let client = match github_client(cfg) {
+        Ok(client) => client,
+        Err(e) => {
+            return Check::fail(
+                "GitHub authentication",
+                e.to_string(),
+                "Check that the token is a valid personal access token.",
+            );
+        }
+    };
+    match client.current().user().await {
+        Ok(user) => Check::pass(
+            "GitHub authentication",
+            format!("Authenticated as `{}`.", user.login),
+        ),
+        Err(octocrab::Error::GitHub { source, .. }) if source.status_code.as_u16() == 401 => {
+            Check::fail(
+                "GitHub authentication",
+                "GitHub rejected the token.",
+                "Create a new token at https://github.com/settings/tokens, with the `repo` scope.",
+            )
+        }
+        // The error itself renders with a backtrace, so only report its cause.
+        Err(e) => Check::warn(
+            "GitHub authentication",
+            match std::error::Error::source(&e) {
+                Some(cause) => format!("Could not reach GitHub: {}", cause),
+                None => "Could not reach GitHub.".to_string(),
+            },
+            "Check your network connection.",
+        ),
+    }
+}
+
+/// Checks that the token can push branches and write pull requests to the repository of the PR
+/// remote.
+async fn check_token_access(repository: &Repository, cfg: &StConfig) -> Check {
+    let repo = repository
+        .find_remote(cfg.pr_remote())
+        .ok()
+        .and_then(|remote| remote.url().map(parse_remote_url));
+    let (owner, repo) = match repo {
+        Some(Ok(repo)) => repo,
+        // The remote check reports a missing or unparsable remote.
+        _ => {
+            return Check::warn(
+                "Token scopes",
+                "Skipped, as the repository on GitHub is unknown.",
+                format!("Configure the `{}` remote first.", cfg.pr_remote()),
+            );
+        }
+    };
+    let client = match github_client(cfg) {
+        Ok(client) => client,
+        Err(e) => return Check::fail("Token scopes", e.to_string(), "Check the token."),
+    };
+    token_access_check(verify_token_access(&client, &owner, &repo).await)
+}
+
+/// Turns the result of [verify_token_access] into a [Check].
+pub(crate) fn token_access_check(result: StResult<()>) -> Check {
+    match result {
+        Ok(()) => Check::pass(
+            "Token scopes",
+            "The token can push branches and write pull requests.",
+        ),
+        Err(StError::MissingTokenScopes { repository, missing }) => Check::fail(
+            "Token scopes",
+            format!(
+                "The token can't write pull requests to `{}`. Missing: {}.",
+                repository,
+                missing.join(", ")
+            ),
+            "Grant them at https://github.com/settings/tokens, or set another token with `st config set github_token <token>`.",
+        ),
+        Err(e) => Check::warn(
+            "Token scopes",
+            format!("Could not check the token's scopes: {}", e),
+            "Check your network connection, and that the repository exists on GitHub.",
+        ),
+    }
+}
+
+/// Checks that Gemini accepts the configured API key, by listing the models available to it.
+async fn check_gemini(api_key: &str) -> Check {
+    match list_gemini_models(api_key).await {
+        Ok(models) => Check::pass(
+            "Gemini",
+            format!("Gemini accepted the API key, with {} models.", models.len()),
+        ),
+        Err(StError::AiRequestFailed(message)) => Check::fail(
+            "Gemini",
+            format!("Gemini rejected the API key: {}", message),
+            "Create a new key at https://aistudio.google.com/app/apikey, or unset `gemini_api_key`.",
+        ),
+        Err(e) => Check::warn(
+            "Gemini",
+            format!("Could not reach Gemini: {}", e),
+            "Check your network connection.",
+        ),
+    }
+}
+
+/// Checks that the store of the repository can be read, and that its tree is consistent.
+///
+/// ## Returns
+/// - `(Check, Option<StackTree>)` - The check, and the tree if the repository is set up.
+fn check_store(repository: &Repository) -> (Check, Option<StackTree>) {
+    match load_tree(repository) {
+        Ok(Some(tree)) => match tree.validate() {
+            Ok(()) => (Check::pass("Store", "The store is readable."), Some(tree)),
+            Err(e) => (
+                Check::fail(
+                    "Store",
+                    e.to_string(),
+                    "Rebuild the stacks with `st import`, or untrack the affected branches.",
+                ),
+                None,
+            ),
+        },
+        Ok(None) => (
+            Check::warn(
+                "Store",
+                "The repository is not set up with `st`.",
+                "Run any `st` command to select the trunk branch.",
+            ),
+            None,
+        ),
+        Err(e) => (
+            Check::fail(
+                "Store",
+                format!("Failed to read the store: {}", e),
+                "Delete `.git/.st_store.toml` and track the branches again with `st adopt`.",
+            ),
+            None,
+        ),
+    }
+}
+
+/// Checks that the active trunk of the tree exists as a local branch.
+pub(crate) fn check_trunk(repository: &Repository, tree: &StackTree) -> Check {
+    let trunk = tree.trunk_name();
+    if repository.find_branch(trunk, BranchType::Local).is_ok() {
+        Check::pass("Trunk branch", format!("`{}` exists locally.", trunk))
+    } else {
+        Check::fail(
+            "Trunk branch",
+            format!("`{}` does not exist locally.", trunk),
+            format!(
+                "Create it with `git branch {}`, or switch trunks with `st trunk switch <name>`.",
+                trunk
+            ),
+        )
+    }
+}
+
+/// Checks that every branch tracked on the active trunk still exists as a local branch.
+pub(crate) fn check_orphans(repository: &Repository, tree: &StackTree) -> Check {
+    match orphaned_branches(repository, tree) {
+        Ok(orphans) if orphans.is_empty() => {
+            Check::pass("Tracked branches", "Every tracked branch exists locally.")
+        }
+        Ok(orphans) => Check::warn(
+            "Tracked branches",
+            format!(
+                "{} tracked branch{} no longer exist{} locally: {}",
+                orphans.len(),
+                if orphans.len() != 1 { "es" } else { "" },
+                if orphans.len() != 1 { "" } else { "s" },
+                orphans
+                    .iter()
+                    .map(|b| format!("`{}`", b))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            "Untrack them with `st doctor --fix`, or restore them with `git branch <name> <commit>`.",
+        ),
+        Err(e) => Check::fail(
+            "Tracked branches",
+            e.to_string(),
+            "Rebuild the stacks with `st import`, or untrack the affected branches.",
+        ),
+    }
+}
+
+/// Checks for local branches that fork off a tracked branch, but aren't tracked themselves.
+pub(crate) fn check_untracked(repository: &Repository, tree: &StackTree) -> Check {
+    match untracked_branches(repository, tree) {
+        Ok(untracked) if untracked.is_empty() => Check::pass(
+            "Untracked branches",
+            "No untracked branch forks off a tracked branch.",
+        ),
+        Ok(untracked) => Check::warn(
+            "Untracked branches",
+            format!(
+                "{} untracked branch{} fork{} off tracked branches: {}",
+                untracked.len(),
+                if untracked.len() != 1 { "es" } else { "" },
+                if untracked.len() != 1 { "" } else { "s" },
+                untracked
+                    .iter()
+                    .map(|(branch, parent)| format!("`{}` (on `{}`)", branch, parent))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            "Track them with `st track <branch>`, or a chain of them with `st adopt <branch>`.",
+        ),
+        Err(e) => Check::warn(
+            "Untracked branches",
+            format!("Failed to list the local branches: {}", e),
+            "Check that the repository is readable with `git branch`.",
+        ),
+    }
+}
+
+/// Offers to untrack the orphaned branches of the active trunk, re-linking their children to
+/// their parents, and persists the store if confirmed.
+///
+/// ## Takes
+/// - `repository` - The repository the store belongs to.
+/// - `tree` - The [StackTree] tracking the branches.
+/// - `confirm` - Asks whether to untrack the given branches.
+///
+/// ## Returns
+/// - `Result<Check>` - The outcome of the repair, as a check.
+pub(crate) fn repair_orphans(
+    repository: &Repository,
+    tree: &mut StackTree,
+    confirm: impl FnOnce(&[String]) -> StResult<bool>,
+) -> StResult<Check> {
+    let orphans = orphaned_branches(repository, tree)?;
+    if orphans.is_empty() || !confirm(&orphans)? {
+        return Ok(check_orphans(repository, tree));
+    }
+    let untracked = untrack_orphans(repository, tree)?;
+    persist_tree(repository, tree)?;
+    Ok(Check::pass(
+        "Tracked branches",
+        format!(
+            "Untracked {}, and re-linked their children.",
+            untracked
+                .iter()
+                .map(|b| format!("`{}`", b))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    ))
+}
+
+/// Asks whether to untrack the given orphaned branches.
+fn confirm_untrack(orphans: &[String]) -> StResult<bool> {
+    let message = format!(
+        "Untrack {}, whose git branch{} no longer exist{}?",
+        orphans.join(", "),
+        if orphans.len() != 1 { "es" } else { "" },
+        if orphans.len() != 1 { "" } else { "s" },
+    );
+    Ok(Confirm::new(&message).with_default(true).prompt()?)
+}
+
+/// Checks that a remote is configured and reachable.
+fn check_remote(repository: &Repository, remote_name: &str) -> Check {
+    if repository.find_remote(remote_name).is_err() {
+        return Check::fail(
+            "Remote",
+            format!("Remote `{}` is not configured.", remote_name),
+            format!("Add it with `git remote add {} <url>`.", remote_name),
+        );
+    }
+    match repository.ls_remote(remote_name) {
+        Ok(()) => Check::pass("Remote", format!("`{}` is reachable.", remote_name)),
+        Err(e) => Check::warn(
+            "Remote",
+            format!("`{}` is not reachable.\n{}", remote_name, e),
+            "Check your network connection and git credentials.",
+        ),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{check_orphans, check_token, check_trunk, failures, repair_orphans, CheckStatus};
+    use crate::{
+        config::StConfig,
+        ctx::{load_tree, persist_tree},
+        git::test::TempRepository,
+        tree::StackTree,
+    };
+    use git2::BranchType;
+    use std::mem::ManuallyDrop;
+
+    #[test]
+    fn missing_token_and_trunk_are_failures() {
+        let repo = TempRepository::new();
+        let cfg = ManuallyDrop::new(StConfig::default());
+
+        let checks = vec![
+            check_token(cfg.validate_with(&[])),
+            check_trunk(&repo.repository, &StackTree::new("develop".to_string())),
+            check_trunk(&repo.repository, &StackTree::new("main".to_string())),
+        ];
+        let statuses = checks.iter().map(|c| c.status).collect::<Vec<_>>();
+        assert_eq!(
+            statuses,
+            vec![CheckStatus::Fail, CheckStatus::Fail, CheckStatus::Pass]
+        );
+        assert_eq!(failures(&checks), 2);
+        assert!(checks[0].hint.as_deref().unwrap().contains("github_token"));
+        assert!(checks[1].message.contains("develop"));
+    }
+
+    #[test]
+    fn orphaned_branches_are_flagged_and_untracked_on_confirmation() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main").to_string();
+        repo.branch("feature-1");
+        let head_1 = repo.commit("Feature 1").to_string();
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+        repo.checkout("main");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head, "feature-1").unwrap();
+        tree.insert("feature-1", &head_1, "feature-2").unwrap();
+        persist_tree(&repo.repository, &tree).unwrap();
+        assert_eq!(
+            check_orphans(&repo.repository, &tree).status,
+            CheckStatus::Pass
+        );
+
+        // Delete the middle branch behind `st`'s back.
+        repo.repository
+            .find_branch("feature-1", BranchType::Local)
+            .unwrap()
+            .delete()
+            .unwrap();
+        let check = check_orphans(&repo.repository, &tree);
+        assert_eq!(check.status, CheckStatus::Warn);
+        assert!(check.message.contains("`feature-1`"));
+
+        // Declining leaves the branch tracked.
+        let declined = repair_orphans(&repo.repository, &mut tree, |_| Ok(false)).unwrap();
+        assert_eq!(declined.status, CheckStatus::Warn);
+        assert!(tree.get("feature-1").is_some());
+
+        // Confirming untracks it, and stacks its child on its parent.
+        let repaired = repair_orphans(&repo.repository, &mut tree, |orphans| {
+            assert_eq!(orphans, ["feature-1"]);
+            Ok(true)
+        })
+        .unwrap();
+        assert_eq!(repaired.status, CheckStatus::Pass);
+        let stored = load_tree(&repo.repository).unwrap().unwrap();
+        assert!(stored.get("feature-1").is_none());
+        let child = stored.get("feature-2").unwrap();
+        assert_eq!(child.parent.as_deref(), Some("main"));
+        assert!(stored.get("main").unwrap().children.contains("feature-2"));
+        assert!(stored.validate().is_ok());
+    }
+}
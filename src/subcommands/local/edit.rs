@@ -0,0 +1,328 @@
+//! `edit` subcommand.
+
+use crate::{
+    ctx::StContext,
+    errors::{StError, StResult},
+    subcommands::remote::{editor_command, pr_description_editor},
+    theme::Color,
+    tree::StackTree,
+};
+use clap::Args;
+
+/// The instructions appended to the plan opened in the editor.
+const PLAN_HELP: &str = "
+# Edit the stack, listed from the branch closest to trunk upwards. Lines starting with `#` are
+# ignored, and an empty plan leaves the stack untouched.
+#
+# Actions:
+# p, pick <branch>          = stack the branch on the kept branch listed above it, or on trunk
+# o, onto <branch> <parent> = stack the branch on <parent>, which is trunk or a branch above it
+# d, drop <branch>          = delete the branch, and remove its commits from the branches above
+#
+# Reorder the lines to reorder the branches. Every branch must be listed exactly once.
+";
+
+/// CLI arguments for the `edit` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct EditCmd;
+
+impl EditCmd {
+    /// Run the `edit` subcommand.
+    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        let stack = ctx.discover_stack()?;
+        let trunk = ctx.tree.trunk_name().to_string();
+        let branches = &stack[1..];
+
+        let editor = editor_command(&ctx.cfg.editor);
+        let saved = pr_description_editor("Edit the stack", &editor)
+            .with_predefined_text(&render_plan(&ctx.tree, &trunk, branches))
+            .prompt()?;
+        let Some(plan) = parse_plan(&saved, &trunk, branches)? else {
+            println!("Empty plan. Left the stack untouched.");
+            return Ok(());
+        };
+
+        let num_rebased = ctx.edit_stack(&plan.parents, &plan.dropped)?;
+        println!(
+            "Edited the stack: rebased {} branch{}, dropped {}.",
+            Color::Green.paint(num_rebased.to_string()),
+            if num_rebased != 1 { "es" } else { "" },
+            Color::Red.paint(plan.dropped.len().to_string())
+        );
+        Ok(())
+    }
+}
+
+/// The new shape of a stack, parsed from the plan saved in the editor.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+struct StackPlan {
+    /// The new parent of each kept branch, with parents listed before children.
+    parents: Vec<(String, String)>,
+    /// The names of the branches to delete.
+    dropped: Vec<String>,
+}
+
+/// Renders the current shape of a stack as an editable plan. Branches stacked on the branch
+/// listed above them are picked, and any other branch is placed onto its parent.
+///
+/// ## Takes
+/// - `tree` - The [StackTree] containing the stack.
+/// - `trunk` - The name of the trunk branch.
+/// - `branches` - The branches of the stack above the trunk, parents before children.
+///
+/// ## Returns
+/// - `String` - The plan, followed by instructions.
+fn render_plan(tree: &StackTree, trunk: &str, branches: &[String]) -> String {
+    let mut plan = String::new();
+    let mut previous = trunk;
+    for branch in branches {
+        match tree.get(branch).and_then(|b| b.parent.as_deref()) {
+            Some(parent) if parent != previous => {
+                plan.push_str(&format!("onto {} {}\n", branch, parent));
+            }
+            _ => plan.push_str(&format!("pick {}\n", branch)),
+        }
+        previous = branch;
+    }
+    plan.push_str(PLAN_HELP);
+    plan
+}
+
+/// Parses the plan saved in the editor, checking that it lists every branch of the stack exactly
+/// once and that each branch is stacked on trunk or a kept branch listed above it. This rules out
+/// cycles, and keeps trunk at the root of the stack.
+///
+/// ## Takes
+/// - `text` - The saved plan.
+/// - `trunk` - The name of the trunk branch.
+/// - `branches` - The branches of the stack above the trunk.
+///
+/// ## Returns
+/// - `Result<Option<StackPlan>>` - The new shape of the stack, [None] if the plan is empty, or
+///   [StError::InvalidStackPlan] describing the first problem found.
+fn parse_plan(text: &str, trunk: &str, branches: &[String]) -> StResult<Option<StackPlan>> {
+    let invalid = |line: usize, reason: String| {
+        StError::InvalidStackPlan(format!("line {}: {}", line, reason))
+    };
+
+    let mut plan = StackPlan::default();
+    let mut listed = Vec::new();
+    let mut previous = trunk.to_string();
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        let (action, branch, parent) = match words.as_slice() {
+            [] => continue,
+            [first, ..] if first.starts_with('#') => continue,
+            ["p" | "pick", branch] => ("pick", *branch, previous.clone()),
+            ["o" | "onto", branch, parent] => ("onto", *branch, parent.to_string()),
+            ["d" | "drop", branch] => ("drop", *branch, String::new()),
+            _ => {
+                return Err(invalid(
+                    line_number,
+                    format!("can't parse `{}`", line.trim()),
+                ))
+            }
+        };
+
+        if branch == trunk {
+            return Err(invalid(
+                line_number,
+                "the trunk branch can't be edited".into(),
+            ));
+        }
+        if !branches.iter().any(|b| b == branch) {
+            return Err(invalid(
+                line_number,
+                format!("`{}` is not in the stack", branch),
+            ));
+        }
+        if listed.contains(&branch) {
+            return Err(invalid(
+                line_number,
+                format!("`{}` is listed more than once", branch),
+            ));
+        }
+        listed.push(branch);
+
+        if action == "drop" {
+            plan.dropped.push(branch.to_string());
+            continue;
+        }
+        let kept_above = plan.parents.iter().any(|(kept, _)| *kept == parent);
+        if parent != trunk && !kept_above {
+            return Err(invalid(
+                line_number,
+                format!(
+                    "`{}` must be stacked on trunk or a kept branch above it, not `{}`",
+                    branch, parent
+                ),
+            ));
+        }
+        plan.parents.push((branch.to_string(), parent));
+        previous = branch.to_string();
+    }
+
+    if listed.is_empty() {
+        return Ok(None);
+    }
+    if let Some(missing) = branches.iter().find(|b| !listed.contains(&b.as_str())) {
+        return Err(StError::InvalidStackPlan(format!(
+            "`{}` is missing. Use `drop` to delete it",
+            missing
+        )));
+    }
+    Ok(Some(plan))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{parse_plan, render_plan};
+    use crate::{
+        ctx::test::test_context, errors::StError, git::test::TempRepository, tree::StackTree,
+    };
+    use git2::{build::CheckoutBuilder, BranchType};
+
+    #[test]
+    fn reordering_plan_rebuilds_stack() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit_file("Main", "main.txt", "main");
+        let mut heads = vec![main_head];
+        for name in ["feature-1", "feature-2", "feature-3"] {
+            repo.branch(name);
+            heads.push(repo.commit_file(name, &format!("{}.txt", name), name));
+        }
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &heads[0].to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &heads[1].to_string(), "feature-2")
+            .unwrap();
+        tree.insert("feature-2", &heads[2].to_string(), "feature-3")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        let branches = ["feature-1", "feature-2", "feature-3"].map(String::from);
+        let rendered = render_plan(&ctx.tree, "main", &branches);
+        assert!(rendered.starts_with("pick feature-1\npick feature-2\npick feature-3\n"));
+
+        // Move `feature-3` to the bottom, and fork `feature-2` off of it.
+        let saved = "pick feature-3\n# A comment\n\np feature-1\nonto feature-2 feature-3\n";
+        let plan = parse_plan(saved, "main", &branches).unwrap().unwrap();
+        assert_eq!(ctx.edit_stack(&plan.parents, &plan.dropped).unwrap(), 3);
+
+        let parent = |branch: &str| ctx.tree.get(branch).unwrap().parent.clone().unwrap();
+        assert_eq!(parent("feature-3"), "main");
+        assert_eq!(parent("feature-1"), "feature-3");
+        assert_eq!(parent("feature-2"), "feature-3");
+        assert!(ctx.tree.validate().is_ok());
+        assert!(ctx.branches_needing_restack().unwrap().is_empty());
+
+        // Each branch carries only its own commit on top of its new parent.
+        let files = |branch: &str| {
+            let tree = repo
+                .repository
+                .find_branch(branch, BranchType::Local)
+                .unwrap()
+                .get()
+                .peel_to_tree()
+                .unwrap();
+            let mut files = tree
+                .iter()
+                .map(|entry| entry.name().unwrap().to_string())
+                .collect::<Vec<_>>();
+            files.sort();
+            files
+        };
+        assert_eq!(files("feature-3"), vec!["feature-3.txt", "main.txt"]);
+        assert_eq!(
+            files("feature-1"),
+            vec!["feature-1.txt", "feature-3.txt", "main.txt"]
+        );
+        assert_eq!(
+            files("feature-2"),
+            vec!["feature-2.txt", "feature-3.txt", "main.txt"]
+        );
+    }
+
+    #[test]
+    fn conflicting_plan_keeps_branches_rebased_so_far() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit_file("Main", "shared.txt", "main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit_file("Feature 1", "shared.txt", "feature 1");
+        repo.branch("feature-2");
+        let feature_2_head = repo.commit_file("Feature 2", "feature-2.txt", "feature 2");
+        repo.branch("feature-3");
+        repo.commit_file("Feature 3", "shared.txt", "feature 3");
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2")
+            .unwrap();
+        tree.insert("feature-2", &feature_2_head.to_string(), "feature-3")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        // Moving `feature-3` off of `feature-1` conflicts, as it edits the same file.
+        let branches = ["feature-1", "feature-2", "feature-3"].map(String::from);
+        let saved = "pick feature-2\npick feature-1\nonto feature-3 feature-2\n";
+        let plan = parse_plan(saved, "main", &branches).unwrap().unwrap();
+        assert!(matches!(
+            ctx.edit_stack(&plan.parents, &plan.dropped),
+            Err(StError::RestackConflict { branch, .. }) if branch == "feature-3"
+        ));
+
+        // The branches rebased before the conflict are stacked on their new parents.
+        let head = |branch: &str| {
+            let branch = repo.repository.find_branch(branch, BranchType::Local);
+            branch.unwrap().get().target().unwrap().to_string()
+        };
+        let feature_2 = ctx.tree.get("feature-2").unwrap();
+        assert_eq!(feature_2.parent.as_deref(), Some("main"));
+        assert_eq!(feature_2.parent_oid_cache, Some(head("main")));
+        let feature_1 = ctx.tree.get("feature-1").unwrap();
+        assert_eq!(feature_1.parent.as_deref(), Some("feature-2"));
+        assert_eq!(feature_1.parent_oid_cache, Some(head("feature-2")));
+        assert_eq!(
+            ctx.tree.get("feature-3").unwrap().parent.as_deref(),
+            Some("feature-1")
+        );
+    }
+
+    #[test]
+    fn invalid_plans_are_rejected() {
+        let branches = ["feature-1", "feature-2"].map(String::from);
+        let parse = |text: &str| parse_plan(text, "main", &branches);
+
+        assert!(parse("# Only comments\n").unwrap().is_none());
+        let dropped = parse("drop feature-1\npick feature-2\n").unwrap().unwrap();
+        assert_eq!(dropped.dropped, vec!["feature-1"]);
+        assert_eq!(
+            dropped.parents,
+            vec![("feature-2".to_string(), "main".to_string())]
+        );
+
+        for plan in [
+            "pick feature-1\n",
+            "pick feature-1\npick feature-1\npick feature-2\n",
+            "pick main\npick feature-1\npick feature-2\n",
+            "pick feature-1\npick feature-2\npick feature-3\n",
+            "onto feature-1 feature-2\npick feature-2\n",
+            "drop feature-1\nonto feature-2 feature-1\n",
+            "squash feature-1\npick feature-2\n",
+        ] {
+            assert!(
+                matches!(parse(plan), Err(StError::InvalidStackPlan(_))),
+                "{plan:?} must be rejected"
+            );
+        }
+    }
+}
@@ -0,0 +1,36 @@
+//! `export` subcommand.
+
+use crate::{ctx::StContext, errors::StResult, theme::Color};
+use clap::Args;
+use std::path::PathBuf;
+
+/// CLI arguments for the `export` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct ExportCmd {
+    /// The file to write the snapshot to. Defaults to stdout.
+    #[clap(long, short)]
+    file: Option<PathBuf>,
+}
+
+impl ExportCmd {
+    /// Run the `export` subcommand.
+    pub fn run(self, ctx: StContext<'_>) -> StResult<()> {
+        let export = ctx.export_stack()?;
+        let json = serde_json::to_string_pretty(&export)?;
+
+        match self.file {
+            Some(path) => {
+                std::fs::write(&path, json + "\n")?;
+                println!(
+                    "Exported {} branch{} on `{}` to `{}`.",
+                    Color::Green.paint(export.branches.len().to_string()),
+                    if export.branches.len() != 1 { "es" } else { "" },
+                    Color::Yellow.paint(&export.trunk),
+                    Color::Blue.paint(path.display().to_string())
+                );
+            }
+            None => println!("{}", json),
+        }
+        Ok(())
+    }
+}
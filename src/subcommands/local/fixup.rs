@@ -0,0 +1,371 @@
+//! `fixup` subcommand.
+
+use crate::{
+    ctx::StContext,
+    errors::{StError, StResult},
+    format::{commit_message, sign_off},
+    git::RepositoryExt,
+    theme::Color,
+};
+use clap::Args;
+use git2::{Commit, IndexAddOption, Oid, Repository, Status};
+
+/// CLI arguments for the `fixup` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct FixupCmd {
+    /// Also stage untracked files, rather than only changes to tracked files.
+    #[clap(short, long)]
+    all: bool,
+    /// Squash the changes into the last commit of the branch, rather than committing them as a
+    /// `fixup!` commit to be squashed later with `git rebase --autosquash`. The message of the
+    /// squashed commit follows the `commit_message_template` configuration value, if set.
+    #[clap(short, long)]
+    squash: bool,
+    /// Add a `Signed-off-by` trailer to the commit. Defaults to the `signoff` configuration value.
+    #[clap(long)]
+    signoff: bool,
+}
+
+impl FixupCmd {
+    /// Run the `fixup` subcommand.
+    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        self.fixup(&mut ctx)
+    }
+
+    /// Commits the working changes onto the current branch, and restacks its descendants.
+    fn fixup(&self, ctx: &mut StContext<'_>) -> StResult<()> {
+        let branch_name = ctx.current_branch()?;
+        if branch_name == ctx.tree.trunk_name() {
+            return Err(StError::CannotFixupTrunkBranch);
+        }
+        if ctx.tree.get(&branch_name).is_none() {
+            return Err(StError::BranchNotTracked(branch_name));
+        }
+        if self.squash && ctx.own_commits(&branch_name)?.is_empty() {
+            return Err(StError::NoCommitsToSquash(branch_name));
+        }
+
+        // Restacking the descendants checks them out, which requires the working tree to be clean
+        // once the changes are committed. Without `--all`, untracked files are left behind.
+        let left_behind = if self.all {
+            Status::empty()
+        } else {
+            ctx.repository.unstaged_changes()? & Status::WT_NEW
+        };
+        if !left_behind.is_empty() && !ctx.tree.descendants(&branch_name)?.is_empty() {
+            return Err(StError::WorkingTreeDirty);
+        }
+
+        let pr_number = ctx
+            .tree
+            .get(&branch_name)
+            .and_then(|b| b.remote.as_ref())
+            .map(|remote| remote.pr_number);
+        let signoff = self.signoff || ctx.cfg.signoff;
+        commit_fixup(
+            ctx.repository,
+            &branch_name,
+            self.all,
+            self.squash
+                .then_some((ctx.cfg.commit_message_template.as_str(), pr_number)),
+            signoff,
+        )?;
+        println!(
+            "{} the changes onto `{}`.",
+            if self.squash { "Squashed" } else { "Committed" },
+            Color::Green.paint(&branch_name)
+        );
+
+        // Restack the branches above, then return to the fixed up branch.
+        ctx.restack_descendants(&branch_name)?;
+        Ok(())
+    }
+}
+
+/// Stages the working changes and commits them onto `HEAD`, either as a `fixup!` commit of the
+/// last commit or squashed into it. Squashing gives the same result as committing a fixup and
+/// running `git rebase --autosquash`, without replaying any commits.
+///
+/// ## Takes
+/// - `repository` - The repository to commit in.
+/// - `branch_name` - The name of the checked out branch.
+/// - `all` - Whether to stage untracked files as well as changes to tracked files.
+/// - `squash` - If set, the last commit is amended rather than adding a `fixup!` commit, and its
+///   message rendered from the contained commit message template and pull request number.
+/// - `signoff` - Whether to add a `Signed-off-by` trailer to the commit message.
+///
+/// ## Returns
+/// - `Result<Oid>` - The new head of the branch, or [StError::NothingToFixup] if there are no
+///   changes to commit.
+fn commit_fixup(
+    repository: &Repository,
+    branch_name: &str,
+    all: bool,
+    squash: Option<(&str, Option<u64>)>,
+    signoff: bool,
+) -> StResult<Oid> {
+    let mut index = repository.index()?;
+    if all {
+        index.add_all(["*"], IndexAddOption::DEFAULT, None)?;
+    }
+    index.update_all(["*"], None)?;
+    index.write()?;
+
+    let tree = repository.find_tree(index.write_tree()?)?;
+    let head = repository.head()?.peel_to_commit()?;
+    if tree.id() == head.tree_id() {
+        return Err(StError::NothingToFixup(branch_name.to_string()));
+    }
+
+    let sig = repository.signature()?;
+    let message = match squash {
+        Some((template, pr_number)) => squash_message(&head, template, branch_name, pr_number),
+        None => format!("fixup! {}", head.summary().unwrap_or_default()),
+    };
+    let message = if signoff {
+        sign_off(
+            &message,
+            sig.name().unwrap_or_default(),
+            sig.email().unwrap_or_default(),
+        )
+    } else {
+        message
+    };
+
+    let oid = if squash.is_some() {
+        head.amend(Some("HEAD"), None, None, None, Some(&message), Some(&tree))?
+    } else {
+        repository.commit(Some("HEAD"), &sig, &sig, &message, &tree, &[&head])?
+    };
+    Ok(oid)
+}
+
+/// Returns the message of a commit that the working changes are squashed into, rendered from the
+/// `commit_message_template` configuration value with the commit's subject as `{subjects}`. The
+/// message is kept if no template is configured, or if its title already follows the template
+/// from an earlier squash.
+///
+/// ## Takes
+/// - `head` - The commit that the changes are squashed into.
+/// - `template` - The commit message template, or an empty string.
+/// - `branch_name` - The name of the branch, substituted for `{branch}`.
+/// - `pr_number` - The number of the branch's pull request, substituted for `{pr_number}`.
+///
+/// ## Returns
+/// - `String` - The message of the squashed commit.
+fn squash_message(
+    head: &Commit<'_>,
+    template: &str,
+    branch_name: &str,
+    pr_number: Option<u64>,
+) -> String {
+    let message = head.message().unwrap_or_default();
+    if template.trim().is_empty() {
+        return message.to_string();
+    }
+    let rendered = commit_message(template, branch_name, pr_number, &[message.to_string()]);
+    if rendered.lines().next() == head.summary() {
+        message.to_string()
+    } else {
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FixupCmd;
+    use crate::{
+        ctx::test::test_context,
+        errors::StError,
+        git::test::TempRepository,
+        tree::{RemoteMetadata, StackTree},
+    };
+    use git2::{build::CheckoutBuilder, BranchType};
+    use std::fs;
+
+    #[test]
+    fn fixup_on_middle_branch_restacks_descendants() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit_file("Feature 1", "feature-1.txt", "one");
+        repo.branch("feature-2");
+        let feature_2_head = repo.commit_file("Feature 2", "feature-2.txt", "two");
+        repo.branch("feature-3");
+        repo.commit_file("Feature 3", "feature-3.txt", "three");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2")
+            .unwrap();
+        tree.insert("feature-2", &feature_2_head.to_string(), "feature-3")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        // Edit `feature-1` in the working tree.
+        repo.checkout("feature-1");
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let workdir = repo.repository.workdir().unwrap();
+        fs::write(workdir.join("feature-1.txt"), "one, fixed").unwrap();
+
+        let cmd = FixupCmd {
+            all: false,
+            squash: false,
+            signoff: false,
+        };
+        cmd.fixup(&mut ctx).unwrap();
+
+        let head = |branch: &str| {
+            let branch = repo.repository.find_branch(branch, BranchType::Local);
+            branch.unwrap().get().peel_to_commit().unwrap()
+        };
+        assert_eq!(head("feature-1").summary(), Some("fixup! Feature 1"));
+        assert_eq!(
+            ctx.tree.get("feature-2").unwrap().parent_oid_cache,
+            Some(head("feature-1").id().to_string())
+        );
+        assert_eq!(
+            ctx.tree.get("feature-3").unwrap().parent_oid_cache,
+            Some(head("feature-2").id().to_string())
+        );
+        assert!(ctx.branches_needing_restack().unwrap().is_empty());
+
+        // The top of the stack carries the fix, and the fixed up branch is checked out again.
+        let fixed = head("feature-3").tree().unwrap();
+        let blob = fixed
+            .get_name("feature-1.txt")
+            .unwrap()
+            .to_object(&repo.repository);
+        assert_eq!(blob.unwrap().as_blob().unwrap().content(), b"one, fixed");
+        assert_eq!(ctx.current_branch().unwrap(), "feature-1");
+
+        // Nothing is left to commit.
+        assert!(matches!(
+            cmd.fixup(&mut ctx),
+            Err(StError::NothingToFixup(branch)) if branch == "feature-1"
+        ));
+    }
+
+    #[test]
+    fn untracked_files_left_behind_refuse_fixup_before_committing() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit_file("Feature 1", "feature-1.txt", "one");
+        repo.branch("feature-2");
+        repo.commit_file("Feature 2", "feature-2.txt", "two");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.insert("feature-1", &feature_1_head.to_string(), "feature-2")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        repo.checkout("feature-1");
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let workdir = repo.repository.workdir().unwrap();
+        fs::write(workdir.join("feature-1.txt"), "one, fixed").unwrap();
+        fs::write(workdir.join("notes.txt"), "untracked").unwrap();
+
+        // The untracked file would keep `feature-2` from being checked out to restack it, so
+        // nothing is committed.
+        let cmd = FixupCmd {
+            all: false,
+            squash: false,
+            signoff: false,
+        };
+        assert!(matches!(
+            cmd.fixup(&mut ctx),
+            Err(StError::WorkingTreeDirty)
+        ));
+        let head = repo.repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.id(), feature_1_head);
+
+        // With `--all`, the untracked file is committed too.
+        let cmd = FixupCmd { all: true, ..cmd };
+        cmd.fixup(&mut ctx).unwrap();
+        assert!(ctx.branches_needing_restack().unwrap().is_empty());
+        assert_eq!(ctx.current_branch().unwrap(), "feature-1");
+    }
+
+    #[test]
+    fn squashed_fixup_is_signed_off_once() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        repo.commit_file("Feature 1", "feature-1.txt", "one");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let workdir = repo.repository.workdir().unwrap().to_path_buf();
+
+        let cmd = FixupCmd {
+            all: false,
+            squash: true,
+            signoff: true,
+        };
+        for contents in ["one, fixed", "one, fixed again"] {
+            fs::write(workdir.join("feature-1.txt"), contents).unwrap();
+            cmd.fixup(&mut ctx).unwrap();
+        }
+
+        let head = repo.repository.head().unwrap().peel_to_commit().unwrap();
+        let message = head.message().unwrap();
+        assert!(message.ends_with("\n\nSigned-off-by: st <st@example.com>"));
+        assert_eq!(message.matches("Signed-off-by:").count(), 1);
+        assert_eq!(head.summary(), Some("Feature 1"));
+    }
+
+    #[test]
+    fn squashed_fixup_follows_commit_message_template() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        repo.commit_file("Feature 1", "feature-1.txt", "one");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", &main_head.to_string(), "feature-1")
+            .unwrap();
+        tree.get_mut("feature-1").unwrap().remote = Some(RemoteMetadata::new(42));
+        let mut ctx = test_context(&repo.repository, tree);
+        ctx.cfg.commit_message_template = "{branch} (#{pr_number})\n\n{subjects}".to_string();
+        repo.repository
+            .checkout_head(Some(CheckoutBuilder::new().force()))
+            .unwrap();
+        let workdir = repo.repository.workdir().unwrap().to_path_buf();
+
+        // Squashing again keeps the message, rather than rendering the template around it.
+        let cmd = FixupCmd {
+            all: false,
+            squash: true,
+            signoff: false,
+        };
+        for contents in ["one, fixed", "one, fixed again"] {
+            fs::write(workdir.join("feature-1.txt"), contents).unwrap();
+            cmd.fixup(&mut ctx).unwrap();
+            let head = repo.repository.head().unwrap().peel_to_commit().unwrap();
+            assert_eq!(head.message(), Some("feature-1 (#42)\n\nFeature 1"));
+        }
+
+        // `fixup!` commits keep the subject that `git rebase --autosquash` looks for.
+        fs::write(workdir.join("feature-1.txt"), "one, fixed once more").unwrap();
+        let cmd = FixupCmd {
+            squash: false,
+            ..cmd
+        };
+        cmd.fixup(&mut ctx).unwrap();
+        let head = repo.repository.head().unwrap().peel_to_commit().unwrap();
+        assert_eq!(head.summary(), Some("fixup! feature-1 (#42)"));
+    }
+}
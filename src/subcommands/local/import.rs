@@ -0,0 +1,42 @@
+//! `import` subcommand.
+
+use crate::{
+    ctx::{export::StackExport, StContext},
+    errors::StResult,
+    theme::Color,
+};
+use clap::Args;
+use std::{io::Read, path::PathBuf};
+
+/// CLI arguments for the `import` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct ImportCmd {
+    /// The file to read the snapshot from. Defaults to stdin.
+    #[clap(long, short)]
+    file: Option<PathBuf>,
+}
+
+impl ImportCmd {
+    /// Run the `import` subcommand.
+    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        let json = match self.file {
+            Some(path) => std::fs::read_to_string(path)?,
+            None => {
+                let mut json = String::new();
+                std::io::stdin().read_to_string(&mut json)?;
+                json
+            }
+        };
+        let export: StackExport = serde_json::from_str(&json)?;
+
+        let count = ctx.import_stack(&export)?;
+        println!(
+            "Imported {} branch{} onto `{}`. Run `{}` to restack them if needed.",
+            Color::Green.paint(count.to_string()),
+            if count != 1 { "es" } else { "" },
+            Color::Yellow.paint(&export.trunk),
+            Color::Blue.paint("st restack")
+        );
+        Ok(())
+    }
+}
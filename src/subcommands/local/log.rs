@@ -9,7 +9,12 @@ pub struct LogCmd;
 
 impl LogCmd {
     /// Run the `log` subcommand.
-    pub fn run(self, ctx: StContext<'_>) -> StResult<()> {
+    pub fn run(self, ctx: StContext<'_>, json: bool) -> StResult<()> {
+        if json {
+            println!("{}", serde_json::to_string_pretty(&ctx.stack_state()?)?);
+            return Ok(());
+        }
+
         ctx.print_tree()?;
         Ok(())
     }
@@ -15,9 +15,30 @@ pub use checkout::CheckoutCmd;
 mod restack;
 pub use restack::RestackCmd;
 
+mod reorder;
+pub use reorder::ReorderCmd;
+
+mod edit;
+pub use edit::EditCmd;
+
+mod split;
+pub use split::SplitCmd;
+
+mod fixup;
+pub use fixup::FixupCmd;
+
+mod amend;
+pub use amend::AmendCmd;
+
+mod describe;
+pub use describe::DescribeCmd;
+
 mod track;
 pub use track::TrackCmd;
 
+mod adopt;
+pub use adopt::AdoptCmd;
+
 mod untrack;
 pub use untrack::UntrackCmd;
 
@@ -26,3 +47,15 @@ pub use config::ConfigCmd;
 
 mod trunk;
 pub use trunk::TrunkCmd;
+
+mod completions;
+pub use completions::CompletionsCmd;
+
+mod doctor;
+pub use doctor::DoctorCmd;
+
+mod export;
+pub use export::ExportCmd;
+
+mod import;
+pub use import::ImportCmd;
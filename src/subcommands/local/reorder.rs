@@ -0,0 +1,36 @@
+//! `reorder` subcommand.
+
+use crate::{ctx::StContext, errors::StResult, git::RepositoryExt, theme::Color};
+use clap::Args;
+
+/// CLI arguments for the `reorder` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct ReorderCmd {
+    /// Name of the branch to swap with its parent. Defaults to the current branch.
+    #[clap(index = 1)]
+    branch_name: Option<String>,
+}
+
+impl ReorderCmd {
+    /// Run the `reorder` subcommand.
+    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        let branch_name = match self.branch_name {
+            Some(name) => name,
+            None => ctx.repository.current_branch_name()?,
+        };
+        let parent_name = ctx
+            .tree
+            .get(&branch_name)
+            .and_then(|b| b.parent.clone())
+            .unwrap_or_default();
+
+        ctx.reorder(&branch_name)?;
+
+        println!(
+            "Moved `{}` below `{}`.",
+            Color::Green.paint(&branch_name),
+            Color::Yellow.paint(&parent_name)
+        );
+        Ok(())
+    }
+}
@@ -1,15 +1,78 @@
 //! `restack` subcommand.
 
-use crate::{ctx::StContext, errors::StResult};
+use crate::{ctx::StContext, errors::StResult, git::RestackStrategy, theme::Color};
 use clap::Args;
 
 /// CLI arguments for the `restack` subcommand.
 #[derive(Debug, Clone, Eq, PartialEq, Args)]
-pub struct RestackCmd;
+pub struct RestackCmd {
+    /// How each branch is brought up to date with its parent.
+    #[clap(long, value_enum, default_value_t = RestackStrategy::Rebase)]
+    strategy: RestackStrategy,
+    /// Only restack the given branch and its descendants.
+    #[clap(long)]
+    only: Option<String>,
+    /// Only restack the descendants of the current branch, leaving the current branch and its
+    /// ancestors untouched.
+    #[clap(long, conflicts_with = "only")]
+    from_here: bool,
+    /// Restack the stacks on every trunk, not just the active one.
+    #[clap(long, conflicts_with_all = ["only", "from_here"])]
+    all_trunks: bool,
+    /// With `--all-trunks`, abort the restack of a trunk that stops on conflicts and carry on with
+    /// the remaining trunks, rather than stopping at the conflicts.
+    #[clap(long, requires = "all_trunks")]
+    keep_going: bool,
+    /// Move the current stack onto the given tracked branch, then restack the rest of the stack
+    /// on top of it.
+    #[clap(
+        long,
+        value_name = "BRANCH",
+        conflicts_with_all = ["strategy", "only", "from_here", "all_trunks"]
+    )]
+    onto: Option<String>,
+    /// Print the branches that would be rebased, and onto which parents, without touching git.
+    #[clap(long, conflicts_with_all = ["all_trunks", "onto"])]
+    dry_run: bool,
+}
 
 impl RestackCmd {
     /// Run the `restack` subcommand.
     pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
-        ctx.restack()
+        if self.dry_run {
+            return self.print_plan(&ctx);
+        }
+
+        match self.onto {
+            Some(onto) => ctx.restack_onto(&onto),
+            None if self.from_here => ctx.restack_from_here(self.strategy),
+            None if self.all_trunks => ctx.restack_all_trunks(self.strategy, self.keep_going),
+            None => ctx.restack_with(self.strategy, self.only.as_deref()),
+        }
+    }
+
+    /// Prints the branches that the restack would rebase, parents before children.
+    fn print_plan(&self, ctx: &StContext<'_>) -> StResult<()> {
+        let targets = if self.from_here {
+            ctx.tree.descendants(&ctx.current_branch()?)?
+        } else {
+            ctx.restack_targets(self.only.as_deref())?
+        };
+
+        let plan = ctx.restack_plan(&targets)?;
+        if plan.is_empty() {
+            println!("Nothing to restack.");
+            return Ok(());
+        }
+
+        println!("Would restack:");
+        for (branch, parent) in plan {
+            println!(
+                "  `{}` onto `{}`",
+                Color::Green.paint(branch),
+                Color::Yellow.paint(parent)
+            );
+        }
+        Ok(())
     }
 }
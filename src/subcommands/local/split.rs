@@ -0,0 +1,79 @@
+//! `split` subcommand.
+
+use crate::{
+    ctx::StContext,
+    errors::{StError, StResult},
+    git::{validate_branch_name, RepositoryExt},
+    theme::Color,
+};
+use clap::Args;
+
+/// CLI arguments for the `split` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct SplitCmd {
+    /// Name of the new branch to hold the lower half of the current branch.
+    #[clap(index = 1)]
+    branch_name: Option<String>,
+}
+
+impl SplitCmd {
+    /// Run the `split` subcommand.
+    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        if let Some(name) = &self.branch_name {
+            validate_branch_name(name)?;
+        }
+        let current_branch_name = ctx.repository.current_branch_name()?;
+        if ctx.tree.get(&current_branch_name).is_none() {
+            return Err(StError::BranchNotTracked(current_branch_name));
+        }
+
+        // The head of the branch always stays on the branch, so it is not offered as a split point.
+        let commits = ctx.own_commits(&current_branch_name)?;
+        let candidates = commits
+            .iter()
+            .skip(1)
+            .map(|oid| {
+                let commit = ctx.repository.find_commit(*oid)?;
+                Ok(format!(
+                    "{} {}",
+                    Color::Yellow.paint(&oid.to_string()[..7]),
+                    commit.summary().unwrap_or_default()
+                ))
+            })
+            .collect::<StResult<Vec<_>>>()?;
+        if candidates.is_empty() {
+            println!(
+                "Branch `{}` has fewer than two commits of its own. Nothing to split.",
+                Color::Green.paint(&current_branch_name)
+            );
+            return Ok(());
+        }
+
+        let selected = inquire::Select::new(
+            "Select the last commit to move to the new lower branch:",
+            candidates,
+        )
+        .raw_prompt()?;
+        let at = commits[selected.index + 1];
+
+        let new_branch_name = match self.branch_name {
+            Some(name) => name,
+            None => {
+                let name = inquire::Text::new("Name of new lower branch:").prompt()?;
+                validate_branch_name(&name)?;
+                name
+            }
+        };
+
+        ctx.split_branch(&current_branch_name, at, &new_branch_name)?;
+
+        println!(
+            "Split `{}` below `{}`, with {} commit{} moved to the new branch.",
+            Color::Blue.paint(&new_branch_name),
+            Color::Green.paint(&current_branch_name),
+            selected.index + 1,
+            if selected.index == 0 { "" } else { "s" }
+        );
+        Ok(())
+    }
+}
@@ -3,42 +3,76 @@
 use crate::{
     ctx::StContext,
     errors::{StError, StResult},
-    git::RepositoryExt,
+    git::{validate_branch_name, RepositoryExt},
+    theme::Color,
+    tree::StackTree,
 };
 use clap::Args;
-use nu_ansi_term::Color;
+use git2::{BranchType, Repository};
 
 /// CLI arguments for the `track` subcommand.
 #[derive(Debug, Clone, Eq, PartialEq, Args)]
-pub struct TrackCmd;
+pub struct TrackCmd {
+    /// Name of the branch to track. Defaults to the current branch.
+    #[clap(index = 1)]
+    branch_name: Option<String>,
+    /// The parent of the branch. Inferred from the merge-base against tracked branches if
+    /// omitted.
+    #[clap(long, short)]
+    parent: Option<String>,
+}
 
 impl TrackCmd {
     /// Run the `track` subcommand.
     pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
-        // Gather metadata about the current branch.
-        let current_branch = ctx.repository.current_branch()?;
-        let current_branch_name = ctx.repository.current_branch_name()?;
+        // Gather metadata about the branch to track.
+        let branch_name = match self.branch_name {
+            Some(name) => {
+                validate_branch_name(&name)?;
+                name
+            }
+            None => ctx.repository.current_branch_name()?,
+        };
 
-        // Ensure the current branch is not already tracked.
-        if ctx.tree.get(&current_branch_name).is_some() {
-            return Err(StError::BranchAlreadyTracked(current_branch_name));
+        // Ensure the branch is not already tracked.
+        if ctx.tree.get(&branch_name).is_some() {
+            return Err(StError::BranchAlreadyTracked(branch_name));
         }
 
-        // Prompt the user for the parent branch of the current branch.
-        let display_branches = ctx.display_branches()?;
-        let prompt = format!(
-            "Select the parent of `{}`",
-            Color::Blue.paint(&current_branch_name)
-        );
-        let parent_branch_name = inquire::Select::new(prompt.as_str(), display_branches)
-            .with_formatter(&|f| f.value.branch_name.clone())
-            .prompt()?;
-
-        // Insert the current branch into the stack tree.
-        ctx.tree.insert(
-            &parent_branch_name.branch_name,
-            &current_branch.get().peel_to_commit()?.id().to_string(),
-            &current_branch_name,
+        // Resolve the parent branch, inferring it from the tracked branches if not provided.
+        let parent_branch_name = match self.parent {
+            Some(parent) => parent,
+            None => match ctx
+                .repository
+                .closest_branch(&branch_name, &ctx.tree.branches()?)?
+            {
+                Some(parent) => {
+                    println!(
+                        "Inferred parent `{}` from the merge-base of `{}`.",
+                        Color::Yellow.paint(&parent),
+                        Color::Blue.paint(&branch_name)
+                    );
+                    parent
+                }
+                None => {
+                    // Prompt the user for the parent branch if no tracked branch shares history.
+                    let display_branches = ctx.display_branches()?;
+                    let prompt =
+                        format!("Select the parent of `{}`", Color::Blue.paint(&branch_name));
+                    inquire::Select::new(prompt.as_str(), display_branches)
+                        .with_formatter(&|f| f.value.branch_name.clone())
+                        .prompt()?
+                        .branch_name
+                }
+            },
+        };
+
+        // Insert the branch into the stack tree.
+        track_branch(
+            ctx.repository,
+            &mut ctx.tree,
+            &branch_name,
+            &parent_branch_name,
         )?;
 
         // Attempt to restack the current stack with the new addition.
@@ -46,9 +80,69 @@ impl TrackCmd {
 
         println!(
             "Tracked branch `{}` on top of `{}`",
-            Color::Green.paint(&current_branch_name),
-            Color::Yellow.paint(&parent_branch_name.branch_name)
+            Color::Green.paint(&branch_name),
+            Color::Yellow.paint(&parent_branch_name)
         );
         Ok(())
     }
 }
+
+/// Inserts an existing git branch into the [StackTree] on top of `parent_name`. The parent oid
+/// cache is set to the merge-base of the two branches, so that a branch based on an older parent
+/// commit is flagged for restacking.
+///
+/// ## Takes
+/// - `repository` - The repository containing both branches.
+/// - `tree` - The [StackTree] to insert the branch into.
+/// - `branch_name` - The name of the branch to track.
+/// - `parent_name` - The name of the tracked parent branch.
+fn track_branch(
+    repository: &Repository,
+    tree: &mut StackTree,
+    branch_name: &str,
+    parent_name: &str,
+) -> StResult<()> {
+    // Resolve both names as local branches, rather than arbitrary revisions.
+    repository.find_branch(branch_name, BranchType::Local)?;
+    repository.find_branch(parent_name, BranchType::Local)?;
+    let merge_base = RepositoryExt::merge_base(repository, branch_name, parent_name)?;
+
+    tree.insert(parent_name, &merge_base.to_string(), branch_name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::track_branch;
+    use crate::{git::test::TempRepository, git::RepositoryExt, tree::StackTree};
+
+    #[test]
+    fn inferred_parent_is_tracked_ancestor() {
+        let repo = TempRepository::new();
+        repo.branch("feature-1");
+        let feature_1_head = repo.commit("Feature 1");
+        repo.branch("untracked");
+        repo.commit("Untracked");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "unused", "feature-1").unwrap();
+
+        let parent = repo
+            .repository
+            .closest_branch("untracked", &tree.branches().unwrap())
+            .unwrap()
+            .unwrap();
+        track_branch(&repo.repository, &mut tree, "untracked", &parent).unwrap();
+
+        let tracked = tree.get("untracked").unwrap();
+        assert_eq!(tracked.parent.as_deref(), Some("feature-1"));
+        assert_eq!(
+            tracked.parent_oid_cache.as_deref(),
+            Some(feature_1_head.to_string().as_str())
+        );
+        assert!(tree
+            .get("feature-1")
+            .unwrap()
+            .children
+            .contains("untracked"));
+    }
+}
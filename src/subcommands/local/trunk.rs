@@ -1,8 +1,13 @@
 //! `trunk` subcommand.
 
-use crate::{ctx::StContext, errors::StResult};
+use crate::{
+    ctx::StContext,
+    errors::{StError, StResult},
+    git::validate_branch_name,
+    theme::Color,
+    tree::TrunkSettings,
+};
 use clap::{Args, Subcommand};
-use nu_ansi_term::Color;
 
 /// CLI arguments for the `trunk` subcommand.
 #[derive(Debug, Clone, Eq, PartialEq, Args)]
@@ -16,11 +21,19 @@ pub enum TrunkSubcommand {
     /// List all trunk branches
     #[clap(visible_alias = "ls")]
     List,
+    /// Show the number of branches, pull requests, and branches needing restack of a trunk
+    Info {
+        /// Name of the trunk. Defaults to the active trunk
+        trunk_name: Option<String>,
+    },
     /// Switch to a different trunk
     #[clap(visible_alias = "sw")]
     Switch {
         /// Name of the trunk to switch to
         trunk_name: String,
+        /// Add the trunk without asking if it is not tracked, but exists as a local branch
+        #[clap(long, short)]
+        create: bool,
     },
     /// Add a new trunk branch
     Add {
@@ -33,16 +46,68 @@ pub enum TrunkSubcommand {
         /// Name of the trunk to remove
         trunk_name: String,
     },
+    /// Set the pull request defaults of a trunk, overriding the global configuration
+    Set {
+        /// Name of the trunk to configure
+        trunk_name: String,
+        /// Whether new pull requests on the trunk default to drafts
+        #[clap(long)]
+        draft: Option<bool>,
+        /// Labels added to each pull request submitted on the trunk
+        #[clap(long = "label", num_args = 1..)]
+        labels: Vec<String>,
+        /// Reset the trunk to the global defaults
+        #[clap(long, conflicts_with_all = ["draft", "labels"])]
+        reset: bool,
+    },
+    /// Rename a trunk branch, keeping its tracked branches
+    #[clap(visible_alias = "mv")]
+    Rename {
+        /// Current name of the trunk
+        old: String,
+        /// New name of the trunk. A local branch by this name must exist
+        new: String,
+    },
 }
 
 impl TrunkCmd {
+    /// Returns whether or not the subcommand leaves the store unmodified.
+    pub fn is_read_only(&self) -> bool {
+        matches!(
+            self.command,
+            TrunkSubcommand::List | TrunkSubcommand::Info { .. }
+        )
+    }
+
+    /// Returns whether or not the subcommand operates on the active trunk, which the global
+    /// `--trunk` option may override.
+    pub fn supports_trunk_override(&self) -> bool {
+        matches!(self.command, TrunkSubcommand::Info { .. })
+    }
+
     /// Run the `trunk` subcommand.
-    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+    pub fn run(self, mut ctx: StContext<'_>, json: bool) -> StResult<()> {
         match &self.command {
+            TrunkSubcommand::List if json => {
+                println!("{}", serde_json::to_string_pretty(&ctx.trunk_list())?);
+                Ok(())
+            }
             TrunkSubcommand::List => self.list(&ctx),
-            TrunkSubcommand::Switch { trunk_name } => self.switch(&mut ctx, trunk_name),
+            TrunkSubcommand::Info { trunk_name } => {
+                self.info(&mut ctx, trunk_name.as_deref(), json)
+            }
+            TrunkSubcommand::Switch { trunk_name, create } => {
+                self.switch(&mut ctx, trunk_name, *create)
+            }
             TrunkSubcommand::Add { trunk_name } => self.add(&mut ctx, trunk_name),
             TrunkSubcommand::Remove { trunk_name } => self.remove(&mut ctx, trunk_name),
+            TrunkSubcommand::Set {
+                trunk_name,
+                draft,
+                labels,
+                reset,
+            } => self.set(&mut ctx, trunk_name, *draft, labels, *reset),
+            TrunkSubcommand::Rename { old, new } => self.rename(&mut ctx, old, new),
         }
     }
 
@@ -66,7 +131,64 @@ impl TrunkCmd {
         Ok(())
     }
 
-    fn switch(&self, ctx: &mut StContext<'_>, trunk_name: &str) -> StResult<()> {
+    fn info(&self, ctx: &mut StContext<'_>, trunk_name: Option<&str>, json: bool) -> StResult<()> {
+        if let Some(trunk_name) = trunk_name {
+            ctx.override_trunk(trunk_name)?;
+        }
+        let info = ctx.trunk_info()?;
+        if json {
+            println!("{}", serde_json::to_string_pretty(&info)?);
+            return Ok(());
+        }
+
+        println!("Trunk `{}`:", Color::Green.paint(&info.trunk));
+        println!("  Tracked branches:        {}", info.branches);
+        println!("  Submitted pull requests: {}", info.pull_requests);
+        println!("  Needing restack:         {}", info.needs_restack);
+        println!("  Deepest stack:           {}", info.max_depth);
+        Ok(())
+    }
+
+    fn switch(&self, ctx: &mut StContext<'_>, trunk_name: &str, create: bool) -> StResult<()> {
+        let branch_exists = ctx
+            .repository
+            .find_branch(trunk_name, git2::BranchType::Local)
+            .is_ok();
+
+        // Resolve a prefix or differently-cased name of a tracked trunk, unless the name exactly
+        // matches a local branch.
+        let resolved = if branch_exists {
+            None
+        } else {
+            resolve_trunk_name(&ctx.tree.list_trunks(), trunk_name, |candidates| {
+                let message = format!(
+                    "Multiple trunks match `{}`. Switch to:",
+                    Color::Yellow.paint(trunk_name)
+                );
+                Ok(inquire::Select::new(&message, candidates).prompt()?)
+            })?
+        };
+        let trunk_name = resolved.as_deref().unwrap_or(trunk_name);
+
+        // Offer to add the trunk if it is not tracked yet, but the branch exists.
+        if !ctx.tree.trunks.contains_key(trunk_name) && branch_exists {
+            let add = create
+                || inquire::Confirm::new(
+                    format!(
+                        "Trunk `{}` is not tracked. Add it as a trunk?",
+                        Color::Yellow.paint(trunk_name)
+                    )
+                    .as_str(),
+                )
+                .with_default(true)
+                .prompt()?;
+            if !add {
+                return Err(StError::TrunkNotFound(trunk_name.to_string()));
+            }
+            ctx.tree.add_trunk(trunk_name.to_string());
+            println!("Added trunk `{}`", Color::Green.paint(trunk_name));
+        }
+
         ctx.tree.switch_trunk(trunk_name)?;
         println!(
             "Switched to trunk `{}`",
@@ -119,4 +241,155 @@ impl TrunkCmd {
         );
         Ok(())
     }
+
+    fn set(
+        &self,
+        ctx: &mut StContext<'_>,
+        trunk_name: &str,
+        draft: Option<bool>,
+        labels: &[String],
+        reset: bool,
+    ) -> StResult<()> {
+        let settings = ctx.tree.trunk_settings_mut(trunk_name)?;
+        if reset {
+            *settings = TrunkSettings::default();
+        }
+        if draft.is_some() {
+            settings.default_draft = draft;
+        }
+        if !labels.is_empty() {
+            settings.default_labels = labels.to_vec();
+        }
+        println!(
+            "Updated pull request defaults of trunk `{}`",
+            Color::Green.paint(trunk_name)
+        );
+        Ok(())
+    }
+
+    fn rename(&self, ctx: &mut StContext<'_>, old: &str, new: &str) -> StResult<()> {
+        // The trunk must name a local branch, e.g. after `git branch -m`.
+        validate_branch_name(new)?;
+        if ctx
+            .repository
+            .find_branch(new, git2::BranchType::Local)
+            .is_err()
+        {
+            return Err(StError::BranchNotFound(new.to_string()));
+        }
+
+        ctx.tree.rename_trunk(old, new)?;
+        println!(
+            "Renamed trunk `{}` to `{}`",
+            Color::Yellow.paint(old),
+            Color::Green.paint(new)
+        );
+        Ok(())
+    }
+}
+
+/// Resolves the name passed to `st trunk switch` to a tracked trunk. An exact match always wins.
+/// Otherwise, trunks matching the name case-insensitively are preferred over trunks starting with
+/// it, and `select` picks one if several match.
+///
+/// ## Takes
+/// - `trunks` - The names of the tracked trunks.
+/// - `name` - The name passed by the user.
+/// - `select` - Picks one of several matching trunks.
+///
+/// ## Returns
+/// - `Result<Option<String>>` - The name of the matching trunk, or [None] if no trunk matches.
+fn resolve_trunk_name(
+    trunks: &[String],
+    name: &str,
+    select: impl FnOnce(Vec<String>) -> StResult<String>,
+) -> StResult<Option<String>> {
+    if trunks.iter().any(|trunk| trunk == name) {
+        return Ok(Some(name.to_string()));
+    }
+
+    let name = name.to_lowercase();
+    let mut candidates = trunks
+        .iter()
+        .filter(|trunk| trunk.to_lowercase() == name)
+        .cloned()
+        .collect::<Vec<_>>();
+    if candidates.is_empty() {
+        candidates = trunks
+            .iter()
+            .filter(|trunk| trunk.to_lowercase().starts_with(&name))
+            .cloned()
+            .collect();
+    }
+    candidates.sort();
+
+    match candidates.len() {
+        0 => Ok(None),
+        1 => Ok(candidates.pop()),
+        _ => select(candidates).map(Some),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{resolve_trunk_name, TrunkCmd, TrunkSubcommand};
+    use crate::{
+        ctx::test::test_context, errors::StResult, git::test::TempRepository, tree::StackTree,
+    };
+
+    #[test]
+    fn switch_with_create_registers_existing_branch() {
+        let repo = TempRepository::new();
+        repo.branch("dev");
+
+        let mut ctx = test_context(&repo.repository, StackTree::new("main".to_string()));
+        let cmd = TrunkCmd {
+            command: TrunkSubcommand::Switch {
+                trunk_name: "dev".to_string(),
+                create: true,
+            },
+        };
+        cmd.switch(&mut ctx, "dev", true).unwrap();
+
+        assert_eq!(ctx.tree.trunk_name(), "dev");
+        assert!(ctx.tree.list_trunks().contains(&"dev".to_string()));
+        assert!(ctx.tree.get("dev").is_some());
+
+        // Branches that do not exist are still rejected.
+        assert!(cmd.switch(&mut ctx, "missing", true).is_err());
+        assert_eq!(ctx.tree.trunk_name(), "dev");
+    }
+
+    #[test]
+    fn trunk_names_resolve_by_prefix_and_case() {
+        let trunks = [
+            "main",
+            "maintenance",
+            "release/1.0",
+            "release/1.1",
+            "Develop",
+        ]
+        .map(String::from);
+        let never = |_: Vec<String>| -> StResult<String> { panic!("Must not prompt") };
+        let resolve = |name| resolve_trunk_name(&trunks, name, never).unwrap();
+
+        // Exact matches win over longer trunks sharing the prefix.
+        assert_eq!(resolve("main").as_deref(), Some("main"));
+        // Unique prefixes and case-insensitive names resolve to the full trunk name.
+        assert_eq!(resolve("Release/1.1").as_deref(), Some("release/1.1"));
+        assert_eq!(resolve("maint").as_deref(), Some("maintenance"));
+        assert_eq!(resolve("dev").as_deref(), Some("Develop"));
+        assert_eq!(resolve("DEVELOP").as_deref(), Some("Develop"));
+        assert_eq!(resolve("hotfix"), None);
+
+        // Ambiguous prefixes ask which trunk to switch to.
+        let mut offered = vec![];
+        let selected = resolve_trunk_name(&trunks, "rel", |candidates| {
+            offered = candidates;
+            Ok(offered[1].clone())
+        })
+        .unwrap();
+        assert_eq!(offered, ["release/1.0", "release/1.1"]);
+        assert_eq!(selected.as_deref(), Some("release/1.1"));
+    }
 }
@@ -1,40 +1,114 @@
 //! `untrack` subcommand.
 
-use crate::{ctx::StContext, errors::StResult};
+use crate::{
+    ctx::StContext,
+    errors::{StError, StResult},
+    subcommands::remote::remove_stack_comment,
+    theme::Color,
+    tree::{StackTree, TrackedBranch},
+};
 use clap::Args;
-use nu_ansi_term::Color;
 
 /// CLI arguments for the `untrack` subcommand.
 #[derive(Debug, Clone, Eq, PartialEq, Args)]
 pub struct UntrackCmd {
-    /// Name of the new branch untrack.
+    /// Name of the branch to untrack.
     #[clap(index = 1)]
     branch_name: Option<String>,
 }
 
 impl UntrackCmd {
     /// Run the `untrack` subcommand.
-    pub fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+    pub async fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
         // Gather the display branches.
         let display_branches = ctx.display_branches()?;
 
-        // Prompt the user for the name of the branch to delete, or use the provided name.
+        // Prompt the user for the name of the branch to untrack, or use the provided name.
         let branch_name = match self.branch_name {
             Some(name) => name,
             None => {
-                inquire::Select::new("Select a branch to delete", display_branches)
+                inquire::Select::new("Select a branch to untrack", display_branches)
                     .with_formatter(&|f| f.value.branch_name.clone())
                     .prompt()?
                     .branch_name
             }
         };
 
-        ctx.tree.delete(&branch_name)?;
+        // Remove the branch from the tree, leaving the git branch and any PR untouched, apart
+        // from its stack comment.
+        let untracked = untrack_branch(&mut ctx.tree, &branch_name)?;
+        remove_stack_comment(&ctx, untracked.remote.as_ref()).await;
 
         println!(
             "Successfully untracked branch `{}`.",
             Color::Blue.paint(&branch_name)
         );
+        if !untracked.children.is_empty() {
+            println!(
+                "Its children now sit on top of `{}`. Run `{}` to re-stack them.",
+                Color::Yellow.paint(untracked.parent.unwrap_or_default()),
+                Color::Blue.paint("st restack")
+            );
+        }
         Ok(())
     }
 }
+
+/// Removes a branch from the [StackTree], re-linking its children onto its parent.
+///
+/// ## Returns
+/// - `Result<TrackedBranch>` - The untracked branch, or an error if the branch is a trunk branch
+///   or is not tracked.
+fn untrack_branch(tree: &mut StackTree, branch_name: &str) -> StResult<TrackedBranch> {
+    let tracked_branch = tree
+        .get(branch_name)
+        .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?;
+    if tracked_branch.parent.is_none() {
+        return Err(StError::CannotUntrackTrunkBranch);
+    }
+
+    tree.delete(branch_name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::untrack_branch;
+    use crate::{errors::StError, git::test::TempRepository, tree::StackTree};
+    use git2::BranchType;
+
+    #[test]
+    fn untrack_keeps_git_branch_and_relinks_children() {
+        let repo = TempRepository::new();
+        repo.branch("feature-1");
+        repo.commit("Feature 1");
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+
+        untrack_branch(&mut tree, "feature-1").unwrap();
+
+        assert!(repo
+            .repository
+            .find_branch("feature-1", BranchType::Local)
+            .is_ok());
+        assert!(tree.get("feature-1").is_none());
+        assert_eq!(
+            tree.get("feature-2").unwrap().parent.as_deref(),
+            Some("main")
+        );
+        assert!(tree.get("main").unwrap().children.contains("feature-2"));
+    }
+
+    #[test]
+    fn untrack_refuses_trunk() {
+        let mut tree = StackTree::new("main".to_string());
+        assert!(matches!(
+            untrack_branch(&mut tree, "main"),
+            Err(StError::CannotUntrackTrunkBranch)
+        ));
+        assert!(tree.get("main").is_some());
+    }
+}
@@ -5,12 +5,13 @@ use clap::Subcommand;
 
 mod local;
 use local::{
-    CheckoutCmd, ConfigCmd, CreateCmd, DeleteCmd, LogCmd, RestackCmd, TrackCmd, TrunkCmd,
-    UntrackCmd,
+    AdoptCmd, AmendCmd, CheckoutCmd, CompletionsCmd, ConfigCmd, CreateCmd, DeleteCmd, DescribeCmd,
+    DoctorCmd, EditCmd, ExportCmd, FixupCmd, ImportCmd, LogCmd, ReorderCmd, RestackCmd, SplitCmd,
+    TrackCmd, TrunkCmd, UntrackCmd,
 };
 
 mod remote;
-use remote::{StatusCmd, SubmitCmd, SyncCmd};
+use remote::{MergeCmd, OpenCmd, PrCmd, PruneCmd, StatusCmd, SubmitCmd, SyncCmd};
 
 #[derive(Debug, Clone, Eq, PartialEq, Subcommand)]
 pub enum Subcommands {
@@ -20,11 +21,22 @@ pub enum Subcommands {
     /// Submit the current PR stack to GitHub.
     #[clap(visible_aliases = ["s", "ss"])]
     Submit(SubmitCmd),
+    /// Merge the approved pull requests in the current stack, from the bottom up.
+    #[clap(visible_alias = "m")]
+    Merge(MergeCmd),
+    /// Delete and untrack the branches of merged and closed pull requests.
+    #[clap(visible_alias = "p")]
+    Prune(PruneCmd),
+    /// Toggle the draft state of a branch's pull request.
+    Pr(PrCmd),
+    /// Open the pull request of a tracked branch in the browser.
+    #[clap(visible_alias = "o")]
+    Open(OpenCmd),
     /// Checkout a branch that is tracked with `st`.
     #[clap(visible_alias = "co")]
     Checkout(CheckoutCmd),
     /// Create and track a new branch within the current stack.
-    #[clap(visible_alias = "c")]
+    #[clap(visible_aliases = ["c", "branch"])]
     Create(CreateCmd),
     /// Delete a branch that is tracked with `st`.
     #[clap(visible_aliases = ["d", "del"])]
@@ -32,6 +44,24 @@ pub enum Subcommands {
     /// Restack the the current stack.
     #[clap(visible_aliases = ["r", "sr"])]
     Restack(RestackCmd),
+    /// Swap a branch with its parent in the stack.
+    #[clap(visible_alias = "ro")]
+    Reorder(ReorderCmd),
+    /// Reorder, re-parent, and drop the branches of the current stack in an editor.
+    #[clap(visible_alias = "e")]
+    Edit(EditCmd),
+    /// Split the current branch into two stacked branches.
+    #[clap(visible_alias = "sp")]
+    Split(SplitCmd),
+    /// Commit the working changes onto the current branch, and restack the branches above it.
+    #[clap(visible_alias = "fx")]
+    Fixup(FixupCmd),
+    /// Amend the last commit of the current branch, and restack the branches above it.
+    #[clap(visible_alias = "am")]
+    Amend(AmendCmd),
+    /// Set the shared description of the current stack, placed at the top of each PR.
+    #[clap(visible_alias = "desc")]
+    Describe(DescribeCmd),
     /// Print a tree of all tracked stacks.
     #[clap(visible_aliases = ["l", "ls"])]
     Log(LogCmd),
@@ -41,6 +71,8 @@ pub enum Subcommands {
     /// Track the current branch on top of a tracked stack node.
     #[clap(visible_alias = "tr")]
     Track(TrackCmd),
+    /// Track an existing chain of branches, from the passed branch down to a tracked branch.
+    Adopt(AdoptCmd),
     /// Untrack the passed branch.
     #[clap(visible_alias = "ut")]
     Untrack(UntrackCmd),
@@ -50,26 +82,76 @@ pub enum Subcommands {
     /// Manage trunk branches (list, switch, add, remove).
     #[clap(visible_alias = "t")]
     Trunk(TrunkCmd),
+    /// Export the stacks on the active trunk to a portable JSON snapshot.
+    Export(ExportCmd),
+    /// Import stacks from a JSON snapshot written by `st export`.
+    Import(ImportCmd),
+    /// Generate shell completion scripts.
+    Completions(CompletionsCmd),
+    /// Diagnose common setup problems, such as a missing token or trunk branch.
+    Doctor(DoctorCmd),
 }
 
 impl Subcommands {
-    /// Run the subcommand with the given store.
-    pub async fn run(self, ctx: StContext<'_>) -> StResult<()> {
+    /// Returns whether or not the subcommand leaves the store unmodified, and may run without
+    /// holding the store lock. The store is then never persisted.
+    pub fn is_read_only(&self) -> bool {
+        match self {
+            Self::Log(_)
+            | Self::Config(_)
+            | Self::Export(_)
+            | Self::Completions(_)
+            | Self::Doctor(_)
+            | Self::Open(_) => true,
+            Self::Trunk(args) => args.is_read_only(),
+            _ => false,
+        }
+    }
+
+    /// Returns whether or not the subcommand operates on a single trunk, and so may operate on a
+    /// non-active trunk passed with the global `--trunk` option.
+    pub fn supports_trunk_override(&self) -> bool {
+        match self {
+            Self::Log(_) | Self::Export(_) | Self::Open(_) | Self::Status(_) | Self::Submit(_) => {
+                true
+            }
+            Self::Trunk(args) => args.supports_trunk_override(),
+            _ => false,
+        }
+    }
+
+    /// Run the subcommand with the given store. Read-only commands emit JSON if `json` is set.
+    pub async fn run(self, ctx: StContext<'_>, json: bool) -> StResult<()> {
         match self {
             // Remote
             Self::Sync(args) => args.run(ctx).await,
             Self::Submit(args) => args.run(ctx).await,
-            Self::Status(args) => args.run(ctx).await,
+            Self::Status(args) => args.run(ctx, json).await,
+            Self::Merge(args) => args.run(ctx).await,
+            Self::Prune(args) => args.run(ctx).await,
+            Self::Pr(args) => args.run(ctx).await,
+            Self::Open(args) => args.run(ctx),
             // Local
             Self::Checkout(args) => args.run(ctx),
             Self::Create(args) => args.run(ctx),
-            Self::Delete(args) => args.run(ctx),
+            Self::Delete(args) => args.run(ctx).await,
             Self::Restack(args) => args.run(ctx),
-            Self::Log(args) => args.run(ctx),
+            Self::Reorder(args) => args.run(ctx),
+            Self::Edit(args) => args.run(ctx),
+            Self::Split(args) => args.run(ctx),
+            Self::Fixup(args) => args.run(ctx),
+            Self::Amend(args) => args.run(ctx),
+            Self::Describe(args) => args.run(ctx),
+            Self::Log(args) => args.run(ctx, json),
             Self::Track(args) => args.run(ctx),
-            Self::Untrack(args) => args.run(ctx),
+            Self::Adopt(args) => args.run(ctx),
+            Self::Untrack(args) => args.run(ctx).await,
             Self::Config(args) => args.run(ctx),
-            Self::Trunk(args) => args.run(ctx),
+            Self::Trunk(args) => args.run(ctx, json),
+            Self::Export(args) => args.run(ctx),
+            Self::Import(args) => args.run(ctx),
+            Self::Completions(args) => args.run(),
+            Self::Doctor(args) => args.run().await,
         }
     }
 }
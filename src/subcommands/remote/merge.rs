@@ -0,0 +1,430 @@
+//! `merge` subcommand.
+
+use crate::{
+    ctx::StContext,
+    errors::{BranchContext, BranchPhase, StError, StResult},
+    format::commit_message,
+    git::{CommitLogOptions, RepositoryExt},
+    github::{
+        fetch_review_decision, github_client, verify_token_access, MergeMethod, ReviewDecision,
+    },
+    output::progress,
+    theme::Color,
+    tree::StackTree,
+};
+use clap::Args;
+use octocrab::{
+    models::{pulls::MergeableState, IssueState},
+    pulls::PullRequestHandler,
+};
+use std::{collections::HashMap, time::Duration};
+
+/// The number of times to poll GitHub while it computes the mergeability of a pull request.
+const MERGEABILITY_POLL_ATTEMPTS: usize = 5;
+
+/// The delay between mergeability polls.
+const MERGEABILITY_POLL_DELAY: Duration = Duration::from_secs(2);
+
+/// CLI arguments for the `merge` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct MergeCmd {
+    /// The method used to merge each pull request.
+    #[clap(long, value_enum, default_value = "squash")]
+    method: MergeMethod,
+}
+
+impl MergeCmd {
+    /// Run the `merge` subcommand.
+    pub async fn run(self, ctx: StContext<'_>) -> StResult<()> {
+        // Establish the GitHub API client.
+        let gh_client = github_client(&ctx.cfg)?;
+        let (owner, repo) = ctx.owner_and_repository()?;
+        let pulls = gh_client.pulls(&owner, &repo);
+
+        // Resolve the pull requests to merge, from the bottom of the stack upwards.
+        let stack = ctx.discover_stack()?;
+        let queue = merge_queue(&ctx.tree, &stack)?;
+        if queue.is_empty() {
+            progress!("No submitted pull requests in the current stack.");
+            return Ok(());
+        }
+
+        // Fail before anything is merged if the token can't write pull requests.
+        verify_token_access(&gh_client, &owner, &repo).await?;
+
+        progress!(
+            "🔀 Merging stack into `{}`...",
+            Color::Yellow.paint(ctx.tree.trunk_name())
+        );
+        let messages = self.commit_messages(&ctx, &queue)?;
+        let num_merged = merge_stack(
+            &pulls,
+            &queue,
+            ctx.tree.trunk_name(),
+            self.method,
+            &messages,
+        )
+        .await?;
+
+        if num_merged > 0 {
+            println!(
+                "\nMerged {} pull request{}. Run `{}` to clean up the merged branches.",
+                Color::Green.paint(num_merged.to_string()),
+                if num_merged != 1 { "s" } else { "" },
+                Color::Blue.paint("st sync")
+            );
+        }
+        Ok(())
+    }
+
+    /// Renders the commit message of each squash merge from the `commit_message_template`
+    /// configuration value. GitHub's default message is kept for other merge methods, or if no
+    /// template is configured.
+    ///
+    /// ## Returns
+    /// - `Result<HashMap<u64, String>>` - The commit message of each pull request in the queue.
+    fn commit_messages(
+        &self,
+        ctx: &StContext<'_>,
+        queue: &[(String, u64)],
+    ) -> StResult<HashMap<u64, String>> {
+        let template = &ctx.cfg.commit_message_template;
+        if self.method != MergeMethod::Squash || template.trim().is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let options = CommitLogOptions {
+            max_commits: None,
+            subjects_only: true,
+        };
+        let mut messages = HashMap::new();
+        for (branch, pr_number) in queue {
+            let parent = ctx
+                .tree
+                .get(branch)
+                .and_then(|b| b.parent.clone())
+                .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?;
+            let subjects = ctx
+                .repository
+                .commit_messages_between(branch, &parent, options)?;
+            let message = commit_message(template, branch, Some(*pr_number), &subjects);
+            messages.insert(*pr_number, message);
+        }
+        Ok(messages)
+    }
+}
+
+/// The merge status of a pull request.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum MergeStatus {
+    /// The pull request is approved and can be merged.
+    Ready,
+    /// The pull request has already been merged.
+    Merged,
+    /// The pull request cannot be merged, for the contained reason.
+    Blocked(String),
+}
+
+/// The GitHub operations required to merge a stack of pull requests.
+pub(crate) trait MergeBackend {
+    /// Returns the [MergeStatus] of a pull request.
+    async fn merge_status(&self, pr_number: u64) -> StResult<MergeStatus>;
+
+    /// Merges a pull request with the given [MergeMethod]. The first line of the commit message,
+    /// if given, is used as the commit title.
+    async fn merge(
+        &self,
+        pr_number: u64,
+        method: MergeMethod,
+        message: Option<&str>,
+    ) -> StResult<()>;
+
+    /// Updates the base branch of a pull request.
+    async fn update_base(&self, pr_number: u64, base: &str) -> StResult<()>;
+}
+
+impl MergeBackend for PullRequestHandler<'_> {
+    async fn merge_status(&self, pr_number: u64) -> StResult<MergeStatus> {
+        // GitHub computes mergeability lazily, so poll until it is available.
+        let mut pr = self.get(pr_number).await?;
+        for _ in 1..MERGEABILITY_POLL_ATTEMPTS {
+            if pr.mergeable.is_some() || pr.merged_at.is_some() {
+                break;
+            }
+            tokio::time::sleep(MERGEABILITY_POLL_DELAY).await;
+            pr = self.get(pr_number).await?;
+        }
+
+        if pr.merged_at.is_some() {
+            return Ok(MergeStatus::Merged);
+        }
+        if matches!(pr.state, Some(IssueState::Closed)) {
+            return Ok(MergeStatus::Blocked("pull request is closed".to_string()));
+        }
+        if pr.draft.unwrap_or_default() {
+            return Ok(MergeStatus::Blocked("pull request is a draft".to_string()));
+        }
+
+        match fetch_review_decision(self, pr_number).await? {
+            ReviewDecision::Approved => {}
+            ReviewDecision::ChangesRequested => {
+                return Ok(MergeStatus::Blocked("changes were requested".to_string()));
+            }
+            ReviewDecision::Pending => {
+                return Ok(MergeStatus::Blocked(
+                    "pull request is not approved".to_string(),
+                ));
+            }
+        }
+
+        let status = match (pr.mergeable, pr.mergeable_state) {
+            (None, _) => MergeStatus::Blocked(
+                "GitHub is still computing mergeability, try again shortly".to_string(),
+            ),
+            (Some(false), _) | (_, Some(MergeableState::Dirty)) => {
+                MergeStatus::Blocked("pull request has merge conflicts".to_string())
+            }
+            (_, Some(MergeableState::Blocked)) => {
+                MergeStatus::Blocked("merging is blocked by branch protection rules".to_string())
+            }
+            (_, Some(MergeableState::Behind)) => {
+                MergeStatus::Blocked("head branch is behind its base".to_string())
+            }
+            (_, Some(MergeableState::Unstable)) => {
+                MergeStatus::Blocked("status checks are failing".to_string())
+            }
+            _ => MergeStatus::Ready,
+        };
+        Ok(status)
+    }
+
+    async fn merge(
+        &self,
+        pr_number: u64,
+        method: MergeMethod,
+        message: Option<&str>,
+    ) -> StResult<()> {
+        let mut merge = PullRequestHandler::merge(self, pr_number).method(method);
+        if let Some(message) = message {
+            let (title, body) = message.split_once('\n').unwrap_or((message, ""));
+            merge = merge.title(title).message(body.trim());
+        }
+        merge.send().await?;
+        Ok(())
+    }
+
+    async fn update_base(&self, pr_number: u64, base: &str) -> StResult<()> {
+        self.update(pr_number).base(base).send().await?;
+        Ok(())
+    }
+}
+
+/// Returns the branches and pull request numbers to merge, ordered from the branch closest to
+/// trunk upwards. The queue ends at the first branch that has not been submitted.
+///
+/// ## Takes
+/// - `tree` - The [StackTree] containing the stack.
+/// - `stack` - The stack, ordered from the trunk branch to the tip of the stack.
+pub(crate) fn merge_queue(tree: &StackTree, stack: &[String]) -> StResult<Vec<(String, u64)>> {
+    let mut queue = Vec::new();
+    for branch in stack.iter().skip(1) {
+        let tracked_branch = tree
+            .get(branch)
+            .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?;
+        let Some(remote) = &tracked_branch.remote else {
+            break;
+        };
+        queue.push((branch.clone(), remote.pr_number));
+    }
+    Ok(queue)
+}
+
+/// Merges the pull requests in the queue in order. After each merge, the next pull request is
+/// re-targeted onto the trunk branch. Stops at the first pull request that cannot be merged.
+///
+/// ## Takes
+/// - `backend` - The [MergeBackend] to merge with.
+/// - `queue` - The branches and pull request numbers to merge, as returned by [merge_queue].
+/// - `trunk` - The name of the trunk branch.
+/// - `method` - The [MergeMethod] to merge each pull request with.
+/// - `messages` - The commit message of each pull request. GitHub's default is used for pull
+///   requests without one.
+///
+/// ## Returns
+/// - `Result<usize>` - The number of merged pull requests.
+pub(crate) async fn merge_stack(
+    backend: &impl MergeBackend,
+    queue: &[(String, u64)],
+    trunk: &str,
+    method: MergeMethod,
+    messages: &HashMap<u64, String>,
+) -> StResult<usize> {
+    let mut num_merged = 0;
+    for (i, (branch, pr_number)) in queue.iter().enumerate() {
+        match backend.merge_status(*pr_number).await? {
+            MergeStatus::Ready => {
+                let message = messages.get(pr_number).map(String::as_str);
+                backend.merge(*pr_number, method, message).await?;
+                num_merged += 1;
+                progress!(
+                    "Merged pull request #{} (`{}`).",
+                    pr_number,
+                    Color::Green.paint(branch)
+                );
+            }
+            MergeStatus::Merged => {
+                progress!(
+                    "Pull request #{} (`{}`) is already merged. Skipping.",
+                    pr_number,
+                    Color::Green.paint(branch)
+                );
+            }
+            MergeStatus::Blocked(reason) => {
+                println!(
+                    "Stopping at pull request #{} (`{}`): {}.",
+                    pr_number,
+                    Color::Green.paint(branch),
+                    Color::Red.paint(reason)
+                );
+                break;
+            }
+        }
+
+        // Point the next pull request in the stack at trunk.
+        if let Some((next_branch, next_pr_number)) = queue.get(i + 1) {
+            backend
+                .update_base(*next_pr_number, trunk)
+                .await
+                .with_branch(next_branch, BranchPhase::UpdateBase)?;
+            progress!(
+                "-> Updated base branch for pull request for branch `{}` to `{}`.",
+                Color::Green.paint(next_branch),
+                Color::Yellow.paint(trunk)
+            );
+        }
+    }
+    Ok(num_merged)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{merge_queue, merge_stack, MergeBackend, MergeStatus};
+    use crate::{
+        errors::StResult,
+        github::MergeMethod,
+        tree::{RemoteMetadata, StackTree},
+    };
+    use std::{cell::RefCell, collections::HashMap};
+
+    /// A mock [MergeBackend] that records the operations performed against it.
+    #[derive(Default)]
+    struct MockBackend {
+        statuses: HashMap<u64, MergeStatus>,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl MergeBackend for MockBackend {
+        async fn merge_status(&self, pr_number: u64) -> StResult<MergeStatus> {
+            Ok(self
+                .statuses
+                .get(&pr_number)
+                .cloned()
+                .unwrap_or(MergeStatus::Ready))
+        }
+
+        async fn merge(
+            &self,
+            pr_number: u64,
+            _: MergeMethod,
+            message: Option<&str>,
+        ) -> StResult<()> {
+            let call = match message {
+                Some(message) => format!("merge #{}: {}", pr_number, message),
+                None => format!("merge #{}", pr_number),
+            };
+            self.calls.borrow_mut().push(call);
+            Ok(())
+        }
+
+        async fn update_base(&self, pr_number: u64, base: &str) -> StResult<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("base #{} -> {}", pr_number, base));
+            Ok(())
+        }
+    }
+
+    fn stacked_tree() -> StackTree {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.insert("feature-2", "c", "feature-3").unwrap();
+        for (branch, pr_number) in [("feature-1", 1), ("feature-2", 2), ("feature-3", 3)] {
+            tree.get_mut(branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+        }
+        tree
+    }
+
+    #[test]
+    fn merge_queue_is_trunk_adjacent_first() {
+        let tree = stacked_tree();
+        let stack = tree.branches().unwrap();
+        let queue = merge_queue(&tree, &stack).unwrap();
+        assert_eq!(
+            queue,
+            vec![
+                ("feature-1".to_string(), 1),
+                ("feature-2".to_string(), 2),
+                ("feature-3".to_string(), 3)
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn merges_bottom_up_and_retargets_onto_trunk() {
+        let tree = stacked_tree();
+        let queue = merge_queue(&tree, &tree.branches().unwrap()).unwrap();
+        let backend = MockBackend::default();
+
+        let messages = HashMap::from([(2, "Feature 2 (#2)".to_string())]);
+
+        let merged = merge_stack(&backend, &queue, "main", MergeMethod::Squash, &messages)
+            .await
+            .unwrap();
+
+        assert_eq!(merged, 3);
+        assert_eq!(
+            *backend.calls.borrow(),
+            vec![
+                "merge #1",
+                "base #2 -> main",
+                "merge #2: Feature 2 (#2)",
+                "base #3 -> main",
+                "merge #3"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn stops_at_blocked_pull_request() {
+        let tree = stacked_tree();
+        let queue = merge_queue(&tree, &tree.branches().unwrap()).unwrap();
+        let backend = MockBackend {
+            statuses: HashMap::from([(2, MergeStatus::Blocked("not approved".to_string()))]),
+            ..Default::default()
+        };
+
+        let merged = merge_stack(
+            &backend,
+            &queue,
+            "main",
+            MergeMethod::Merge,
+            &HashMap::new(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(merged, 1);
+        assert_eq!(*backend.calls.borrow(), vec!["merge #1", "base #2 -> main"]);
+    }
+}
@@ -2,9 +2,22 @@
 
 mod submit;
 pub use submit::SubmitCmd;
+pub(crate) use submit::{editor_command, pr_description_editor, remove_stack_comment};
 
 mod sync;
 pub use sync::SyncCmd;
 
 mod status;
 pub use status::StatusCmd;
+
+mod merge;
+pub use merge::MergeCmd;
+
+mod prune;
+pub use prune::PruneCmd;
+
+mod pr;
+pub use pr::PrCmd;
+
+mod open;
+pub use open::OpenCmd;
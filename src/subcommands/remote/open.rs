@@ -0,0 +1,182 @@
+//! `open` subcommand.
+
+use super::pr::pr_number;
+use crate::{
+    ctx::StContext, errors::StResult, github::pull_request_url, theme::Color, tree::RemoteMetadata,
+};
+use clap::Args;
+use std::process::{Command, Stdio};
+
+/// The program and leading arguments used to open a URL in the default browser.
+#[cfg(target_os = "macos")]
+const BROWSER_COMMAND: &[&str] = &["open"];
+#[cfg(target_os = "windows")]
+const BROWSER_COMMAND: &[&str] = &["cmd", "/C", "start", ""];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const BROWSER_COMMAND: &[&str] = &["xdg-open"];
+
+/// CLI arguments for the `open` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct OpenCmd {
+    /// Name of the branch whose pull request is opened. Defaults to the current branch.
+    branch: Option<String>,
+    /// Print the URL of the pull request instead of opening it in the browser.
+    #[clap(long)]
+    print: bool,
+}
+
+impl OpenCmd {
+    /// Run the `open` subcommand.
+    pub fn run(self, ctx: StContext<'_>) -> StResult<()> {
+        let branch = match self.branch {
+            Some(branch) => branch,
+            None => ctx.current_branch()?,
+        };
+        let url = branch_pr_url(&ctx, &branch)?;
+
+        if self.print || !open_in_browser(&url) {
+            println!("{url}");
+        } else {
+            println!(
+                "Opened the pull request for `{}` @ `{}`",
+                Color::Green.paint(&branch),
+                Color::Blue.paint(&url)
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns the URL of the pull request submitted for the passed branch. The URL cached by
+/// `submit` or `status` is used while fresh, and otherwise built for the configured GitHub host.
+///
+/// ## Takes
+/// - `ctx` - The [StContext] holding the tracked branches and configuration.
+/// - `branch` - The name of the branch.
+///
+/// ## Returns
+/// - `Result<String>` - The URL of the pull request, or an error if the branch is not tracked or
+///   has not been submitted.
+fn branch_pr_url(ctx: &StContext<'_>, branch: &str) -> StResult<String> {
+    let pr_number = pr_number(&ctx.tree, branch)?;
+    if let Some(url) = ctx
+        .tree
+        .get(branch)
+        .and_then(|b| b.remote.as_ref())
+        .and_then(RemoteMetadata::cached_url)
+    {
+        return Ok(url.to_string());
+    }
+    let (owner, repo) = ctx.owner_and_repository()?;
+    Ok(pull_request_url(
+        ctx.cfg.github_host(),
+        &owner,
+        &repo,
+        pr_number,
+    ))
+}
+
+/// Opens the URL in the default browser. Returns `false` if no browser could be launched.
+fn open_in_browser(url: &str) -> bool {
+    let Some((program, args)) = BROWSER_COMMAND.split_first() else {
+        return false;
+    };
+    Command::new(program)
+        .args(args)
+        .arg(url)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .is_ok_and(|status| status.success())
+}
+
+#[cfg(test)]
+mod test {
+    use super::branch_pr_url;
+    use crate::{
+        constants::PR_CACHE_TTL,
+        ctx::{json::PrState, test::test_context},
+        errors::StError,
+        git::test::TempRepository,
+        tree::{unix_timestamp, RemoteMetadata, StackTree},
+    };
+
+    #[test]
+    fn pr_url_uses_configured_host() {
+        let repo = TempRepository::new();
+        repo.repository
+            .remote("origin", "git@github.example.com:org/repo.git")
+            .unwrap();
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.get_mut("feature-1").unwrap().remote = Some(RemoteMetadata::new(42));
+
+        let mut ctx = test_context(&repo.repository, tree);
+        assert_eq!(
+            branch_pr_url(&ctx, "feature-1").unwrap(),
+            "https://github.com/org/repo/pull/42"
+        );
+
+        ctx.cfg.github_host = "github.example.com".to_string();
+        assert_eq!(
+            branch_pr_url(&ctx, "feature-1").unwrap(),
+            "https://github.example.com/org/repo/pull/42"
+        );
+
+        assert!(matches!(
+            branch_pr_url(&ctx, "feature-2"),
+            Err(StError::BranchNotSubmitted(branch)) if branch == "feature-2"
+        ));
+    }
+
+    #[test]
+    fn cached_pr_url_is_used_while_fresh() {
+        let repo = TempRepository::new();
+        repo.repository
+            .remote("origin", "git@github.com:org/repo.git")
+            .unwrap();
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        let mut remote = RemoteMetadata::new(42);
+        remote.cache(
+            Some("https://github.com/upstream/repo/pull/42".to_string()),
+            PrState::Open,
+        );
+        tree.get_mut("feature-1").unwrap().remote = Some(remote);
+
+        let mut ctx = test_context(&repo.repository, tree);
+        assert_eq!(
+            branch_pr_url(&ctx, "feature-1").unwrap(),
+            "https://github.com/upstream/repo/pull/42"
+        );
+
+        // Once the cache is older than the freshness window, the URL is built again.
+        let remote = ctx
+            .tree
+            .get_mut("feature-1")
+            .unwrap()
+            .remote
+            .as_mut()
+            .unwrap();
+        remote.fetched_at = Some(unix_timestamp() - PR_CACHE_TTL.as_secs());
+        assert!(remote.cached_state().is_none());
+        assert_eq!(
+            branch_pr_url(&ctx, "feature-1").unwrap(),
+            "https://github.com/org/repo/pull/42"
+        );
+    }
+
+    #[test]
+    fn stores_without_cached_pr_fields_still_load() {
+        let remote: RemoteMetadata = toml::from_str("pr-number = 7\ncomment-id = 9\n").unwrap();
+        assert_eq!(
+            remote,
+            RemoteMetadata {
+                comment_id: Some(9),
+                ..RemoteMetadata::new(7)
+            }
+        );
+        assert!(remote.cached_url().is_none());
+    }
+}
@@ -0,0 +1,177 @@
+//! `pr` subcommand.
+
+use crate::{
+    ctx::StContext,
+    errors::{StError, StResult},
+    github::{self, GraphQlClient},
+    theme::Color,
+    tree::StackTree,
+};
+use clap::{Args, Subcommand};
+
+/// CLI arguments for the `pr` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct PrCmd {
+    #[clap(subcommand)]
+    pub command: PrSubcommand,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Subcommand)]
+pub enum PrSubcommand {
+    /// Convert a branch's pull request back into a draft
+    Draft {
+        /// Name of the branch. Defaults to the current branch
+        branch: Option<String>,
+    },
+    /// Mark a branch's draft pull request as ready for review
+    Ready {
+        /// Name of the branch. Defaults to the current branch
+        branch: Option<String>,
+    },
+}
+
+impl PrCmd {
+    /// Run the `pr` subcommand.
+    pub async fn run(self, ctx: StContext<'_>) -> StResult<()> {
+        let (branch, draft) = match self.command {
+            PrSubcommand::Draft { branch } => (branch, true),
+            PrSubcommand::Ready { branch } => (branch, false),
+        };
+        let branch = match branch {
+            Some(branch) => branch,
+            None => ctx.current_branch()?,
+        };
+
+        // Establish the GitHub API client.
+        let gh_client = github::github_client(&ctx.cfg)?;
+        let (owner, repo) = ctx.owner_and_repository()?;
+
+        let pr_number = pr_number(&ctx.tree, &branch)?;
+
+        // Fail with a clear message if the token can't write pull requests.
+        github::verify_token_access(&gh_client, &owner, &repo).await?;
+
+        let changed = set_draft(&gh_client, &owner, &repo, pr_number, draft).await?;
+        let state = if draft { "a draft" } else { "ready for review" };
+        if changed {
+            println!(
+                "Marked pull request #{} (`{}`) as {}.",
+                pr_number,
+                Color::Green.paint(&branch),
+                state
+            );
+        } else {
+            println!(
+                "Pull request #{} (`{}`) is already {}.",
+                pr_number,
+                Color::Green.paint(&branch),
+                state
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Returns the number of the pull request submitted for the passed branch.
+///
+/// ## Takes
+/// - `tree` - The tree of tracked branches.
+/// - `branch` - The name of the branch.
+///
+/// ## Returns
+/// - `Result<u64>` - The pull request number, or an error if the branch is not tracked or has
+///   not been submitted.
+pub(super) fn pr_number(tree: &StackTree, branch: &str) -> StResult<u64> {
+    let tracked_branch = tree
+        .get(branch)
+        .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?;
+    tracked_branch
+        .remote
+        .as_ref()
+        .map(|remote| remote.pr_number)
+        .ok_or_else(|| StError::BranchNotSubmitted(branch.to_string()))
+}
+
+/// Converts the pull request into a draft if `draft` is set, or marks it as ready for review
+/// otherwise. Returns whether or not the draft state of the pull request changed.
+async fn set_draft(
+    client: &impl GraphQlClient,
+    owner: &str,
+    repo: &str,
+    pr_number: u64,
+    draft: bool,
+) -> StResult<bool> {
+    if draft {
+        github::convert_to_draft(client, owner, repo, pr_number).await
+    } else {
+        github::mark_ready_for_review(client, owner, repo, pr_number).await
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{pr_number, set_draft};
+    use crate::{
+        errors::StError,
+        github::test::MockGraphQl,
+        tree::{RemoteMetadata, StackTree},
+    };
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn draft_state_mutation_targets_branch_pr() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.insert("feature-2", "c", "feature-3").unwrap();
+        tree.get_mut("feature-1").unwrap().remote = Some(RemoteMetadata::new(11));
+        tree.get_mut("feature-2").unwrap().remote = Some(RemoteMetadata::new(12));
+
+        let client = MockGraphQl {
+            pull_requests: HashMap::from([
+                (11, json!({ "id": "PR_11", "isDraft": true })),
+                (12, json!({ "id": "PR_12", "isDraft": false })),
+            ]),
+            ..Default::default()
+        };
+        let toggle = |number, draft| set_draft(&client, "owner", "repo", number, draft);
+        let mutations = || {
+            client
+                .requests
+                .borrow()
+                .iter()
+                .filter(|(q, _)| q.contains("mutation"))
+                .map(|(q, v)| (q.clone(), v["id"].clone()))
+                .collect::<Vec<_>>()
+        };
+
+        let number = pr_number(&tree, "feature-2").unwrap();
+        assert!(toggle(number, true).await.unwrap());
+        let issued = mutations();
+        assert_eq!(issued.len(), 1);
+        assert!(issued[0].0.contains("convertPullRequestToDraft"));
+        assert_eq!(issued[0].1, "PR_12");
+
+        let number = pr_number(&tree, "feature-1").unwrap();
+        assert!(toggle(number, false).await.unwrap());
+        let issued = mutations();
+        assert_eq!(issued.len(), 2);
+        assert!(issued[1].0.contains("markPullRequestReadyForReview"));
+        assert_eq!(issued[1].1, "PR_11");
+
+        // Pull requests already in the requested state are left untouched.
+        let number = pr_number(&tree, "feature-2").unwrap();
+        assert!(!toggle(number, false).await.unwrap());
+        assert_eq!(mutations().len(), 2);
+
+        assert!(matches!(
+            pr_number(&tree, "feature-3"),
+            Err(StError::BranchNotSubmitted(branch)) if branch == "feature-3"
+        ));
+        assert!(matches!(
+            pr_number(&tree, "feature-4"),
+            Err(StError::BranchNotTracked(_))
+        ));
+    }
+}
@@ -0,0 +1,105 @@
+//! `prune` subcommand.
+
+use super::remove_stack_comment;
+use crate::{
+    ctx::{json::PrState, StContext},
+    errors::StResult,
+    github::github_client,
+    output::progress,
+    theme::Color,
+};
+use clap::Args;
+
+/// CLI arguments for the `prune` subcommand.
+#[derive(Debug, Clone, Eq, PartialEq, Args)]
+pub struct PruneCmd {
+    /// Delete the branches of merged and closed pull requests without asking for confirmation.
+    #[clap(long, short)]
+    yes: bool,
+}
+
+impl PruneCmd {
+    /// Run the `prune` subcommand.
+    pub async fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        // Establish the GitHub API client.
+        let gh_client = github_client(&ctx.cfg)?;
+        let (owner, repo) = ctx.owner_and_repository()?;
+        let pulls = gh_client.pulls(&owner, &repo);
+
+        // Find the tracked branches whose pull requests are no longer open.
+        progress!("🔍 Checking for merged and closed pull requests...");
+        let branches = ctx.tree.branches()?;
+        let closed = ctx.closed_branches(&branches[1..], &pulls).await?;
+        if closed.is_empty() {
+            progress!("No merged or closed pull requests to prune.");
+            return Ok(());
+        }
+
+        let mut num_pruned = 0;
+        let mut merged_children = Vec::new();
+        for (branch, state) in closed {
+            if !self.yes {
+                let confirm = inquire::Confirm::new(
+                    format!(
+                        "Pull request for branch `{}` is {}. Delete the local branch?",
+                        Color::Green.paint(&branch),
+                        Color::Purple.bold().paint(state.to_string())
+                    )
+                    .as_str(),
+                )
+                .with_default(true)
+                .prompt()?;
+                if !confirm {
+                    continue;
+                }
+            }
+
+            let tracked = ctx.tree.get(&branch);
+            let remote = tracked.and_then(|b| b.remote.clone());
+            if state == PrState::Merged {
+                merged_children.extend(tracked.into_iter().flat_map(|b| b.children.clone()));
+            }
+            ctx.remove_branch(&branch)?;
+            remove_stack_comment(&ctx, remote.as_ref()).await;
+            num_pruned += 1;
+            progress!(
+                "Pruned branch `{}` ({}).",
+                Color::Green.paint(&branch),
+                state
+            );
+        }
+
+        // Rebase the children of merged branches past the merged commits, which may have been
+        // squashed into a single commit on the trunk.
+        let mut targets = Vec::new();
+        for child in merged_children {
+            if ctx.tree.get(&child).is_none() {
+                continue;
+            }
+            for target in ctx.restack_targets(Some(&child))? {
+                if !targets.contains(&target) {
+                    targets.push(target);
+                }
+            }
+        }
+        if !targets.is_empty() {
+            progress!("\n🧙 Restacking the children of merged branches...");
+            ctx.try_restack_branches(&targets)?;
+        }
+
+        if num_pruned > 0 {
+            println!(
+                "\nPruned {} branch{}.",
+                Color::Red.paint(num_pruned.to_string()),
+                if num_pruned != 1 { "es" } else { "" }
+            );
+            if !ctx.branches_needing_restack()?.is_empty() {
+                println!(
+                    "Run `{}` to restack the remaining branches.",
+                    Color::Blue.paint("st restack")
+                );
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,12 +1,14 @@
 //! `status` subcommand.
 
 use crate::{
-    ctx::StContext,
-    errors::{StError, StResult},
+    ctx::{json::PrState, untracked_branches, StContext},
+    errors::StResult,
+    github::{github_client, pr_states},
+    theme::Color,
+    tree::RemoteMetadata,
 };
 use clap::Args;
 use cli_table::{Cell, Style, Table};
-use octocrab::{models::IssueState, Octocrab};
 
 /// CLI arguments for the `status` subcommand.
 #[derive(Debug, Clone, Eq, PartialEq, Args)]
@@ -14,58 +16,75 @@ pub struct StatusCmd;
 
 impl StatusCmd {
     /// Run the `status` subcommand.
-    pub async fn run(self, ctx: StContext<'_>) -> StResult<()> {
+    pub async fn run(self, mut ctx: StContext<'_>, json: bool) -> StResult<()> {
+        // Fail before any network activity if there is no checked out branch.
+        ctx.current_branch()?;
+
         // Establish the GitHub API client.
-        let gh_client = Octocrab::builder()
-            .personal_token(ctx.cfg.github_token.clone())
-            .build()?;
+        let gh_client = github_client(&ctx.cfg)?;
         let (owner, repo) = ctx.owner_and_repository()?;
         let pulls = gh_client.pulls(&owner, &repo);
 
         // Show all tracked branches instead of just the linear stack
-        let all_branches = ctx.tree.branches()?;
+        let mut state = ctx.stack_state()?;
+        for branch in state.branches.iter_mut() {
+            branch.pr_state = ctx
+                .tree
+                .get(&branch.name)
+                .and_then(|b| b.remote.as_ref())
+                .and_then(RemoteMetadata::cached_state);
+        }
+
+        // Fetch the states that aren't cached, or were cached too long ago, and cache them.
+        let pr_numbers = state
+            .branches
+            .iter()
+            .filter(|branch| branch.pr_state.is_none())
+            .filter_map(|branch| branch.pr_number)
+            .collect::<Vec<_>>();
+        let mut pr_states = pr_states(&pulls, &pr_numbers).await?.into_iter();
+        for branch in state
+            .branches
+            .iter_mut()
+            .filter(|b| b.pr_number.is_some() && b.pr_state.is_none())
+        {
+            branch.pr_state = pr_states.next();
+            if let Some(pr_state) = branch.pr_state
+                && let Some(remote) = ctx
+                    .tree
+                    .get_mut(&branch.name)
+                    .and_then(|b| b.remote.as_mut())
+            {
+                remote.cache(None, pr_state);
+            }
+        }
+
+        if json {
+            println!("{}", serde_json::to_string_pretty(&state)?);
+            return Ok(());
+        }
 
         let mut rows = vec![];
-        for branch in all_branches.into_iter() {
-            let tracked_branch = ctx
-                .tree
-                .get(&branch)
-                .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?;
+        for branch in state.branches.into_iter() {
             let mut row = Vec::with_capacity(4);
 
-            row.push(branch.clone());
-            row.push(
-                tracked_branch
-                    .parent
-                    .clone()
-                    .unwrap_or("n/a: trunk branch".to_string()),
-            );
-            row.push(if ctx.needs_restack(&branch)? {
+            row.push(branch.name);
+            row.push(branch.parent.unwrap_or("n/a: trunk branch".to_string()));
+            row.push(if branch.needs_restack {
                 "🔴 Needs Restack".to_string()
             } else {
                 "✅ Restacked".to_string()
             });
-
-            if let Some(remote) = &tracked_branch.remote {
-                let pr_info = pulls.get(remote.pr_number).await?;
-                let is_draft = pr_info.draft.unwrap_or_default();
-                let is_merged = pr_info.merged_at.is_some();
-                let is_closed = pr_info
-                    .state
-                    .map_or(true, |s| matches!(s, IssueState::Closed));
-
-                if is_draft {
-                    row.push("📝 Draft".to_string());
-                } else if is_merged {
-                    row.push("✅ Merged".to_string());
-                } else if is_closed {
-                    row.push("❌ Closed".to_string());
-                } else {
-                    row.push("🔍 In Review".to_string());
+            row.push(
+                match branch.pr_state {
+                    Some(PrState::Draft) => "📝 Draft",
+                    Some(PrState::Merged) => "✅ Merged",
+                    Some(PrState::Closed) => "❌ Closed",
+                    Some(PrState::Open) => "🔍 In Review",
+                    None => "🚧 Not Submitted",
                 }
-            } else {
-                row.push("🚧 Not Submitted".to_string());
-            }
+                .to_string(),
+            );
 
             rows.push(row);
         }
@@ -80,6 +99,45 @@ impl StatusCmd {
             ])
             .bold(true);
         println!("{}", table.display().expect("Failed to display table"));
+
+        // Summarize the branches whose parents have moved since they were last restacked.
+        let stale = ctx.branches_needing_restack()?;
+        if !stale.is_empty() {
+            println!(
+                "\n{} branch{} need{} restacking: {}. Run `{}` to restack them.",
+                Color::Red.paint(stale.len().to_string()),
+                if stale.len() != 1 { "es" } else { "" },
+                if stale.len() != 1 { "" } else { "s" },
+                stale
+                    .iter()
+                    .map(|b| format!("`{}`", Color::Green.paint(b)))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                Color::Blue.paint("st restack")
+            );
+        }
+
+        // List the branches created outside of `st` on top of tracked branches.
+        let untracked = untracked_branches(ctx.repository, &ctx.tree)?;
+        if !untracked.is_empty() {
+            println!(
+                "\n{} untracked branch{} fork{} off tracked branches:",
+                Color::Yellow.paint(untracked.len().to_string()),
+                if untracked.len() != 1 { "es" } else { "" },
+                if untracked.len() != 1 { "" } else { "s" },
+            );
+            for (branch, parent) in &untracked {
+                println!(
+                    "  `{}` on top of `{}`",
+                    Color::Green.paint(branch),
+                    Color::Yellow.paint(parent)
+                );
+            }
+            println!(
+                "Run `{}` to track them.",
+                Color::Blue.paint("st track <branch>")
+            );
+        }
         Ok(())
     }
 }
@@ -1,18 +1,41 @@
 //! `submit` subcommand.
 
 use crate::{
-    ctx::StContext,
-    errors::{StError, StResult},
-    git::RepositoryExt,
-    tree::RemoteMetadata,
+    ai::{is_connection_error, prompt_diff_options, PROMPT_COMMIT_LOG},
+    config::{PrTemplate, StConfig},
+    constants::{GIT_DIR, ST_PR_BODY_DIR, ST_SUBMIT_PROGRESS_FILE_NAME},
+    ctx::{json::PrState, persist_tree, StContext},
+    errors::{BranchContext, BranchPhase, StError, StResult},
+    format::{
+        diff_stat, issue_references, link_issues, restore_checklist, title_from_commits,
+        with_stack_context,
+    },
+    git::{BranchDiff, FileDelta, RepositoryExt},
+    github::{self, CiStatus, MergeMethod, ReviewDecision},
+    output::progress,
+    theme::Color,
+    tree::{RemoteMetadata, StackTree},
 };
 use clap::Args;
-use git2::BranchType;
-use nu_ansi_term::Color;
-use octocrab::{issues::IssueHandler, models::CommentId, pulls::PullRequestHandler, Octocrab};
+use git2::{BranchType, Oid, Repository};
+use inquire::{validator::Validation, CustomUserError};
+use octocrab::{
+    issues::IssueHandler,
+    models::{CommentId, Milestone},
+    pulls::PullRequestHandler,
+    Octocrab,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    fmt, fs,
+    future::Future,
+    path::{Path, PathBuf},
+};
 
 /// CLI arguments for the `submit` subcommand.
-#[derive(Debug, Clone, Eq, PartialEq, Args)]
+#[derive(Debug, Default, Clone, Eq, PartialEq, Args)]
 pub struct SubmitCmd {
     /// Force the submission of the stack, analogous to `git push --force`.
     #[clap(long, short)]
@@ -20,76 +43,285 @@ pub struct SubmitCmd {
     /// Submit all tracked branches, not just the current stack.
     #[clap(long, short)]
     all: bool,
+    /// Submit only the given branch, rather than the whole stack. Its parent must already be
+    /// submitted, or exist on the remote.
+    #[clap(long, value_name = "BRANCH", conflicts_with = "all")]
+    only: Option<String>,
+    /// Enable auto-merge on each pull request with the given merge method.
+    #[clap(long, value_enum, num_args = 0..=1, default_missing_value = "squash")]
+    auto_merge: Option<MergeMethod>,
+    /// Mark all draft pull requests in the submitted stack as ready for review.
+    #[clap(long)]
+    ready: bool,
+    /// Abort the submission if CI checks are failing on any submitted branch.
+    #[clap(long)]
+    require_green: bool,
+    /// Abort the submission if the local trunk branch differs from its remote counterpart.
+    #[clap(long)]
+    require_synced_trunk: bool,
+    /// Edit the title and description of pull requests that have already been submitted.
+    #[clap(long)]
+    update_metadata: bool,
+    /// Target the bottom pull request of the stack at this remote branch instead of its tracked
+    /// parent. Pull requests further up the stack still target their parents.
+    #[clap(long)]
+    base: Option<String>,
+    /// Assign each pull request to this milestone, by title or number. Defaults to the
+    /// `default_milestone` configuration value.
+    #[clap(long)]
+    milestone: Option<String>,
+    /// Assign the given GitHub users to each pull request. Defaults to the `default_assignees`
+    /// configuration value.
+    #[clap(long = "assignee", num_args = 1..)]
+    assignees: Vec<String>,
+    /// Prepend a table of files changed, insertions, and deletions to the description of new
+    /// pull requests. Defaults to the `diff_stat` configuration value.
+    #[clap(long)]
+    stat: bool,
+    /// Skip posting and updating the stack navigation comment on each pull request. Defaults to
+    /// the `disable_stack_comment` configuration value.
+    #[clap(long)]
+    no_comment: bool,
+    /// Open new pull requests as drafts, without asking.
+    #[clap(long, conflicts_with = "no_draft")]
+    draft: bool,
+    /// Open new pull requests ready for review, without asking.
+    #[clap(long)]
+    no_draft: bool,
+    /// Use the contents of this file as the PR template for new pull requests, instead of
+    /// prompting for one of the configured templates.
+    #[clap(long, value_name = "PATH")]
+    template_file: Option<PathBuf>,
+    /// Print a summary of the pushes, new pull requests, and base changes, and ask for
+    /// confirmation before submitting. Always enabled with `--all` and `--force`.
+    #[clap(long)]
+    confirm: bool,
+    /// Use the descriptions prepared in `.st/pr/<branch>.md` as they are, without opening them in
+    /// the editor.
+    #[clap(long)]
+    no_edit: bool,
 }
 
 impl SubmitCmd {
     /// Run the `submit` subcommand.
     pub async fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
+        // Fail before any network activity if there is no checked out branch.
+        ctx.current_branch()?;
+
         // Establish the GitHub API client.
-        let gh_client = Octocrab::builder()
-            .personal_token(ctx.cfg.github_token.clone())
-            .build()?;
+        let gh_client = github::github_client(&ctx.cfg)?;
         let (owner, repo) = ctx.owner_and_repository()?;
         let mut pulls = gh_client.pulls(&owner, &repo);
 
         // Resolve the branches to submit
-        let branches_to_submit = if self.all {
-            // Submit all tracked branches
-            ctx.tree.branches()?
-        } else {
-            // Submit only the current stack
-            ctx.discover_stack()?
-        };
+        let mut branches_to_submit = self.branches_to_submit(&ctx)?;
+
+        // Verify the base override exists on the remote before anything is pushed.
+        if let Some(base) = &self.base {
+            let remote_base = format!("{}/{}", ctx.cfg.pr_remote(), base);
+            if ctx
+                .repository
+                .find_branch(&remote_base, BranchType::Remote)
+                .is_err()
+            {
+                return Err(StError::BaseBranchNotOnRemote(base.clone()));
+            }
+        }
 
-        // Perform pre-flight checks.
-        println!("🔍 Checking for closed pull requests...");
-        self.pre_flight(&mut ctx, &branches_to_submit, &mut pulls).await?;
+        // Fail before anything is written if the token can't write pull requests.
+        github::verify_token_access(&gh_client, &owner, &repo).await?;
+
+        // Perform pre-flight checks, which change nothing locally or on the remote.
+        progress!("🔍 Checking for closed pull requests...");
+        let closed = self
+            .pre_flight(&ctx, &branches_to_submit, &pulls, &gh_client, &owner, &repo)
+            .await?;
+
+        // Summarize the submission and ask for confirmation before anything is deleted or pushed.
+        if self.confirm || self.all || self.force {
+            let bases = pr_bases(&ctx.tree, &branches_to_submit, self.base.as_deref())?;
+            let mut plan = submit_plan(
+                ctx.repository,
+                &ctx.tree,
+                &branches_to_submit,
+                &bases,
+                &pulls,
+            )
+            .await?;
+            plan.closed = closed.clone();
+            confirm_submit_plan(&plan, Self::confirm_plan)?;
+        }
+
+        // Offer to delete the branches of closed PRs, and leave the deleted ones out.
+        let num_closed = ctx.offer_to_delete_closed(&closed)?;
+        if num_closed > 0 {
+            println!(
+                "Deleted {} closed pull request{}. Run `{}` to re-stack the branches.",
+                Color::Red.paint(num_closed.to_string()),
+                if num_closed != 1 { "s" } else { "" },
+                Color::Blue.paint("st restack")
+            );
+            branches_to_submit = self.branches_to_submit(&ctx)?;
+        }
 
         // Submit the stack.
-        println!(
+        progress!(
             "\n🐙 Submitting changes to remote `{}`...",
-            Color::Blue.paint("origin")
+            Color::Blue.paint(ctx.cfg.push_remote())
         );
         self.submit_stack(&mut ctx, &mut pulls, &owner, &repo)
             .await?;
 
-        // Update the stack navigation comments on the PRs.
-        println!("\n📝 Updating stack navigation comments...");
-        self.update_pr_comments(&mut ctx, gh_client.issues(owner, repo), &branches_to_submit)
-            .await?;
+        // Update the stack navigation comments on the PRs, unless disabled.
+        self.update_pr_comments(
+            &mut ctx,
+            &gh_client.issues(&owner, &repo),
+            &pulls,
+            &branches_to_submit,
+        )
+        .await?;
+
+        // Assign the PRs to a milestone, if requested or configured.
+        let milestone = self
+            .milestone
+            .clone()
+            .or_else(|| Some(ctx.cfg.default_milestone.clone()).filter(|m| !m.is_empty()));
+        if let Some(milestone) = milestone {
+            progress!("\n🏁 Setting milestone...");
+            let backend = RepoIssues {
+                client: &gh_client,
+                owner: &owner,
+                repo: &repo,
+            };
+            apply_milestone(&ctx.tree, &branches_to_submit, &milestone, &backend).await?;
+        }
+
+        // Assign users to the PRs, if requested or configured.
+        let assignees = if self.assignees.is_empty() {
+            &ctx.cfg.default_assignees
+        } else {
+            &self.assignees
+        };
+        if !assignees.is_empty() {
+            progress!("\n🙋 Assigning pull requests...");
+            let backend = RepoIssues {
+                client: &gh_client,
+                owner: &owner,
+                repo: &repo,
+            };
+            apply_assignees(&ctx.tree, &branches_to_submit, assignees, &backend).await?;
+        }
+
+        // Label the PRs with the trunk's default labels, if configured.
+        let labels = &ctx.tree.trunk_settings().default_labels;
+        if !labels.is_empty() {
+            progress!("\n🏷️  Labeling pull requests...");
+            let backend = RepoIssues {
+                client: &gh_client,
+                owner: &owner,
+                repo: &repo,
+            };
+            apply_labels(&ctx.tree, &branches_to_submit, labels, &backend).await?;
+        }
+
+        // Enable auto-merge on the PRs, if requested.
+        if let Some(method) = self.auto_merge {
+            progress!("\n🤖 Enabling auto-merge...");
+            Self::enable_auto_merge(&ctx, &gh_client, &owner, &repo, &branches_to_submit, method)
+                .await?;
+        }
+
+        // Mark draft PRs as ready for review, if requested.
+        if self.ready {
+            progress!("\n👀 Marking pull requests as ready for review...");
+            Self::mark_ready_for_review(&ctx, &gh_client, &owner, &repo, &branches_to_submit)
+                .await?;
+        }
 
-        println!("\n🧙💫 All pull requests up to date.");
+        progress!("\n🧙💫 All pull requests up to date.");
         Ok(())
     }
 
-    /// Performs pre-flight checks before submitting the stack.
+    /// Returns the branches to submit, ordered from the bottom branch upwards: every tracked
+    /// branch with `--all`, the given branch and its parent with `--only`, or the current stack.
+    fn branches_to_submit(&self, ctx: &StContext<'_>) -> StResult<Vec<String>> {
+        if self.all {
+            ctx.tree.branches()
+        } else if let Some(branch) = &self.only {
+            single_branch_submission(ctx.repository, &ctx.tree, branch, ctx.cfg.pr_remote())
+        } else {
+            ctx.discover_stack()
+        }
+    }
+
+    /// Performs pre-flight checks before submitting the stack, without changing anything.
+    /// Returns the branches whose pull requests have been merged or closed, which are offered for
+    /// deletion once the submission is confirmed.
     async fn pre_flight(
         &self,
-        ctx: &mut StContext<'_>,
+        ctx: &StContext<'_>,
         stack: &[String],
-        pulls: &mut PullRequestHandler<'_>,
-    ) -> StResult<()> {
+        pulls: &PullRequestHandler<'_>,
+        gh_client: &Octocrab,
+        owner: &str,
+        repo: &str,
+    ) -> StResult<Vec<(String, PrState)>> {
+        // Warn if the trunk has moved on the remote since it was last synced.
+        let trunk = ctx.tree.trunk_name().to_string();
+        if let Some(warning) = trunk_sync_warning(ctx.repository, &trunk, ctx.cfg.pr_remote())? {
+            println!("⚠️  {warning}");
+            if self.require_synced_trunk {
+                return Err(StError::TrunkOutOfSync(trunk));
+            }
+        }
+
+        // Warn about any tracked branches whose parents have moved since they were restacked.
+        let stale = ctx.branches_needing_restack()?;
+        if !stale.is_empty() {
+            println!(
+                "⚠️  Branches needing restack: {}",
+                stale
+                    .iter()
+                    .map(|b| format!("`{}`", Color::Green.paint(b)))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+
         // Return early if the stack is not restacked or the current working tree is dirty.
         ctx.check_cleanliness(stack)?;
 
-        // Check if any PRs have been closed, and offer to delete them before starting the submission process.
-        let num_closed = ctx
-            .delete_closed_branches(
-                stack.iter().skip(1).cloned().collect::<Vec<_>>().as_slice(),
-                pulls,
-            )
-            .await?;
+        // Check if any PRs have been closed. They are offered for deletion after the plan is
+        // confirmed.
+        let closed = ctx.closed_branches(&stack[1..], pulls).await?;
 
-        if num_closed > 0 {
-            println!(
-                "Deleted {} closed pull request{}. Run `{}` to re-stack the branches.",
-                Color::Red.paint(num_closed.to_string()),
-                (num_closed != 1).then_some("s").unwrap_or_default(),
-                Color::Blue.paint("st restack")
-            );
+        // Report the CI status of the submitted branches.
+        progress!("\n🚦 Checking CI status...");
+        let mut ci_statuses = Vec::new();
+        for branch in stack.iter().skip(1) {
+            // Skip branches that are not submitted as PRs, or whose PRs are closed.
+            let submitted = ctx.tree.get(branch).is_some_and(|b| b.remote.is_some());
+            if !submitted || closed.iter().any(|(closed, _)| closed == branch) {
+                continue;
+            }
+
+            let status = github::fetch_ci_status(gh_client, owner, repo, branch).await?;
+            let summary = match status {
+                CiStatus::Passing => "✅ Passing",
+                CiStatus::Failing => "❌ Failing",
+                CiStatus::Pending => "⏳ Pending",
+                CiStatus::NoChecks => "➖ No checks",
+            };
+            progress!("{} `{}`", summary, Color::Green.paint(branch));
+            ci_statuses.push((branch.clone(), status));
         }
 
-        Ok(())
+        // Abort if any checks are failing and the user requires a green stack.
+        if self.require_green {
+            ensure_ci_green(&ci_statuses)?;
+        }
+
+        Ok(closed)
     }
 
     /// Submits the stack of branches to GitHub.
@@ -101,27 +333,56 @@ impl SubmitCmd {
         repo: &str,
     ) -> StResult<()> {
         // Get all branches to process
-        let all_branches = if self.all {
-            ctx.tree.branches()?
-        } else {
-            ctx.discover_stack()?
-        };
+        let all_branches = self.branches_to_submit(ctx)?;
+
+        // Offer the markdown templates in the repository alongside the configured ones.
+        if let Some(workdir) = ctx.repository.workdir() {
+            let templates_dir = ctx.cfg.templates_dir(workdir);
+            ctx.cfg.load_file_templates(&templates_dir)?;
+        }
+
+        // A one-off template file takes precedence over the configured templates.
+        let template_file = self
+            .template_file
+            .as_deref()
+            .map(load_template_file)
+            .transpose()?;
+
+        // The PR template chosen for the previous branch, offered as the default for the next.
+        let mut last_template = None;
+
+        // Branches are pushed to the push remote, while PRs target the PR remote's repository.
+        let push_remote = ctx.cfg.push_remote().to_string();
+        let pr_remote = ctx.cfg.pr_remote().to_string();
+
+        // Resolve the base branch of each PR, applying the base override to the bottom PR.
+        let bases = pr_bases(&ctx.tree, &all_branches, self.base.as_deref())?;
+
+        // Resume from the first incomplete branch if a previous run was interrupted.
+        let mut progress = SubmitProgress::load(ctx.repository);
+
+        // New PRs default to drafts, unless the trunk says otherwise.
+        let default_draft = default_draft(&ctx.tree, ctx.cfg.default_draft);
 
         // Iterate over the branches and submit PRs.
-        for branch in all_branches.iter().skip(1) {
-            // Get the parent for this specific branch
-            let parent = {
-                let tracked_branch = ctx
-                    .tree
-                    .get(branch)
-                    .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?;
-                
-                tracked_branch
-                    .parent
-                    .clone()
-                    .ok_or_else(|| StError::BranchNotTracked(format!("Parent not found for {}", branch)))?
-            };
+        for (branch, (parent, base)) in all_branches.iter().skip(1).zip(bases) {
+            let head = ctx
+                .repository
+                .find_branch(branch, BranchType::Local)?
+                .get()
+                .target()
+                .ok_or(StError::BranchUnavailable)?
+                .to_string();
+            if progress.is_complete(branch, &head, &base) {
+                progress!(
+                    "Branch `{}` was submitted by an interrupted run. Skipping.",
+                    Color::Green.paint(branch)
+                );
+                continue;
+            }
 
+            let pr_head = ctx.pr_head(branch)?;
+            let stack_context = ctx.tree.stack_description(branch).map(str::to_string);
             let tracked_branch = ctx
                 .tree
                 .get_mut(branch)
@@ -134,153 +395,327 @@ impl SubmitCmd {
                 let remote_pr = pulls.get(remote_meta.pr_number).await?;
 
                 // Check if the PR base needs to be updated
-                if &remote_pr.base.ref_field != &parent {
+                if remote_pr.base.ref_field != base {
                     // Update the PR base.
-                    pulls
-                        .update(remote_meta.pr_number)
-                        .base(&parent)
-                        .send()
-                        .await?;
-                    println!(
+                    update_pr_base(branch, remote_meta.pr_number, &base, &*pulls).await?;
+                    progress!(
                         "-> Updated base branch for pull request for branch `{}` to `{}`.",
                         Color::Green.paint(branch),
-                        Color::Yellow.paint(&parent)
+                        Color::Yellow.paint(&base)
                     );
                 }
 
-                // Check if the local branch is ahead of the remote.
-                let remote_synced = remote_pr.head.sha
-                    == ctx
-                        .repository
-                        .find_branch(branch, BranchType::Local)?
-                        .get()
-                        .target()
-                        .ok_or(StError::BranchUnavailable)?
-                        .to_string();
-                if remote_synced {
-                    println!(
-                        "Branch `{}` is up-to-date with the remote. Skipping push.",
+                // Re-open the PR metadata for editing, if requested.
+                if self.update_metadata {
+                    let (title, body) = existing_pr_metadata(
+                        remote_meta,
+                        remote_pr.title.as_deref(),
+                        remote_pr.body.as_deref(),
+                    );
+                    let (title, body) =
+                        Self::prompt_pr_update(&ctx.cfg, branch, &base, &title, &body)?;
+                    let body = with_stack_context(&body, stack_context.as_deref());
+                    let pr_number = remote_meta.pr_number;
+                    pulls
+                        .update(pr_number)
+                        .title(&title)
+                        .body(&body)
+                        .send()
+                        .await
+                        .with_branch(branch, BranchPhase::UpdatePullRequest)?;
+
+                    let remote_meta = tracked_branch.remote.as_mut().expect("Must exist");
+                    remote_meta.pr_title = Some(title);
+                    remote_meta.pr_body = Some(body);
+                    progress!(
+                        "-> Updated title and description of pull request #{} for branch `{}`.",
+                        pr_number,
                         Color::Green.paint(branch)
                     );
-                    continue;
                 }
 
-                // Push the branch to the remote.
-                ctx.repository.push_branch(branch, "origin", self.force)?;
+                // Push the branch to the remote, unless it is already up-to-date. If the remote
+                // branch has diverged, confirm before overwriting it.
+                let mode = push_mode(
+                    ctx.repository,
+                    branch,
+                    &head,
+                    &remote_pr.head.sha,
+                    self.force,
+                    Self::confirm_force_push,
+                )?;
+                if mode == PushMode::Skip {
+                    progress!(
+                        "Branch `{}` is up-to-date with the remote. Skipping push.",
+                        Color::Green.paint(branch)
+                    );
+                } else {
+                    ctx.repository
+                        .push_branch(branch, &push_remote, mode == PushMode::Force)
+                        .with_branch(branch, BranchPhase::Push)?;
+                    progress!("Updated branch `{}` on remote.", Color::Green.paint(branch));
+                }
 
-                // Print success message.
-                println!("Updated branch `{}` on remote.", Color::Green.paint(branch));
+                // Cache the URL and state of the PR, for `open` and `status`.
+                let remote_meta = tracked_branch.remote.as_mut().expect("Must exist");
+                remote_meta.cache(
+                    remote_pr.html_url.as_ref().map(ToString::to_string),
+                    PrState::from(&remote_pr),
+                );
             } else {
                 // If the PR has not been submitted yet.
 
                 // Verify the base branch exists on remote
-                let remote_base = format!("origin/{}", parent);
+                let remote_base = format!("{}/{}", pr_remote, base);
                 if ctx.repository.find_branch(&remote_base, BranchType::Remote).is_err() {
-                    return Err(StError::BaseBranchNotOnRemote(parent.clone()));
+                    return Err(StError::BaseBranchNotOnRemote(base.clone()));
                 }
 
                 // Push the branch to the remote.
-                ctx.repository.push_branch(branch, "origin", self.force)?;
+                ctx.repository
+                    .push_branch(branch, &push_remote, self.force)
+                    .with_branch(branch, BranchPhase::Push)?;
 
-                // Get the diff between the branch and its parent
-                let diff = ctx
-                    .repository
-                    .diff_branches(branch, &parent)
-                    .unwrap_or_else(|_| String::from("Unable to generate diff"));
+                // Get the diff between the branch and its parent, and a summary of its files.
+                let diff = BranchDiff {
+                    patch: ctx
+                        .repository
+                        .diff_branches_opts(branch, &parent, &prompt_diff_options(&ctx.cfg))
+                        .unwrap_or_else(|_| String::from("Unable to generate diff")),
+                    files: ctx
+                        .repository
+                        .diff_summary(branch, &parent)
+                        .unwrap_or_default(),
+                };
 
                 // Get commit messages between the branch and its parent
                 let commits = ctx
                     .repository
-                    .commit_messages_between(branch, &parent)
+                    .commit_messages_between(branch, &parent, PROMPT_COMMIT_LOG)
                     .unwrap_or_else(|_| vec![]);
 
                 // Prompt the user for PR metadata.
-                let metadata = Self::prompt_pr_metadata(&mut ctx.cfg, branch, &parent, &commits, &diff).await?;
+                let body_override = pr_body_override(ctx.repository, branch)?;
+                let defaults = PrDefaults {
+                    stat: self.stat || ctx.cfg.diff_stat,
+                    draft: default_draft,
+                    draft_flag: self.draft_flag(),
+                    template_file: template_file.as_ref(),
+                    body_override: body_override.as_deref(),
+                    no_edit: self.no_edit,
+                };
+                let mut metadata = Self::prompt_pr_metadata(
+                    &mut ctx.cfg,
+                    branch,
+                    &base,
+                    &commits,
+                    &diff,
+                    defaults,
+                    &mut last_template,
+                )
+                .await?;
+                if ctx.cfg.link_issues {
+                    let issues = issue_references(branch, &commits);
+                    metadata.body = link_issues(&metadata.body, &issues);
+                }
+                metadata.body = with_stack_context(&metadata.body, stack_context.as_deref());
 
                 // Submit PR.
                 let pr_info = pulls
-                    .create(metadata.title, branch, &parent)
-                    .body(metadata.body)
+                    .create(&metadata.title, &pr_head, &base)
+                    .body(&metadata.body)
                     .draft(metadata.is_draft)
                     .send()
-                    .await?;
+                    .await
+                    .with_branch(branch, BranchPhase::CreatePullRequest)?;
 
                 // Update the tracked branch with the remote information.
-                tracked_branch.remote = Some(RemoteMetadata::new(pr_info.number));
+                let mut remote_meta = RemoteMetadata::new(pr_info.number);
+                remote_meta.pr_title = Some(metadata.title);
+                remote_meta.pr_body = Some(metadata.body);
+                remote_meta.cache(
+                    pr_info.html_url.as_ref().map(ToString::to_string),
+                    PrState::from(&pr_info),
+                );
+                tracked_branch.remote = Some(remote_meta);
 
                 // Print success message.
-                let pr_link = format!(
-                    "https://github.com/{}/{}/pull/{}",
-                    owner, repo, pr_info.number
-                );
+                let pr_link =
+                    github::pull_request_url(ctx.cfg.github_host(), owner, repo, pr_info.number);
                 println!(
                     "Submitted new pull request for branch `{}` @ `{}`",
                     Color::Green.paint(branch),
                     Color::Blue.paint(pr_link)
                 );
             }
+
+            // Record the branch as complete, persisting the PR metadata right away so that an
+            // interrupted run never re-creates the PR.
+            persist_tree(ctx.repository, &ctx.tree)?;
+            progress.complete(branch, &head, &base);
+            progress.save(ctx.repository)?;
         }
 
+        SubmitProgress::clear(ctx.repository)?;
         Ok(())
     }
 
-    /// Updates the comments on a PR with the current stack information.
+    /// Returns whether new pull requests are drafts as decided by the `--draft` and `--no-draft`
+    /// flags, or [None] to ask.
+    fn draft_flag(&self) -> Option<bool> {
+        match (self.draft, self.no_draft) {
+            (true, _) => Some(true),
+            (_, true) => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Returns whether or not the stack navigation comments are posted, which is disabled with
+    /// `--no-comment` or the `disable_stack_comment` configuration value.
+    fn stack_comments_enabled(&self, cfg: &StConfig) -> bool {
+        !self.no_comment && !cfg.disable_stack_comment
+    }
+
+    /// Updates the comments on a PR with the current stack information. With `--only`, just the
+    /// comment of the submitted branch is updated, still listing its whole stack.
+    ///
+    /// Nothing is fetched or posted while stack comments are disabled. Comment IDs are kept, so
+    /// the existing comments are updated once re-enabled.
     async fn update_pr_comments(
         &self,
         ctx: &mut StContext<'_>,
-        issue_handler: IssueHandler<'_>,
+        comments: &impl CommentBackend,
+        reviews: &impl ReviewBackend,
         stack: &[String],
     ) -> StResult<()> {
+        if !self.stack_comments_enabled(&ctx.cfg) {
+            return Ok(());
+        }
+        progress!("\n📝 Updating stack navigation comments...");
+
+        let stack = match &self.only {
+            Some(branch) => ctx.tree.stack_of(branch)?,
+            None => stack.to_vec(),
+        };
+
+        // Fetch the review decision of each PR once, as every comment displays all of them.
+        let mut decisions = HashMap::new();
         for branch in stack.iter().skip(1) {
-            let tracked_branch = ctx
-                .tree
-                .get_mut(branch)
-                .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?;
+            if let Some(remote) = ctx.tree.get(branch).and_then(|b| b.remote.as_ref()) {
+                let decision = reviews.review_decision(remote.pr_number).await?;
+                decisions.insert(remote.pr_number, decision);
+            }
+        }
+
+        sync_stack_comments(
+            &mut ctx.tree,
+            ctx.cfg.stack_comment_template(),
+            &stack,
+            self.only.as_deref(),
+            &decisions,
+            comments,
+        )
+        .await
+    }
 
-            // Skip branches that are not submitted as PRs.
-            let Some(remote_meta) = tracked_branch.remote else {
+    /// Enables auto-merge on the PRs for the passed branches. PRs that already have auto-merge
+    /// enabled are skipped.
+    async fn enable_auto_merge(
+        ctx: &StContext<'_>,
+        gh_client: &Octocrab,
+        owner: &str,
+        repo: &str,
+        stack: &[String],
+        method: MergeMethod,
+    ) -> StResult<()> {
+        for branch in stack.iter().skip(1) {
+            // Skip branches that are not submitted as PRs, or were deleted in pre-flight.
+            let Some(remote_meta) = ctx.tree.get(branch).and_then(|b| b.remote.as_ref()) else {
                 continue;
             };
 
-            // If the PR has been submitted, update the comment.
-            // If the PR is new, create a new comment.
-            let rendered_comment = Self::render_pr_comment(ctx, branch, stack)?;
-            match remote_meta.comment_id {
-                Some(id) => {
-                    // Update the existing comment.
-                    issue_handler
-                        .update_comment(CommentId(id), rendered_comment)
-                        .await?;
-                }
-                None => {
-                    // Create a new comment.
-                    let comment_info = issue_handler
-                        .create_comment(remote_meta.pr_number, rendered_comment)
-                        .await?;
-
-                    // Get a new mutable reference to the branch and update the comment ID.
-                    ctx.tree
-                        .get_mut(branch)
-                        .expect("Must exist")
-                        .remote
-                        .as_mut()
-                        .expect("Must exist")
-                        .comment_id = Some(comment_info.id.0);
-                }
+            if github::enable_auto_merge(gh_client, owner, repo, remote_meta.pr_number, method)
+                .await?
+            {
+                progress!(
+                    "Enabled auto-merge for pull request #{} (`{}`).",
+                    remote_meta.pr_number,
+                    Color::Green.paint(branch)
+                );
+            } else {
+                progress!(
+                    "Auto-merge already enabled for pull request #{} (`{}`). Skipping.",
+                    remote_meta.pr_number,
+                    Color::Green.paint(branch)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Marks the draft PRs for the passed branches as ready for review. PRs that are not drafts
+    /// are left untouched.
+    async fn mark_ready_for_review(
+        ctx: &StContext<'_>,
+        gh_client: &Octocrab,
+        owner: &str,
+        repo: &str,
+        stack: &[String],
+    ) -> StResult<()> {
+        let mut num_transitioned = 0;
+        for branch in stack.iter().skip(1) {
+            // Skip branches that are not submitted as PRs, or were deleted in pre-flight.
+            let Some(remote_meta) = ctx.tree.get(branch).and_then(|b| b.remote.as_ref()) else {
+                continue;
+            };
+
+            if github::mark_ready_for_review(gh_client, owner, repo, remote_meta.pr_number).await? {
+                num_transitioned += 1;
+                progress!(
+                    "Marked pull request #{} (`{}`) as ready for review.",
+                    remote_meta.pr_number,
+                    Color::Green.paint(branch)
+                );
             }
         }
+
+        if num_transitioned == 0 {
+            progress!("No draft pull requests to mark as ready.");
+        }
         Ok(())
     }
 
     /// Prompts the user for metadata about the PR during the initial submission process.
     async fn prompt_pr_metadata(
-        config: &mut crate::config::StConfig,
+        config: &mut StConfig,
         branch_name: &str,
         parent_name: &str,
         commits: &[String],
-        diff: &str,
+        diff: &BranchDiff,
+        defaults: PrDefaults<'_>,
+        last_template: &mut Option<TemplateOption>,
     ) -> StResult<PRCreationMetadata> {
-        // Step 1: Write PR title
+        // Step 1: Write PR title, suggesting one derived from the commit subjects.
+        let suggested_title =
+            title_from_commits(commits, config.clean_commit_titles).unwrap_or_default();
+
+        // A prepared description takes precedence over templates and AI, and is submitted
+        // without prompting for the title or the draft state.
+        if let Some(body) = defaults.body_override {
+            progress!(
+                "Using the description prepared in `{}`.",
+                Color::Blue.paint(format!("{}/{}.md", ST_PR_BODY_DIR, branch_name))
+            );
+            let editor = editor_command(&config.editor);
+            return prepared_pr_metadata(body, &suggested_title, &diff.files, defaults, |text| {
+                Ok(
+                    pr_description_editor("Review and edit PR description", &editor)
+                        .with_predefined_text(text)
+                        .prompt()?,
+                )
+            });
+        }
+
         let title = inquire::Text::new(
             format!(
                 "Title of pull request (`{}` -> `{}`):",
@@ -289,88 +724,75 @@ impl SubmitCmd {
             )
             .as_str(),
         )
+        .with_initial_value(&suggested_title)
+        .with_validator(pr_title_validator)
         .prompt()?;
+        let title = Self::confirm_pr_title(title)?;
 
-        // Step 2: Ask to choose template if multiple templates available
-        let template_names: Vec<&str> = config.template_names();
-        let selected_template = if template_names.len() > 1 {
-            // Multiple templates, ask user to choose
-            let selected_name = inquire::Select::new(
-                "Select a PR template:",
-                template_names,
-            )
-            .prompt()?;
-
-            println!(
-                "{} {}",
-                Color::Green.paint("✓ Selected template:"),
-                Color::Cyan.paint(selected_name)
-            );
-            config.get_template(selected_name)
-        } else if template_names.len() == 1 {
-            // Single template, use it directly (no prompt)
-            config.get_template(template_names[0])
-        } else {
-            // No templates configured
-            None
+        // Step 2: Ask to choose a template, defaulting to the last choice made this session,
+        // unless a template file was given.
+        let selected_template = match defaults.template_file {
+            Some(template) => Some(template),
+            None => Self::prompt_template(config, last_template)?,
         };
 
         // Step 3: Generate PR description from Gemini (skip if no API key)
         let gemini_available = !config.gemini_api_key.is_empty();
         let ai_generated_description = if gemini_available {
-            let result = if let Some(template) = selected_template {
-                println!(
-                    "{}",
-                    Color::Blue.paint("Generating PR description with Gemini...")
-                );
-
-                crate::ai::generate_pr_description_with_template_gemini(
-                    &config.gemini_api_key,
-                    template,
-                    &title,
-                    branch_name,
-                    parent_name,
-                    commits,
-                    diff,
-                )
-                .await
-            } else {
-                println!(
-                    "{}",
-                    Color::Blue.paint("Generating PR description with Gemini...")
-                );
-
-                crate::ai::generate_pr_description_with_gemini(
-                    &config.gemini_api_key,
-                    &title,
-                    branch_name,
-                    parent_name,
-                    commits,
-                    diff,
-                )
-                .await
-            };
+            println!(
+                "{}",
+                Color::Blue.paint("Generating PR description with Gemini...")
+            );
 
-            match result {
-                Ok(generated) => {
-                    println!(
-                        "{}",
-                        Color::Green.paint("✓ Generated PR description.")
-                    );
-                    Some(generated)
-                }
-                Err(e) => {
-                    eprintln!("{}: {}", Color::Red.paint("Gemini generation failed"), e);
-                    None
+            let config = &*config;
+            let title = title.as_str();
+            let generated = generate_with_fallback(
+                || async move {
+                    match selected_template {
+                        Some(template) => {
+                            crate::ai::generate_pr_description_with_template_gemini(
+                                config,
+                                template,
+                                title,
+                                branch_name,
+                                parent_name,
+                                commits,
+                                diff,
+                            )
+                            .await
+                        }
+                        None => {
+                            crate::ai::generate_pr_description_with_gemini(
+                                config,
+                                title,
+                                branch_name,
+                                parent_name,
+                                commits,
+                                diff,
+                            )
+                            .await
+                        }
+                    }
+                },
+                prompt_generation_fallback,
+            )
+            .await?;
+            if generated.is_some() {
+                println!("{}", Color::Green.paint("✓ Generated PR description."));
+            }
+            match (generated, selected_template) {
+                (Some(generated), Some(template)) if config.preserve_template_checklists => {
+                    Some(restore_checklist(&template.content, &generated))
                 }
+                (generated, _) => generated,
             }
         } else {
             None
         };
 
         // Step 4: Open editor to edit PR description
-        // Show AI-generated description if available, otherwise show template content
-        let predefined_text = if let Some(ref ai_desc) = ai_generated_description {
+        // Show the AI-generated description if available, otherwise the template content
+        let mut predefined_text = if let Some(ref ai_desc) = ai_generated_description {
             ai_desc.clone()
         } else if let Some(template) = selected_template {
             template.content.clone()
@@ -378,21 +800,32 @@ impl SubmitCmd {
             String::new()
         };
 
+        // Prepend the diff statistics, if requested.
+        if defaults.stat {
+            predefined_text = with_diff_stat(&predefined_text, &diff.files);
+        }
+
+        let editor = editor_command(&config.editor);
         let body = if predefined_text.is_empty() {
-            inquire::Editor::new("Pull request description")
-                .with_file_extension(".md")
-                .prompt()?
+            pr_description_editor("Pull request description", &editor).prompt()?
         } else {
-            inquire::Editor::new("Review and edit PR description")
-                .with_file_extension(".md")
+            pr_description_editor("Review and edit PR description", &editor)
                 .with_predefined_text(&predefined_text)
                 .prompt()?
         };
 
-        // Step 5: Ask if it is draft or not
-        let is_draft = inquire::Confirm::new("Is this PR a draft? (default: yes)")
-            .with_default(true)
-            .prompt()?;
+        // Step 5: Ask if it is draft or not, unless decided by a flag
+        let is_draft = resolve_draft(defaults.draft_flag, defaults.draft, |default| {
+            Ok(inquire::Confirm::new(
+                format!(
+                    "Is this PR a draft? (default: {})",
+                    if default { "yes" } else { "no" }
+                )
+                .as_str(),
+            )
+            .with_default(default)
+            .prompt()?)
+        })?;
 
         // Step 6: Return metadata for submission
         Ok(PRCreationMetadata {
@@ -402,47 +835,2429 @@ impl SubmitCmd {
         })
     }
 
-    /// Renders the PR comment body for the current stack.
+    /// Prompts the user to edit the title and description of an existing PR, prefilled with
+    /// the current values.
+    fn prompt_pr_update(
+        config: &StConfig,
+        branch_name: &str,
+        parent_name: &str,
+        title: &str,
+        body: &str,
+    ) -> StResult<(String, String)> {
+        let title = inquire::Text::new(
+            format!(
+                "Title of pull request (`{}` -> `{}`):",
+                Color::Green.paint(branch_name),
+                Color::Yellow.paint(parent_name)
+            )
+            .as_str(),
+        )
+        .with_initial_value(title)
+        .with_validator(pr_title_validator)
+        .prompt()?;
+        let title = Self::confirm_pr_title(title)?;
+
+        let editor = editor_command(&config.editor);
+        let body = pr_description_editor("Review and edit PR description", &editor)
+            .with_predefined_text(body)
+            .prompt()?;
+
+        Ok((title, body))
+    }
+
+    /// Asks whether to overwrite a remote branch that has diverged from the local branch.
+    fn confirm_force_push(branch: &str) -> StResult<bool> {
+        println!(
+            "⚠️  Branch `{}` has diverged from the remote, because it was restacked or someone \
+             else force-pushed it.",
+            Color::Green.paint(branch)
+        );
+        let overwrite = inquire::Confirm::new("Force-push and overwrite the remote branch?")
+            .with_default(false)
+            .prompt()?;
+        Ok(overwrite)
+    }
+
+    /// Asks whether to go ahead with the summarized submission.
+    fn confirm_plan(_: &SubmitPlan) -> StResult<bool> {
+        let proceed = inquire::Confirm::new("Submit the stack?")
+            .with_default(true)
+            .prompt()?;
+        Ok(proceed)
+    }
+
+    /// Validates a PR title, offering to truncate titles longer than GitHub accepts.
+    fn confirm_pr_title(title: String) -> StResult<String> {
+        let title = validate_pr_title(&title)?;
+        if title.chars().count() <= MAX_PR_TITLE_LENGTH {
+            return Ok(title);
+        }
+
+        println!(
+            "⚠️  Title is longer than the {} characters GitHub accepts.",
+            Color::Yellow.paint(MAX_PR_TITLE_LENGTH.to_string())
+        );
+        let truncate = inquire::Confirm::new("Truncate the title?")
+            .with_default(true)
+            .prompt()?;
+        Ok(if truncate {
+            truncate_pr_title(&title)
+        } else {
+            title
+        })
+    }
+
+    /// Prompts the user to select one of the configured PR templates, or to write the PR
+    /// description freeform. The selection is stored in `last_template`, and is used as the
+    /// default for subsequent prompts.
+    fn prompt_template<'c>(
+        config: &'c StConfig,
+        last_template: &mut Option<TemplateOption>,
+    ) -> StResult<Option<&'c PrTemplate>> {
+        // Skip the prompt if no templates are configured.
+        if config.template_names().is_empty() {
+            return Ok(None);
+        }
+
+        let options = template_options(config);
+        let starting_cursor = last_template
+            .as_ref()
+            .and_then(|last| options.iter().position(|o| o == last))
+            .unwrap_or_default();
+        let selected = inquire::Select::new("Select a PR template:", options)
+            .with_starting_cursor(starting_cursor)
+            .prompt()?;
+
+        println!(
+            "{} {}",
+            Color::Green.paint("✓ Selected template:"),
+            Color::Cyan.paint(selected.to_string())
+        );
+
+        let template = selected_template(config, &selected);
+        *last_template = Some(selected);
+        Ok(template)
+    }
+
+    /// Renders the PR comment body for the current stack from the given template. See
+    /// [DEFAULT_STACK_COMMENT_TEMPLATE] for the supported placeholders.
+    ///
+    /// Each PR is annotated with its [ReviewDecision] from `reviews`, keyed by PR number. Only the
+    /// branches stacked with `current_branch` are listed, so independent stacks sharing the trunk
+    /// branch don't appear in each other's comments.
+    ///
+    /// [DEFAULT_STACK_COMMENT_TEMPLATE]: crate::config::DEFAULT_STACK_COMMENT_TEMPLATE
     fn render_pr_comment(
-        ctx: &StContext<'_>,
+        tree: &StackTree,
+        template: &str,
         current_branch: &str,
         stack: &[String],
+        reviews: &HashMap<u64, ReviewDecision>,
     ) -> StResult<String> {
-        let mut comment = String::new();
-        comment.push_str("## 📚 $\\text{Stack Overview}$\n\n");
-        comment.push_str("Pulls submitted in this stack:\n");
+        let mut stack_list = String::new();
+        let mut current_marker = String::new();
 
-        // Display all branches in the stack.
-        for branch in stack.iter().skip(1).rev() {
-            let tracked_branch = ctx
-                .tree
+        // Display all branches in the stack of the current branch.
+        let own_stack = tree.stack_of(current_branch)?;
+        for branch in stack
+            .iter()
+            .skip(1)
+            .rev()
+            .filter(|branch| own_stack.contains(branch))
+        {
+            let tracked_branch = tree
                 .get(branch)
                 .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?;
-            if let Some(remote) = tracked_branch.remote {
-                comment.push_str(&format!(
-                    "* #{}{}\n",
+            if let Some(remote) = &tracked_branch.remote {
+                let review_marker = match reviews.get(&remote.pr_number) {
+                    Some(ReviewDecision::Approved) => " ✅",
+                    Some(ReviewDecision::ChangesRequested) => " 🔁",
+                    Some(ReviewDecision::Pending) => " ⏳",
+                    None => "",
+                };
+                let is_current = branch == current_branch;
+                if is_current {
+                    current_marker = format!("#{}", remote.pr_number);
+                }
+                stack_list.push_str(&format!(
+                    "* #{}{}{}\n",
                     remote.pr_number,
-                    (branch == current_branch)
-                        .then_some(" 👈")
-                        .unwrap_or_default()
+                    review_marker,
+                    if is_current { " 👈" } else { "" }
                 ));
             }
         }
-        comment.push_str(format!("* `{}`\n", ctx.tree.trunk_name()).as_str());
+        stack_list.push_str(format!("* `{}`\n", tree.trunk_name()).as_str());
 
-        comment.push_str(
-            "\n_This comment was automatically generated by [`st`](https://github.com/mahbd/st)._",
-        );
-        Ok(comment)
+        Ok(template
+            .replace("{stack_list}", &stack_list)
+            .replace("{current_marker}", &current_marker)
+            .replace("{trunk}", tree.trunk_name()))
     }
 }
 
-/// Metadata about pull request creation.
-struct PRCreationMetadata {
-    /// Title of the pull request.
-    title: String,
-    /// Body of the pull request.
-    body: String,
-    /// Whether or not the pull request is a draft.
-    is_draft: bool,
+/// The GitHub operations required to maintain the stack status comments on pull requests.
+pub(crate) trait CommentBackend {
+    /// Creates a comment on a pull request, returning the ID of the new comment.
+    async fn create_comment(&self, pr_number: u64, body: String) -> StResult<u64>;
+
+    /// Replaces the body of an existing comment.
+    async fn update_comment(&self, comment_id: u64, body: String) -> StResult<()>;
+
+    /// Deletes a comment. Comments that no longer exist are treated as deleted.
+    async fn delete_comment(&self, comment_id: u64) -> StResult<()>;
+}
+
+impl CommentBackend for IssueHandler<'_> {
+    async fn create_comment(&self, pr_number: u64, body: String) -> StResult<u64> {
+        let comment = IssueHandler::create_comment(self, pr_number, body).await?;
+        Ok(comment.id.0)
+    }
+
+    async fn update_comment(&self, comment_id: u64, body: String) -> StResult<()> {
+        IssueHandler::update_comment(self, CommentId(comment_id), body).await?;
+        Ok(())
+    }
+
+    async fn delete_comment(&self, comment_id: u64) -> StResult<()> {
+        match IssueHandler::delete_comment(self, CommentId(comment_id)).await {
+            // The comment was already deleted by hand.
+            Err(octocrab::Error::GitHub { source, .. }) if source.status_code.as_u16() == 404 => {
+                Ok(())
+            }
+            result => Ok(result?),
+        }
+    }
+}
+
+/// The GitHub operations required to annotate the stack status comments with review states.
+pub(crate) trait ReviewBackend {
+    /// Returns the [ReviewDecision] of a pull request.
+    async fn review_decision(&self, pr_number: u64) -> StResult<ReviewDecision>;
+}
+
+impl ReviewBackend for PullRequestHandler<'_> {
+    async fn review_decision(&self, pr_number: u64) -> StResult<ReviewDecision> {
+        github::fetch_review_decision(self, pr_number).await
+    }
+}
+
+/// The GitHub operations required to plan a submission.
+pub(crate) trait PlanBackend {
+    /// Returns the base branch and the head commit SHA of a pull request.
+    async fn pull_request_refs(&self, pr_number: u64) -> StResult<(String, String)>;
+}
+
+impl PlanBackend for PullRequestHandler<'_> {
+    async fn pull_request_refs(&self, pr_number: u64) -> StResult<(String, String)> {
+        let pr = self.get(pr_number).await?;
+        Ok((pr.base.ref_field, pr.head.sha))
+    }
+}
+
+/// The changes a `submit` run makes on the remote, summarized for confirmation before anything
+/// is pushed.
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub(crate) struct SubmitPlan {
+    /// The branches pushed to the remote.
+    pushes: Vec<String>,
+    /// The `(branch, base)` of each pull request opened.
+    new_prs: Vec<(String, String)>,
+    /// The `(branch, old base, new base)` of each pull request retargeted.
+    base_changes: Vec<(String, String, String)>,
+    /// The branches whose pull requests are merged or closed, offered for deletion once the
+    /// submission is confirmed.
+    closed: Vec<(String, PrState)>,
+}
+
+impl SubmitPlan {
+    /// Returns whether or not the submission changes anything on the remote.
+    fn is_empty(&self) -> bool {
+        self.pushes.is_empty()
+            && self.new_prs.is_empty()
+            && self.base_changes.is_empty()
+            && self.closed.is_empty()
+    }
+}
+
+impl fmt::Display for SubmitPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let plural =
+            |n: usize, one: &str, many: &str| format!("{} {}", n, if n == 1 { one } else { many });
+        writeln!(
+            f,
+            "📋 Submit plan: {}, {}, {}.",
+            plural(self.pushes.len(), "push", "pushes"),
+            plural(self.new_prs.len(), "new pull request", "new pull requests"),
+            plural(self.base_changes.len(), "base change", "base changes")
+        )?;
+        for branch in &self.pushes {
+            writeln!(f, "  ⬆️  Push `{}`", Color::Green.paint(branch))?;
+        }
+        for (branch, base) in &self.new_prs {
+            writeln!(
+                f,
+                "  ✨ Open a pull request for `{}` onto `{}`",
+                Color::Green.paint(branch),
+                Color::Yellow.paint(base)
+            )?;
+        }
+        for (branch, old, new) in &self.base_changes {
+            writeln!(
+                f,
+                "  🔀 Retarget `{}` from `{}` to `{}`",
+                Color::Green.paint(branch),
+                Color::Yellow.paint(old),
+                Color::Yellow.paint(new)
+            )?;
+        }
+        for (branch, state) in &self.closed {
+            writeln!(
+                f,
+                "  🗑️  Offer to delete `{}`, whose pull request is {}",
+                Color::Green.paint(branch),
+                Color::Purple.paint(state.to_string())
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Computes the pushes, new pull requests, and base changes a submission makes, without
+/// changing anything locally or on the remote.
+///
+/// ## Takes
+/// - `repository` - The repository containing the branches.
+/// - `tree` - The [StackTree] containing the branches.
+/// - `branches` - The submitted branches, ordered from the trunk branch upwards.
+/// - `bases` - The `(parent, base)` of each branch above the trunk, as returned by [pr_bases].
+/// - `backend` - The [PlanBackend] to look up submitted pull requests with.
+///
+/// ## Returns
+/// - `Result<SubmitPlan>` - The summary of the submission.
+pub(crate) async fn submit_plan(
+    repository: &Repository,
+    tree: &StackTree,
+    branches: &[String],
+    bases: &[(String, String)],
+    backend: &impl PlanBackend,
+) -> StResult<SubmitPlan> {
+    let mut plan = SubmitPlan::default();
+    for (branch, (_, base)) in branches.iter().skip(1).zip(bases) {
+        let remote = tree
+            .get(branch)
+            .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?
+            .remote
+            .as_ref();
+        let Some(remote) = remote else {
+            plan.pushes.push(branch.clone());
+            plan.new_prs.push((branch.clone(), base.clone()));
+            continue;
+        };
+
+        let (remote_base, remote_head) = backend.pull_request_refs(remote.pr_number).await?;
+        let head = repository
+            .find_branch(branch, BranchType::Local)?
+            .get()
+            .target()
+            .ok_or(StError::BranchUnavailable)?
+            .to_string();
+        if head != remote_head {
+            plan.pushes.push(branch.clone());
+        }
+        if &remote_base != base {
+            plan.base_changes
+                .push((branch.clone(), remote_base, base.clone()));
+        }
+    }
+    Ok(plan)
+}
+
+/// Prints the submission plan and asks for confirmation. Submissions that change nothing on the
+/// remote proceed without asking.
+///
+/// ## Takes
+/// - `plan` - The [SubmitPlan] to confirm.
+/// - `confirm` - Asks whether to go ahead with the submission.
+///
+/// ## Returns
+/// - `Result<()>` - [StError::SubmitAborted] if the user declines the submission.
+fn confirm_submit_plan(
+    plan: &SubmitPlan,
+    confirm: impl FnOnce(&SubmitPlan) -> StResult<bool>,
+) -> StResult<()> {
+    if plan.is_empty() {
+        return Ok(());
+    }
+    println!("\n{plan}");
+    if confirm(plan)? {
+        Ok(())
+    } else {
+        Err(StError::SubmitAborted)
+    }
+}
+
+/// The GitHub operations required to retarget pull requests.
+pub(crate) trait BaseBackend {
+    /// Sets the base branch of a pull request.
+    async fn update_base(&self, pr_number: u64, base: &str) -> StResult<()>;
+}
+
+impl BaseBackend for PullRequestHandler<'_> {
+    async fn update_base(&self, pr_number: u64, base: &str) -> StResult<()> {
+        self.update(pr_number).base(base).send().await?;
+        Ok(())
+    }
+}
+
+/// Sets the base branch of the pull request of a branch, reporting the branch on failure.
+///
+/// ## Takes
+/// - `branch` - The name of the branch.
+/// - `pr_number` - The number of the branch's pull request.
+/// - `base` - The new base branch.
+/// - `backend` - The [BaseBackend] to update the pull request with.
+pub(crate) async fn update_pr_base(
+    branch: &str,
+    pr_number: u64,
+    base: &str,
+    backend: &impl BaseBackend,
+) -> StResult<()> {
+    backend
+        .update_base(pr_number, base)
+        .await
+        .with_branch(branch, BranchPhase::UpdateBase)
+}
+
+/// The GitHub operations required to assign pull requests to a milestone.
+pub(crate) trait MilestoneBackend {
+    /// Returns the number and title of each open milestone in the repository.
+    async fn milestones(&self) -> StResult<Vec<(u64, String)>>;
+
+    /// Sets the milestone of a pull request.
+    async fn set_milestone(&self, pr_number: u64, milestone: u64) -> StResult<()>;
+}
+
+/// The issues API of a GitHub repository. Pull requests are issues, so their milestone is set
+/// through it.
+struct RepoIssues<'a> {
+    /// The GitHub API client.
+    client: &'a Octocrab,
+    /// The owner of the repository.
+    owner: &'a str,
+    /// The name of the repository.
+    repo: &'a str,
+}
+
+impl MilestoneBackend for RepoIssues<'_> {
+    async fn milestones(&self) -> StResult<Vec<(u64, String)>> {
+        let route = format!("/repos/{}/{}/milestones", self.owner, self.repo);
+        let milestones: Vec<Milestone> = self
+            .client
+            .get(route, Some(&[("state", "open"), ("per_page", "100")]))
+            .await?;
+        Ok(milestones
+            .into_iter()
+            .map(|m| (m.number as u64, m.title))
+            .collect())
+    }
+
+    async fn set_milestone(&self, pr_number: u64, milestone: u64) -> StResult<()> {
+        self.client
+            .issues(self.owner, self.repo)
+            .update(pr_number)
+            .milestone(milestone)
+            .send()
+            .await?;
+        Ok(())
+    }
+}
+
+/// The GitHub operations required to assign users to pull requests.
+pub(crate) trait AssigneeBackend {
+    /// Returns the logins of the users assigned to a pull request.
+    async fn assignees(&self, pr_number: u64) -> StResult<Vec<String>>;
+
+    /// Returns whether or not a user can be assigned to issues in the repository.
+    async fn is_assignable(&self, login: &str) -> StResult<bool>;
+
+    /// Adds assignees to a pull request, keeping any existing assignees.
+    async fn add_assignees(&self, pr_number: u64, logins: &[&str]) -> StResult<()>;
+}
+
+impl AssigneeBackend for RepoIssues<'_> {
+    async fn assignees(&self, pr_number: u64) -> StResult<Vec<String>> {
+        let issue = self
+            .client
+            .issues(self.owner, self.repo)
+            .get(pr_number)
+            .await?;
+        Ok(issue.assignees.into_iter().map(|a| a.login).collect())
+    }
+
+    async fn is_assignable(&self, login: &str) -> StResult<bool> {
+        Ok(self
+            .client
+            .issues(self.owner, self.repo)
+            .check_assignee(login)
+            .await?)
+    }
+
+    async fn add_assignees(&self, pr_number: u64, logins: &[&str]) -> StResult<()> {
+        self.client
+            .issues(self.owner, self.repo)
+            .add_assignees(pr_number, logins)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Assigns users to each submitted PR in the stack. Users already assigned to a PR are skipped,
+/// and unknown logins are reported as a warning rather than failing the submission.
+///
+/// ## Takes
+/// - `tree` - The [StackTree] containing the stack.
+/// - `stack` - The stack, ordered from the trunk branch to the tip of the stack.
+/// - `logins` - The logins of the users to assign.
+/// - `backend` - The [AssigneeBackend] to assign users with.
+pub(crate) async fn apply_assignees(
+    tree: &StackTree,
+    stack: &[String],
+    logins: &[String],
+    backend: &impl AssigneeBackend,
+) -> StResult<()> {
+    // Drop duplicate and unknown logins up front, so each is only reported once.
+    let mut assignable: Vec<&str> = Vec::new();
+    for login in logins {
+        if assignable.iter().any(|a| a.eq_ignore_ascii_case(login)) {
+            continue;
+        }
+        if backend.is_assignable(login).await? {
+            assignable.push(login);
+        } else {
+            println!(
+                "⚠️  User `{}` cannot be assigned in this repository. Skipping.",
+                Color::Yellow.paint(login)
+            );
+        }
+    }
+    if assignable.is_empty() {
+        return Ok(());
+    }
+
+    for branch in stack.iter().skip(1) {
+        // Skip branches that are not submitted as PRs.
+        let Some(remote) = tree.get(branch).and_then(|b| b.remote.as_ref()) else {
+            continue;
+        };
+
+        let existing = backend.assignees(remote.pr_number).await?;
+        let missing = assignable
+            .iter()
+            .copied()
+            .filter(|login| !existing.iter().any(|e| e.eq_ignore_ascii_case(login)))
+            .collect::<Vec<_>>();
+        if missing.is_empty() {
+            continue;
+        }
+
+        backend.add_assignees(remote.pr_number, &missing).await?;
+        progress!(
+            "Assigned {} to pull request #{} (`{}`).",
+            missing
+                .iter()
+                .map(|login| format!("`{}`", Color::Yellow.paint(*login)))
+                .collect::<Vec<_>>()
+                .join(", "),
+            remote.pr_number,
+            Color::Green.paint(branch)
+        );
+    }
+    Ok(())
+}
+
+/// The GitHub operations required to label pull requests.
+pub(crate) trait LabelBackend {
+    /// Adds labels to a pull request, keeping any existing labels.
+    async fn add_labels(&self, pr_number: u64, labels: &[String]) -> StResult<()>;
+}
+
+impl LabelBackend for RepoIssues<'_> {
+    async fn add_labels(&self, pr_number: u64, labels: &[String]) -> StResult<()> {
+        self.client
+            .issues(self.owner, self.repo)
+            .add_labels(pr_number, labels)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Adds labels to each submitted PR in the stack. Labels that do not exist yet are created by
+/// GitHub.
+///
+/// ## Takes
+/// - `tree` - The [StackTree] containing the stack.
+/// - `stack` - The stack, ordered from the trunk branch to the tip of the stack.
+/// - `labels` - The names of the labels to add.
+/// - `backend` - The [LabelBackend] to add labels with.
+pub(crate) async fn apply_labels(
+    tree: &StackTree,
+    stack: &[String],
+    labels: &[String],
+    backend: &impl LabelBackend,
+) -> StResult<()> {
+    for branch in stack.iter().skip(1) {
+        // Skip branches that are not submitted as PRs.
+        let Some(remote) = tree.get(branch).and_then(|b| b.remote.as_ref()) else {
+            continue;
+        };
+        backend.add_labels(remote.pr_number, labels).await?;
+        progress!(
+            "Labeled pull request #{} (`{}`) with {}.",
+            remote.pr_number,
+            Color::Green.paint(branch),
+            labels
+                .iter()
+                .map(|label| format!("`{}`", Color::Yellow.paint(label)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+    }
+    Ok(())
+}
+
+/// Returns whether new PRs default to drafts when submitted on the active trunk of the
+/// [StackTree]. The trunk's [TrunkSettings] take precedence over the configured default.
+///
+/// [TrunkSettings]: crate::tree::TrunkSettings
+fn default_draft(tree: &StackTree, configured: bool) -> bool {
+    tree.trunk_settings().default_draft.unwrap_or(configured)
+}
+
+/// Decides whether a new PR is a draft. A `--draft` or `--no-draft` flag decides without asking;
+/// otherwise the user is asked, with the default draft setting as the default answer.
+///
+/// ## Takes
+/// - `flag` - The decision of the `--draft` or `--no-draft` flag, if either was passed.
+/// - `default` - Whether the PR defaults to a draft.
+/// - `prompt` - Asks whether the PR is a draft, given the default answer.
+///
+/// ## Returns
+/// - `Result<bool>` - Whether the PR is a draft.
+fn resolve_draft(
+    flag: Option<bool>,
+    default: bool,
+    prompt: impl FnOnce(bool) -> StResult<bool>,
+) -> StResult<bool> {
+    match flag {
+        Some(draft) => Ok(draft),
+        None => prompt(default),
+    }
+}
+
+/// Assigns each submitted PR in the stack to a milestone. A warning is printed, rather than an
+/// error returned, if the milestone does not exist.
+///
+/// ## Takes
+/// - `tree` - The [StackTree] containing the stack.
+/// - `stack` - The stack, ordered from the trunk branch to the tip of the stack.
+/// - `milestone` - The title or number of the milestone.
+/// - `backend` - The [MilestoneBackend] to set milestones with.
+pub(crate) async fn apply_milestone(
+    tree: &StackTree,
+    stack: &[String],
+    milestone: &str,
+    backend: &impl MilestoneBackend,
+) -> StResult<()> {
+    let milestones = backend.milestones().await?;
+    let Some(number) = resolve_milestone(&milestones, milestone) else {
+        println!(
+            "⚠️  Milestone `{}` not found. Skipping.",
+            Color::Yellow.paint(milestone)
+        );
+        return Ok(());
+    };
+
+    for branch in stack.iter().skip(1) {
+        // Skip branches that are not submitted as PRs.
+        let Some(remote) = tree.get(branch).and_then(|b| b.remote.as_ref()) else {
+            continue;
+        };
+        backend.set_milestone(remote.pr_number, number).await?;
+        progress!(
+            "Set milestone of pull request #{} (`{}`) to `{}`.",
+            remote.pr_number,
+            Color::Green.paint(branch),
+            Color::Yellow.paint(milestone)
+        );
+    }
+    Ok(())
+}
+
+/// Resolves a milestone title or number to the number of one of the given milestones.
+fn resolve_milestone(milestones: &[(u64, String)], milestone: &str) -> Option<u64> {
+    milestones
+        .iter()
+        .find(|(_, title)| title == milestone)
+        .or_else(|| {
+            let number = milestone.trim_start_matches('#').parse::<u64>().ok()?;
+            milestones.iter().find(|(n, _)| *n == number)
+        })
+        .map(|(number, _)| *number)
+}
+
+/// Creates or updates the stack status comment on each submitted PR in the stack. Comments whose
+/// rendered body is unchanged since they were last posted are left untouched.
+///
+/// ## Takes
+/// - `tree` - The [StackTree], updated with the ID and hash of each posted comment.
+/// - `template` - The stack comment template.
+/// - `stack` - The stack, ordered from the trunk branch to the tip of the stack.
+/// - `only` - The single branch whose comment is posted, or [None] for every branch in `stack`.
+/// - `reviews` - The [ReviewDecision] of each PR, keyed by PR number.
+/// - `backend` - The [CommentBackend] to post comments with.
+pub(crate) async fn sync_stack_comments(
+    tree: &mut StackTree,
+    template: &str,
+    stack: &[String],
+    only: Option<&str>,
+    reviews: &HashMap<u64, ReviewDecision>,
+    backend: &impl CommentBackend,
+) -> StResult<()> {
+    for branch in stack.iter().skip(1) {
+        if only.is_some_and(|only| only != branch) {
+            continue;
+        }
+
+        let tracked_branch = tree
+            .get(branch)
+            .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?;
+
+        // Skip branches that are not submitted as PRs.
+        let Some(remote_meta) = &tracked_branch.remote else {
+            continue;
+        };
+
+        let rendered_comment =
+            SubmitCmd::render_pr_comment(tree, template, branch, stack, reviews)?;
+        let hash = comment_hash(&rendered_comment);
+
+        // If the PR has been submitted, update the comment when it has changed.
+        // If the PR is new, create a new comment.
+        let comment_id = match remote_meta.comment_id {
+            Some(_) if remote_meta.comment_hash == Some(hash) => continue,
+            Some(id) => {
+                backend
+                    .update_comment(id, rendered_comment)
+                    .await
+                    .with_branch(branch, BranchPhase::WriteComment)?;
+                id
+            }
+            None => backend
+                .create_comment(remote_meta.pr_number, rendered_comment)
+                .await
+                .with_branch(branch, BranchPhase::WriteComment)?,
+        };
+
+        // Get a new mutable reference to the branch and record the posted comment.
+        let remote = tree
+            .get_mut(branch)
+            .expect("Must exist")
+            .remote
+            .as_mut()
+            .expect("Must exist");
+        remote.comment_id = Some(comment_id);
+        remote.comment_hash = Some(hash);
+    }
+    Ok(())
+}
+
+/// Deletes the stack navigation comment from the PR of a branch that has left the stack, as it
+/// would otherwise keep listing the branches the PR is no longer stacked with.
+///
+/// ## Takes
+/// - `remote` - The [RemoteMetadata] of the removed branch.
+/// - `backend` - The [CommentBackend] to delete the comment with.
+pub(crate) async fn delete_stack_comment(
+    remote: &RemoteMetadata,
+    backend: &impl CommentBackend,
+) -> StResult<()> {
+    match remote.comment_id {
+        Some(comment_id) => backend.delete_comment(comment_id).await,
+        None => Ok(()),
+    }
+}
+
+/// Deletes the stack navigation comment from the PR of a branch removed from the [StContext].
+/// The branch is already gone, so failing to reach GitHub is reported as a warning rather than
+/// an error.
+///
+/// ## Takes
+/// - `ctx` - The [StContext] the branch was removed from.
+/// - `remote` - The [RemoteMetadata] of the removed branch, if it was submitted.
+pub(crate) async fn remove_stack_comment(ctx: &StContext<'_>, remote: Option<&RemoteMetadata>) {
+    let Some(remote) = remote.filter(|r| r.comment_id.is_some()) else {
+        return;
+    };
+
+    let result = async {
+        let gh_client = github::github_client(&ctx.cfg)?;
+        let (owner, repo) = ctx.owner_and_repository()?;
+        delete_stack_comment(remote, &gh_client.issues(&owner, &repo)).await
+    }
+    .await;
+    if let Err(e) = result {
+        eprintln!(
+            "{}: {}",
+            Color::Yellow.paint(format!(
+                "Warning: Failed to delete the stack comment on pull request #{}",
+                remote.pr_number
+            )),
+            e
+        );
+    }
+}
+
+/// Returns the branches submitted with `--only`: the branch itself, preceded by its parent as
+/// the bottom of the set. The parent must already be submitted, or exist on the remote, for the
+/// pull request to target it.
+///
+/// ## Takes
+/// - `repository` - The repository containing the branches.
+/// - `tree` - The [StackTree] containing the branch.
+/// - `branch` - The name of the branch to submit.
+/// - `pr_remote` - The name of the remote that pull requests target.
+///
+/// ## Returns
+/// - `StResult<Vec<String>>` - The parent and the branch, or [StError::ParentNotSubmitted] if
+///   the pull request would have no base to target.
+fn single_branch_submission(
+    repository: &Repository,
+    tree: &StackTree,
+    branch: &str,
+    pr_remote: &str,
+) -> StResult<Vec<String>> {
+    let parent = tree
+        .get(branch)
+        .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?
+        .parent
+        .clone()
+        .ok_or(StError::CannotSubmitTrunkBranch)?;
+
+    let submitted = tree.get(&parent).is_some_and(|p| p.remote.is_some());
+    let on_remote = repository
+        .find_branch(&format!("{}/{}", pr_remote, parent), BranchType::Remote)
+        .is_ok();
+    if !submitted && !on_remote {
+        return Err(StError::ParentNotSubmitted(branch.to_string(), parent));
+    }
+    Ok(vec![parent, branch.to_string()])
+}
+
+/// Returns the tracked parent and the PR base branch of each branch in the submitted set. The base
+/// is the tracked parent, except for branches based directly on the bottom of the set, which
+/// target `base_override` when it is provided.
+///
+/// ## Takes
+/// - `tree` - The [StackTree] containing the branches.
+/// - `branches` - The submitted branches, ordered from the trunk branch upwards.
+/// - `base_override` - The base branch for the bottom PR, if overridden.
+///
+/// ## Returns
+/// - `StResult<Vec<(String, String)>>` - The `(parent, base)` of each branch above the trunk.
+fn pr_bases(
+    tree: &StackTree,
+    branches: &[String],
+    base_override: Option<&str>,
+) -> StResult<Vec<(String, String)>> {
+    let bottom = branches.first().map(String::as_str);
+    branches
+        .iter()
+        .skip(1)
+        .map(|branch| {
+            let parent = tree
+                .get(branch)
+                .ok_or_else(|| StError::BranchNotTracked(branch.to_string()))?
+                .parent
+                .clone()
+                .ok_or_else(|| {
+                    StError::BranchNotTracked(format!("Parent not found for {}", branch))
+                })?;
+            let base = match base_override {
+                Some(base) if Some(parent.as_str()) == bottom => base.to_string(),
+                _ => parent.clone(),
+            };
+            Ok((parent, base))
+        })
+        .collect()
+}
+
+/// The progress of a `submit` run, persisted after each branch so that a run interrupted partway
+/// resumes from the first incomplete branch.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub(crate) struct SubmitProgress {
+    /// Branch name -> `(head, base)` of each branch fully submitted by the interrupted run.
+    completed: HashMap<String, (String, String)>,
+}
+
+impl SubmitProgress {
+    /// Returns the path of the progress file for the given [Repository].
+    fn path(repository: &Repository) -> Option<PathBuf> {
+        repository
+            .workdir()
+            .map(|p| p.join(GIT_DIR).join(ST_SUBMIT_PROGRESS_FILE_NAME))
+    }
+
+    /// Loads the progress of an interrupted run, or empty progress if there is none.
+    pub(crate) fn load(repository: &Repository) -> Self {
+        Self::path(repository)
+            .and_then(|path| fs::read_to_string(path).ok())
+            .and_then(|contents| serde_json::from_str(&contents).ok())
+            .unwrap_or_default()
+    }
+
+    /// Writes the progress to disk.
+    pub(crate) fn save(&self, repository: &Repository) -> StResult<()> {
+        let path = Self::path(repository).ok_or(StError::GitRepositoryRootNotFound)?;
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// Removes the progress file, once a run has submitted every branch.
+    pub(crate) fn clear(repository: &Repository) -> StResult<()> {
+        match Self::path(repository) {
+            Some(path) if path.exists() => Ok(fs::remove_file(path)?),
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns whether the branch was submitted at the given head and base. A branch that has
+    /// moved or been re-targeted since must be submitted again.
+    pub(crate) fn is_complete(&self, branch: &str, head: &str, base: &str) -> bool {
+        self.completed
+            .get(branch)
+            .is_some_and(|(h, b)| h == head && b == base)
+    }
+
+    /// Records the branch as submitted at the given head and base.
+    pub(crate) fn complete(&mut self, branch: &str, head: &str, base: &str) {
+        self.completed
+            .insert(branch.to_string(), (head.to_string(), base.to_string()));
+    }
+}
+
+/// Returns a stable 64-bit FNV-1a hash of a comment body.
+fn comment_hash(body: &str) -> u64 {
+    body.bytes().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Returns the title and body to prefill when editing an existing PR. The values last submitted
+/// by `st` are preferred, falling back to the values on GitHub for PRs submitted before they
+/// were stored.
+fn existing_pr_metadata(
+    remote: &RemoteMetadata,
+    remote_title: Option<&str>,
+    remote_body: Option<&str>,
+) -> (String, String) {
+    let title = remote
+        .pr_title
+        .as_deref()
+        .or(remote_title)
+        .unwrap_or_default();
+    let body = remote
+        .pr_body
+        .as_deref()
+        .or(remote_body)
+        .unwrap_or_default();
+    (title.to_string(), body.to_string())
+}
+
+/// How to push a branch whose pull request has already been submitted.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum PushMode {
+    /// The remote branch is up-to-date.
+    Skip,
+    /// The remote branch can be fast-forwarded.
+    Push,
+    /// The remote branch is overwritten.
+    Force,
+}
+
+/// Decides how to push `branch` over the head of its pull request. If the remote head is not an
+/// ancestor of the local head, e.g. because a collaborator force-pushed the branch, a plain push
+/// would be rejected, so the user is asked whether to overwrite the remote branch.
+///
+/// ## Takes
+/// - `repository` - The repository containing the branch.
+/// - `branch` - The name of the branch.
+/// - `local_head` - The SHA of the local branch.
+/// - `remote_head` - The SHA of the pull request head.
+/// - `force` - Whether to force-push without asking, as with `--force`.
+/// - `confirm` - Asks whether to force-push a diverged branch.
+///
+/// ## Returns
+/// - `Result<PushMode>` - How to push the branch, or [StError::RemoteBranchDiverged] if the user
+///   declines to force-push a diverged branch.
+pub(crate) fn push_mode(
+    repository: &Repository,
+    branch: &str,
+    local_head: &str,
+    remote_head: &str,
+    force: bool,
+    confirm: impl FnOnce(&str) -> StResult<bool>,
+) -> StResult<PushMode> {
+    if local_head == remote_head {
+        return Ok(PushMode::Skip);
+    }
+    if force {
+        return Ok(PushMode::Force);
+    }
+
+    // A remote head that is missing locally was never fetched, so it can't be in local history.
+    let local = Oid::from_str(local_head)?;
+    let diverged = match Oid::from_str(remote_head) {
+        Ok(remote) if repository.find_commit(remote).is_ok() => {
+            !repository.is_ancestor(remote, local)?
+        }
+        _ => true,
+    };
+    if !diverged {
+        Ok(PushMode::Push)
+    } else if confirm(branch)? {
+        Ok(PushMode::Force)
+    } else {
+        Err(StError::RemoteBranchDiverged(branch.to_string()))
+    }
+}
+
+/// Compares the local trunk branch to its remote-tracking branch.
+///
+/// ## Takes
+/// - `repository` - The repository containing the trunk branch.
+/// - `trunk` - The name of the trunk branch.
+/// - `remote` - The name of the remote the trunk is compared against.
+///
+/// ## Returns
+/// - `Result<Option<String>>` - A warning describing how the trunk differs from the remote, or
+///   [None] if they match or the trunk has never been fetched from the remote.
+fn trunk_sync_warning(
+    repository: &Repository,
+    trunk: &str,
+    remote: &str,
+) -> StResult<Option<String>> {
+    let remote_trunk = format!("{remote}/{trunk}");
+    let Ok(remote_branch) = repository.find_branch(&remote_trunk, BranchType::Remote) else {
+        return Ok(None);
+    };
+    let local_oid = repository
+        .find_branch(trunk, BranchType::Local)?
+        .get()
+        .peel_to_commit()?
+        .id();
+    let remote_oid = remote_branch.get().peel_to_commit()?.id();
+    if local_oid == remote_oid {
+        return Ok(None);
+    }
+
+    let (ahead, behind) = repository.graph_ahead_behind(local_oid, remote_oid)?;
+    let plural = |n: usize| if n != 1 { "s" } else { "" };
+    let state = match (ahead, behind) {
+        (0, behind) => format!("{behind} commit{} behind", plural(behind)),
+        (ahead, 0) => format!("{ahead} commit{} ahead of", plural(ahead)),
+        (ahead, behind) => format!(
+            "diverged ({ahead} commit{} ahead, {behind} commit{} behind) from",
+            plural(ahead),
+            plural(behind)
+        ),
+    };
+    Ok(Some(format!(
+        "Trunk branch `{}` is {} `{}`. Run `{}` to update it before submitting.",
+        Color::Green.paint(trunk),
+        state,
+        Color::Green.paint(&remote_trunk),
+        Color::Blue.paint("st sync")
+    )))
+}
+
+/// Returns an error for the first branch whose CI checks are failing.
+fn ensure_ci_green(statuses: &[(String, CiStatus)]) -> StResult<()> {
+    match statuses
+        .iter()
+        .find(|(_, status)| matches!(status, CiStatus::Failing))
+    {
+        Some((branch, _)) => Err(StError::ChecksFailing(branch.clone())),
+        None => Ok(()),
+    }
+}
+
+/// The maximum length of a pull request title accepted by GitHub, in characters.
+const MAX_PR_TITLE_LENGTH: usize = 256;
+
+/// Returns the trimmed PR title, or [StError::PrTitleRequired] if it is empty.
+fn validate_pr_title(title: &str) -> StResult<String> {
+    let title = title.trim();
+    if title.is_empty() {
+        return Err(StError::PrTitleRequired);
+    }
+    Ok(title.to_string())
+}
+
+/// Truncates a PR title to [MAX_PR_TITLE_LENGTH] characters.
+fn truncate_pr_title(title: &str) -> String {
+    title.chars().take(MAX_PR_TITLE_LENGTH).collect()
+}
+
+/// Rejects empty PR titles while the user is typing them.
+fn pr_title_validator(title: &str) -> Result<Validation, CustomUserError> {
+    Ok(match validate_pr_title(title) {
+        Ok(_) => Validation::Valid,
+        Err(e) => Validation::Invalid(e.to_string().into()),
+    })
+}
+
+/// Splits the `editor` configuration value into the editor program and its arguments, e.g.
+/// `code --wait`. Returns an empty command if no editor is configured.
+pub(crate) fn editor_command(editor: &str) -> Vec<&OsStr> {
+    editor.split_whitespace().map(OsStr::new).collect()
+}
+
+/// Builds the editor prompt for a PR description. The configured editor command is used when
+/// present, falling back to `$VISUAL` / `$EDITOR` otherwise.
+///
+/// ## Takes
+/// - `message` - The prompt message.
+/// - `command` - The editor program and its arguments, from [editor_command].
+pub(crate) fn pr_description_editor<'a>(
+    message: &'a str,
+    command: &'a [&'a OsStr],
+) -> inquire::Editor<'a> {
+    let editor = inquire::Editor::new(message).with_file_extension(".md");
+    match command.split_first() {
+        Some((program, args)) => editor.with_editor_command(program).with_args(args),
+        None => editor,
+    }
+}
+
+/// An option of the PR template prompt.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub(crate) enum TemplateOption {
+    /// The configured template with the contained name.
+    Template(String),
+    /// Write the PR description without a template.
+    Freeform,
+}
+
+impl fmt::Display for TemplateOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Template(name) => write!(f, "{}", name),
+            Self::Freeform => write!(f, "No template (freeform)"),
+        }
+    }
+}
+
+/// Returns the template options offered at submit time: the configured templates, followed by
+/// the freeform option.
+fn template_options(config: &StConfig) -> Vec<TemplateOption> {
+    config
+        .template_names()
+        .into_iter()
+        .map(|name| TemplateOption::Template(name.to_string()))
+        .chain(std::iter::once(TemplateOption::Freeform))
+        .collect()
+}
+
+/// Resolves a template option to its [PrTemplate]. Returns [None] for the freeform option.
+fn selected_template<'c>(config: &'c StConfig, option: &TemplateOption) -> Option<&'c PrTemplate> {
+    match option {
+        TemplateOption::Template(name) => config.get_template(name),
+        TemplateOption::Freeform => None,
+    }
+}
+
+/// What to do after generating a PR description fails.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum GenerationFallback {
+    /// Try generating the description again.
+    Retry,
+    /// Write the description in the editor, starting from the template if one was selected.
+    Manual,
+    /// Abort the submission.
+    Abort,
+}
+
+impl fmt::Display for GenerationFallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Retry => write!(f, "Retry"),
+            Self::Manual => write!(f, "Write the description manually"),
+            Self::Abort => write!(f, "Abort the submission"),
+        }
+    }
+}
+
+/// Generates a PR description, asking what to do each time generation fails.
+///
+/// ## Takes
+/// - `generate` - Generates the description.
+/// - `choose` - Asks whether to retry, write the description manually, or abort after the given
+///   failure.
+///
+/// ## Returns
+/// - `StResult<Option<String>>` - The generated description, [None] to write it manually, or
+///   [StError::SubmitAborted] if the user aborts.
+pub(crate) async fn generate_with_fallback<F, Fut>(
+    mut generate: F,
+    mut choose: impl FnMut(&StError) -> StResult<GenerationFallback>,
+) -> StResult<Option<String>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = StResult<String>>,
+{
+    loop {
+        let err = match generate().await {
+            Ok(description) => return Ok(Some(description)),
+            Err(err) => err,
+        };
+        match choose(&err)? {
+            GenerationFallback::Retry => continue,
+            GenerationFallback::Manual => return Ok(None),
+            GenerationFallback::Abort => return Err(StError::SubmitAborted),
+        }
+    }
+}
+
+/// Reports a failure to generate a PR description, telling apart a Gemini API that can't be
+/// reached from one that failed to generate, and asks what to do next.
+fn prompt_generation_fallback(err: &StError) -> StResult<GenerationFallback> {
+    let reason = if is_connection_error(err) {
+        "Could not reach Gemini"
+    } else {
+        "Gemini generation failed"
+    };
+    eprintln!("{}: {}", Color::Red.paint(reason), err);
+
+    let options = vec![
+        GenerationFallback::Retry,
+        GenerationFallback::Manual,
+        GenerationFallback::Abort,
+    ];
+    Ok(inquire::Select::new("How would you like to continue?", options).prompt()?)
+}
+
+/// Loads a one-off PR template from a file, named after the file's stem.
+///
+/// ## Takes
+/// - `path` - The path of the template file.
+///
+/// ## Returns
+/// - `StResult<PrTemplate>` - The template, or [StError::TemplateFileUnreadable] if the file
+///   could not be read.
+fn load_template_file(path: &Path) -> StResult<PrTemplate> {
+    let content = fs::read_to_string(path).map_err(|source| StError::TemplateFileUnreadable {
+        path: path.display().to_string(),
+        source,
+    })?;
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    Ok(PrTemplate { name, content })
+}
+
+/// Loads the description prepared for a new pull request in `.st/pr/<branch>.md`, relative to
+/// the repository root. Branch names with slashes map to subdirectories.
+///
+/// ## Takes
+/// - `repository` - The repository containing the prepared descriptions.
+/// - `branch` - The name of the branch.
+///
+/// ## Returns
+/// - `StResult<Option<String>>` - The prepared description, or [None] if there is none.
+fn pr_body_override(repository: &Repository, branch: &str) -> StResult<Option<String>> {
+    let Some(workdir) = repository.workdir() else {
+        return Ok(None);
+    };
+    let path = workdir.join(ST_PR_BODY_DIR).join(format!("{}.md", branch));
+    match fs::read_to_string(&path) {
+        Ok(body) => Ok(Some(body)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns the metadata of a new pull request from a description prepared in
+/// `.st/pr/<branch>.md`, without prompting. A leading `# Title` heading becomes the title of the
+/// pull request and is removed from the description, and the suggested title is used otherwise.
+/// The draft state is decided by the flags, falling back to the default.
+///
+/// ## Takes
+/// - `prepared` - The prepared description.
+/// - `suggested_title` - The title derived from the commit subjects.
+/// - `files` - The changed files, summarized in the description if `--stat` is set.
+/// - `defaults` - The [PrDefaults] of the submission.
+/// - `editor` - Opens the editor prefilled with the given text, returning the saved text. Not
+///   called with `--no-edit`.
+///
+/// ## Returns
+/// - `StResult<PRCreationMetadata>` - The metadata, or [StError::PrTitleRequired] if there is no
+///   title.
+fn prepared_pr_metadata(
+    prepared: &str,
+    suggested_title: &str,
+    files: &[FileDelta],
+    defaults: PrDefaults<'_>,
+    editor: impl FnOnce(&str) -> StResult<String>,
+) -> StResult<PRCreationMetadata> {
+    let trimmed = prepared.trim_start();
+    let (title, body) = match trimmed.strip_prefix("# ") {
+        Some(rest) => {
+            let (heading, body) = rest.split_once('\n').unwrap_or((rest, ""));
+            (heading, body.trim_start())
+        }
+        None => (suggested_title, prepared),
+    };
+    let title = truncate_pr_title(&validate_pr_title(title)?);
+
+    let body = if defaults.stat {
+        with_diff_stat(body, files)
+    } else {
+        body.to_string()
+    };
+    let body = if defaults.no_edit {
+        body
+    } else {
+        editor(&body)?
+    };
+
+    Ok(PRCreationMetadata {
+        title,
+        body,
+        is_draft: defaults.draft_flag.unwrap_or(defaults.draft),
+    })
+}
+
+/// Prepends a table of the changed files to a PR description, if any files changed. See
+/// [diff_stat].
+fn with_diff_stat(description: &str, files: &[FileDelta]) -> String {
+    match diff_stat(files) {
+        Some(table) => format!("{}\n\n{}", table, description)
+            .trim_end()
+            .to_string(),
+        None => description.to_string(),
+    }
+}
+
+/// Defaults offered when prompting for the metadata of a new pull request.
+#[derive(Debug, Clone, Copy)]
+struct PrDefaults<'a> {
+    /// Whether or not to prepend a diff stat table to the description.
+    stat: bool,
+    /// Whether or not the pull request defaults to a draft.
+    draft: bool,
+    /// Whether or not the pull request is a draft, if decided by a flag rather than a prompt.
+    draft_flag: Option<bool>,
+    /// The template to use instead of prompting for one, if given with `--template-file`.
+    template_file: Option<&'a PrTemplate>,
+    /// The description prepared in `.st/pr/<branch>.md`, used instead of a template or an
+    /// AI-generated description.
+    body_override: Option<&'a str>,
+    /// Whether or not to use the prepared description as is, without opening the editor.
+    no_edit: bool,
+}
+
+/// Metadata about pull request creation.
+struct PRCreationMetadata {
+    /// Title of the pull request.
+    title: String,
+    /// Body of the pull request.
+    body: String,
+    /// Whether or not the pull request is a draft.
+    is_draft: bool,
+}
+
+#[cfg(test)]
+mod test {
+    use super::{
+        apply_assignees, apply_labels, apply_milestone, confirm_submit_plan, default_draft,
+        delete_stack_comment, editor_command, ensure_ci_green, existing_pr_metadata,
+        generate_with_fallback, load_template_file, pr_bases, pr_body_override,
+        pr_description_editor, prepared_pr_metadata, push_mode, resolve_draft, selected_template,
+        single_branch_submission, submit_plan, sync_stack_comments, template_options,
+        truncate_pr_title, trunk_sync_warning, update_pr_base, validate_pr_title,
+        with_stack_context, AssigneeBackend, BaseBackend, CommentBackend, GenerationFallback,
+        LabelBackend, MilestoneBackend, PlanBackend, PrDefaults, PushMode, ReviewBackend,
+        SubmitCmd, SubmitPlan, SubmitProgress, TemplateOption,
+    };
+    use crate::{
+        ai::{build_pr_prompt_with_template, is_connection_error},
+        config::{PrTemplate, StConfig, DEFAULT_STACK_COMMENT_TEMPLATE},
+        ctx::{json::PrState, test::test_context},
+        errors::{BranchPhase, StError, StResult},
+        git::{test::TempRepository, BranchDiff},
+        github::{aggregate_ci_status, ReviewDecision},
+        output::test::CapturedOutput,
+        tree::{RemoteMetadata, StackTree},
+    };
+    use octocrab::models::StatusState;
+    use std::{
+        cell::{Cell, RefCell},
+        collections::HashMap,
+        fs,
+        mem::ManuallyDrop,
+    };
+
+    /// A mock [CommentBackend] that records the operations performed against it.
+    #[derive(Default)]
+    struct MockComments {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl CommentBackend for MockComments {
+        async fn create_comment(&self, pr_number: u64, _: String) -> StResult<u64> {
+            self.calls
+                .borrow_mut()
+                .push(format!("create #{}", pr_number));
+            Ok(100 + pr_number)
+        }
+
+        async fn update_comment(&self, comment_id: u64, _: String) -> StResult<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("update {}", comment_id));
+            Ok(())
+        }
+
+        async fn delete_comment(&self, comment_id: u64) -> StResult<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("delete {}", comment_id));
+            Ok(())
+        }
+    }
+
+    impl ReviewBackend for MockComments {
+        async fn review_decision(&self, pr_number: u64) -> StResult<ReviewDecision> {
+            self.calls
+                .borrow_mut()
+                .push(format!("reviews #{}", pr_number));
+            Ok(ReviewDecision::Pending)
+        }
+    }
+
+    /// A mock [MilestoneBackend] that serves a fixed set of milestones and records the
+    /// operations performed against it.
+    #[derive(Default)]
+    struct MockMilestones {
+        milestones: Vec<(u64, String)>,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl MilestoneBackend for MockMilestones {
+        async fn milestones(&self) -> StResult<Vec<(u64, String)>> {
+            Ok(self.milestones.clone())
+        }
+
+        async fn set_milestone(&self, pr_number: u64, milestone: u64) -> StResult<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("milestone #{} -> {}", pr_number, milestone));
+            Ok(())
+        }
+    }
+
+    /// A mock [AssigneeBackend] with fixed collaborators and existing assignees, that records
+    /// the operations performed against it.
+    #[derive(Default)]
+    struct MockAssignees {
+        collaborators: Vec<&'static str>,
+        assigned: HashMap<u64, Vec<String>>,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl AssigneeBackend for MockAssignees {
+        async fn assignees(&self, pr_number: u64) -> StResult<Vec<String>> {
+            Ok(self.assigned.get(&pr_number).cloned().unwrap_or_default())
+        }
+
+        async fn is_assignable(&self, login: &str) -> StResult<bool> {
+            Ok(self.collaborators.contains(&login))
+        }
+
+        async fn add_assignees(&self, pr_number: u64, logins: &[&str]) -> StResult<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("assign #{} {}", pr_number, logins.join(",")));
+            Ok(())
+        }
+    }
+
+    /// A mock [LabelBackend] that records the labels added to each pull request.
+    #[derive(Default)]
+    struct MockLabels {
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl LabelBackend for MockLabels {
+        async fn add_labels(&self, pr_number: u64, labels: &[String]) -> StResult<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("label #{} {}", pr_number, labels.join(",")));
+            Ok(())
+        }
+    }
+
+    /// Returns a config with two PR templates. It is never dropped, so the user's config file
+    /// is left untouched.
+    fn config_with_templates() -> ManuallyDrop<StConfig> {
+        let mut config = ManuallyDrop::new(StConfig::default());
+        config.github_token = "ghp_test".to_string();
+        config.editor = "vim".to_string();
+        config.clean_commit_titles = true;
+        config.default_draft = true;
+        config.pr_templates = vec![
+            PrTemplate {
+                name: "feature".to_string(),
+                content: "## Summary\nFeature description.".to_string(),
+            },
+            PrTemplate {
+                name: "bugfix".to_string(),
+                content: "## Problem\nBug description.".to_string(),
+            },
+        ];
+        config
+    }
+
+    #[test]
+    fn template_options_include_freeform() {
+        let config = config_with_templates();
+        assert_eq!(
+            template_options(&config),
+            vec![
+                TemplateOption::Template("bugfix".to_string()),
+                TemplateOption::Template("feature".to_string()),
+                TemplateOption::Freeform,
+            ]
+        );
+    }
+
+    #[test]
+    fn freeform_selects_no_template() {
+        let mut config = config_with_templates();
+        assert!(selected_template(&config, &TemplateOption::Freeform).is_none());
+
+        // A template named like the freeform option is still selectable.
+        config.pr_templates.push(PrTemplate {
+            name: TemplateOption::Freeform.to_string(),
+            content: "## Notes".to_string(),
+        });
+        let option = TemplateOption::Template(TemplateOption::Freeform.to_string());
+        assert!(template_options(&config).contains(&option));
+        assert_eq!(
+            selected_template(&config, &option).map(|t| t.content.as_str()),
+            Some("## Notes")
+        );
+        assert!(selected_template(&config, &TemplateOption::Freeform).is_none());
+    }
+
+    #[test]
+    fn selected_template_feeds_generation_prompt() {
+        let config = config_with_templates();
+        let template =
+            selected_template(&config, &TemplateOption::Template("bugfix".to_string())).unwrap();
+        let diff = BranchDiff::default();
+        let prompt =
+            build_pr_prompt_with_template("", template, "Fix crash", "fix", "main", &[], &diff);
+        assert!(prompt.contains("## Problem\nBug description."));
+        assert!(!prompt.contains("## Summary\nFeature description."));
+    }
+
+    #[test]
+    fn prepared_description_is_used_without_editor() {
+        let repo = TempRepository::new();
+        let dir = repo.repository.workdir().unwrap().join(".st/pr/feature");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("login.md"), "## Summary\nPrepared by hand.").unwrap();
+
+        let body = pr_body_override(&repo.repository, "feature/login")
+            .unwrap()
+            .unwrap();
+        assert_eq!(body, "## Summary\nPrepared by hand.");
+        assert!(pr_body_override(&repo.repository, "feature/signup")
+            .unwrap()
+            .is_none());
+
+        // With `--no-edit`, nothing is prompted for: the prepared description is the body, the
+        // suggested title is the title, and the draft state is the default.
+        let defaults = PrDefaults {
+            stat: false,
+            draft: true,
+            draft_flag: None,
+            template_file: None,
+            body_override: Some(body.as_str()),
+            no_edit: true,
+        };
+        let metadata = prepared_pr_metadata(&body, "Add login", &[], defaults, |_| {
+            panic!("The editor must not open.")
+        })
+        .unwrap();
+        assert_eq!(metadata.title, "Add login");
+        assert_eq!(metadata.body, body);
+        assert!(metadata.is_draft);
+
+        // A leading heading is the title, and a draft flag decides the draft state.
+        let defaults = PrDefaults {
+            draft_flag: Some(false),
+            ..defaults
+        };
+        let metadata = prepared_pr_metadata(
+            "# Log in with SSO\n\nPrepared by hand.",
+            "Add login",
+            &[],
+            defaults,
+            |_| panic!("The editor must not open."),
+        )
+        .unwrap();
+        assert_eq!(metadata.title, "Log in with SSO");
+        assert_eq!(metadata.body, "Prepared by hand.");
+        assert!(!metadata.is_draft);
+
+        // Otherwise, the editor is prefilled with it.
+        let defaults = PrDefaults {
+            no_edit: false,
+            ..defaults
+        };
+        let metadata = prepared_pr_metadata(&body, "Add login", &[], defaults, |text| {
+            Ok(format!("{}\nEdited.", text))
+        })
+        .unwrap();
+        assert_eq!(metadata.body, "## Summary\nPrepared by hand.\nEdited.");
+    }
+
+    #[test]
+    fn template_file_feeds_generation_prompt() {
+        let repo = TempRepository::new();
+        let path = repo.repository.workdir().unwrap().join("migration.md");
+        fs::write(&path, "## Rollback plan\nHow to revert.").unwrap();
+
+        let template = load_template_file(&path).unwrap();
+        assert_eq!(template.name, "migration");
+        let diff = BranchDiff::default();
+        let prompt =
+            build_pr_prompt_with_template("", &template, "Migrate", "migrate", "main", &[], &diff);
+        assert!(prompt.contains("## Rollback plan\nHow to revert."));
+
+        // A missing file is reported with its path.
+        let missing = path.with_file_name("missing.md");
+        assert!(matches!(
+            load_template_file(&missing),
+            Err(StError::TemplateFileUnreadable { path, .. }) if path.ends_with("missing.md")
+        ));
+    }
+
+    #[test]
+    fn require_green_blocks_on_failing_checks() {
+        let statuses = vec![
+            (
+                "feature-1".to_string(),
+                aggregate_ci_status([StatusState::Success], [Some("success")]),
+            ),
+            (
+                "feature-2".to_string(),
+                aggregate_ci_status([], [Some("success"), Some("failure")]),
+            ),
+        ];
+        let err = ensure_ci_green(&statuses).unwrap_err();
+        assert!(err.to_string().contains("feature-2"));
+    }
+
+    #[test]
+    fn require_green_proceeds_when_checks_pass() {
+        let statuses = vec![
+            (
+                "feature-1".to_string(),
+                aggregate_ci_status([StatusState::Success], [Some("success")]),
+            ),
+            (
+                "feature-2".to_string(),
+                aggregate_ci_status([], [Some("neutral"), Some("skipped")]),
+            ),
+        ];
+        assert!(ensure_ci_green(&statuses).is_ok());
+    }
+
+    #[test]
+    fn stack_comment_includes_review_markers() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.insert("feature-2", "c", "feature-3").unwrap();
+        for (branch, pr_number) in [("feature-1", 1), ("feature-2", 2), ("feature-3", 3)] {
+            tree.get_mut(branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+        }
+        let stack = tree.branches().unwrap();
+        let reviews = HashMap::from([
+            (1, ReviewDecision::Approved),
+            (2, ReviewDecision::ChangesRequested),
+            (3, ReviewDecision::Pending),
+        ]);
+
+        let comment = SubmitCmd::render_pr_comment(
+            &tree,
+            DEFAULT_STACK_COMMENT_TEMPLATE,
+            "feature-2",
+            &stack,
+            &reviews,
+        )
+        .unwrap();
+        assert!(comment.contains("* #3 ⏳\n* #2 🔁 👈\n* #1 ✅\n* `main`\n"));
+    }
+
+    #[test]
+    fn stack_comment_renders_custom_template() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        for (branch, pr_number) in [("feature-1", 1), ("feature-2", 2)] {
+            tree.get_mut(branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+        }
+        let stack = tree.branches().unwrap();
+
+        let comment = SubmitCmd::render_pr_comment(
+            &tree,
+            "### {current_marker} into `{trunk}`\n{stack_list}",
+            "feature-1",
+            &stack,
+            &HashMap::new(),
+        )
+        .unwrap();
+        assert_eq!(comment, "### #1 into `main`\n* #2\n* #1 👈\n* `main`\n");
+    }
+
+    #[test]
+    fn stack_comment_lists_only_own_stack() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-a1").unwrap();
+        tree.insert("feature-a1", "b", "feature-a2").unwrap();
+        tree.insert("main", "a", "feature-b1").unwrap();
+        tree.insert("feature-b1", "c", "feature-b2").unwrap();
+        for (branch, pr_number) in [
+            ("feature-a1", 1),
+            ("feature-a2", 2),
+            ("feature-b1", 3),
+            ("feature-b2", 4),
+        ] {
+            tree.get_mut(branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+        }
+        // Submitting with `--all` passes every branch sharing the trunk.
+        let stack = tree.branches().unwrap();
+        let render = |branch| {
+            SubmitCmd::render_pr_comment(&tree, "{stack_list}", branch, &stack, &HashMap::new())
+                .unwrap()
+        };
+
+        assert_eq!(render("feature-a1"), "* #2\n* #1 👈\n* `main`\n");
+        assert_eq!(render("feature-a2"), "* #2 👈\n* #1\n* `main`\n");
+        assert_eq!(render("feature-b1"), "* #4\n* #3 👈\n* `main`\n");
+        assert_eq!(render("feature-b2"), "* #4 👈\n* #3\n* `main`\n");
+    }
+
+    #[tokio::test]
+    async fn generation_error_offers_retry_or_fallback() {
+        let attempts = Cell::new(0);
+        let generate = || {
+            attempts.set(attempts.get() + 1);
+            let attempt = attempts.get();
+            async move {
+                if attempt == 1 {
+                    Err(StError::AiGenerationFailed("model overloaded".to_string()))
+                } else {
+                    Ok("Generated".to_string())
+                }
+            }
+        };
+
+        // The failure is offered to the user, and retrying generates the description.
+        let failures = RefCell::new(Vec::new());
+        let description = generate_with_fallback(generate, |err| {
+            failures.borrow_mut().push(err.to_string());
+            Ok(GenerationFallback::Retry)
+        })
+        .await
+        .unwrap();
+        assert_eq!(description.as_deref(), Some("Generated"));
+        assert_eq!(failures.borrow().len(), 1);
+        assert!(failures.borrow()[0].contains("model overloaded"));
+        assert!(!is_connection_error(&StError::AiGenerationFailed(
+            String::new()
+        )));
+
+        // Falling back leaves the description to be written manually, or aborts.
+        let failing = || async { Err(StError::AiGenerationFailed("unloaded".to_string())) };
+        let description = generate_with_fallback(failing, |_| Ok(GenerationFallback::Manual))
+            .await
+            .unwrap();
+        assert!(description.is_none());
+        assert!(matches!(
+            generate_with_fallback(failing, |_| Ok(GenerationFallback::Abort)).await,
+            Err(StError::SubmitAborted)
+        ));
+    }
+
+    #[tokio::test]
+    async fn unchanged_stack_comments_are_not_updated() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        for (branch, pr_number) in [("feature-1", 1), ("feature-2", 2)] {
+            tree.get_mut(branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+        }
+        let stack = tree.branches().unwrap();
+        let template = DEFAULT_STACK_COMMENT_TEMPLATE;
+
+        // First submission creates the comments.
+        let backend = MockComments::default();
+        sync_stack_comments(&mut tree, template, &stack, None, &HashMap::new(), &backend)
+            .await
+            .unwrap();
+        assert_eq!(*backend.calls.borrow(), vec!["create #1", "create #2"]);
+
+        // Re-submitting an unchanged stack makes no calls.
+        let backend = MockComments::default();
+        sync_stack_comments(&mut tree, template, &stack, None, &HashMap::new(), &backend)
+            .await
+            .unwrap();
+        assert!(backend.calls.borrow().is_empty());
+
+        // A change in review state updates every comment.
+        let reviews = HashMap::from([(1, ReviewDecision::Approved)]);
+        let backend = MockComments::default();
+        sync_stack_comments(&mut tree, template, &stack, None, &reviews, &backend)
+            .await
+            .unwrap();
+        assert_eq!(*backend.calls.borrow(), vec!["update 101", "update 102"]);
+    }
+
+    #[tokio::test]
+    async fn disabled_stack_comments_make_no_calls_and_keep_comment_ids() {
+        let repo = TempRepository::new();
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        let mut remote = RemoteMetadata::new(1);
+        remote.comment_id = Some(101);
+        remote.comment_hash = Some(0);
+        tree.get_mut("feature-1").unwrap().remote = Some(remote.clone());
+        let stack = tree.branches().unwrap();
+        let mut ctx = test_context(&repo.repository, tree);
+
+        // With `--no-comment` or `disable_stack_comment`, the comment phase makes no calls and
+        // leaves the comment ID in place.
+        let no_comment = SubmitCmd {
+            no_comment: true,
+            ..Default::default()
+        };
+        let backend = MockComments::default();
+        no_comment
+            .update_pr_comments(&mut ctx, &backend, &backend, &stack)
+            .await
+            .unwrap();
+        ctx.cfg.disable_stack_comment = true;
+        SubmitCmd::default()
+            .update_pr_comments(&mut ctx, &backend, &backend, &stack)
+            .await
+            .unwrap();
+        assert!(backend.calls.borrow().is_empty());
+        assert_eq!(ctx.tree.get("feature-1").unwrap().remote, Some(remote));
+
+        // Once re-enabled, the existing comment is updated rather than duplicated.
+        ctx.cfg.disable_stack_comment = false;
+        SubmitCmd::default()
+            .update_pr_comments(&mut ctx, &backend, &backend, &stack)
+            .await
+            .unwrap();
+        assert_eq!(*backend.calls.borrow(), vec!["reviews #1", "update 101"]);
+    }
+
+    #[tokio::test]
+    async fn removed_branch_deletes_its_stack_comment() {
+        let mut remote = RemoteMetadata::new(1);
+        let backend = MockComments::default();
+        delete_stack_comment(&remote, &backend).await.unwrap();
+        assert!(backend.calls.borrow().is_empty());
+
+        remote.comment_id = Some(101);
+        delete_stack_comment(&remote, &backend).await.unwrap();
+        assert_eq!(*backend.calls.borrow(), vec!["delete 101"]);
+    }
+
+    /// A [BaseBackend] whose updates always fail.
+    struct FailingBase;
+
+    impl BaseBackend for FailingBase {
+        async fn update_base(&self, _: u64, _: &str) -> StResult<()> {
+            Err(StError::PullRequestNotFound)
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_base_update_names_branch_and_phase() {
+        let err = update_pr_base("feature-1", 1, "main", &FailingBase)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            &err,
+            StError::BranchOperationFailed { branch, phase: BranchPhase::UpdateBase, source }
+                if branch == "feature-1" && matches!(**source, StError::PullRequestNotFound)
+        ));
+        let message = err.to_string();
+        assert!(message.contains("Failed to update base for branch"));
+        assert!(message.contains("feature-1"));
+        assert!(message.contains("Remote pull request not found."));
+    }
+
+    #[test]
+    fn stack_context_is_shared_by_every_pr_in_stack() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.insert("main", "c", "other").unwrap();
+        assert_eq!(tree.stack_base("feature-2").unwrap().unwrap(), "feature-1");
+        tree.get_mut("feature-1").unwrap().stack_description =
+            Some("Migrate the store to SQLite.".to_string());
+
+        let body = |branch: &str| {
+            let context = tree.stack_description(branch);
+            with_stack_context(&format!("Changes in `{branch}`."), context)
+        };
+        for branch in ["feature-1", "feature-2"] {
+            let body = body(branch);
+            assert!(body.starts_with("<!-- st:stack-context -->"));
+            assert!(body.contains("Migrate the store to SQLite."));
+            assert!(body.ends_with(&format!("Changes in `{branch}`.")));
+        }
+        assert_eq!(body("other"), "Changes in `other`.");
+    }
+
+    #[test]
+    fn trunk_behind_remote_warns() {
+        let repo = TempRepository::new();
+        let local = repo.commit("Main");
+        let pushed = repo.commit("Merged on the remote");
+        let warning = || trunk_sync_warning(&repo.repository, "main", "origin").unwrap();
+
+        // A trunk that was never fetched is not compared.
+        assert!(warning().is_none());
+
+        repo.repository
+            .reference("refs/remotes/origin/main", pushed, true, "fetch")
+            .unwrap();
+        assert!(warning().is_none());
+
+        repo.repository
+            .reference("refs/heads/main", local, true, "reset")
+            .unwrap();
+        let message = warning().expect("Trunk behind the remote must warn");
+        assert!(message.contains("1 commit behind"));
+        assert!(message.contains("origin/main"));
+        assert!(message.contains("st sync"));
+    }
+
+    #[test]
+    fn diverged_remote_head_asks_before_force_pushing() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main").to_string();
+        repo.branch("feature-1");
+        let theirs = repo.commit("Force-pushed by a collaborator").to_string();
+        repo.checkout("main");
+        repo.branch("feature-1-local");
+        let ours = repo.commit("Local change").to_string();
+
+        let prompted = RefCell::new(vec![]);
+        let mode = |remote: &str, force: bool, confirm: &dyn Fn(&str) -> StResult<bool>| {
+            push_mode(&repo.repository, "feature-1", &ours, remote, force, confirm)
+        };
+        let never = |_: &str| -> StResult<bool> { panic!("Must not prompt") };
+        let prompted_ref = &prompted;
+        let answer = |answer| {
+            move |branch: &str| {
+                prompted_ref.borrow_mut().push(branch.to_string());
+                Ok(answer)
+            }
+        };
+
+        // Fast-forwards and up-to-date branches never prompt.
+        assert_eq!(mode(&main_head, false, &never).unwrap(), PushMode::Push);
+        assert_eq!(mode(&ours, false, &never).unwrap(), PushMode::Skip);
+        assert_eq!(mode(&theirs, true, &never).unwrap(), PushMode::Force);
+
+        // A diverged remote head force-pushes only once confirmed.
+        let confirmed = mode(&theirs, false, &answer(true));
+        assert_eq!(confirmed.unwrap(), PushMode::Force);
+        assert!(matches!(
+            mode(&theirs, false, &answer(false)),
+            Err(StError::RemoteBranchDiverged(branch)) if branch == "feature-1"
+        ));
+
+        // A remote head that was never fetched is treated as diverged.
+        let unfetched = "0123456789abcdef0123456789abcdef01234567";
+        let confirmed = mode(unfetched, false, &answer(true));
+        assert_eq!(confirmed.unwrap(), PushMode::Force);
+        assert_eq!(prompted.into_inner(), vec!["feature-1"; 3]);
+    }
+
+    #[test]
+    fn base_override_only_retargets_bottom_pr() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.insert("feature-2", "c", "feature-3").unwrap();
+        let stack = tree.branches().unwrap();
+
+        let bases = pr_bases(&tree, &stack, Some("integration")).unwrap();
+        assert_eq!(
+            bases,
+            vec![
+                ("main".to_string(), "integration".to_string()),
+                ("feature-1".to_string(), "feature-1".to_string()),
+                ("feature-2".to_string(), "feature-2".to_string()),
+            ]
+        );
+
+        let bases = pr_bases(&tree, &stack, None).unwrap();
+        assert_eq!(bases[0], ("main".to_string(), "main".to_string()));
+    }
+
+    #[tokio::test]
+    async fn milestone_applied_to_submitted_prs() {
+        // `feature-1` was submitted previously and `feature-2` was created this run; both are
+        // recorded in the tree by the time milestones are applied. `feature-3` is unsubmitted.
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.insert("feature-2", "c", "feature-3").unwrap();
+        for (branch, pr_number) in [("feature-1", 1), ("feature-2", 2)] {
+            tree.get_mut(branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+        }
+        let stack = tree.branches().unwrap();
+        let backend = MockMilestones {
+            milestones: vec![(3, "v1.0".to_string()), (7, "v2.0".to_string())],
+            ..Default::default()
+        };
+
+        apply_milestone(&tree, &stack, "v2.0", &backend)
+            .await
+            .unwrap();
+        apply_milestone(&tree, &stack, "3", &backend).await.unwrap();
+        assert_eq!(
+            *backend.calls.borrow(),
+            vec![
+                "milestone #1 -> 7",
+                "milestone #2 -> 7",
+                "milestone #1 -> 3",
+                "milestone #2 -> 3"
+            ]
+        );
+
+        // Unknown milestones are skipped without failing the submission.
+        let backend = MockMilestones::default();
+        apply_milestone(&tree, &stack, "v3.0", &backend)
+            .await
+            .unwrap();
+        assert!(backend.calls.borrow().is_empty());
+    }
+
+    #[tokio::test]
+    async fn assignees_added_only_where_missing() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.insert("feature-2", "c", "feature-3").unwrap();
+        for (branch, pr_number) in [("feature-1", 1), ("feature-2", 2), ("feature-3", 3)] {
+            tree.get_mut(branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+        }
+        let stack = tree.branches().unwrap();
+        let backend = MockAssignees {
+            collaborators: vec!["alice", "bob"],
+            assigned: HashMap::from([
+                (1, vec!["alice".to_string(), "bob".to_string()]),
+                (2, vec!["Alice".to_string()]),
+            ]),
+            ..Default::default()
+        };
+
+        let logins = ["alice", "bob", "bob", "mallory"].map(String::from);
+        apply_assignees(&tree, &stack, &logins, &backend)
+            .await
+            .unwrap();
+        assert_eq!(
+            *backend.calls.borrow(),
+            vec!["assign #2 bob", "assign #3 alice,bob"]
+        );
+    }
+
+    #[tokio::test]
+    async fn quiet_submit_prints_no_progress() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        for (branch, pr_number) in [("feature-1", 1), ("feature-2", 2)] {
+            tree.get_mut(branch).unwrap().remote = Some(RemoteMetadata::new(pr_number));
+        }
+        let stack = tree.branches().unwrap();
+        let submit = async |quiet| {
+            let output = CapturedOutput::start(quiet);
+            let milestones = MockMilestones {
+                milestones: vec![(3, "v1.0".to_string())],
+                ..Default::default()
+            };
+            let assignees = MockAssignees {
+                collaborators: vec!["alice"],
+                ..Default::default()
+            };
+            let labels = MockLabels::default();
+            apply_milestone(&tree, &stack, "v1.0", &milestones).await?;
+            apply_assignees(&tree, &stack, &["alice".to_string()], &assignees).await?;
+            apply_labels(&tree, &stack, &["stacked".to_string()], &labels).await?;
+            StResult::Ok((output.output(), labels.calls.take().len()))
+        };
+
+        let (output, labeled) = submit(false).await.unwrap();
+        assert_eq!(output.lines().count(), 6);
+        assert_eq!(labeled, 2);
+
+        // Quiet submissions succeed and make the same changes, without informational output.
+        let (output, labeled) = submit(true).await.unwrap();
+        assert_eq!(output, "");
+        assert_eq!(labeled, 2);
+    }
+
+    #[tokio::test]
+    async fn trunk_settings_override_pr_defaults() {
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.get_mut("feature-1").unwrap().remote = Some(RemoteMetadata::new(1));
+        tree.add_trunk("release".to_string());
+        let settings = tree.trunk_settings_mut("release").unwrap();
+        settings.default_draft = Some(false);
+        settings.default_labels = vec!["release".to_string()];
+
+        // PRs on `main` default to drafts, without labels.
+        assert!(default_draft(&tree, true));
+        assert!(tree.trunk_settings().default_labels.is_empty());
+
+        // PRs on `release` are ready for review, and labeled.
+        tree.switch_trunk("release").unwrap();
+        tree.insert("release", "b", "hotfix").unwrap();
+        tree.get_mut("hotfix").unwrap().remote = Some(RemoteMetadata::new(2));
+        assert!(!default_draft(&tree, true));
+
+        let backend = MockLabels::default();
+        let stack = tree.branches().unwrap();
+        let labels = &tree.trunk_settings().default_labels;
+        apply_labels(&tree, &stack, labels, &backend).await.unwrap();
+        assert_eq!(*backend.calls.borrow(), vec!["label #2 release"]);
+
+        // Stores written before trunk settings existed still load.
+        let store = toml::to_string(&tree).unwrap();
+        assert!(store.contains("default-draft = false"));
+        let legacy = store.replace("default-draft = false", "");
+        let legacy = legacy.replace("default-labels = [\"release\"]", "");
+        let loaded: StackTree = toml::from_str(&legacy).unwrap();
+        assert!(loaded.trunk_settings().is_empty());
+    }
+
+    #[test]
+    fn configured_draft_default_and_flags() {
+        let tree = StackTree::new("main".to_string());
+        let default = default_draft(&tree, false);
+        assert!(!default);
+
+        // Without a flag, the prompt defaults to the configured value.
+        let asked = Cell::new(None);
+        let draft = resolve_draft(SubmitCmd::default().draft_flag(), default, |default| {
+            asked.set(Some(default));
+            Ok(default)
+        });
+        assert!(!draft.unwrap());
+        assert_eq!(asked.get(), Some(false));
+
+        // `--no-draft` decides without prompting.
+        let cmd = SubmitCmd {
+            no_draft: true,
+            ..Default::default()
+        };
+        let draft = resolve_draft(cmd.draft_flag(), true, |_| panic!("prompted for draft"));
+        assert!(!draft.unwrap());
+    }
+
+    #[test]
+    fn configured_editor_command_is_used() {
+        let command = editor_command("code --wait");
+        let editor = pr_description_editor("Pull request description", &command);
+        assert_eq!(editor.editor_command, "code");
+        assert_eq!(editor.editor_command_args, ["--wait"]);
+        assert_eq!(editor.file_extension, ".md");
+
+        // An empty editor falls back to the environment.
+        let command = editor_command("");
+        let editor = pr_description_editor("Pull request description", &command);
+        let default = inquire::Editor::new("Pull request description");
+        assert_eq!(editor.editor_command, default.editor_command);
+        assert!(editor.editor_command_args.is_empty());
+    }
+
+    #[test]
+    fn empty_pr_title_is_rejected() {
+        assert!(matches!(
+            validate_pr_title(""),
+            Err(StError::PrTitleRequired)
+        ));
+        assert!(matches!(
+            validate_pr_title("  \t"),
+            Err(StError::PrTitleRequired)
+        ));
+        assert_eq!(validate_pr_title(" Add feature ").unwrap(), "Add feature");
+    }
+
+    #[test]
+    fn long_pr_title_is_truncated_to_limit() {
+        let title = "é".repeat(300);
+        assert_eq!(truncate_pr_title(&title).chars().count(), 256);
+        assert_eq!(truncate_pr_title("Short"), "Short");
+    }
+
+    #[test]
+    fn update_metadata_prefills_stored_body() {
+        let mut remote = RemoteMetadata::new(1);
+        remote.pr_title = Some("Stored title".to_string());
+        remote.pr_body = Some("## Summary\nStored body.".to_string());
+
+        let (title, body) =
+            existing_pr_metadata(&remote, Some("GitHub title"), Some("GitHub body"));
+        assert_eq!(title, "Stored title");
+        assert_eq!(body, "## Summary\nStored body.");
+    }
+
+    #[test]
+    fn update_metadata_falls_back_to_remote_pr() {
+        let remote = RemoteMetadata::new(1);
+        let (title, body) = existing_pr_metadata(&remote, Some("GitHub title"), None);
+        assert_eq!(title, "GitHub title");
+        assert_eq!(body, "");
+    }
+
+    #[test]
+    fn interrupted_submit_resumes_after_completed_branches() {
+        let repo = TempRepository::new();
+        let stack = [
+            ("feature-1", "main"),
+            ("feature-2", "feature-1"),
+            ("feature-3", "feature-2"),
+        ];
+        let heads = stack
+            .iter()
+            .map(|(branch, _)| {
+                repo.branch(branch);
+                repo.commit(branch).to_string()
+            })
+            .collect::<Vec<_>>();
+
+        // Interrupt the run after the second branch.
+        let mut progress = SubmitProgress::load(&repo.repository);
+        for ((branch, base), head) in stack.iter().zip(&heads).take(2) {
+            progress.complete(branch, head, base);
+            progress.save(&repo.repository).unwrap();
+        }
+
+        let resumed = SubmitProgress::load(&repo.repository);
+        let remaining = stack
+            .iter()
+            .zip(&heads)
+            .filter(|((branch, base), head)| !resumed.is_complete(branch, head, base))
+            .map(|((branch, _), _)| *branch)
+            .collect::<Vec<_>>();
+        assert_eq!(remaining, vec!["feature-3"]);
+
+        // Branches that moved or were re-targeted since the interrupted run are submitted again.
+        assert!(!resumed.is_complete("feature-1", &heads[2], "main"));
+        assert!(!resumed.is_complete("feature-2", &heads[1], "main"));
+
+        SubmitProgress::clear(&repo.repository).unwrap();
+        assert_eq!(
+            SubmitProgress::load(&repo.repository),
+            SubmitProgress::default()
+        );
+    }
+
+    /// A mock [PlanBackend] and [BaseBackend] that records the operations performed against it.
+    #[derive(Default)]
+    struct MockPlan {
+        /// PR number -> `(base, head)` of each submitted pull request.
+        pull_requests: HashMap<u64, (String, String)>,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl PlanBackend for MockPlan {
+        async fn pull_request_refs(&self, pr_number: u64) -> StResult<(String, String)> {
+            self.calls.borrow_mut().push(format!("get #{}", pr_number));
+            Ok(self.pull_requests[&pr_number].clone())
+        }
+    }
+
+    impl BaseBackend for MockPlan {
+        async fn update_base(&self, pr_number: u64, base: &str) -> StResult<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("base #{} {}", pr_number, base));
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn declined_submit_plan_changes_nothing() {
+        let repo = TempRepository::new();
+        let remote = TempRepository::new();
+        repo.repository
+            .remote("origin", remote.repository.path().to_str().unwrap())
+            .unwrap();
+        repo.branch("feature-1");
+        let pushed = repo.commit("Feature 1").to_string();
+        repo.branch("feature-2");
+        let stale = repo.commit("Feature 2").to_string();
+        repo.commit("Feature 2, amended");
+        repo.branch("feature-3");
+        repo.commit("Feature 3");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.insert("feature-2", "c", "feature-3").unwrap();
+        tree.get_mut("feature-1").unwrap().remote = Some(RemoteMetadata::new(1));
+        tree.get_mut("feature-2").unwrap().remote = Some(RemoteMetadata::new(2));
+        let backend = MockPlan {
+            pull_requests: HashMap::from([
+                (1, ("main".to_string(), pushed)),
+                (2, ("main".to_string(), stale)),
+            ]),
+            ..Default::default()
+        };
+
+        let branches = tree.branches().unwrap();
+        let bases = pr_bases(&tree, &branches, None).unwrap();
+        let mut plan = submit_plan(&repo.repository, &tree, &branches, &bases, &backend)
+            .await
+            .unwrap();
+        assert_eq!(plan.pushes, vec!["feature-2", "feature-3"]);
+        assert_eq!(
+            plan.new_prs,
+            vec![("feature-3".to_string(), "feature-2".to_string())]
+        );
+        assert_eq!(
+            plan.base_changes,
+            vec![(
+                "feature-2".to_string(),
+                "main".to_string(),
+                "feature-1".to_string()
+            )]
+        );
+
+        // Closed pull requests found in pre-flight are only offered for deletion once confirmed.
+        plan.closed = vec![("feature-1".to_string(), PrState::Merged)];
+        let summary = plan.to_string();
+        assert!(summary.contains("2 pushes, 1 new pull request, 1 base change"));
+        assert!(summary.contains("Offer to delete"));
+
+        // Declining aborts before anything is deleted, pushed, or changed through the API.
+        let asked = Cell::new(false);
+        let result = confirm_submit_plan(&plan, |_| {
+            asked.set(true);
+            Ok(false)
+        });
+        assert!(asked.get());
+        assert!(matches!(result, Err(StError::SubmitAborted)));
+        assert_eq!(*backend.calls.borrow(), vec!["get #1", "get #2"]);
+        assert_eq!(remote.repository.branches(None).unwrap().count(), 1);
+        assert!(tree.get("feature-1").is_some());
+
+        // Submissions that change nothing proceed without asking.
+        assert!(confirm_submit_plan(&SubmitPlan::default(), |_| unreachable!()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn only_submits_a_single_branch() {
+        let repo = TempRepository::new();
+        let main_head = repo.commit("Main");
+        repo.branch("feature-1");
+        let pushed = repo.commit("Feature 1").to_string();
+        repo.branch("feature-2");
+        repo.commit("Feature 2");
+        repo.branch("feature-3");
+        repo.commit("Feature 3");
+
+        let mut tree = StackTree::new("main".to_string());
+        tree.insert("main", "a", "feature-1").unwrap();
+        tree.insert("feature-1", "b", "feature-2").unwrap();
+        tree.insert("feature-2", "c", "feature-3").unwrap();
+        tree.get_mut("feature-1").unwrap().remote = Some(RemoteMetadata::new(1));
+        let backend = MockPlan {
+            pull_requests: HashMap::from([(1, ("main".to_string(), pushed))]),
+            ..Default::default()
+        };
+
+        // Only `feature-2` is pushed and opened, onto its submitted parent.
+        let branches =
+            single_branch_submission(&repo.repository, &tree, "feature-2", "origin").unwrap();
+        assert_eq!(branches, vec!["feature-1", "feature-2"]);
+        let bases = pr_bases(&tree, &branches, None).unwrap();
+        let plan = submit_plan(&repo.repository, &tree, &branches, &bases, &backend)
+            .await
+            .unwrap();
+        assert_eq!(plan.pushes, vec!["feature-2"]);
+        assert_eq!(
+            plan.new_prs,
+            vec![("feature-2".to_string(), "feature-1".to_string())]
+        );
+        assert!(plan.base_changes.is_empty());
+        assert!(backend.calls.borrow().is_empty());
+
+        // Only its own comment is posted, listing the whole stack.
+        tree.get_mut("feature-2").unwrap().remote = Some(RemoteMetadata::new(2));
+        let stack = tree.stack_of("feature-2").unwrap();
+        let comments = MockComments::default();
+        sync_stack_comments(
+            &mut tree,
+            DEFAULT_STACK_COMMENT_TEMPLATE,
+            &stack,
+            Some("feature-2"),
+            &HashMap::new(),
+            &comments,
+        )
+        .await
+        .unwrap();
+        assert_eq!(*comments.calls.borrow(), vec!["create #2"]);
+        let feature_1 = tree.get("feature-1").unwrap();
+        assert!(feature_1.remote.as_ref().unwrap().comment_id.is_none());
+
+        // The parent must be submitted or on the remote, and the trunk can't be submitted alone.
+        tree.get_mut("feature-2").unwrap().remote = None;
+        assert!(matches!(
+            single_branch_submission(&repo.repository, &tree, "feature-3", "origin"),
+            Err(StError::ParentNotSubmitted(branch, parent))
+                if branch == "feature-3" && parent == "feature-2"
+        ));
+        assert!(matches!(
+            single_branch_submission(&repo.repository, &tree, "main", "origin"),
+            Err(StError::CannotSubmitTrunkBranch)
+        ));
+        repo.repository
+            .reference("refs/remotes/origin/main", main_head, true, "test")
+            .unwrap();
+        tree.get_mut("feature-1").unwrap().remote = None;
+        assert_eq!(
+            single_branch_submission(&repo.repository, &tree, "feature-1", "origin").unwrap(),
+            vec!["main", "feature-1"]
+        );
+    }
 }
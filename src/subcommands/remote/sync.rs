@@ -4,10 +4,12 @@ use crate::{
     ctx::{discovery, StContext},
     errors::{StError, StResult},
     git::RepositoryExt,
+    github::github_client,
+    output::progress,
+    theme::Color,
 };
 use clap::Args;
-use nu_ansi_term::Color;
-use octocrab::{pulls::PullRequestHandler, Octocrab};
+use octocrab::pulls::PullRequestHandler;
 
 /// CLI arguments for the `sync` subcommand.
 #[derive(Debug, Clone, Eq, PartialEq, Args)]
@@ -21,9 +23,7 @@ impl SyncCmd {
     /// Run the `sync` subcommand.
     pub async fn run(self, mut ctx: StContext<'_>) -> StResult<()> {
         // Establish the GitHub API client.
-        let gh_client = Octocrab::builder()
-            .personal_token(ctx.cfg.github_token.clone())
-            .build()?;
+        let gh_client = github_client(&ctx.cfg)?;
         let (owner, repo) = ctx.owner_and_repository()?;
         let mut pulls = gh_client.pulls(&owner, &repo);
 
@@ -32,17 +32,17 @@ impl SyncCmd {
 
         // Discover new branches from GitHub if requested.
         if self.discover {
-            println!("\n🔍 Discovering new branches from GitHub...");
+            progress!("\n🔍 Discovering new branches from GitHub...");
             match discovery::sync_discovered_branches(&ctx.cfg, ctx.repository, &mut ctx.tree).await
             {
                 Ok(count) if count > 0 => {
-                    println!(
+                    progress!(
                         "✓ Discovered {} new branch(es) from GitHub.",
                         Color::Green.paint(count.to_string())
                     );
                 }
                 Ok(_) => {
-                    println!("No new branches to discover.");
+                    progress!("No new branches to discover.");
                 }
                 Err(e) => {
                     eprintln!(
@@ -59,18 +59,18 @@ impl SyncCmd {
         let branches_without_trunk = branches.iter().skip(1).cloned().collect::<Vec<_>>();
 
         // Pull all of the latest changes from GitHub.
-        println!(
+        progress!(
             "\n🐙 Pulling latest changes from remote `{}`...",
-            Color::Blue.paint("origin")
+            Color::Blue.paint(ctx.cfg.push_remote())
         );
         self.pull_changes(&mut ctx, branches.as_slice()).await?;
 
         // Attempt to restack the current stack with the new changes.
-        println!("\n🧙 Restacking branches...");
+        progress!("\n🧙 Restacking branches...");
         self.try_restack_branches(ctx, branches_without_trunk.as_slice())
             .await?;
 
-        println!("\n🔄 Sync completed");
+        progress!("\n🔄 Sync completed");
         Ok(())
     }
 
@@ -113,7 +113,15 @@ impl SyncCmd {
                 continue;
             }
 
-            if let Err(e) = ctx.repository.pull_branch(branch, "origin") {
+            // Trunk is pulled from the repository PRs target, and stacked branches from the
+            // repository they are pushed to.
+            let remote_name = if branch == ctx.tree.trunk_name() {
+                ctx.cfg.pr_remote().to_string()
+            } else {
+                ctx.cfg.push_remote().to_string()
+            };
+
+            if let Err(e) = ctx.repository.pull_branch(branch, &remote_name) {
                 eprintln!("{}\n\n", e);
 
                 let message = format!(
@@ -128,7 +136,7 @@ impl SyncCmd {
 
                 if option.contains("Overwrite") {
                     ctx.repository
-                        .set_target_to_upstream_ref(branch, "origin")?;
+                        .set_target_to_upstream_ref(branch, &remote_name)?;
                     println!(
                         "Successfully overwrote local branch `{}` with remote version.",
                         Color::Green.paint(branch)
@@ -145,37 +153,13 @@ impl SyncCmd {
         mut ctx: StContext<'_>,
         branches: &[String],
     ) -> StResult<()> {
-        for branch in branches {
-            if ctx.needs_restack(branch)? {
-                let tracked_branch = ctx
-                    .tree
-                    .get(branch)
-                    .ok_or_else(|| StError::BranchNotTracked(branch.clone()))?;
-                let parent_name = tracked_branch
-                    .parent
-                    .as_ref()
-                    .expect("Parent must exist")
-                    .clone();
-
-                let mut num_conflicts = 0;
-                if ctx.restack_branch(branch, &parent_name).is_err() {
-                    ctx.repository.abort_rebase()?;
-                    println!(
-                        "Failed to restack branch `{}` onto `{}`.",
-                        Color::Green.paint(branch),
-                        Color::Yellow.paint(parent_name)
-                    );
-                    num_conflicts += 1;
-                }
-
-                if num_conflicts > 0 {
-                    println!(
-                        "Failed to restack {} branches. You can resolve conflicts by checking out the stack and running `{}`.",
-                        Color::Red.paint(num_conflicts.to_string()),
-                        Color::Blue.paint("st restack")
-                    );
-                }
-            }
+        let num_conflicts = ctx.try_restack_branches(branches)?;
+        if num_conflicts > 0 {
+            println!(
+                "Failed to restack {} branches. You can resolve conflicts by checking out the stack and running `{}`.",
+                Color::Red.paint(num_conflicts.to_string()),
+                Color::Blue.paint("st restack")
+            );
         }
         Ok(())
     }
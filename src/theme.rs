@@ -0,0 +1,198 @@
+//! Terminal colors and symbols for `st` output.
+//!
+//! [Color] mirrors [nu_ansi_term::Color], but painting is a no-op while colors are disabled, either
+//! with the global `--color never` flag or the `NO_COLOR` environment variable. The palette and
+//! symbols of the stack tree can be customized with the `[theme]` section of the configuration.
+
+use crate::constants::{COLORS, EMPTY_CIRCLE, FILLED_CIRCLE};
+use nu_ansi_term::AnsiString;
+use serde::{Deserialize, Serialize};
+use std::{
+    borrow::Cow,
+    env,
+    io::{self, IsTerminal},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+/// Whether or not output is styled.
+static COLORS_ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// When to style output, passed with the global `--color` flag.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, clap::ValueEnum)]
+pub enum ColorChoice {
+    /// Style output if stdout is a terminal and `NO_COLOR` is unset.
+    #[default]
+    Auto,
+    /// Always style output.
+    Always,
+    /// Never style output.
+    Never,
+}
+
+/// Enables or disables styled output for the given [ColorChoice].
+///
+/// ## Takes
+/// - `choice` - The [ColorChoice] passed on the command line.
+pub fn init(choice: ColorChoice) {
+    let enabled = match choice {
+        ColorChoice::Always => true,
+        ColorChoice::Never => false,
+        ColorChoice::Auto => {
+            env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+                && io::stdout().is_terminal()
+        }
+    };
+    set_colors_enabled(enabled);
+}
+
+/// Enables or disables styled output.
+pub fn set_colors_enabled(enabled: bool) {
+    COLORS_ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Returns whether or not output is styled.
+pub fn colors_enabled() -> bool {
+    #[cfg(test)]
+    if let Some(enabled) = test::overridden_colors() {
+        return enabled;
+    }
+    COLORS_ENABLED.load(Ordering::Relaxed)
+}
+
+/// A terminal color.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Purple,
+    Cyan,
+    White,
+    DarkGray,
+}
+
+impl Color {
+    /// Paints the input in this color, unless colors are disabled.
+    pub fn paint<'a, I: Into<Cow<'a, str>>>(self, input: I) -> AnsiString<'a> {
+        self.normal().paint(input)
+    }
+
+    /// Returns a [Style] with this foreground color.
+    pub fn normal(self) -> Style {
+        Style(nu_ansi_term::Color::from(self).normal())
+    }
+
+    /// Returns a bold [Style] with this foreground color.
+    pub fn bold(self) -> Style {
+        Style(nu_ansi_term::Color::from(self).bold())
+    }
+
+    /// Returns an italic [Style] with this foreground color.
+    pub fn italic(self) -> Style {
+        Style(nu_ansi_term::Color::from(self).italic())
+    }
+}
+
+impl From<Color> for nu_ansi_term::Color {
+    fn from(color: Color) -> Self {
+        match color {
+            Color::Black => Self::Black,
+            Color::Red => Self::Red,
+            Color::Green => Self::Green,
+            Color::Yellow => Self::Yellow,
+            Color::Blue => Self::Blue,
+            Color::Purple => Self::Purple,
+            Color::Cyan => Self::Cyan,
+            Color::White => Self::White,
+            Color::DarkGray => Self::DarkGray,
+        }
+    }
+}
+
+/// A text style, which is only applied while colors are enabled.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Style(nu_ansi_term::Style);
+
+impl Style {
+    /// Paints the input in this style, unless colors are disabled.
+    pub fn paint<'a, I: Into<Cow<'a, str>>>(self, input: I) -> AnsiString<'a> {
+        if colors_enabled() {
+            self.0.paint(input)
+        } else {
+            nu_ansi_term::Style::new().paint(input)
+        }
+    }
+}
+
+/// The palette and symbols used to render the stack tree.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// The colors of each level of the stack tree, repeating for deeper levels.
+    pub palette: Vec<Color>,
+    /// The symbol marking the checked out branch.
+    pub checked_out_symbol: String,
+    /// The symbol marking every other branch.
+    pub branch_symbol: String,
+}
+
+impl Theme {
+    /// Returns whether or not the theme is the default theme.
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// Returns the color of the given level of the stack tree.
+    pub fn color(&self, depth: usize) -> Color {
+        if self.palette.is_empty() {
+            return Color::Blue;
+        }
+        self.palette[depth % self.palette.len()]
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            palette: COLORS.to_vec(),
+            checked_out_symbol: FILLED_CIRCLE.to_string(),
+            branch_symbol: EMPTY_CIRCLE.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use std::cell::Cell;
+
+    thread_local! {
+        /// Whether output of the current thread is styled, overriding the global setting.
+        static OVERRIDE: Cell<Option<bool>> = const { Cell::new(None) };
+    }
+
+    /// Enables or disables styled output on the current thread, until dropped. Unlike
+    /// [super::set_colors_enabled], this doesn't affect tests running in parallel.
+    pub(crate) struct ColorOverride;
+
+    impl ColorOverride {
+        /// Starts styling output of the current thread as if colors were `enabled`.
+        pub(crate) fn start(enabled: bool) -> Self {
+            OVERRIDE.set(Some(enabled));
+            Self
+        }
+    }
+
+    impl Drop for ColorOverride {
+        fn drop(&mut self) {
+            OVERRIDE.set(None);
+        }
+    }
+
+    /// Returns the override of the current thread, if any.
+    pub(super) fn overridden_colors() -> Option<bool> {
+        OVERRIDE.get()
+    }
+}
@@ -1,8 +1,16 @@
 //! Structured, [Serialize] + [Deserialize] representation of a stack of branches.
 
-use crate::errors::{StError, StResult};
+use crate::{
+    constants::PR_CACHE_TTL,
+    errors::{StError, StResult},
+};
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
 
 /// A simple n-nary tree of branches, with bidirectional references.
 ///
@@ -11,7 +19,7 @@ use std::collections::{HashMap, HashSet};
 ///
 /// [StContext]: crate::ctx::StContext
 /// [Repository]: git2::Repository
-#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct StackTree {
     /// The name of the active trunk branch.
@@ -26,8 +34,15 @@ pub struct StackTree {
     /// Legacy branches field for backward compatibility.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub branches: Option<HashMap<String, TrackedBranch>>,
+    /// The maximum depth of a branch below its trunk branch when traversing the tree, guarding
+    /// against overflowing the stack on a corrupted store. Defaults to [DEFAULT_MAX_STACK_DEPTH].
+    #[serde(skip)]
+    pub max_depth: Option<usize>,
 }
 
+/// The maximum depth of a branch below its trunk branch, unless configured otherwise.
+pub const DEFAULT_MAX_STACK_DEPTH: usize = 1000;
+
 /// Branches associated with a specific trunk.
 #[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 pub struct TrunkBranches {
@@ -35,16 +50,29 @@ pub struct TrunkBranches {
     pub name: String,
     /// Map of branch names to tracked branches.
     pub branches: HashMap<String, TrackedBranch>,
+    /// Pull request defaults for branches stacked on this trunk.
+    #[serde(default, skip_serializing_if = "TrunkSettings::is_empty")]
+    pub settings: TrunkSettings,
 }
 
-impl Default for StackTree {
-    fn default() -> Self {
-        Self {
-            active_trunk: String::new(),
-            trunk_name: None,
-            trunks: HashMap::new(),
-            branches: None,
-        }
+/// Per-trunk pull request defaults, which take precedence over the global configuration when
+/// submitting branches stacked on the trunk.
+#[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TrunkSettings {
+    /// Whether new pull requests default to drafts. Defaults to the `default_draft`
+    /// configuration value if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_draft: Option<bool>,
+    /// Labels added to each submitted pull request.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub default_labels: Vec<String>,
+}
+
+impl TrunkSettings {
+    /// Returns whether or not no settings are configured.
+    pub fn is_empty(&self) -> bool {
+        self == &Self::default()
     }
 }
 
@@ -66,6 +94,7 @@ impl StackTree {
                 TrunkBranches {
                     name: trunk_name.clone(),
                     branches,
+                    settings: TrunkSettings::default(),
                 },
             );
             self.active_trunk = trunk_name;
@@ -75,6 +104,7 @@ impl StackTree {
     /// Adds a new trunk branch.
     pub fn add_trunk(&mut self, trunk_name: String) {
         if !self.trunks.contains_key(&trunk_name) {
+            info!(trunk = %trunk_name, "Added trunk");
             let branches = HashMap::from([(
                 trunk_name.clone(),
                 TrackedBranch::new(trunk_name.clone(), None, None),
@@ -84,6 +114,7 @@ impl StackTree {
                 TrunkBranches {
                     name: trunk_name.clone(),
                     branches,
+                    settings: TrunkSettings::default(),
                 },
             );
         }
@@ -102,6 +133,7 @@ impl StackTree {
                 trunk_name
             )));
         }
+        info!(trunk = trunk_name, "Switched trunk");
         self.active_trunk = trunk_name.to_string();
         Ok(())
     }
@@ -119,10 +151,74 @@ impl StackTree {
                 trunk_name
             )));
         }
+        info!(trunk = trunk_name, "Removed trunk");
         self.trunks.remove(trunk_name);
         Ok(())
     }
 
+    /// Renames a trunk, keeping its tracked branches. The active trunk follows the rename.
+    ///
+    /// ## Takes
+    /// - `old_name` - The current name of the trunk.
+    /// - `new_name` - The new name of the trunk.
+    ///
+    /// ## Returns
+    /// - `Result<()>` - [StError::TrunkNotFound] if `old_name` is not a trunk, or
+    ///   [StError::TrunkAlreadyExists] if `new_name` is.
+    pub fn rename_trunk(&mut self, old_name: &str, new_name: &str) -> StResult<()> {
+        if self.trunks.contains_key(new_name) {
+            return Err(StError::TrunkAlreadyExists(new_name.to_string()));
+        }
+        let mut trunk = self
+            .trunks
+            .remove(old_name)
+            .ok_or_else(|| StError::TrunkNotFound(old_name.to_string()))?;
+
+        // Rename the trunk's own branch, and re-parent its children.
+        let mut trunk_branch = trunk
+            .branches
+            .remove(old_name)
+            .expect("Trunk branch must exist");
+        for child in &trunk_branch.children {
+            if let Some(child) = trunk.branches.get_mut(child) {
+                child.parent = Some(new_name.to_string());
+            }
+        }
+        trunk_branch.name = new_name.to_string();
+        trunk.branches.insert(new_name.to_string(), trunk_branch);
+        trunk.name = new_name.to_string();
+        self.trunks.insert(new_name.to_string(), trunk);
+
+        if self.active_trunk == old_name {
+            self.active_trunk = new_name.to_string();
+        }
+        info!(trunk = old_name, new_name, "Renamed trunk");
+        Ok(())
+    }
+
+    /// Gets the [TrunkSettings] of the active trunk.
+    pub fn trunk_settings(&self) -> &TrunkSettings {
+        static EMPTY: TrunkSettings = TrunkSettings {
+            default_draft: None,
+            default_labels: Vec::new(),
+        };
+
+        self.trunks
+            .get(&self.active_trunk)
+            .map_or(&EMPTY, |t| &t.settings)
+    }
+
+    /// Gets the [TrunkSettings] of a trunk for modification.
+    ///
+    /// ## Returns
+    /// - `Result<&mut TrunkSettings>` - [StError::TrunkNotFound] if the trunk is not tracked.
+    pub fn trunk_settings_mut(&mut self, trunk_name: &str) -> StResult<&mut TrunkSettings> {
+        self.trunks
+            .get_mut(trunk_name)
+            .map(|trunk| &mut trunk.settings)
+            .ok_or_else(|| StError::TrunkNotFound(trunk_name.to_string()))
+    }
+
     /// Gets the current trunk name.
     pub fn trunk_name(&self) -> &str {
         &self.active_trunk
@@ -195,6 +291,12 @@ impl StackTree {
 
         // Register the child branch with the parent.
         parent.children.insert(branch_name.to_string());
+        info!(
+            branch = branch_name,
+            parent = parent_name,
+            parent_oid_cache,
+            "Tracked branch"
+        );
 
         // Create the child branch.
         let child = TrackedBranch::new(
@@ -223,6 +325,7 @@ impl StackTree {
         let branch = branches
             .remove(branch_name)
             .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?;
+        info!(branch = branch_name, "Untracked branch");
 
         // Remove the child from the parent's children list.
         if let Some(ref parent_name) = branch.parent {
@@ -254,17 +357,339 @@ impl StackTree {
         Ok(branch)
     }
 
+    /// Inserts a new branch between a branch and its parent. The new branch inherits the
+    /// branch's parent and parent oid cache, and becomes the branch's only parent.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch to insert the new branch below.
+    /// - `new_branch_name` - The name of the new branch.
+    /// - `parent_oid_cache` - The [git2::Oid] cache of the new branch, for the branch above it.
+    ///
+    /// ## Returns
+    /// - `Ok(())` if the new branch was inserted.
+    /// - `Err(_)` if the branch is not tracked, or is the trunk branch.
+    pub fn insert_below(
+        &mut self,
+        branch_name: &str,
+        new_branch_name: &str,
+        parent_oid_cache: &str,
+    ) -> StResult<()> {
+        let branch = self
+            .get(branch_name)
+            .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?;
+        let parent_name = branch
+            .parent
+            .clone()
+            .ok_or(StError::CannotSplitTrunkBranch)?;
+        let branch_parent_oid_cache = branch
+            .parent_oid_cache
+            .clone()
+            .ok_or(StError::MissingParentOidCache)?;
+
+        // Stack the new branch on the parent, in place of the branch.
+        self.insert(&parent_name, &branch_parent_oid_cache, new_branch_name)?;
+        self.get_mut(&parent_name)
+            .expect("Must exist")
+            .children
+            .remove(branch_name);
+
+        // Stack the branch on the new branch.
+        self.get_mut(new_branch_name)
+            .expect("Must exist")
+            .children
+            .insert(branch_name.to_string());
+        let branch = self.get_mut(branch_name).expect("Must exist");
+        branch.parent = Some(new_branch_name.to_string());
+        branch.parent_oid_cache = Some(parent_oid_cache.to_string());
+        info!(
+            branch = branch_name,
+            below = new_branch_name,
+            "Inserted branch below"
+        );
+        Ok(())
+    }
+
+    /// Swaps a branch with its parent, so that the branch is stacked on its former grandparent
+    /// and the former parent is stacked on the branch. The branch's child, if any, is moved onto
+    /// the former parent. Parent oid caches are left untouched.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch to move down the stack.
+    ///
+    /// ## Returns
+    /// - `Ok(())` if the branches were swapped.
+    /// - `Err(_)` if the branch is stacked directly on trunk, or either branch has siblings
+    ///   that make the new order ambiguous.
+    pub fn swap_with_parent(&mut self, branch_name: &str) -> StResult<()> {
+        let branch = self
+            .get(branch_name)
+            .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?;
+        let parent_name = branch
+            .parent
+            .clone()
+            .ok_or_else(|| StError::ReorderAtBottom(branch_name.to_string()))?;
+        let parent = self
+            .get(&parent_name)
+            .ok_or_else(|| StError::BranchNotTracked(parent_name.clone()))?;
+        let Some(grandparent_name) = parent.parent.clone() else {
+            return Err(StError::ReorderAtBottom(branch_name.to_string()));
+        };
+        if parent.children.len() != 1 || branch.children.len() > 1 {
+            return Err(StError::ReorderAmbiguous(branch_name.to_string()));
+        }
+        let children = branch.children.clone();
+
+        // Re-link the grandparent onto the branch.
+        let grandparent = self
+            .get_mut(&grandparent_name)
+            .ok_or_else(|| StError::BranchNotTracked(grandparent_name.clone()))?;
+        grandparent.children.remove(&parent_name);
+        grandparent.children.insert(branch_name.to_string());
+
+        // Stack the branch on the grandparent, and the former parent on the branch.
+        let branch = self.get_mut(branch_name).expect("Must exist");
+        branch.parent = Some(grandparent_name);
+        branch.children = HashSet::from([parent_name.clone()]);
+        let parent = self.get_mut(&parent_name).expect("Must exist");
+        parent.parent = Some(branch_name.to_string());
+        parent.children = children.clone();
+
+        // Move the branch's child onto the former parent.
+        for child_name in children {
+            self.get_mut(&child_name)
+                .ok_or_else(|| StError::BranchNotTracked(child_name.clone()))?
+                .parent = Some(parent_name.clone());
+        }
+        info!(branch = branch_name, parent = %parent_name, "Swapped branch with parent");
+        Ok(())
+    }
+
+    /// Moves a branch, along with its descendants, onto a new parent. The parent oid cache of the
+    /// branch is left untouched.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch to move.
+    /// - `parent_name` - The name of the new parent.
+    ///
+    /// ## Returns
+    /// - `Ok(())` if the branch was moved.
+    /// - `Err(_)` if the branch is the trunk branch, or the new parent is the branch itself or
+    ///   one of its descendants.
+    pub fn reparent(&mut self, branch_name: &str, parent_name: &str) -> StResult<()> {
+        let old_parent_name = self
+            .get(branch_name)
+            .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?
+            .parent
+            .clone()
+            .ok_or(StError::CannotMoveTrunkBranch)?;
+        if self.get(parent_name).is_none() {
+            return Err(StError::BranchNotTracked(parent_name.to_string()));
+        }
+        if self.subtree(branch_name)?.iter().any(|b| b == parent_name) {
+            return Err(StError::InvalidOnto(
+                branch_name.to_string(),
+                parent_name.to_string(),
+            ));
+        }
+
+        // Unlink the branch from its former parent, and link it to the new parent.
+        self.get_mut(&old_parent_name)
+            .ok_or_else(|| StError::BranchNotTracked(old_parent_name.clone()))?
+            .children
+            .remove(branch_name);
+        self.get_mut(parent_name)
+            .expect("Must exist")
+            .children
+            .insert(branch_name.to_string());
+        self.get_mut(branch_name).expect("Must exist").parent = Some(parent_name.to_string());
+        info!(branch = branch_name, parent = parent_name, "Moved branch");
+        Ok(())
+    }
+
     /// Returns a vector of branch names in the stack graph. The vector is filled recursively, meaning that children are
     /// guaranteed to be listed after their parents.
     pub fn branches(&self) -> StResult<Vec<String>> {
         let mut branch_names = Vec::new();
-        self.fill_branches(self.trunk_name(), &mut branch_names)?;
+        self.fill_branches(self.trunk_name(), 0, &mut branch_names)?;
         Ok(branch_names)
     }
 
+    /// Returns a vector of the names of a branch and its descendants. Children are guaranteed to
+    /// be listed after their parents.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch at the root of the subtree.
+    ///
+    /// ## Returns
+    /// - `Result<Vec<String>>` - The branch names, starting with `branch_name`.
+    pub fn subtree(&self, branch_name: &str) -> StResult<Vec<String>> {
+        let mut branch_names = Vec::new();
+        self.fill_branches(branch_name, 0, &mut branch_names)?;
+        Ok(branch_names)
+    }
+
+    /// Returns a vector of the names of the descendants of a branch, excluding the branch itself.
+    /// Children are guaranteed to be listed after their parents.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch.
+    ///
+    /// ## Returns
+    /// - `Result<Vec<String>>` - The branch names, empty if the branch has no children.
+    pub fn descendants(&self, branch_name: &str) -> StResult<Vec<String>> {
+        let mut descendants = self.subtree(branch_name)?;
+        descendants.retain(|branch| branch != branch_name);
+        Ok(descendants)
+    }
+
+    /// Returns a vector of the names of the branches stacked with a branch: its ancestors, the
+    /// branch itself, and its descendants. Sibling stacks sharing the trunk branch are excluded.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch.
+    ///
+    /// ## Returns
+    /// - `Result<Vec<String>>` - The branch names, starting with the trunk branch.
+    pub fn stack_of(&self, branch_name: &str) -> StResult<Vec<String>> {
+        let mut ancestors = Vec::new();
+        let mut parent = self
+            .get(branch_name)
+            .ok_or_else(|| StError::BranchNotTracked(branch_name.to_string()))?
+            .parent
+            .as_deref();
+        while let Some(name) = parent {
+            ancestors.push(name.to_string());
+            parent = self
+                .get(name)
+                .ok_or_else(|| StError::BranchNotTracked(name.to_string()))?
+                .parent
+                .as_deref();
+        }
+
+        ancestors.reverse();
+        ancestors.extend(self.subtree(branch_name)?);
+        Ok(ancestors)
+    }
+
+    /// Returns the name of the bottom branch of the stack containing a branch, directly above the
+    /// trunk branch.
+    ///
+    /// ## Takes
+    /// - `branch_name` - The name of the branch.
+    ///
+    /// ## Returns
+    /// - `Result<Option<String>>` - The name of the bottom branch, or [None] if the branch is the
+    ///   trunk branch.
+    pub fn stack_base(&self, branch_name: &str) -> StResult<Option<String>> {
+        if branch_name == self.trunk_name() {
+            return Ok(None);
+        }
+        Ok(self.stack_of(branch_name)?.into_iter().nth(1))
+    }
+
+    /// Returns the shared description of the stack containing a branch, if one was set.
+    pub fn stack_description(&self, branch_name: &str) -> Option<&str> {
+        let base = self.stack_base(branch_name).ok()??;
+        self.get(&base)?.stack_description.as_deref()
+    }
+
+    /// Validates the links of every trunk's tree: the trunk branch is tracked, each branch's
+    /// parent is tracked and lists it as a child, each child points back at its parent, and every
+    /// branch is reachable from the trunk branch.
+    ///
+    /// ## Returns
+    /// - `Result<()>` - [StError::InvalidStackTree] describing the first inconsistency found.
+    pub fn validate(&self) -> StResult<()> {
+        if !self.trunks.contains_key(&self.active_trunk) {
+            return Err(StError::InvalidStackTree(format!(
+                "active trunk `{}` has no branches",
+                self.active_trunk
+            )));
+        }
+
+        let mut trunk_names = self.trunks.keys().collect::<Vec<_>>();
+        trunk_names.sort();
+        for trunk_name in trunk_names {
+            let branches = &self.trunks[trunk_name].branches;
+            let invalid = |reason: String| {
+                Err(StError::InvalidStackTree(format!(
+                    "{} on trunk `{}`",
+                    reason, trunk_name
+                )))
+            };
+            if !branches.contains_key(trunk_name) {
+                return invalid("the trunk branch is not tracked".to_string());
+            }
+
+            let mut names = branches.keys().collect::<Vec<_>>();
+            names.sort();
+            for name in names {
+                let branch = &branches[name];
+                match &branch.parent {
+                    Some(parent) => match branches.get(parent) {
+                        None => {
+                            return invalid(format!(
+                                "parent `{}` of branch `{}` is not tracked",
+                                parent, name
+                            ));
+                        }
+                        Some(parent_branch) if !parent_branch.children.contains(name) => {
+                            return invalid(format!(
+                                "branch `{}` is missing from the children of `{}`",
+                                name, parent
+                            ));
+                        }
+                        Some(_) => {}
+                    },
+                    None if name != trunk_name => {
+                        return invalid(format!("branch `{}` has no parent", name));
+                    }
+                    None => {}
+                }
+                for child in &branch.children {
+                    if branches.get(child).and_then(|c| c.parent.as_ref()) != Some(name) {
+                        return invalid(format!(
+                            "child `{}` of branch `{}` does not point back at it",
+                            child, name
+                        ));
+                    }
+                }
+            }
+
+            // With consistent links, a branch is only unreachable from the trunk within a cycle.
+            let mut reachable = HashSet::new();
+            let mut queue = vec![trunk_name];
+            while let Some(name) = queue.pop() {
+                if reachable.insert(name) {
+                    queue.extend(branches[name].children.iter());
+                }
+            }
+            if reachable.len() != branches.len() {
+                return invalid("some branches form a cycle".to_string());
+            }
+        }
+        Ok(())
+    }
+
     /// Fills a vector with the trunk branch and its children. The resulting vector is filled recursively, meaning that
     /// children are guaranteed to be listed after their parents.
-    fn fill_branches(&self, name: &str, branch_names: &mut Vec<String>) -> StResult<()> {
+    ///
+    /// Returns [StError::CorruptedStore] rather than recursing past the maximum depth, e.g. when
+    /// a branch is listed as its own descendant.
+    fn fill_branches(
+        &self,
+        name: &str,
+        depth: usize,
+        branch_names: &mut Vec<String>,
+    ) -> StResult<()> {
+        let max_depth = self.max_depth.unwrap_or(DEFAULT_MAX_STACK_DEPTH);
+        if depth > max_depth {
+            return Err(StError::CorruptedStore(format!(
+                "branch `{}` is stacked more than {} branches deep",
+                name, max_depth
+            )));
+        }
+
         let current = self
             .active_branches()
             .get(name)
@@ -274,7 +699,7 @@ impl StackTree {
         current
             .children
             .iter()
-            .try_for_each(|child| self.fill_branches(child, branch_names))
+            .try_for_each(|child| self.fill_branches(child, depth + 1, branch_names))
     }
 }
 
@@ -299,6 +724,11 @@ pub struct TrackedBranch {
     /// The [RemoteMetadata] for the branch.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub remote: Option<RemoteMetadata>,
+    /// The shared description of the stack, set with `st describe`.
+    ///
+    /// Only stored on the bottom branch of a stack, directly above the trunk branch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stack_description: Option<String>,
 }
 
 impl TrackedBranch {
@@ -320,7 +750,7 @@ impl TrackedBranch {
 }
 
 /// Remote metadata for a branch that is tracked by `st`.
-#[derive(Default, Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct RemoteMetadata {
     /// The number of the pull request on GitHub associated with the branch.
@@ -330,6 +760,26 @@ pub struct RemoteMetadata {
     /// This is used to update the comment with the latest stack status each time the stack
     /// is submitted.
     pub comment_id: Option<u64>,
+    /// A hash of the last stack status comment body posted to the pull request.
+    ///
+    /// This is used to skip updating the comment when its contents have not changed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment_hash: Option<u64>,
+    /// The title of the pull request, as last submitted by `st`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr_title: Option<String>,
+    /// The body of the pull request, as last submitted by `st`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr_body: Option<String>,
+    /// The URL of the pull request, as last fetched from GitHub.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub html_url: Option<String>,
+    /// The state of the pull request, as last fetched from GitHub.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<PrState>,
+    /// When the URL and state were last fetched, in seconds since the Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fetched_at: Option<u64>,
 }
 
 impl RemoteMetadata {
@@ -338,6 +788,74 @@ impl RemoteMetadata {
         Self {
             pr_number,
             comment_id: None,
+            comment_hash: None,
+            pr_title: None,
+            pr_body: None,
+            html_url: None,
+            state: None,
+            fetched_at: None,
         }
     }
+
+    /// Caches the URL and state of the pull request, as just fetched from GitHub.
+    ///
+    /// ## Takes
+    /// - `html_url` - The URL of the pull request, if GitHub returned one.
+    /// - `state` - The state of the pull request.
+    pub fn cache(&mut self, html_url: Option<String>, state: PrState) {
+        self.html_url = html_url.or(self.html_url.take());
+        self.state = Some(state);
+        self.fetched_at = Some(unix_timestamp());
+    }
+
+    /// Returns whether the cached URL and state were fetched within [PR_CACHE_TTL].
+    pub fn is_fresh(&self) -> bool {
+        self.fetched_at.is_some_and(|fetched_at| {
+            unix_timestamp().saturating_sub(fetched_at) < PR_CACHE_TTL.as_secs()
+        })
+    }
+
+    /// Returns the cached URL of the pull request, if it is fresh. See [Self::is_fresh].
+    pub fn cached_url(&self) -> Option<&str> {
+        self.html_url.as_deref().filter(|_| self.is_fresh())
+    }
+
+    /// Returns the cached state of the pull request, if it is fresh. See [Self::is_fresh].
+    pub fn cached_state(&self) -> Option<PrState> {
+        self.state.filter(|_| self.is_fresh())
+    }
+}
+
+/// The state of a pull request on GitHub.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PrState {
+    /// The pull request is a draft.
+    Draft,
+    /// The pull request is open for review.
+    Open,
+    /// The pull request has been merged.
+    Merged,
+    /// The pull request was closed without merging.
+    Closed,
+}
+
+impl fmt::Display for PrState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let state = match self {
+            Self::Draft => "draft",
+            Self::Open => "open",
+            Self::Merged => "merged",
+            Self::Closed => "closed",
+        };
+        f.write_str(state)
+    }
+}
+
+/// Returns the current time, in seconds since the Unix epoch.
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
 }
@@ -1,67 +1,69 @@
-use st::config::{PrTemplate, StConfig};
+use st::config::{
+    config_keys, is_secret, mask_secret, override_config_path, PrTemplate, StConfig,
+    StConfigError,
+};
+use st::theme::Theme;
+use std::mem::ManuallyDrop;
+
+/// Returns a valid configuration for a test to modify. It is never dropped, so that the user's
+/// config file is left untouched.
+fn base_config() -> ManuallyDrop<StConfig> {
+    ManuallyDrop::new(
+        toml::from_str("github_token = \"ghp_test\"\neditor = \"nano\"\n").unwrap(),
+    )
+}
 
 #[test]
 fn test_config_defaults() {
-    let config = StConfig::default();
+    let config = ManuallyDrop::new(StConfig::default());
     assert_eq!(config.github_token, "");
-    assert_eq!(config.editor, ""); // Default derive doesn't apply serde defaults
     assert_eq!(config.gemini_api_key, "");
     assert!(config.pr_templates.is_empty());
+
+    // The defaults match those of a config file that sets nothing.
+    let loaded = ManuallyDrop::new(toml::from_str::<StConfig>("github_token = \"\"").unwrap());
+    assert_eq!(config, loaded);
+    assert_eq!(config.editor, "nano");
+    assert!(config.clean_commit_titles);
+    assert!(config.default_draft);
 }
 
 #[test]
 fn test_config_serialization() {
-    let config = StConfig {
-        github_token: "test_token".to_string(),
-        editor: "vim".to_string(),
-        gemini_api_key: "test_gemini_key".to_string(),
-        pr_templates: vec![],
-    };
+    let mut config = base_config();
+    config.github_token = "test_token".to_string();
+    config.editor = "vim".to_string();
+    config.gemini_api_key = "test_gemini_key".to_string();
     
-    let serialized = toml::to_string(&config).unwrap();
-    let deserialized: StConfig = toml::from_str(&serialized).unwrap();
+    let serialized = toml::to_string(&*config).unwrap();
+    let deserialized = ManuallyDrop::new(toml::from_str::<StConfig>(&serialized).unwrap());
     
     assert_eq!(config, deserialized);
 }
 
 #[test]
 fn test_config_validate_valid() {
-    let valid_config = StConfig {
-        github_token: "ghp_test123".to_string(),
-        editor: "vim".to_string(),
-        gemini_api_key: "".to_string(),
-        pr_templates: vec![],
-    };
+    let mut valid_config = base_config();
+    valid_config.github_token = "ghp_test123".to_string();
+    valid_config.editor = "vim".to_string();
     assert!(valid_config.validate().is_ok());
 }
 
-#[test]
-fn test_config_validate_invalid() {
-    let invalid_config = StConfig {
-        github_token: "".to_string(),
-        editor: "vim".to_string(),
-        gemini_api_key: "test_key".to_string(),
-        pr_templates: vec![],
-    };
-    assert!(invalid_config.validate().is_err());
-}
-
 #[test]
 fn test_config_roundtrip() {
-    let original = StConfig {
-        github_token: "ghp_abc123".to_string(),
-        editor: "emacs".to_string(),
-        gemini_api_key: "gemini_test_key".to_string(),
-        pr_templates: vec![
-            PrTemplate {
-                name: "feature".to_string(),
-                content: "## Summary\nDescription here.".to_string(),
-            },
-        ],
-    };
+    let mut original = base_config();
+    original.github_token = "ghp_abc123".to_string();
+    original.editor = "emacs".to_string();
+    original.gemini_api_key = "gemini_test_key".to_string();
+    original.pr_templates = vec![
+        PrTemplate {
+            name: "feature".to_string(),
+            content: "## Summary\nDescription here.".to_string(),
+        },
+    ];
     
-    let toml_str = toml::to_string_pretty(&original).unwrap();
-    let parsed: StConfig = toml::from_str(&toml_str).unwrap();
+    let toml_str = toml::to_string_pretty(&*original).unwrap();
+    let parsed = ManuallyDrop::new(toml::from_str::<StConfig>(&toml_str).unwrap());
     
     assert_eq!(original, parsed);
 }
@@ -73,7 +75,7 @@ fn test_config_partial_deserialization() {
         github_token = "test_token"
     "#;
     
-    let config: StConfig = toml::from_str(toml_str).unwrap();
+    let config = ManuallyDrop::new(toml::from_str::<StConfig>(toml_str).unwrap());
     assert_eq!(config.github_token, "test_token");
     assert_eq!(config.editor, "nano"); // serde default applied during deserialization
     assert_eq!(config.gemini_api_key, ""); // skip_serializing_if means empty string default
@@ -88,7 +90,7 @@ fn test_config_all_fields() {
         gemini_api_key = "test_gemini_api_key"
     "#;
     
-    let config: StConfig = toml::from_str(toml_str).unwrap();
+    let config = ManuallyDrop::new(toml::from_str::<StConfig>(toml_str).unwrap());
     assert_eq!(config.github_token, "ghp_test");
     assert_eq!(config.editor, "code");
     assert_eq!(config.gemini_api_key, "test_gemini_api_key");
@@ -96,12 +98,8 @@ fn test_config_all_fields() {
 
 #[test]
 fn test_config_gemini_only() {
-    let config = StConfig {
-        github_token: "ghp_test".to_string(),
-        editor: "nano".to_string(),
-        gemini_api_key: "gemini_key_123".to_string(),
-        pr_templates: vec![],
-    };
+    let mut config = base_config();
+    config.gemini_api_key = "gemini_key_123".to_string();
     
     assert!(config.validate().is_ok());
     assert_eq!(config.gemini_api_key, "gemini_key_123");
@@ -109,24 +107,20 @@ fn test_config_gemini_only() {
 
 #[test]
 fn test_config_with_templates() {
-    let config = StConfig {
-        github_token: "ghp_test".to_string(),
-        editor: "nano".to_string(),
-        gemini_api_key: "".to_string(),
-        pr_templates: vec![
-            PrTemplate {
-                name: "feature".to_string(),
-                content: "## Summary\nFeature description.".to_string(),
-            },
-            PrTemplate {
-                name: "bugfix".to_string(),
-                content: "## Problem\nBug description.".to_string(),
-            },
-        ],
-    };
+    let mut config = base_config();
+    config.pr_templates = vec![
+        PrTemplate {
+            name: "feature".to_string(),
+            content: "## Summary\nFeature description.".to_string(),
+        },
+        PrTemplate {
+            name: "bugfix".to_string(),
+            content: "## Problem\nBug description.".to_string(),
+        },
+    ];
     
     assert!(config.validate().is_ok());
-    assert_eq!(config.template_names(), vec!["feature", "bugfix"]);
+    assert_eq!(config.template_names(), vec!["bugfix", "feature"]);
     assert!(config.get_template("feature").is_some());
     assert!(config.get_template("bugfix").is_some());
     assert!(config.get_template("nonexistent").is_none());
@@ -134,20 +128,18 @@ fn test_config_with_templates() {
 
 #[test]
 fn test_config_template_serialization() {
-    let config = StConfig {
-        github_token: "ghp_test".to_string(),
-        editor: "vim".to_string(),
-        gemini_api_key: "gemini_key".to_string(),
-        pr_templates: vec![
-            PrTemplate {
-                name: "refactor".to_string(),
-                content: "## Motivation\nWhy refactor?\n\n## Changes\nWhat changed?".to_string(),
-            },
-        ],
-    };
+    let mut config = base_config();
+    config.editor = "vim".to_string();
+    config.gemini_api_key = "gemini_key".to_string();
+    config.pr_templates = vec![
+        PrTemplate {
+            name: "refactor".to_string(),
+            content: "## Motivation\nWhy refactor?\n\n## Changes\nWhat changed?".to_string(),
+        },
+    ];
     
-    let serialized = toml::to_string_pretty(&config).unwrap();
-    let deserialized: StConfig = toml::from_str(&serialized).unwrap();
+    let serialized = toml::to_string_pretty(&*config).unwrap();
+    let deserialized = ManuallyDrop::new(toml::from_str::<StConfig>(&serialized).unwrap());
     
     assert_eq!(config, deserialized);
     assert_eq!(deserialized.pr_templates.len(), 1);
@@ -156,13 +148,169 @@ fn test_config_template_serialization() {
 
 #[test]
 fn test_config_no_templates() {
-    let config = StConfig {
-        github_token: "ghp_test".to_string(),
-        editor: "nano".to_string(),
-        gemini_api_key: "key".to_string(),
-        pr_templates: vec![],
-    };
+    let mut config = base_config();
+    config.gemini_api_key = "key".to_string();
     
     assert!(config.template_names().is_empty());
     assert!(config.get_template("any").is_none());
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_config_set_get_roundtrip() {
+    let mut config = base_config();
+
+    config.set_field("editor", "vim").unwrap();
+    assert_eq!(config.get_field("editor").unwrap(), Some("vim".into()));
+
+    config.set_field("gemini_api_key", "gemini_key").unwrap();
+    assert_eq!(config.get_field("gemini_api_key").unwrap(), Some("gemini_key".into()));
+
+    config.set_field("pr_templates_dir", "docs/templates").unwrap();
+    assert_eq!(
+        config.get_field("pr_templates_dir").unwrap(),
+        Some("docs/templates".into())
+    );
+
+    config.set_field("ai_prompt_template", "Describe {title}.").unwrap();
+    assert_eq!(
+        config.get_field("ai_prompt_template").unwrap(),
+        Some("Describe {title}.".into())
+    );
+
+    // Values that aren't valid TOML are taken as strings.
+    config.set_field("editor", "code --wait").unwrap();
+    assert_eq!(config.editor, "code --wait");
+    config.set_field("push_remote", "42").unwrap();
+    assert_eq!(config.push_remote, "42");
+}
+
+#[test]
+fn test_config_set_get_typed_fields() {
+    let mut config = base_config();
+
+    // Fields left at their default are omitted from the config file.
+    assert_eq!(config.get_field("default_draft").unwrap(), None);
+    config.set_field("default_draft", "false").unwrap();
+    assert!(!config.default_draft);
+    assert_eq!(config.get_field("default_draft").unwrap(), Some(false.into()));
+
+    config.set_field("max_stack_depth", "50").unwrap();
+    assert_eq!(config.max_stack_depth, Some(50));
+
+    config
+        .set_field("default_assignees", r#"["octocat", "hubot"]"#)
+        .unwrap();
+    assert_eq!(config.default_assignees, vec!["octocat", "hubot"]);
+
+    config
+        .set_field("pr_templates", r#"[{ name = "feature", content = "## Summary" }]"#)
+        .unwrap();
+    assert_eq!(config.template_names(), vec!["feature"]);
+}
+
+#[test]
+fn test_config_set_invalid_restores_previous() {
+    let mut config = base_config();
+
+    // Values of the wrong type are refused.
+    let err = config.set_field("default_draft", "maybe").unwrap_err();
+    assert!(matches!(&err, StConfigError::InvalidValue(key, _) if key == "default_draft"));
+    assert!(config.default_draft);
+    assert!(config.set_field("max_stack_depth", "-1").is_err());
+    assert_eq!(config.max_stack_depth, None);
+
+    // As are values that make the configuration invalid.
+    let duplicates = r#"[{ name = "a", content = "" }, { name = "a", content = "" }]"#;
+    let err = config.set_field("pr_templates", duplicates).unwrap_err();
+    assert!(matches!(err, StConfigError::DuplicateTemplateName(name) if name == "a"));
+    assert!(config.pr_templates.is_empty());
+}
+
+#[test]
+fn test_config_unknown_key() {
+    let mut config = base_config();
+
+    let err = config.get_field("nonexistent").unwrap_err();
+    assert!(err.to_string().contains("github_token"));
+    assert!(err.to_string().contains("default_draft"));
+    assert!(config.set_field("nonexistent", "value").is_err());
+
+    // Fields that are never written to the config file aren't keys.
+    assert!(!config_keys().contains(&"file_templates"));
+    assert!(config_keys().contains(&"theme"));
+}
+
+#[test]
+fn test_config_secret_masking() {
+    assert!(is_secret("github_token"));
+    assert!(is_secret("gemini_api_key"));
+    assert!(!is_secret("editor"));
+
+    assert_eq!(mask_secret("ghp_abcdef1234"), "**********1234");
+    assert_eq!(mask_secret("abc"), "***");
+    assert_eq!(mask_secret(""), "");
+}
+
+#[test]
+fn test_config_theme_overrides_palette_and_symbols() {
+    let toml_str = r#"
+github_token = "test_token"
+
+[theme]
+palette = ["red", "dark-gray"]
+checked_out_symbol = "*"
+"#;
+    let config = ManuallyDrop::new(toml::from_str::<StConfig>(toml_str).unwrap());
+    assert_eq!(
+        config.theme.palette,
+        vec![st::theme::Color::Red, st::theme::Color::DarkGray]
+    );
+    assert_eq!(config.theme.color(3), st::theme::Color::DarkGray);
+    assert_eq!(config.theme.checked_out_symbol, "*");
+    assert_eq!(config.theme.branch_symbol, Theme::default().branch_symbol);
+}
+
+#[test]
+fn test_config_duplicate_template_names() {
+    let toml_str = r###"
+github_token = "test_token"
+
+[[pr_templates]]
+name = "feature"
+content = "## Summary"
+
+[[pr_templates]]
+name = "bugfix"
+content = "## Problem"
+
+[[pr_templates]]
+name = "feature"
+content = "## Motivation"
+"###;
+    let config = ManuallyDrop::new(toml::from_str::<StConfig>(toml_str).unwrap());
+
+    let err = config.validate().unwrap_err();
+    assert!(matches!(&err, StConfigError::DuplicateTemplateName(name) if name == "feature"));
+    assert!(err.to_string().contains("`feature`"));
+    assert_eq!(config.template_names(), vec!["bugfix", "feature"]);
+}
+
+#[test]
+fn test_config_loads_from_explicit_path() {
+    let dir = std::env::temp_dir().join(format!("st-config-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("work.toml");
+    std::fs::write(&path, "github_token = \"work_token\"\neditor = \"nano\"\n").unwrap();
+
+    let config = ManuallyDrop::new(StConfig::try_load_from(&path).unwrap().unwrap());
+    assert_eq!(config.github_token, "work_token");
+    assert_eq!(config.editor, "nano");
+
+    // An overriding file must exist.
+    let err = override_config_path(dir.join("missing.toml")).unwrap_err();
+    assert!(matches!(&err, StConfigError::Unreadable(path, _) if path.ends_with("missing.toml")));
+    let err = override_config_path(dir.clone()).unwrap_err();
+    assert!(matches!(err, StConfigError::Unreadable(..)));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,4 +1,10 @@
-use st::errors::StError;
+use st::{
+    config::StConfigError,
+    constants::{
+        EXIT_AUTH_FAILURE, EXIT_DIRTY_TREE, EXIT_FAILURE, EXIT_NEEDS_RESTACK, EXIT_NOTHING_TO_DO,
+    },
+    errors::{BranchPhase, StError},
+};
 
 #[test]
 fn test_branch_not_tracked_error() {
@@ -16,6 +22,14 @@ fn test_branch_already_tracked_error() {
     assert!(msg.contains("already tracked"));
 }
 
+#[test]
+fn test_branch_already_exists_error() {
+    let err = StError::BranchAlreadyExists("existing-branch".to_string());
+    let msg = format!("{}", err);
+    assert!(msg.contains("existing-branch"));
+    assert!(msg.contains("already exists"));
+}
+
 #[test]
 fn test_cannot_delete_trunk_branch() {
     let err = StError::CannotDeleteTrunkBranch;
@@ -23,6 +37,13 @@ fn test_cannot_delete_trunk_branch() {
     assert!(msg.contains("Cannot delete the trunk branch"));
 }
 
+#[test]
+fn test_cannot_untrack_trunk_branch() {
+    let err = StError::CannotUntrackTrunkBranch;
+    let msg = format!("{}", err);
+    assert!(msg.contains("Cannot untrack the trunk branch"));
+}
+
 #[test]
 fn test_needs_restack_error() {
     let err = StError::NeedsRestack("feature-1".to_string());
@@ -76,6 +97,13 @@ fn test_commit_message_required() {
     assert!(msg.contains("Commit message is required"));
 }
 
+#[test]
+fn test_pr_title_required() {
+    let err = StError::PrTitleRequired;
+    let msg = format!("{}", err);
+    assert!(msg.contains("Pull request title is required"));
+}
+
 #[test]
 fn test_missing_parent_oid_cache() {
     let err = StError::MissingParentOidCache;
@@ -121,3 +149,30 @@ fn test_error_from_fmt() {
     let msg = format!("{}", st_err);
     assert!(msg.contains("write error"));
 }
+
+#[test]
+fn test_exit_codes() {
+    assert_eq!(StError::WorkingTreeDirty.exit_code(), EXIT_DIRTY_TREE);
+    assert_eq!(
+        StError::NeedsRestack("feature-1".to_string()).exit_code(),
+        EXIT_NEEDS_RESTACK
+    );
+    let missing_token = StConfigError::MissingField("github_token".to_string());
+    assert_eq!(
+        StError::StConfigError(missing_token).exit_code(),
+        EXIT_AUTH_FAILURE
+    );
+    assert_eq!(
+        StError::NothingToFixup("feature-1".to_string()).exit_code(),
+        EXIT_NOTHING_TO_DO
+    );
+    assert_eq!(StError::PrTitleRequired.exit_code(), EXIT_FAILURE);
+
+    // Errors wrapped with the branch they occurred on keep their code.
+    let wrapped = StError::BranchOperationFailed {
+        branch: "feature-1".to_string(),
+        phase: BranchPhase::Push,
+        source: Box::new(StError::WorkingTreeDirty),
+    };
+    assert_eq!(wrapped.exit_code(), EXIT_DIRTY_TREE);
+}
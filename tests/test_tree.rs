@@ -1,3 +1,4 @@
+use st::errors::StError;
 use st::tree::{StackTree, TrackedBranch, RemoteMetadata};
 use std::collections::HashMap;
 
@@ -42,6 +43,34 @@ fn test_remove_trunk() {
     assert!(!tree.list_trunks().contains(&"staging".to_string()));
 }
 
+#[test]
+fn test_rename_trunk() {
+    let mut tree = StackTree::new("main".to_string());
+    tree.insert("main", "abc123", "feature-1").unwrap();
+    tree.insert("feature-1", "def456", "feature-2").unwrap();
+    tree.add_trunk("dev".to_string());
+
+    tree.rename_trunk("main", "master").unwrap();
+    assert_eq!(tree.trunk_name(), "master");
+    assert!(!tree.list_trunks().contains(&"main".to_string()));
+    assert_eq!(tree.trunks["master"].name, "master");
+    assert_eq!(
+        tree.branches().unwrap(),
+        vec!["master", "feature-1", "feature-2"]
+    );
+    assert_eq!(tree.get("feature-1").unwrap().parent.as_deref(), Some("master"));
+    assert!(tree.get("main").is_none());
+
+    assert!(matches!(
+        tree.rename_trunk("master", "dev"),
+        Err(StError::TrunkAlreadyExists(name)) if name == "dev"
+    ));
+    assert!(matches!(
+        tree.rename_trunk("missing", "other"),
+        Err(StError::TrunkNotFound(name)) if name == "missing"
+    ));
+}
+
 #[test]
 fn test_insert_branch() {
     let mut tree = StackTree::new("main".to_string());
@@ -89,6 +118,7 @@ fn test_migration_from_legacy_format() {
             "master".to_string(),
             TrackedBranch::new("master".to_string(), None, None),
         )])),
+        max_depth: None,
     };
     
     tree.migrate_if_needed();
@@ -156,9 +186,162 @@ fn test_multiple_children() {
     assert_eq!(tree.get("feature-1b").unwrap().parent.as_ref().unwrap(), "feature-1");
 }
 
+#[test]
+fn test_swap_with_parent_reorders_linear_stack() {
+    let mut tree = StackTree::new("main".to_string());
+    tree.insert("main", "abc", "feature-1").unwrap();
+    tree.insert("feature-1", "def", "feature-2").unwrap();
+    tree.insert("feature-2", "ghi", "feature-3").unwrap();
+
+    tree.swap_with_parent("feature-2").unwrap();
+
+    assert_eq!(
+        tree.branches().unwrap(),
+        vec!["main", "feature-2", "feature-1", "feature-3"]
+    );
+    assert_eq!(tree.get("feature-2").unwrap().parent.as_deref(), Some("main"));
+    assert_eq!(tree.get("feature-1").unwrap().parent.as_deref(), Some("feature-2"));
+    assert_eq!(tree.get("feature-3").unwrap().parent.as_deref(), Some("feature-1"));
+    assert!(tree.get("main").unwrap().children.contains("feature-2"));
+    assert!(!tree.get("main").unwrap().children.contains("feature-1"));
+}
+
+#[test]
+fn test_swap_with_parent_rejects_bottom_and_ambiguous_branches() {
+    let mut tree = StackTree::new("main".to_string());
+    tree.insert("main", "abc", "feature-1").unwrap();
+    tree.insert("feature-1", "def", "feature-2").unwrap();
+    tree.insert("feature-1", "def", "sibling").unwrap();
+
+    assert!(matches!(
+        tree.swap_with_parent("feature-1"),
+        Err(StError::ReorderAtBottom(_))
+    ));
+    assert!(matches!(
+        tree.swap_with_parent("feature-2"),
+        Err(StError::ReorderAmbiguous(_))
+    ));
+}
+
 #[test]
 fn test_delete_nonexistent_branch() {
     let mut tree = StackTree::new("main".to_string());
     let result = tree.delete("nonexistent");
     assert!(result.is_err());
 }
+
+#[test]
+fn test_stack_of_excludes_sibling_stacks() {
+    let mut tree = StackTree::new("main".to_string());
+    tree.insert("main", "abc123", "feature-a1").unwrap();
+    tree.insert("feature-a1", "def456", "feature-a2").unwrap();
+    tree.insert("main", "abc123", "feature-b1").unwrap();
+
+    assert_eq!(
+        tree.stack_of("feature-a2").unwrap(),
+        vec!["main", "feature-a1", "feature-a2"]
+    );
+    assert_eq!(
+        tree.stack_of("feature-a1").unwrap(),
+        vec!["main", "feature-a1", "feature-a2"]
+    );
+    assert_eq!(
+        tree.stack_of("feature-b1").unwrap(),
+        vec!["main", "feature-b1"]
+    );
+    assert!(matches!(
+        tree.stack_of("missing"),
+        Err(StError::BranchNotTracked(name)) if name == "missing"
+    ));
+}
+
+#[test]
+fn test_reparent_moves_stack_onto_new_base() {
+    let mut tree = StackTree::new("main".to_string());
+    tree.insert("main", "abc123", "release").unwrap();
+    tree.insert("main", "abc123", "feature-1").unwrap();
+    tree.insert("feature-1", "def456", "feature-2").unwrap();
+
+    tree.reparent("feature-1", "release").unwrap();
+    assert_eq!(
+        tree.get("feature-1").unwrap().parent.as_deref(),
+        Some("release")
+    );
+    assert!(tree.get("release").unwrap().children.contains("feature-1"));
+    assert!(!tree.get("main").unwrap().children.contains("feature-1"));
+    assert_eq!(
+        tree.get("feature-2").unwrap().parent.as_deref(),
+        Some("feature-1")
+    );
+    assert_eq!(
+        tree.branches().unwrap(),
+        vec!["main", "release", "feature-1", "feature-2"]
+    );
+
+    assert!(matches!(
+        tree.reparent("feature-1", "feature-2"),
+        Err(StError::InvalidOnto(branch, onto)) if branch == "feature-1" && onto == "feature-2"
+    ));
+    assert!(matches!(
+        tree.reparent("main", "release"),
+        Err(StError::CannotMoveTrunkBranch)
+    ));
+}
+
+#[test]
+fn test_validate_detects_broken_links() {
+    let mut tree = StackTree::new("main".to_string());
+    tree.insert("main", "abc123", "feature-1").unwrap();
+    tree.insert("feature-1", "def456", "feature-2").unwrap();
+    assert!(tree.validate().is_ok());
+
+    // A child whose parent no longer lists it.
+    let mut orphaned = tree.clone();
+    orphaned.get_mut("feature-1").unwrap().children.clear();
+    assert!(matches!(
+        orphaned.validate(),
+        Err(StError::InvalidStackTree(reason)) if reason.contains("`feature-2` is missing")
+    ));
+
+    // A parent that is not tracked.
+    let mut dangling = orphaned.clone();
+    dangling.get_mut("feature-2").unwrap().parent = Some("gone".to_string());
+    assert!(matches!(
+        dangling.validate(),
+        Err(StError::InvalidStackTree(reason)) if reason.contains("`gone`")
+    ));
+}
+
+#[test]
+fn test_deep_trees_report_corrupted_store() {
+    let mut tree = StackTree::new("main".to_string());
+    let mut parent = "main".to_string();
+    for i in 0..5_000 {
+        let branch = format!("feature-{}", i);
+        tree.insert(&parent, "abc123", &branch).unwrap();
+        parent = branch;
+    }
+
+    // The default depth guard fires long before the stack overflows.
+    assert!(matches!(
+        tree.branches(),
+        Err(StError::CorruptedStore(reason)) if reason.contains("1000")
+    ));
+
+    // The depth is configurable.
+    tree.max_depth = Some(2);
+    assert!(matches!(
+        tree.subtree("feature-0"),
+        Err(StError::CorruptedStore(reason)) if reason.contains("`feature-3`")
+    ));
+
+    // A branch listed as its own child is reported rather than recursing forever.
+    let mut cyclic = StackTree::new("main".to_string());
+    cyclic.insert("main", "abc123", "feature-1").unwrap();
+    cyclic
+        .get_mut("feature-1")
+        .unwrap()
+        .children
+        .insert("feature-1".to_string());
+    assert!(matches!(cyclic.branches(), Err(StError::CorruptedStore(_))));
+}